@@ -1,19 +1,328 @@
+import fs from "fs";
+import os from "os";
+import path from "path";
+
+// "hash=count,hash=count" -> Map of per-account overrides
+function parseAccountLimits(value: string): Map<string, number> {
+  const limits = new Map<string, number>();
+  for (const entry of value.split(",")) {
+    const [hash, count] = entry.split("=").map((s) => s.trim());
+    const limit = parseInt(count ?? "", 10);
+    if (hash && limit >= 0) limits.set(hash, limit);
+  }
+  return limits;
+}
+
+// Backend package version; package.json sits next to both src/ and the
+// built dist/ (in the image too)
+function readPackageVersion(): string {
+  try {
+    const file = path.resolve(import.meta.dirname, "../package.json");
+    return JSON.parse(fs.readFileSync(file, "utf-8")).version ?? "unknown";
+  } catch {
+    return "unknown";
+  }
+}
+
+// "/asspp/" -> "/asspp", "/" or "" -> ""
+function normalizeBasePath(value: string): string {
+  const trimmed = value.trim().replace(/^\/+|\/+$/g, "");
+  return trimmed ? `/${trimmed}` : "";
+}
+
+export const WISP_LOG_LEVELS = ["off", "connections", "streams"] as const;
+export type WispLogLevel = (typeof WISP_LOG_LEVELS)[number];
+
+// Settings that can change without a restart: re-read from CONFIG_FILE on
+// SIGHUP or POST /api/admin/config/reload and assigned onto `config`.
+// Everything reads them through `config` at use time, never at startup.
+export function parseRuntimeSettings(env: NodeJS.ProcessEnv) {
+  return {
+    // Auto-cleanup: 0 disables
+    autoCleanupDays: parseInt(env.AUTO_CLEANUP_DAYS || "0", 10) || 0,
+    autoCleanupMaxMB: parseInt(env.AUTO_CLEANUP_MAX_MB || "0", 10) || 0,
+    // Completed packages kept per account, the earliest finished deleted
+    // first; overrides are "hash=count" pairs (count 0 = unlimited for that
+    // account)
+    autoCleanupMaxPerAccount:
+      parseInt(env.AUTO_CLEANUP_MAX_PER_ACCOUNT || "0", 10) || 0,
+    autoCleanupAccountLimits: parseAccountLimits(
+      env.AUTO_CLEANUP_ACCOUNT_LIMITS || "",
+    ),
+    // Refuse new downloads while DATA_DIR has less free space (0 = off),
+    // and optionally pause running ones until space recovers
+    minFreeSpaceMB: parseInt(env.MIN_FREE_SPACE_MB || "0", 10) || 0,
+    pauseOnLowSpace: env.PAUSE_ON_LOW_SPACE === "true",
+    // Per-download size limit in bytes (default 8 GB, also for 0 or less)
+    maxDownloadSize:
+      (Math.max(0, parseInt(env.MAX_DOWNLOAD_SIZE_MB || "0", 10)) || 8192) *
+      1024 *
+      1024,
+    // Downloads running at once (0 = unlimited); the rest wait as "pending"
+    // in priority order, low-priority tasks moving up one level per
+    // PRIORITY_AGING_SECONDS of waiting (0 = no aging)
+    maxConcurrentDownloads:
+      parseInt(env.MAX_CONCURRENT_DOWNLOADS || "0", 10) || 0,
+    priorityAgingSeconds: Math.max(
+      0,
+      parseInt(env.PRIORITY_AGING_SECONDS ?? "600", 10) || 0,
+    ),
+    // Sinf injections (full package rewrites) running at once (0 =
+    // unlimited, default one per CPU); completed downloads wait in
+    // "injecting" for a slot
+    maxConcurrentInjections:
+      parseInt(
+        env.MAX_CONCURRENT_INJECTIONS ?? String(os.availableParallelism()),
+        10,
+      ) || 0,
+    // Concurrent ranged connections per download (1 = single stream)
+    downloadConnections: parseInt(env.DOWNLOAD_CONNECTIONS || "1", 10) || 1,
+    // Deleted packages stay restorable in DATA_DIR/trash this long (0 = off)
+    trashRetentionHours: Math.max(
+      0,
+      parseInt(env.TRASH_RETENTION_HOURS ?? "24", 10) || 0,
+    ),
+    // Concurrent connection caps (0 = unlimited). Streams are SSE progress
+    // and Wisp tunnels; per-IP caps see the proxy's IP behind a reverse
+    // proxy.
+    maxConcurrentRequests:
+      parseInt(env.MAX_CONCURRENT_REQUESTS ?? "512", 10) || 0,
+    maxConcurrentRequestsPerIp:
+      parseInt(env.MAX_CONCURRENT_REQUESTS_PER_IP || "0", 10) || 0,
+    maxStreams: parseInt(env.MAX_STREAMS ?? "256", 10) || 0,
+    maxStreamsPerIp: parseInt(env.MAX_STREAMS_PER_IP || "0", 10) || 0,
+    // Progress updates queued per SSE stream while its client is backed
+    // up; past this the stream skips to the latest state (0 = always skip)
+    sseBufferUpdates: Math.max(
+      0,
+      parseInt(env.SSE_BUFFER_UPDATES ?? "64", 10) || 0,
+    ),
+    // Read size when serving package files; larger reads cut syscalls for
+    // big IPAs on fast links at the cost of memory per download
+    fileReadBufferKB: Math.min(
+      16 * 1024,
+      Math.max(1, parseInt(env.FILE_READ_BUFFER_KB || "0", 10) || 64),
+    ),
+    // Largest DATA payload a Wisp client may send in one packet; larger
+    // packets close the connection before they are buffered
+    wispMaxDataBytes:
+      parseInt(env.WISP_MAX_DATA_BYTES || "0", 10) || 1024 * 1024,
+    // How long Wisp target addresses are cached (0 = resolve every CONNECT)
+    wispDnsTtlSeconds: Math.max(
+      0,
+      parseInt(env.WISP_DNS_TTL_SECONDS ?? "60", 10) || 0,
+    ),
+    // Wisp lifecycle logging: "connections" logs each connection's open and
+    // close, "streams" also every stream's CONNECT and CLOSE with the bytes
+    // relayed. With WISP_LOG_PRIVACY target hosts keep only their last two
+    // labels and client addresses are replaced with "*".
+    wispLogLevel: (WISP_LOG_LEVELS.find((l) => l === env.WISP_LOG_LEVEL) ??
+      "off") as WispLogLevel,
+    wispLogPrivacy: env.WISP_LOG_PRIVACY === "true",
+    // Restart (then fail) downloads that receive no bytes this long (0 = off)
+    stallTimeoutSeconds: Math.max(
+      0,
+      parseInt(env.STALL_TIMEOUT_SECONDS ?? "300", 10) || 0,
+    ),
+    // Paused downloads older than this are resumed, or failed as
+    // download_url_expired when their URL already expired (0 = never)
+    pauseMaxAgeHours: Math.max(
+      0,
+      parseInt(env.PAUSE_MAX_AGE_HOURS || "0", 10) || 0,
+    ),
+    // Browser/CDN cache lifetime for search and lookup responses
+    // (0 = no-cache)
+    searchCacheTtlSeconds: parseInt(env.SEARCH_CACHE_TTL_SECONDS || "300", 10),
+  };
+}
+
+export type RuntimeSettings = ReturnType<typeof parseRuntimeSettings>;
+
+// Variables CONFIG_FILE may set, and the value format a reload accepts
+// (a list names the allowed values)
+export const RUNTIME_SETTING_FORMATS: Record<
+  string,
+  "integer" | "boolean" | "limits" | readonly string[]
+> = {
+  AUTO_CLEANUP_DAYS: "integer",
+  AUTO_CLEANUP_MAX_MB: "integer",
+  AUTO_CLEANUP_MAX_PER_ACCOUNT: "integer",
+  AUTO_CLEANUP_ACCOUNT_LIMITS: "limits",
+  MIN_FREE_SPACE_MB: "integer",
+  PAUSE_ON_LOW_SPACE: "boolean",
+  MAX_DOWNLOAD_SIZE_MB: "integer",
+  MAX_CONCURRENT_DOWNLOADS: "integer",
+  PRIORITY_AGING_SECONDS: "integer",
+  MAX_CONCURRENT_INJECTIONS: "integer",
+  DOWNLOAD_CONNECTIONS: "integer",
+  TRASH_RETENTION_HOURS: "integer",
+  MAX_CONCURRENT_REQUESTS: "integer",
+  MAX_CONCURRENT_REQUESTS_PER_IP: "integer",
+  MAX_STREAMS: "integer",
+  MAX_STREAMS_PER_IP: "integer",
+  SSE_BUFFER_UPDATES: "integer",
+  FILE_READ_BUFFER_KB: "integer",
+  WISP_MAX_DATA_BYTES: "integer",
+  WISP_DNS_TTL_SECONDS: "integer",
+  WISP_LOG_LEVEL: WISP_LOG_LEVELS,
+  WISP_LOG_PRIVACY: "boolean",
+  STALL_TIMEOUT_SECONDS: "integer",
+  PAUSE_MAX_AGE_HOURS: "integer",
+  SEARCH_CACHE_TTL_SECONDS: "integer",
+};
+
 export const config = {
   port: parseInt(process.env.PORT || "8080"),
   dataDir: process.env.DATA_DIR || "./data",
   publicBaseUrl: process.env.PUBLIC_BASE_URL || "",
+  // Absolute https:// URL used only for install links (manifest, payload,
+  // icons), e.g. an HTTPS tunnel in front of a LAN server
+  installBaseUrl: process.env.INSTALL_BASE_URL || "",
+  // Path prefix when hosted behind a reverse proxy sub-path (e.g. /asspp)
+  basePath: normalizeBasePath(process.env.BASE_PATH || ""),
+  // Hostnames install URLs may be generated for (empty = any Host header)
+  allowedHosts: (process.env.ALLOWED_HOSTS || "")
+    .split(",")
+    .map((h) => h.trim().toLowerCase())
+    .filter(Boolean),
   disableHttpsRedirect:
     process.env.UNSAFE_DANGEROUSLY_DISABLE_HTTPS_REDIRECT === "true",
-  // Auto-cleanup: 0 disables
-  autoCleanupDays: parseInt(process.env.AUTO_CLEANUP_DAYS || "0", 10) || 0,
-  autoCleanupMaxMB: parseInt(process.env.AUTO_CLEANUP_MAX_MB || "0", 10) || 0,
+  ...parseRuntimeSettings(process.env),
+  // KEY=VALUE file of runtime settings layered over the environment and
+  // re-read on reload; bearer token for /api/admin (unset = no admin API)
+  configFile: process.env.CONFIG_FILE || "",
+  adminToken: process.env.ADMIN_TOKEN || "",
+  // Layout of new package files: "uuid" nests <id>.ipa under account,
+  // bundle and version directories; "descriptive" writes flat
+  // <bundle>_<version>_<account8>.ipa names for external tooling
+  packageNaming: (process.env.PACKAGE_NAMING === "descriptive"
+    ? "descriptive"
+    : "uuid") as "uuid" | "descriptive",
+  // Extra package directories, e.g. a large disk mounted elsewhere; files
+  // may live in any of them or DATA_DIR/packages. New downloads go to the
+  // first listed ("first") or the one with the most free space
+  // ("most-free").
+  packageDirs: (process.env.PACKAGE_DIRS || "")
+    .split(",")
+    .map((d) => d.trim())
+    .filter(Boolean),
+  packageDirPolicy: (process.env.PACKAGE_DIR_POLICY === "most-free"
+    ? "most-free"
+    : "first") as "first" | "most-free",
+  // Zip method for injected iTunesMetadata.plist unless a request picks
+  // one: "stored" as before, or "deflated" for smaller packages. Sinfs are
+  // always stored.
+  metadataCompression: (process.env.METADATA_COMPRESSION === "deflated"
+    ? "deflated"
+    : "stored") as "stored" | "deflated",
+  // Software metadata of imported packages: "ipa" reads it from the
+  // package's Info.plist, "itunes" also fills artwork/seller from an
+  // iTunes lookup, "off" leaves it as imported
+  enrichMetadata: (["off", "ipa"].includes(process.env.ENRICH_METADATA ?? "")
+    ? process.env.ENRICH_METADATA
+    : "itunes") as "off" | "ipa" | "itunes",
+  // Pre-allocate downloads and fsync files/directories before completion.
+  // Off by default since network filesystems behave differently.
+  durableWrites: process.env.DURABLE_WRITES === "true",
+  // Persist task metadata as tasks.json.gz (either file is read on startup)
+  compressTaskStore: process.env.COMPRESS_TASKS_FILE === "true",
+  // Base64 32-byte keys for encrypting download tickets of unfinished tasks
+  // in tasks.json. Comma-separated: the first encrypts, all decrypt. Without
+  // a key unfinished tasks are never persisted.
+  persistEncryptionKeys: (process.env.PERSIST_ENCRYPTION_KEY || "")
+    .split(",")
+    .map((k) => k.trim())
+    .filter(Boolean),
+  // Distinct hashes one ?accountHashes= list may name (larger lists get 400)
+  maxAccountHashes: parseInt(process.env.MAX_ACCOUNT_HASHES || "20", 10) || 20,
+  // iTunes storefront used when search/lookup requests omit `country`
+  defaultStorefront: (process.env.DEFAULT_STOREFRONT || "us")
+    .trim()
+    .toLowerCase(),
+  // Optional DNS-over-HTTPS JSON endpoint for Wisp targets instead of the
+  // system resolver
+  wispDnsResolver: process.env.WISP_DNS_RESOLVER || "",
+  // HMAC key for the short-lived tokens in install links (unset = random
+  // per process) and how long a minted link stays valid
+  installTokenSecret: process.env.INSTALL_TOKEN_SECRET || "",
+  installTokenTtlSeconds:
+    parseInt(process.env.INSTALL_TOKEN_TTL_SECONDS || "3600", 10) || 3600,
+  // Lifetime of a POST /api/session token (Authorization: Session <token>)
+  sessionTtlSeconds:
+    parseInt(process.env.SESSION_TTL_SECONDS || "43200", 10) || 43200,
+  // Serve install manifests over plain HTTP (iOS will refuse them)
+  allowInsecureInstall: process.env.ALLOW_INSECURE_INSTALL === "true",
+  // Reject ?accountHash= / ?accountHashes= (400 ACCOUNT_HASH_IN_QUERY) so
+  // hashes only travel in X-Account-Hash(es) headers, except on package
+  // file download links
+  strictAccountHeader: process.env.STRICT_ACCOUNT_HEADER === "true",
+  // Accept pre-validation account hashes (any 8+ safe characters) for
+  // older clients; to be removed in the next release
+  allowLegacyAccountHashes: process.env.ALLOW_LEGACY_ACCOUNT_HASHES === "true",
+  // Built-in TLS: serve HTTPS when both paths are set
+  tlsCertPath: process.env.TLS_CERT_PATH || "",
+  tlsKeyPath: process.env.TLS_KEY_PATH || "",
+  // Optional plain-HTTP listener that redirects to HTTPS (0 disables)
+  httpRedirectPort: parseInt(process.env.HTTP_REDIRECT_PORT || "0", 10) || 0,
+  // S3-compatible bucket (e.g. R2) that POST /api/admin/migrate-storage
+  // copies completed packages into; keys are MIGRATE_S3_PREFIX + the
+  // package path relative to its package root
+  migrateS3Endpoint: process.env.MIGRATE_S3_ENDPOINT || "",
+  migrateS3Bucket: process.env.MIGRATE_S3_BUCKET || "",
+  migrateS3Region: process.env.MIGRATE_S3_REGION || "auto",
+  migrateS3AccessKeyId: process.env.MIGRATE_S3_ACCESS_KEY_ID || "",
+  migrateS3SecretAccessKey: process.env.MIGRATE_S3_SECRET_ACCESS_KEY || "",
+  migrateS3Prefix: process.env.MIGRATE_S3_PREFIX || "",
+  // Maintenance mode: packages stay downloadable and installable, but
+  // downloads and packages can't be created, changed or deleted, queued
+  // downloads wait and cleanup is suspended. Toggled at runtime with
+  // PUT /api/admin/read-only.
+  readOnly: process.env.READ_ONLY === "true",
+  // Development fault injection via POST /api/dev/chaos; never available
+  // with NODE_ENV=production, which the Docker image sets
+  chaosEnabled:
+    process.env.CHAOS_ENABLED === "true" &&
+    process.env.NODE_ENV !== "production",
+  version: readPackageVersion(),
   // Build info (injected via Docker build args)
   buildCommit: process.env.BUILD_COMMIT || "unknown",
   buildDate: process.env.BUILD_DATE || "unknown",
 };
 
-export const MAX_DOWNLOAD_SIZE = 8 * 1024 * 1024 * 1024; // 8 GB
 export const DOWNLOAD_TIMEOUT_MS = 8 * 60 * 60 * 1000; // 8 hours
 export const BAG_TIMEOUT_MS = 15_000; // 15 seconds
 export const BAG_MAX_BYTES = 1024 * 1024; // 1 MB
+export const UPSTREAM_FAILURE_THRESHOLD = 5; // consecutive failures to open
+export const UPSTREAM_COOLDOWN_MS = 30_000; // 30 seconds before probing
+export const TRASH_SWEEP_INTERVAL_MS = 60 * 60 * 1000; // 1 hour
+export const PROGRESS_POLL_INTERVAL_MS = 1000; // advertised to polling clients
+export const STALE_PROGRESS_MS = 3000; // no bytes for this long = speed 0
+export const MAX_JSON_BODY_BYTES = 4 * 1024 * 1024; // 4 MB
+export const MAX_DOWNLOAD_HEADERS = 16;
+export const MAX_SINFS = 32; // per download ticket
+export const SPACE_CHECK_INTERVAL_MS = 30_000; // free space monitor period
+export const STALL_CHECK_INTERVAL_MS = 30_000; // stall watchdog period
+export const PAUSE_SWEEP_INTERVAL_MS = 5 * 60 * 1000; // paused task expiry
+export const MAX_STALL_RECOVERIES = 1; // restarts before failing as stalled
+export const VERIFY_INLINE_MAX_BYTES = 256 * 1024 * 1024; // larger = 202 + poll
+export const PACKAGE_CONTENTS_CACHE_ENTRIES = 64; // cached zip listings
+export const MAX_PACKAGE_CONTENTS_ENTRIES = 5000; // per contents response
+export const MAX_IMPORT_BYTES = 64 * 1024 * 1024; // POST /api/admin/import
+export const MAX_PAGE_SIZE = 200; // items per paginated list response
+export const MAX_SEARCH_QUERY_LENGTH = 200; // package search ?q=
+export const MAX_SHARED_ACCOUNTS = 20;
+export const MAX_SESSIONS = 10_000; // live session tokens held in memory
+export const SESSION_SWEEP_INTERVAL_MS = 10 * 60 * 1000; // expired sessions
+export const DNS_CACHE_MAX_ENTRIES = 1024; // Wisp target hostnames
+export const DOH_TIMEOUT_MS = 5000; // per DNS-over-HTTPS query
+export const MAX_DOWNLOAD_REDIRECTS = 5; // hops followed per download request
+export const MAX_DOWNLOAD_MIRRORS = 5; // candidate URLs per create request
+export const CREATE_PROBE_TIMEOUT_MS = 3000; // upstream probe on create
+export const MAX_UPLOADS = 16; // package uploads in progress at once
+export const MAX_UPLOADS_PER_ACCOUNT = 4; // ... and per account
+export const UPLOAD_IDLE_TIMEOUT_MS = 60 * 60 * 1000; // unfinished uploads
+export const MAX_UPLOAD_METADATA_BYTES = 8 * 1024; // X-Upload-Metadata header
+export const ENRICH_LOOKUP_INTERVAL_MS = 3000; // iTunes lookups when enriching
+export const MIGRATE_PART_BYTES = 16 * 1024 * 1024; // multipart part size
+export const MIGRATE_PART_RETRIES = 3; // per part before the package fails
 export const MIN_ACCOUNT_HASH_LENGTH = 8;
@@ -1,44 +1,104 @@
+// Must stay first: CONFIG_FILE is applied before the download manager loads
+import "./services/startupConfig.js";
 import express from "express";
-import { createServer } from "http";
 import path from "path";
 import fs from "fs";
-import { config } from "./config.js";
+import { config, MAX_JSON_BODY_BYTES } from "./config.js";
 import { httpsRedirect } from "./middleware/httpsRedirect.js";
+import { connectionLimit } from "./middleware/connectionLimit.js";
 import { errorHandler } from "./middleware/errorHandler.js";
+import {
+  createAppServer,
+  createRedirectServer,
+  tlsEnabled,
+} from "./services/httpServer.js";
 import { setupWsProxy } from "./services/wsProxy.js";
+import { reloadConfigOnSignal } from "./services/configReload.js";
+import { resumeStorageMigration } from "./services/storageMigration.js";
 import searchRoutes from "./routes/search.js";
 import downloadRoutes from "./routes/downloads.js";
 import packageRoutes from "./routes/packages.js";
-import installRoutes from "./routes/install.js";
+import uploadRoutes from "./routes/uploads.js";
+import installRoutes, { installBaseUrlError } from "./routes/install.js";
 import settingsRoutes from "./routes/settings.js";
+import sessionRoutes from "./routes/session.js";
 import bagRoutes from "./routes/bag.js";
+import adminRoutes from "./routes/admin.js";
+import openapiRoutes from "./routes/openapi.js";
+import devRoutes from "./routes/dev.js";
+
+// Install links would silently point nowhere; refuse to start instead
+const installBaseInvalid = installBaseUrlError(config.installBaseUrl);
+if (installBaseInvalid) {
+  console.error(installBaseInvalid);
+  process.exit(1);
+}
 
 const app = express();
 
 // Middleware
+app.use(connectionLimit);
 app.use(httpsRedirect);
-app.use(express.json({ limit: "50mb" }));
+app.use(express.json({ limit: MAX_JSON_BODY_BYTES }));
+
+// Everything is mounted under the optional BASE_PATH prefix
+const { basePath } = config;
+const apiPath = `${basePath}/api`;
+
+// Fault injection for development; ahead of the rest so it can fail them
+if (config.chaosEnabled) {
+  console.warn("CHAOS_ENABLED: fault injection is available at /api/dev/chaos");
+  app.use(apiPath, devRoutes);
+}
 
 // API routes
-app.use("/api", searchRoutes);
-app.use("/api", downloadRoutes);
-app.use("/api", packageRoutes);
-app.use("/api", installRoutes);
-app.use("/api", settingsRoutes);
-app.use("/api", bagRoutes);
-
-// Serve static frontend files
+app.use(apiPath, searchRoutes);
+app.use(apiPath, downloadRoutes);
+app.use(apiPath, packageRoutes);
+app.use(apiPath, uploadRoutes);
+app.use(apiPath, installRoutes);
+app.use(apiPath, settingsRoutes);
+app.use(apiPath, sessionRoutes);
+app.use(apiPath, bagRoutes);
+app.use(apiPath, adminRoutes);
+app.use(apiPath, openapiRoutes);
+
+// Redirect the bare prefix so relative asset URLs resolve under it
+if (basePath) {
+  app.get(basePath, (req, res, next) => {
+    // Non-strict routing also matches the trailing-slash form
+    if (req.path !== basePath) return next();
+    const query = req.originalUrl.slice(req.path.length);
+    res.redirect(301, `${basePath}/${query}`);
+  });
+}
+
+// Serve static frontend files (index.html goes through the fallback below)
 const publicDir = path.resolve(import.meta.dirname, "../public");
-app.use(express.static(publicDir));
+app.use(basePath || "/", express.static(publicDir, { index: false }));
+
+// index.html with a <base href> announcing the prefix to the SPA
+let indexHtml: string | null = null;
+function loadIndexHtml(): string | null {
+  if (indexHtml !== null) return indexHtml;
+  const indexPath = path.join(publicDir, "index.html");
+  if (!fs.existsSync(indexPath)) return null;
+  const html = fs.readFileSync(indexPath, "utf-8");
+  indexHtml = html.replace(
+    "<head>",
+    `<head>\n    <base href="${basePath}/" />`,
+  );
+  return indexHtml;
+}
 
 // SPA fallback: serve index.html for non-API routes
-app.get("*", (req, res, next) => {
-  if (req.path.startsWith("/api")) {
+app.get(`${basePath}/*`, (req, res, next) => {
+  if (req.path.startsWith(apiPath)) {
     return next();
   }
-  const indexPath = path.join(publicDir, "index.html");
-  if (fs.existsSync(indexPath)) {
-    res.sendFile(indexPath);
+  const html = loadIndexHtml();
+  if (html !== null) {
+    res.type("html").send(html);
   } else {
     next();
   }
@@ -47,8 +107,8 @@ app.get("*", (req, res, next) => {
 // Error handler (must be last)
 app.use(errorHandler);
 
-// Create HTTP server
-const server = createServer(app);
+// Create HTTP server, or HTTPS when a certificate is configured
+const server = createAppServer(app);
 
 // WebSocket proxy for Apple TCP connections
 setupWsProxy(server);
@@ -56,9 +116,27 @@ setupWsProxy(server);
 // Ensure data directory exists
 fs.mkdirSync(config.dataDir, { recursive: true });
 
+// Runtime settings from CONFIG_FILE (loaded by startupConfig.ts), re-read
+// on SIGHUP
+reloadConfigOnSignal();
+
+// A storage migration interrupted by a restart continues where it stopped
+resumeStorageMigration();
+
 server.listen(config.port, () => {
-  console.log(`Server listening on port ${config.port}`);
+  console.log(
+    `Server listening on port ${config.port}${tlsEnabled() ? " (HTTPS)" : ""}`,
+  );
   console.log(`Data directory: ${path.resolve(config.dataDir)}`);
 });
 
+// Plain-HTTP listener that only redirects to the HTTPS port
+if (tlsEnabled() && config.httpRedirectPort) {
+  createRedirectServer().listen(config.httpRedirectPort, () => {
+    console.log(
+      `Redirecting HTTP on port ${config.httpRedirectPort} to HTTPS`,
+    );
+  });
+}
+
 export { app, server };
@@ -0,0 +1,80 @@
+import { Request, Response, NextFunction } from "express";
+import { config } from "../config.js";
+import { sendError } from "../utils/route.js";
+
+interface Limits {
+  max: number;
+  perIp: number;
+}
+
+/**
+ * Counts open connections globally and per client IP against two caps
+ * (0 = unlimited). tryAcquire returns an idempotent release, or null when
+ * a cap is reached.
+ */
+export class ConnectionLimiter {
+  private total = 0;
+  private readonly perIp = new Map<string, number>();
+
+  constructor(private readonly limits: () => Limits) {}
+
+  get size(): number {
+    return this.total;
+  }
+
+  tryAcquire(ip: string): (() => void) | null {
+    const { max, perIp } = this.limits();
+    const current = this.perIp.get(ip) ?? 0;
+    if ((max > 0 && this.total >= max) || (perIp > 0 && current >= perIp)) {
+      return null;
+    }
+
+    this.total++;
+    this.perIp.set(ip, current + 1);
+    let released = false;
+    return () => {
+      if (released) return;
+      released = true;
+      this.total--;
+      const remaining = (this.perIp.get(ip) ?? 1) - 1;
+      if (remaining > 0) this.perIp.set(ip, remaining);
+      else this.perIp.delete(ip);
+    };
+  }
+}
+
+// Regular requests and long-lived streams (SSE, Wisp tunnels) are counted
+// separately so idle streams can't starve ordinary API calls.
+export const requestLimiter = new ConnectionLimiter(() => ({
+  max: config.maxConcurrentRequests,
+  perIp: config.maxConcurrentRequestsPerIp,
+}));
+export const streamLimiter = new ConnectionLimiter(() => ({
+  max: config.maxStreams,
+  perIp: config.maxStreamsPerIp,
+}));
+
+export function connectionLimit(
+  req: Request,
+  res: Response,
+  next: NextFunction,
+) {
+  // SSE progress streams, identified by Accept header or route suffix
+  const isStream =
+    (req.headers.accept || "").includes("text/event-stream") ||
+    req.path.endsWith("/progress");
+  const limiter = isStream ? streamLimiter : requestLimiter;
+  const release = limiter.tryAcquire(req.socket.remoteAddress || "unknown");
+  if (!release) {
+    res.setHeader("Retry-After", 1);
+    sendError(
+      res,
+      503,
+      "Too many concurrent connections",
+      "TOO_MANY_CONNECTIONS",
+    );
+    return;
+  }
+  res.once("close", release);
+  next();
+}
@@ -1,11 +1,27 @@
 import { Request, Response, NextFunction } from "express";
+import { sendError } from "../utils/route.js";
+
+// Errors raised by express.json() (body-parser) carry a client status
+interface BodyParserError extends Error {
+  status?: number;
+  type?: string;
+}
 
 export function errorHandler(
-  err: Error,
+  err: BodyParserError,
   req: Request,
   res: Response,
   next: NextFunction,
 ) {
+  if (err.type === "entity.too.large") {
+    sendError(res, 413, "Request body too large", "PAYLOAD_TOO_LARGE");
+    return;
+  }
+  if (err.type && err.status && err.status >= 400 && err.status < 500) {
+    sendError(res, err.status, "Invalid request body");
+    return;
+  }
+
   console.error("Error:", err.message);
-  res.status(500).json({ error: "Internal server error" });
+  sendError(res, 500, "Internal server error");
 }
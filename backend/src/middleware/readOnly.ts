@@ -0,0 +1,21 @@
+import { Request, Response, NextFunction } from "express";
+import { config } from "../config.js";
+import { sendError } from "../utils/route.js";
+
+/**
+ * Answer 503 for anything but reads while the server is in read-only
+ * maintenance mode (READ_ONLY). Mounted on the download and package routes;
+ * listing, streaming and installing packages keep working.
+ */
+export function rejectWritesWhenReadOnly(
+  req: Request,
+  res: Response,
+  next: NextFunction,
+) {
+  if (config.readOnly && req.method !== "GET" && req.method !== "HEAD") {
+    res.setHeader("Retry-After", 300);
+    sendError(res, 503, "Server is in read-only maintenance mode", "READ_ONLY");
+    return;
+  }
+  next();
+}
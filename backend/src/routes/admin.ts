@@ -0,0 +1,297 @@
+import express, { Router, Request, Response, NextFunction } from "express";
+import crypto from "crypto";
+import { Readable } from "stream";
+import { config, MAX_IMPORT_BYTES } from "../config.js";
+import {
+  getLastReloadAt,
+  getRuntimeSettings,
+  reloadConfig,
+} from "../services/configReload.js";
+import {
+  exportTasks,
+  forceTask,
+  getTask,
+  importTasks,
+  isTaskStoreUnreadable,
+  sanitizeTaskForResponse,
+  setReadOnly,
+} from "../services/downloadManager.js";
+import {
+  getMigrationState,
+  getMigrationTarget,
+  isMigrationRunning,
+  startStorageMigration,
+  summarizeMigration,
+} from "../services/storageMigration.js";
+import {
+  closeWispConnection,
+  listWispConnections,
+} from "../services/wispConnections.js";
+import { getIdParam, sendError } from "../utils/route.js";
+import type { RuntimeSettings } from "../config.js";
+import type { ForceTarget } from "../services/downloadManager.js";
+
+const router = Router();
+
+function digest(value: string): Buffer {
+  return crypto.createHash("sha256").update(value).digest();
+}
+
+// `Bearer <token>`, or Basic credentials with the token as the password so
+// a browser can open the docs page (any user name)
+function adminTokenOf(req: Request): string {
+  const auth = req.headers["authorization"];
+  if (typeof auth !== "string") return "";
+  if (auth.startsWith("Bearer ")) return auth.slice("Bearer ".length).trim();
+  if (auth.startsWith("Basic ")) {
+    const decoded = Buffer.from(auth.slice("Basic ".length), "base64");
+    const credentials = decoded.toString("utf-8");
+    return credentials.slice(credentials.indexOf(":") + 1);
+  }
+  return "";
+}
+
+// Operator endpoints behind `Authorization: Bearer <ADMIN_TOKEN>`; without
+// ADMIN_TOKEN they do not exist
+function requireAdminToken(req: Request, res: Response, next: NextFunction) {
+  if (!config.adminToken) {
+    sendError(res, 404, "Not found");
+    return;
+  }
+  const token = adminTokenOf(req);
+  // Compare digests so the check takes the same time for any length
+  if (!crypto.timingSafeEqual(digest(token), digest(config.adminToken))) {
+    // Lets a browser prompt for the token on the docs page
+    if (req.path === "/docs") {
+      res.setHeader("WWW-Authenticate", 'Basic realm="AssppWeb admin"');
+    }
+    sendError(res, 401, "Invalid admin token", "INVALID_ADMIN_TOKEN");
+    return;
+  }
+  next();
+}
+
+// Maps don't serialize; show per-account limits as an object
+function settingsForResponse(settings: RuntimeSettings) {
+  return {
+    ...settings,
+    autoCleanupAccountLimits: Object.fromEntries(
+      settings.autoCleanupAccountLimits,
+    ),
+  };
+}
+
+router.use("/admin", requireAdminToken);
+
+// Runtime settings in effect and when they were last reloaded
+router.get("/admin/config", (_req: Request, res: Response) => {
+  res.json({
+    configFile: config.configFile || null,
+    reloadedAt: getLastReloadAt(),
+    settings: settingsForResponse(getRuntimeSettings()),
+  });
+});
+
+// Re-read CONFIG_FILE; an invalid file is rejected and nothing changes
+router.post("/admin/config/reload", (_req: Request, res: Response) => {
+  const result = reloadConfig();
+  if (!result.ok) {
+    sendError(res, 400, result.error, "INVALID_CONFIG");
+    return;
+  }
+  res.json({
+    reloadedAt: getLastReloadAt(),
+    settings: settingsForResponse(result.settings),
+  });
+});
+
+// Copy completed packages to the MIGRATE_S3_* bucket in the background;
+// `deleteSource: true` deletes each package once its copy is verified
+router.post("/admin/migrate-storage", (req: Request, res: Response) => {
+  if (!getMigrationTarget()) {
+    sendError(
+      res,
+      400,
+      "Storage migration target is not configured",
+      "MIGRATION_NOT_CONFIGURED",
+    );
+    return;
+  }
+  const deleteSource = req.body?.deleteSource ?? false;
+  if (typeof deleteSource !== "boolean") {
+    sendError(res, 400, "deleteSource must be a boolean");
+    return;
+  }
+  if (isMigrationRunning()) {
+    sendError(
+      res,
+      409,
+      "A storage migration is already running",
+      "MIGRATION_RUNNING",
+    );
+    return;
+  }
+  res.status(202).json(summarizeMigration(startStorageMigration(deleteSource)));
+});
+
+// Progress of the current or last migration, per package
+router.get("/admin/migrate-storage/status", (_req: Request, res: Response) => {
+  const job = getMigrationState();
+  if (!job) {
+    sendError(res, 404, "No storage migration has been started");
+    return;
+  }
+  res.json(summarizeMigration(job));
+});
+
+const FORCE_TARGETS: ForceTarget[] = ["fail", "complete", "pause"];
+
+// Move a wedged task to `target` (fail, complete or pause) whatever its
+// state, abandoning in-flight work; only in-flight tasks can be paused.
+// Recovery only; users pause and delete through the downloads routes.
+router.post("/admin/tasks/:id/force", (req: Request, res: Response) => {
+  const { target } = req.body ?? {};
+  if (!FORCE_TARGETS.includes(target)) {
+    sendError(res, 400, "target must be fail, complete or pause");
+    return;
+  }
+  const id = getIdParam(req);
+  const rejected = forceTask(id, target);
+  if (rejected === "NOT_FOUND") {
+    sendError(res, 404, "Download not found");
+    return;
+  }
+  if (rejected) {
+    sendError(res, 409, "Cannot force this download", rejected);
+    return;
+  }
+  res.json(
+    sanitizeTaskForResponse(getTask(id)!, { owner: true, history: true }),
+  );
+});
+
+// Turn read-only maintenance mode on or off; GET /api/settings reports it
+router.put("/admin/read-only", (req: Request, res: Response) => {
+  const { enabled } = req.body ?? {};
+  if (typeof enabled !== "boolean") {
+    sendError(res, 400, "enabled must be a boolean");
+    return;
+  }
+  if (!enabled && isTaskStoreUnreadable()) {
+    sendError(
+      res,
+      409,
+      "The persisted tasks could not be read; fix tasks.json and restart",
+      "TASK_STORE_UNREADABLE",
+    );
+    return;
+  }
+  setReadOnly(enabled);
+  res.json({ readOnly: config.readOnly });
+});
+
+// Every stored task as JSON lines, in the tasks.json record format without
+// download tickets: a backup, or the input of POST /admin/import elsewhere
+router.get("/admin/export", (_req: Request, res: Response) => {
+  const date = new Date().toISOString().slice(0, 10);
+  res.type("application/x-ndjson");
+  res.setHeader(
+    "Content-Disposition",
+    `attachment; filename="asspp-tasks-${date}.jsonl"`,
+  );
+  Readable.from(
+    (function* () {
+      for (const record of exportTasks()) yield `${JSON.stringify(record)}\n`;
+    })(),
+  ).pipe(res);
+});
+
+// Merge tasks exported by GET /admin/export. Copy the package files into
+// place first: tasks whose file is missing are skipped, as are ids that
+// already exist here.
+router.post(
+  "/admin/import",
+  express.text({
+    type: ["application/x-ndjson", "application/jsonl", "text/plain"],
+    limit: MAX_IMPORT_BYTES,
+  }),
+  (req: Request, res: Response) => {
+    if (typeof req.body !== "string") {
+      sendError(res, 400, "Send the export as application/x-ndjson");
+      return;
+    }
+    const records: unknown[] = [];
+    const lines = req.body.split("\n");
+    for (let i = 0; i < lines.length; i++) {
+      if (!lines[i].trim()) continue;
+      try {
+        records.push(JSON.parse(lines[i]));
+      } catch {
+        sendError(
+          res,
+          400,
+          `Line ${i + 1} is not valid JSON`,
+          "INVALID_IMPORT",
+        );
+        return;
+      }
+    }
+    res.json(importTasks(records));
+  },
+);
+
+// Open Wisp connections: client address, connect time and each stream's
+// target with the bytes relayed so far
+router.get("/admin/wisp/connections", (_req: Request, res: Response) => {
+  res.json(listWispConnections());
+});
+
+// Cut off a Wisp connection: its streams get CLOSE packets, then the
+// WebSocket and its target sockets are torn down
+router.delete("/admin/wisp/connections/:id", (req: Request, res: Response) => {
+  if (!closeWispConnection(getIdParam(req))) {
+    sendError(res, 404, "Wisp connection not found");
+    return;
+  }
+  res.status(204).end();
+});
+
+// Swagger UI for GET /api/openapi.json. Its assets come from a CDN; the
+// document itself is public, only this page sits behind the token.
+// Pinned, and loaded with Subresource Integrity so a changed CDN file is
+// refused rather than run with access to the admin token
+const SWAGGER_UI = "https://cdn.jsdelivr.net/npm/swagger-ui-dist@5.17.14";
+const SWAGGER_UI_CSS_SRI =
+  "sha384-wxLW6kwyHktdDGr6Pv1zgm/VGJh99lfUbzSn6HNHBENZlCN7W602k9VkGdxuFvPn";
+const SWAGGER_UI_JS_SRI =
+  "sha384-wmyclcVGX/WhUkdkATwhaK1X1JtiNrr2EoYJ+diV3vj4v6OC5yCeSu+yW13SYJep";
+
+router.get("/admin/docs", (_req: Request, res: Response) => {
+  res.type("html").send(`<!doctype html>
+<html>
+  <head>
+    <meta charset="utf-8" />
+    <title>AssppWeb API</title>
+    <link
+      rel="stylesheet"
+      href="${SWAGGER_UI}/swagger-ui.css"
+      integrity="${SWAGGER_UI_CSS_SRI}"
+      crossorigin="anonymous"
+    />
+  </head>
+  <body>
+    <div id="swagger-ui"></div>
+    <script
+      src="${SWAGGER_UI}/swagger-ui-bundle.js"
+      integrity="${SWAGGER_UI_JS_SRI}"
+      crossorigin="anonymous"
+    ></script>
+    <script>
+      SwaggerUIBundle({ url: "../openapi.json", dom_id: "#swagger-ui" });
+    </script>
+  </body>
+</html>
+`);
+});
+
+export default router;
@@ -1,6 +1,11 @@
 import { Router, Request, Response } from "express";
 import https from "https";
 import { BAG_TIMEOUT_MS, BAG_MAX_BYTES } from "../config.js";
+import {
+  bagBreaker,
+  isUpstreamFailureStatus,
+} from "../services/circuitBreaker.js";
+import { rejectIfCircuitOpen, sendError } from "../utils/route.js";
 
 const router = Router();
 const userAgent =
@@ -13,18 +18,22 @@ const userAgent =
 router.get("/bag", async (req: Request, res: Response) => {
   const guid = req.query.guid as string | undefined;
   if (!guid) {
-    res.status(400).json({ error: "Missing guid parameter" });
+    sendError(res, 400, "Missing guid parameter");
     return;
   }
 
   // Validate guid format (should be hex string)
   if (!/^[a-fA-F0-9]+$/.test(guid)) {
-    res.status(400).json({ error: "Invalid guid format" });
+    sendError(res, 400, "Invalid guid format");
     return;
   }
 
   const url = `https://init.itunes.apple.com/bag.xml?guid=${encodeURIComponent(guid)}`;
 
+  if (rejectIfCircuitOpen(bagBreaker, res)) return;
+
+  // Upstream status, 0 until a response arrives (network error/timeout)
+  let upstreamStatus = 0;
   try {
     const body = await new Promise<string>((resolve, reject) => {
       const request = https.get(
@@ -37,6 +46,7 @@ router.get("/bag", async (req: Request, res: Response) => {
           timeout: BAG_TIMEOUT_MS,
         },
         (resp) => {
+          upstreamStatus = resp.statusCode ?? 0;
           let data = "";
           let totalBytes = 0;
 
@@ -67,19 +77,25 @@ router.get("/bag", async (req: Request, res: Response) => {
         reject(new Error("Bag request timed out"));
       });
     });
+    bagBreaker.recordSuccess();
 
     // Extract plist from XML wrapper
     const plistMatch = body.match(/<plist[\s\S]*<\/plist>/);
     if (!plistMatch) {
-      res.status(502).json({ error: "No plist found in bag response" });
+      sendError(res, 502, "No plist found in bag response");
       return;
     }
 
     // Return raw plist XML for the client to parse
     res.type("text/xml").send(plistMatch[0]);
   } catch (err) {
+    if (!upstreamStatus || isUpstreamFailureStatus(upstreamStatus)) {
+      bagBreaker.recordFailure();
+    } else {
+      bagBreaker.recordSuccess();
+    }
     console.error("Bag proxy error:", err instanceof Error ? err.message : err);
-    res.status(502).json({ error: "Bag request failed" });
+    sendError(res, 502, "Bag request failed");
   }
 });
 
@@ -0,0 +1,62 @@
+import { Router, Request, Response, NextFunction } from "express";
+import { armChaosFaults, takeChaosRouteFault } from "../services/chaos.js";
+import { sendError } from "../utils/route.js";
+
+// Mounted ahead of the other API routes, and only with CHAOS_ENABLED
+const router = Router();
+
+// An armed route fault answers the next matching request with a 500
+router.use((req: Request, res: Response, next: NextFunction) => {
+  if (takeChaosRouteFault(req.method, req.path)) {
+    sendError(res, 500, "Injected failure", "CHAOS");
+    return;
+  }
+  next();
+});
+
+function isIntegerIn(value: unknown, min: number, max: number) {
+  return (
+    typeof value === "number" &&
+    Number.isInteger(value) &&
+    value >= min &&
+    value <= max
+  );
+}
+
+// Arm faults (null disarms one); responds with everything still armed
+router.post("/dev/chaos", (req: Request, res: Response) => {
+  const { failDownloadAtPercent, sseDelayMs, failInjection, failRoute } =
+    req.body ?? {};
+  if (
+    failDownloadAtPercent != null &&
+    !isIntegerIn(failDownloadAtPercent, 0, 100)
+  ) {
+    sendError(res, 400, "failDownloadAtPercent must be an integer 0-100");
+    return;
+  }
+  if (sseDelayMs != null && !isIntegerIn(sseDelayMs, 0, 60_000)) {
+    sendError(res, 400, "sseDelayMs must be an integer 0-60000");
+    return;
+  }
+  if (failInjection != null && typeof failInjection !== "boolean") {
+    sendError(res, 400, "failInjection must be a boolean");
+    return;
+  }
+  if (
+    failRoute != null &&
+    (typeof failRoute !== "string" || !/^[A-Z]+ \/\S*$/.test(failRoute))
+  ) {
+    sendError(res, 400, 'failRoute must look like "GET /packages"');
+    return;
+  }
+  res.json(
+    armChaosFaults({
+      failDownloadAtPercent,
+      sseDelayMs,
+      failInjection,
+      failRoute,
+    }),
+  );
+});
+
+export default router;
@@ -1,84 +1,188 @@
 import { Router, Request, Response } from "express";
+import { config } from "../config.js";
+import { rejectWritesWhenReadOnly } from "../middleware/readOnly.js";
 import {
   createTask,
+  findDuplicateTask,
   getAllTasks,
   getTask,
   deleteTask,
+  isValidPriority,
   pauseTask,
+  probeDownloadURL,
   resumeTask,
+  setTaskPriority,
+  summarizeTask,
+  refreshDownloadURL,
+  trashTask,
+  updateTaskSharing,
   addProgressListener,
   removeProgressListener,
   sanitizeTaskForResponse,
   validateDownloadURL,
 } from "../services/downloadManager.js";
+import { withChaosDelay } from "../services/chaos.js";
+import { parseCreateRequest } from "../services/createRequest.js";
+import { isUnderStoragePressure } from "../services/storageMonitor.js";
+import { accountHashesEqual } from "../utils/accountHash.js";
 import {
+  canAccessTask,
   getIdParam,
+  INVALID_DOWNLOAD_HEADERS,
+  INVALID_SINFS,
+  isValidDownloadHeaders,
+  isValidSinfs,
+  parseAccountHashes,
   requireAccountHash,
+  sendError,
+  sessionAccountHash,
+  verifyTaskOwner,
   verifyTaskOwnership,
 } from "../utils/route.js";
+import {
+  changedSince,
+  paginate,
+  parsePageParams,
+  parseSince,
+  setNextCursor,
+  setServerTime,
+} from "../utils/pagination.js";
+import { createCoalescingSseWriter } from "../utils/sse.js";
 
 const router = Router();
 
-// Start a new download
-router.post("/downloads", (req: Request, res: Response) => {
-  const { software, accountHash, downloadURL, sinfs, iTunesMetadata } =
-    req.body;
+router.use("/downloads", rejectWritesWhenReadOnly);
 
-  if (!software || !accountHash || !downloadURL || !sinfs) {
-    res.status(400).json({
-      error:
-        "Missing required fields: software, accountHash, downloadURL, sinfs",
-    });
+// Start a new download, or return the existing task it would duplicate
+router.post("/downloads", async (req: Request, res: Response) => {
+  // A session supplies the account instead of the body's accountHash
+  const session = sessionAccountHash(req, res);
+  if (session === null) return;
+  const body = req.body ?? {};
+  const parsed = parseCreateRequest(
+    session ? { ...body, accountHash: session } : body,
+  );
+  if (!parsed.ok) {
+    const { error, code } = parsed;
+    sendError(res, 400, error, code);
     return;
   }
+  const {
+    software,
+    accountHash,
+    downloadURLs,
+    sinfs,
+    iTunesMetadata,
+    metadataCompression,
+    headers,
+    priority,
+    expectedDigests,
+    replace,
+    skipProbe,
+  } = parsed.request;
 
-  // Validate download URL before creating task
-  try {
-    validateDownloadURL(downloadURL);
-  } catch (err) {
-    res.status(400).json({
-      error: err instanceof Error ? err.message : "Invalid download URL",
-    });
+  // Repeated taps (or retries after a dropped response) reuse the task;
+  // with replace=true a completed package is downloaded again instead
+  const duplicate = () =>
+    findDuplicateTask(
+      accountHash,
+      software.bundleID,
+      software.version,
+      replace,
+    );
+  const existing = duplicate();
+  if (existing) {
+    res.status(200).json(sanitizeTaskForResponse(existing));
+    return;
+  }
+
+  if (isUnderStoragePressure()) {
+    sendError(
+      res,
+      507,
+      "Server is low on disk space; try again later",
+      "INSUFFICIENT_STORAGE",
+    );
+    return;
+  }
+
+  // Size and validity of the ticket before anything is queued. With
+  // mirrors an expired first URL is left to the usual fallback.
+  const probe = skipProbe
+    ? null
+    : await probeDownloadURL(downloadURLs[0], headers);
+  if (probe?.expired && downloadURLs.length === 1) {
+    sendError(
+      res,
+      410,
+      "Download URL rejected by the CDN; it has likely expired",
+      "DOWNLOAD_URL_EXPIRED",
+    );
+    return;
+  }
+  // A repeated tap may have created it while this one was probing
+  const created = !skipProbe && duplicate();
+  if (created) {
+    res.status(200).json(sanitizeTaskForResponse(created));
     return;
   }
 
   try {
-    const task = createTask(
+    const task = createTask({
       software,
       accountHash,
-      downloadURL,
+      downloadURL: downloadURLs[0],
       sinfs,
       iTunesMetadata,
-    );
+      downloadHeaders: headers,
+      priority,
+      expectedDigests,
+      fallbackURLs: downloadURLs.slice(1),
+      replace,
+      metadataCompression,
+      probe,
+    });
     res.status(201).json(sanitizeTaskForResponse(task));
   } catch (err) {
     console.error(
       "Create download error:",
       err instanceof Error ? err.message : err,
     );
-    res.status(400).json({ error: "Failed to create download" });
+    sendError(res, 400, "Failed to create download");
   }
 });
 
-// List downloads filtered by account hashes
+// List downloads filtered by account hashes (?summary=true returns compact
+// records without per-task file checks; ?since= only those created or
+// updated since, see X-Server-Time)
 router.get("/downloads", (req: Request, res: Response) => {
-  const hashesParam = req.query.accountHashes;
-  if (!hashesParam || typeof hashesParam !== "string") {
-    res.json([]);
-    return;
-  }
-  const hashes = new Set(hashesParam.split(",").filter(Boolean));
+  const hashes = parseAccountHashes(req, res);
+  if (!hashes) return;
   if (hashes.size === 0) {
     res.json([]);
     return;
   }
+  const pageParams = parsePageParams(req, res);
+  if (!pageParams) return;
+  const since = parseSince(req, res);
+  if (since === null) return;
+  const includeTrashed = req.query.includeTrashed === "true";
+  setServerTime(res);
   const filtered = getAllTasks()
-    .filter((t) => hashes.has(t.accountHash))
-    .map(sanitizeTaskForResponse);
-  res.json(filtered);
+    .filter((t) => includeTrashed || t.status !== "trashed")
+    .filter((t) => canAccessTask(t, hashes));
+  const page = paginate(changedSince(filtered, since), pageParams);
+  setNextCursor(res, page.nextCursor);
+  if (req.query.summary === "true") {
+    res.json(page.items.map(summarizeTask));
+    return;
+  }
+  res.json(page.items.map((t) => sanitizeTaskForResponse(t)));
 });
 
-// Get single download (requires accountHash)
+// Get single download with its event history (requires accountHash;
+// ?verbose=true adds stats). Pollable alternative to the SSE stream:
+// Express sets a weak ETag, so unchanged polls with If-None-Match get 304.
 router.get("/downloads/:id", (req: Request, res: Response) => {
   const accountHash = requireAccountHash(req, res);
   if (!accountHash) return;
@@ -86,28 +190,36 @@ router.get("/downloads/:id", (req: Request, res: Response) => {
   const id = getIdParam(req);
   const task = getTask(id);
   if (!task) {
-    res.status(404).json({ error: "Download not found" });
+    sendError(res, 404, "Download not found");
     return;
   }
 
   if (!verifyTaskOwnership(task, accountHash, res)) return;
 
-  res.json(sanitizeTaskForResponse(task));
+  res.json(
+    sanitizeTaskForResponse(task, {
+      owner: accountHashesEqual(task.accountHash, accountHash),
+      verbose: req.query.verbose === "true",
+      history: true,
+    }),
+  );
 });
 
-// SSE progress stream (requires accountHash)
+// SSE progress stream (requires accountHash). EventSource can't send
+// headers, so the query param stays allowed under STRICT_ACCOUNT_HEADER.
 router.get("/downloads/:id/progress", (req: Request, res: Response) => {
-  const accountHash = requireAccountHash(req, res);
+  const accountHash = requireAccountHash(req, res, { allowQuery: true });
   if (!accountHash) return;
 
   const id = getIdParam(req);
   const task = getTask(id);
   if (!task) {
-    res.status(404).json({ error: "Download not found" });
+    sendError(res, 404, "Download not found");
     return;
   }
 
   if (!verifyTaskOwnership(task, accountHash, res)) return;
+  const owner = accountHashesEqual(task.accountHash, accountHash);
 
   res.writeHead(200, {
     "Content-Type": "text/event-stream",
@@ -115,14 +227,16 @@ router.get("/downloads/:id/progress", (req: Request, res: Response) => {
     Connection: "keep-alive",
   });
 
-  // Send current state immediately
-  res.write(`data: ${JSON.stringify(sanitizeTaskForResponse(task))}\n\n`);
+  // Slow clients get a bounded backlog, then the latest state on drain
+  const write = createCoalescingSseWriter(
+    res,
+    (t: typeof task) => sanitizeTaskForResponse(t, { owner }),
+    config.sseBufferUpdates,
+  );
+  const listener = config.chaosEnabled ? withChaosDelay(write) : write;
 
-  const listener = (updatedTask: typeof task) => {
-    res.write(
-      `data: ${JSON.stringify(sanitizeTaskForResponse(updatedTask))}\n\n`,
-    );
-  };
+  // Send current state immediately
+  listener(task);
 
   addProgressListener(id, listener);
 
@@ -131,7 +245,7 @@ router.get("/downloads/:id/progress", (req: Request, res: Response) => {
   });
 });
 
-// Pause download (requires accountHash)
+// Pause download (owner only)
 router.post("/downloads/:id/pause", (req: Request, res: Response) => {
   const accountHash = requireAccountHash(req, res);
   if (!accountHash) return;
@@ -139,22 +253,28 @@ router.post("/downloads/:id/pause", (req: Request, res: Response) => {
   const id = getIdParam(req);
   const task = getTask(id);
   if (!task) {
-    res.status(404).json({ error: "Download not found" });
+    sendError(res, 404, "Download not found");
     return;
   }
 
-  if (!verifyTaskOwnership(task, accountHash, res)) return;
+  if (!verifyTaskOwner(task, accountHash, res)) return;
 
-  const success = pauseTask(id);
-  if (!success) {
-    res.status(400).json({ error: "Cannot pause this download" });
+  const rejected = pauseTask(id);
+  if (rejected) {
+    sendError(res, 409, "Cannot pause this download", rejected);
     return;
   }
   const updated = getTask(id);
-  res.json(updated ? sanitizeTaskForResponse(updated) : { success: true });
+  res.json(
+    updated
+      ? sanitizeTaskForResponse(updated, {
+          owner: accountHashesEqual(updated.accountHash, accountHash),
+        })
+      : { success: true },
+  );
 });
 
-// Resume download (requires accountHash)
+// Resume download (owner only)
 router.post("/downloads/:id/resume", (req: Request, res: Response) => {
   const accountHash = requireAccountHash(req, res);
   if (!accountHash) return;
@@ -162,22 +282,166 @@ router.post("/downloads/:id/resume", (req: Request, res: Response) => {
   const id = getIdParam(req);
   const task = getTask(id);
   if (!task) {
-    res.status(404).json({ error: "Download not found" });
+    sendError(res, 404, "Download not found");
     return;
   }
 
-  if (!verifyTaskOwnership(task, accountHash, res)) return;
+  if (!verifyTaskOwner(task, accountHash, res)) return;
 
-  const success = resumeTask(id);
-  if (!success) {
-    res.status(400).json({ error: "Cannot resume this download" });
+  const rejected = resumeTask(id);
+  if (rejected) {
+    sendError(res, 409, "Cannot resume this download", rejected);
     return;
   }
   const updated = getTask(id);
-  res.json(updated ? sanitizeTaskForResponse(updated) : { success: true });
+  res.json(
+    updated
+      ? sanitizeTaskForResponse(updated, {
+          owner: accountHashesEqual(updated.accountHash, accountHash),
+        })
+      : { success: true },
+  );
+});
+
+// Reorder a download still waiting for a slot (owner only)
+router.post("/downloads/:id/priority", (req: Request, res: Response) => {
+  const accountHash = requireAccountHash(req, res);
+  if (!accountHash) return;
+
+  const { priority } = req.body ?? {};
+  if (!isValidPriority(priority)) {
+    sendError(res, 400, "priority must be high, normal or low");
+    return;
+  }
+
+  const id = getIdParam(req);
+  const task = getTask(id);
+  if (!task) {
+    sendError(res, 404, "Download not found");
+    return;
+  }
+
+  if (!verifyTaskOwner(task, accountHash, res)) return;
+
+  const rejected = setTaskPriority(id, priority);
+  if (rejected) {
+    sendError(res, 409, "Only queued downloads can be reprioritized", rejected);
+    return;
+  }
+  res.json(
+    sanitizeTaskForResponse(task, {
+      owner: accountHashesEqual(task.accountHash, accountHash),
+    }),
+  );
 });
 
-// Delete download (requires accountHash)
+// Refresh an expired download ticket and restart (owner only)
+router.patch("/downloads/:id", (req: Request, res: Response) => {
+  const accountHash = requireAccountHash(req, res);
+  if (!accountHash) return;
+
+  const { downloadURL, sinfs, iTunesMetadata, headers } = req.body ?? {};
+  if (!downloadURL || typeof downloadURL !== "string") {
+    sendError(res, 400, "Missing required field: downloadURL");
+    return;
+  }
+  if (sinfs !== undefined && !isValidSinfs(sinfs)) {
+    sendError(res, 400, INVALID_SINFS);
+    return;
+  }
+  if (headers !== undefined && !isValidDownloadHeaders(headers)) {
+    sendError(res, 400, INVALID_DOWNLOAD_HEADERS);
+    return;
+  }
+
+  try {
+    validateDownloadURL(downloadURL);
+  } catch (err) {
+    sendError(
+      res,
+      400,
+      err instanceof Error ? err.message : "Invalid download URL",
+    );
+    return;
+  }
+
+  const id = getIdParam(req);
+  const task = getTask(id);
+  if (!task) {
+    sendError(res, 404, "Download not found");
+    return;
+  }
+
+  if (!verifyTaskOwner(task, accountHash, res)) return;
+
+  const rejected = refreshDownloadURL(
+    id,
+    downloadURL,
+    sinfs,
+    iTunesMetadata,
+    headers,
+  );
+  if (rejected) {
+    sendError(res, 409, "Cannot refresh this download", rejected);
+    return;
+  }
+  res.json(
+    sanitizeTaskForResponse(task, {
+      owner: accountHashesEqual(task.accountHash, accountHash),
+    }),
+  );
+});
+
+// List the accounts a download is shared with (owner only)
+router.get("/downloads/:id/sharing", (req: Request, res: Response) => {
+  const accountHash = requireAccountHash(req, res);
+  if (!accountHash) return;
+
+  const id = getIdParam(req);
+  const task = getTask(id);
+  if (!task) {
+    sendError(res, 404, "Download not found");
+    return;
+  }
+
+  if (!verifyTaskOwner(task, accountHash, res)) return;
+
+  res.json({ sharedWith: task.sharedWith ?? [] });
+});
+
+// Grant/revoke access for other account hashes (owner only)
+router.post("/downloads/:id/sharing", (req: Request, res: Response) => {
+  const accountHash = requireAccountHash(req, res);
+  if (!accountHash) return;
+
+  const { grant = [], revoke = [] } = req.body ?? {};
+  if (!Array.isArray(grant) || !Array.isArray(revoke)) {
+    sendError(res, 400, "grant and revoke must be arrays");
+    return;
+  }
+
+  const id = getIdParam(req);
+  const task = getTask(id);
+  if (!task) {
+    sendError(res, 404, "Download not found");
+    return;
+  }
+
+  if (!verifyTaskOwner(task, accountHash, res)) return;
+
+  try {
+    const sharedWith = updateTaskSharing(id, grant, revoke);
+    res.json({ sharedWith });
+  } catch (err) {
+    sendError(
+      res,
+      400,
+      err instanceof Error ? err.message : "Failed to update sharing",
+    );
+  }
+});
+
+// Delete download (owner only; shared accounts cannot remove it)
 router.delete("/downloads/:id", (req: Request, res: Response) => {
   const accountHash = requireAccountHash(req, res);
   if (!accountHash) return;
@@ -185,15 +449,22 @@ router.delete("/downloads/:id", (req: Request, res: Response) => {
   const id = getIdParam(req);
   const task = getTask(id);
   if (!task) {
-    res.status(404).json({ error: "Download not found" });
+    sendError(res, 404, "Download not found");
     return;
   }
 
-  if (!verifyTaskOwnership(task, accountHash, res)) return;
+  if (!verifyTaskOwner(task, accountHash, res)) return;
+
+  // Completed packages go to the trash unless ?purge=true
+  const purge = req.query.purge === "true" || config.trashRetentionHours <= 0;
+  if (!purge && trashTask(id)) {
+    res.json({ success: true, trashed: true });
+    return;
+  }
 
   const success = deleteTask(id);
   if (!success) {
-    res.status(404).json({ error: "Download not found" });
+    sendError(res, 404, "Download not found");
     return;
   }
   res.json({ success: true });
@@ -4,11 +4,54 @@ import path from "path";
 import { config } from "../config.js";
 import { getAllTasks } from "../services/downloadManager.js";
 import { buildManifest, getWhitePng } from "../services/manifestBuilder.js";
-import { getIdParam } from "../utils/route.js";
+import { packageRootOf } from "../services/packageRoots.js";
+import { mintInstallToken, verifyInstallToken } from "../utils/installToken.js";
+import {
+  getIdParam,
+  requireAccountHash,
+  sendError,
+  sendPackageFile,
+  verifyTaskOwnership,
+} from "../utils/route.js";
 
 const router = Router();
 
+// Public base URL including the BASE_PATH prefix, so manifest and payload
+// URLs resolve when hosted under a reverse-proxy sub-path
 export function getBaseUrl(req: Request): string {
+  return withBasePath(getOrigin(req));
+}
+
+// Base for install links: INSTALL_BASE_URL when set (e.g. an HTTPS tunnel
+// that only exposes the install routes), otherwise getBaseUrl
+export function getInstallBaseUrl(req: Request): string {
+  const override = normalizeBaseUrl(config.installBaseUrl);
+  return override ? withBasePath(override) : getBaseUrl(req);
+}
+
+/** Startup check of INSTALL_BASE_URL; returns the problem, if any. */
+export function installBaseUrlError(value: string): string | null {
+  if (!value) return null;
+  let url: URL;
+  try {
+    url = new URL(value);
+  } catch {
+    return "INSTALL_BASE_URL must be an absolute URL";
+  }
+  if (url.protocol !== "https:") return "INSTALL_BASE_URL must use https://";
+  if (url.search || url.hash) {
+    return "INSTALL_BASE_URL must not have a query or fragment";
+  }
+  return null;
+}
+
+function withBasePath(base: string): string {
+  const { basePath } = config;
+  if (!basePath || base.endsWith(basePath)) return base;
+  return `${base}${basePath}`;
+}
+
+function getOrigin(req: Request): string {
   const configured = normalizeBaseUrl(config.publicBaseUrl);
   if (configured) return configured;
 
@@ -18,8 +61,9 @@ export function getBaseUrl(req: Request): string {
   const proto = forwardedProto === "https" || req.secure ? "https" : "http";
   const host = req.headers["host"] || "localhost";
 
-  // Validate host header to prevent injection
-  const sanitizedHost = host.replace(/[^\w.\-:]/g, "");
+  // Validate host header to prevent injection, then against the allowlist
+  // so a spoofed Host cannot point manifests at another domain
+  const sanitizedHost = allowedHost(host.replace(/[^\w.\-:]/g, ""));
 
   // Support X-Forwarded-Port for reverse proxies that strip port from Host header.
   // Common when deploying HTTPS on non-443 ports (e.g., nginx with $host instead of $http_host).
@@ -40,6 +84,24 @@ export function getBaseUrl(req: Request): string {
   return `${proto}://${sanitizedHost}`;
 }
 
+// iOS silently ignores itms-services manifests served over plain HTTP, so
+// refuse to hand out such URLs unless explicitly allowed.
+export function insecureInstallError(baseUrl: string): string | null {
+  if (config.allowInsecureInstall || baseUrl.startsWith("https://")) {
+    return null;
+  }
+  return "iOS requires HTTPS for app installation. Serve AssppWeb over HTTPS (reverse proxy, PUBLIC_BASE_URL, INSTALL_BASE_URL or TLS_CERT_PATH) or set ALLOW_INSECURE_INSTALL=true.";
+}
+
+// Fall back to the first configured host when the request's host is not
+// in ALLOWED_HOSTS (port is ignored for matching)
+function allowedHost(host: string): string {
+  const { allowedHosts } = config;
+  if (allowedHosts.length === 0) return host;
+  const hostname = host.replace(/:\d+$/, "").toLowerCase();
+  return allowedHosts.includes(hostname) ? host : allowedHosts[0];
+}
+
 function normalizeBaseUrl(value?: string): string {
   if (!value) return "";
   return value.trim().replace(/\/+$/, "");
@@ -51,6 +113,69 @@ function joinUrl(baseUrl: string, path: string): string {
   return `${base}/${suffix}`;
 }
 
+// Manifest and payload are fetched by iOS itself, which can't send an
+// account hash; they are authorized by the token minted in /url instead
+function rejectInvalidToken(req: Request, res: Response, id: string) {
+  if (verifyInstallToken(id, req.query.token)) return false;
+  sendError(
+    res,
+    403,
+    "Invalid or expired install link",
+    "INVALID_INSTALL_TOKEN",
+  );
+  return true;
+}
+
+const MAX_PREVIEW_FIELD_LENGTH = 1024;
+
+// The manifest a package with the given metadata would be installed with,
+// without any task: placeholder payload URL, and the given icon URL or the
+// placeholder icons. For clients checking their metadata before a
+// download exists.
+router.get("/install/preview", (req: Request, res: Response) => {
+  const field = (name: string): string | undefined => {
+    const value = req.query[name] ?? req.body?.[name];
+    return typeof value === "string" && value.trim() ? value.trim() : undefined;
+  };
+  const bundleID = field("bundleID");
+  const version = field("version");
+  const name = field("name") ?? bundleID;
+  const iconUrl = field("iconUrl");
+  if (!bundleID || !version || !name) {
+    sendError(res, 400, "bundleID and version are required");
+    return;
+  }
+  if (
+    [bundleID, version, name, iconUrl].some(
+      (v) => v && v.length > MAX_PREVIEW_FIELD_LENGTH,
+    )
+  ) {
+    sendError(
+      res,
+      400,
+      `Fields must be at most ${MAX_PREVIEW_FIELD_LENGTH} characters`,
+    );
+    return;
+  }
+  if (iconUrl && !/^https?:\/\//i.test(iconUrl)) {
+    sendError(res, 400, "iconUrl must be an http(s) URL");
+    return;
+  }
+
+  const baseUrl = getInstallBaseUrl(req);
+  const asset = (file: string) =>
+    joinUrl(baseUrl, `/api/install/preview/${file}`);
+  const manifest = buildManifest(
+    { bundleID, version, name },
+    asset("payload.ipa"),
+    iconUrl ?? asset("icon-small.png"),
+    iconUrl ?? asset("icon-large.png"),
+  );
+
+  res.setHeader("Content-Type", "application/xml");
+  res.send(manifest);
+});
+
 // Manifest plist for iTMS installation
 router.get("/install/:id/manifest.plist", (req: Request, res: Response) => {
   const id = getIdParam(req);
@@ -59,14 +184,30 @@ router.get("/install/:id/manifest.plist", (req: Request, res: Response) => {
   );
 
   if (!task || !task.filePath) {
-    res.status(404).json({ error: "Package not found" });
+    sendError(res, 404, "Package not found");
     return;
   }
 
-  const baseUrl = getBaseUrl(req);
-  const payloadUrl = joinUrl(baseUrl, `/api/install/${id}/payload.ipa`);
-  const smallIconUrl = joinUrl(baseUrl, `/api/install/${id}/icon-small.png`);
-  const largeIconUrl = joinUrl(baseUrl, `/api/install/${id}/icon-large.png`);
+  if (rejectInvalidToken(req, res, id)) return;
+
+  const baseUrl = getInstallBaseUrl(req);
+  const insecure = insecureInstallError(baseUrl);
+  if (insecure) {
+    sendError(res, 400, insecure);
+    return;
+  }
+
+  // Reuse the manifest's token so every URL expires together
+  const query = `?token=${encodeURIComponent(String(req.query.token))}`;
+  const payloadUrl = joinUrl(baseUrl, `/api/install/${id}/payload.ipa${query}`);
+  const smallIconUrl = joinUrl(
+    baseUrl,
+    `/api/install/${id}/icon-small.png${query}`,
+  );
+  const largeIconUrl = joinUrl(
+    baseUrl,
+    `/api/install/${id}/icon-large.png${query}`,
+  );
 
   const manifest = buildManifest(
     task.software,
@@ -79,19 +220,35 @@ router.get("/install/:id/manifest.plist", (req: Request, res: Response) => {
   res.send(manifest);
 });
 
+// Mint a tokenized install link (requires accountHash; owner or shared)
 router.get("/install/:id/url", (req: Request, res: Response) => {
+  const accountHash = requireAccountHash(req, res);
+  if (!accountHash) return;
+
   const id = getIdParam(req);
   const task = getAllTasks().find(
     (t) => t.id === id && t.status === "completed",
   );
 
   if (!task || !task.filePath) {
-    res.status(404).json({ error: "Package not found" });
+    sendError(res, 404, "Package not found");
     return;
   }
 
-  const baseUrl = getBaseUrl(req);
-  const manifestUrl = joinUrl(baseUrl, `/api/install/${id}/manifest.plist`);
+  if (!verifyTaskOwnership(task, accountHash, res)) return;
+
+  const baseUrl = getInstallBaseUrl(req);
+  const insecure = insecureInstallError(baseUrl);
+  if (insecure) {
+    sendError(res, 400, insecure);
+    return;
+  }
+
+  const token = encodeURIComponent(mintInstallToken(id));
+  const manifestUrl = joinUrl(
+    baseUrl,
+    `/api/install/${id}/manifest.plist?token=${token}`,
+  );
   const installUrl = `itms-services://?action=download-manifest&url=${encodeURIComponent(
     manifestUrl,
   )}`;
@@ -99,7 +256,7 @@ router.get("/install/:id/url", (req: Request, res: Response) => {
   res.json({ installUrl, manifestUrl });
 });
 
-// Stream IPA payload for installation
+// Stream IPA payload for installation (HEAD is served by the same handler)
 router.get("/install/:id/payload.ipa", (req: Request, res: Response) => {
   const id = getIdParam(req);
   const task = getAllTasks().find(
@@ -107,24 +264,20 @@ router.get("/install/:id/payload.ipa", (req: Request, res: Response) => {
   );
 
   if (!task || !task.filePath || !fs.existsSync(task.filePath)) {
-    res.status(404).json({ error: "Package not found" });
+    sendError(res, 404, "Package not found");
     return;
   }
 
-  // Verify file path is within packages directory
-  const packagesBase = path.resolve(path.join(config.dataDir, "packages"));
+  if (rejectInvalidToken(req, res, id)) return;
+
+  // Verify file path is within a package root
   const resolvedPath = path.resolve(task.filePath);
-  if (!resolvedPath.startsWith(packagesBase + path.sep)) {
-    res.status(403).json({ error: "Access denied" });
+  if (!packageRootOf(resolvedPath)) {
+    sendError(res, 403, "Access denied");
     return;
   }
 
-  res.setHeader("Content-Type", "application/octet-stream");
-  const stats = fs.statSync(resolvedPath);
-  res.setHeader("Content-Length", stats.size);
-
-  const stream = fs.createReadStream(resolvedPath);
-  stream.pipe(res);
+  sendPackageFile(req, res, resolvedPath);
 });
 
 // Small icon placeholder (57x57)
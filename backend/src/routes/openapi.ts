@@ -0,0 +1,1256 @@
+import { Router, Request, Response } from "express";
+import {
+  config,
+  MAX_DOWNLOAD_MIRRORS,
+  MAX_PACKAGE_CONTENTS_ENTRIES,
+  MAX_PAGE_SIZE,
+} from "../config.js";
+
+// OpenAPI 3.1 description of the HTTP API, for third-party clients. It is
+// written by hand next to the routes; tests/openapi.test.ts fails when a
+// registered route is missing from it. Field names follow src/types/api.ts.
+
+const router = Router();
+
+type Schema = Record<string, unknown>;
+
+const ref = (name: string) => ({ $ref: `#/components/schemas/${name}` });
+const json = (schema: Schema) => ({ "application/json": { schema } });
+const param = (name: string) => ({ $ref: `#/components/parameters/${name}` });
+
+function ok(description: string, schema?: Schema) {
+  return schema ? { description, content: json(schema) } : { description };
+}
+
+function body(schema: Schema, required = true) {
+  return { required, content: json(schema) };
+}
+
+// Error responses all share the ApiError body
+function errors(...statuses: number[]) {
+  return Object.fromEntries(
+    statuses.map((status) => [
+      String(status),
+      { $ref: `#/components/responses/Error${status}` },
+    ]),
+  );
+}
+
+const ERROR_DESCRIPTIONS: Record<number, string> = {
+  400: "Invalid request",
+  401: "Missing or wrong admin token, or expired session",
+  403: "Not allowed for this account or link",
+  404: "Not found",
+  409: "Not possible in the current state",
+  410: "No longer available",
+  413: "Larger than allowed",
+  422: "Could not be processed",
+  500: "Upstream or server failure",
+  502: "Bad upstream response",
+  503: "Upstream or connection limit; see Retry-After",
+  507: "Server is low on disk space",
+};
+
+const ACCOUNT = [{ accountHash: [] }, { session: [] }];
+const ADMIN = [{ adminToken: [] }];
+
+const schemas: Record<string, Schema> = {
+  ApiError: {
+    type: "object",
+    required: ["error"],
+    properties: {
+      error: { type: "string" },
+      code: {
+        type: "string",
+        description: "Stable UPPER_SNAKE identifier, when there is one",
+      },
+    },
+  },
+  Software: {
+    type: "object",
+    required: ["id", "bundleID", "version"],
+    properties: {
+      id: { type: "integer" },
+      bundleID: { type: "string" },
+      name: { type: "string" },
+      version: { type: "string" },
+      artistName: { type: "string" },
+      artworkUrl: { type: "string" },
+      externalVersionId: { type: "string" },
+      purchaseDate: { type: "string", format: "date-time" },
+    },
+    additionalProperties: true,
+  },
+  Sinf: {
+    type: "object",
+    required: ["id", "sinf"],
+    properties: {
+      id: { type: "integer" },
+      sinf: { type: "string", contentEncoding: "base64" },
+    },
+  },
+  Task: {
+    type: "object",
+    required: ["id", "software", "accountHash", "status", "progress"],
+    properties: {
+      id: { type: "string" },
+      software: ref("Software"),
+      accountHash: { type: "string" },
+      status: {
+        enum: [
+          "pending",
+          "downloading",
+          "paused",
+          "injecting",
+          "completed",
+          "failed",
+          "trashed",
+          "removed",
+        ],
+      },
+      priority: { enum: ["high", "normal", "low"] },
+      metadataCompression: { enum: ["stored", "deflated"] },
+      injectionPhase: { enum: ["queued", "running"] },
+      progress: { type: "number" },
+      speed: { type: "string" },
+      etaSeconds: { type: "integer" },
+      indeterminate: {
+        type: "boolean",
+        description:
+          "While downloading: the size is unknown, so progress stays 0; show downloadedBytes",
+      },
+      downloadedBytes: { type: "integer" },
+      totalBytes: {
+        type: "integer",
+        description: "Upstream size probed at creation",
+      },
+      resumable: {
+        type: "boolean",
+        description: "Whether the upstream honoured a byte range when probed",
+      },
+      queuePosition: { type: "integer" },
+      mirrorIndex: { type: "integer" },
+      replaces: {
+        type: "array",
+        items: { type: "string" },
+        description:
+          "Completed tasks deleted once this replace=true download completes",
+      },
+      error: { type: "string" },
+      errorCategory: { type: "string" },
+      errorCode: { type: "string", description: "Owner only" },
+      errorDetail: { type: "string", description: "Owner only" },
+      hasFile: { type: "boolean" },
+      fileSize: { type: "integer" },
+      sha256: { type: "string" },
+      corrupt: { type: "boolean" },
+      origin: { enum: ["imported", "adopted", "uploaded"] },
+      trashedAt: { type: "string", format: "date-time" },
+      removedAt: { type: "string", format: "date-time" },
+      pauseExpiresAt: {
+        type: "string",
+        format: "date-time",
+        description: "Owner only, with PAUSE_MAX_AGE_HOURS",
+      },
+      removedReason: { enum: ["age", "space", "account_limit", "replaced"] },
+      stats: { type: "object", description: "With ?verbose=true" },
+      events: {
+        type: "array",
+        description: "Single-task view only",
+        items: {
+          type: "object",
+          properties: {
+            type: { type: "string" },
+            at: { type: "string", format: "date-time" },
+            detail: { type: "string" },
+          },
+        },
+      },
+      updatedAt: { type: "string", format: "date-time" },
+      createdAt: { type: "string", format: "date-time" },
+    },
+  },
+  TaskSummary: {
+    type: "object",
+    required: ["id", "name", "version", "status"],
+    properties: {
+      id: { type: "string" },
+      name: { type: "string" },
+      version: { type: "string" },
+      status: { type: "string" },
+      fileSize: { type: "integer" },
+    },
+  },
+  CreateDownload: {
+    type: "object",
+    required: ["software", "accountHash", "sinfs"],
+    properties: {
+      software: ref("Software"),
+      accountHash: { type: "string" },
+      downloadURL: { type: "string", format: "uri" },
+      downloadURLs: {
+        type: "array",
+        items: { type: "string", format: "uri" },
+        maxItems: MAX_DOWNLOAD_MIRRORS,
+        description: "Mirrors tried in order; downloadURL alone is one",
+      },
+      sinfs: { type: "array", items: ref("Sinf") },
+      iTunesMetadata: { type: "string", contentEncoding: "base64" },
+      metadataCompression: {
+        enum: ["stored", "deflated"],
+        description:
+          "Zip method for iTunesMetadata.plist (default METADATA_COMPRESSION); sinfs are always stored",
+      },
+      headers: {
+        type: "object",
+        additionalProperties: { type: "string" },
+        description: "Authorization, Cookie and X-Apple-* only",
+      },
+      priority: { enum: ["high", "normal", "low"] },
+      expectedMd5: { type: "string", pattern: "^[0-9a-fA-F]{32}$" },
+      expectedSha1: { type: "string", pattern: "^[0-9a-fA-F]{40}$" },
+      externalVersionId: { type: "string", pattern: "^\\d{1,20}$" },
+      purchaseDate: { type: "string", format: "date-time" },
+      replace: {
+        type: "boolean",
+        description:
+          "Download a completed version again and delete the old package once the new one is verified",
+      },
+      skipProbe: {
+        type: "boolean",
+        description:
+          "Skip the bytes=0-0 request that reports totalBytes/resumable and answers 410 for an expired URL",
+      },
+    },
+  },
+  Package: {
+    type: "object",
+    required: ["id", "software", "accountHash", "fileSize", "createdAt"],
+    properties: {
+      id: { type: "string" },
+      software: ref("Software"),
+      accountHash: { type: "string" },
+      relativePath: { type: "string" },
+      fileSize: { type: "integer" },
+      corrupt: { type: "boolean" },
+      trashedAt: { type: "string", format: "date-time" },
+      createdAt: { type: "string", format: "date-time" },
+      updatedAt: { type: "string", format: "date-time" },
+    },
+  },
+  PackageWarning: {
+    type: "object",
+    required: ["id", "reason"],
+    properties: {
+      id: { type: "string" },
+      reason: { enum: ["file_missing", "file_unreadable"] },
+    },
+  },
+  PackageHealth: {
+    type: "object",
+    properties: {
+      ok: { type: "boolean" },
+      size: { type: "integer" },
+      sha256: { type: "string" },
+      validZip: { type: "boolean" },
+      entries: { type: "integer" },
+      sinfEntries: { type: "integer" },
+      problems: { type: "array", items: { type: "string" } },
+    },
+  },
+  PackageContents: {
+    type: "object",
+    properties: {
+      entries: {
+        type: "array",
+        items: {
+          type: "object",
+          properties: {
+            path: { type: "string" },
+            compressedSize: { type: "integer" },
+            uncompressedSize: { type: "integer" },
+            method: {
+              type: "string",
+              description: '"stored", "deflated" or "method <n>"',
+            },
+          },
+        },
+      },
+      total: { type: "integer" },
+      truncated: { type: "boolean" },
+    },
+  },
+  UploadMetadata: {
+    type: "object",
+    properties: {
+      software: {
+        type: "object",
+        description:
+          "Store fields; bundleID and version must match the IPA when given",
+      },
+      sinfs: { type: "array", items: ref("Sinf") },
+      iTunesMetadata: { type: "string" },
+      metadataCompression: { enum: ["stored", "deflated"] },
+    },
+  },
+  Upload: {
+    type: "object",
+    properties: {
+      id: { type: "string" },
+      size: { type: "integer" },
+      received: {
+        type: "integer",
+        description: "Bytes stored; the Upload-Offset of the next PUT",
+      },
+      expiresAt: { type: "string", format: "date-time" },
+    },
+  },
+  WispConnection: {
+    type: "object",
+    properties: {
+      id: { type: "string" },
+      remoteAddress: { type: "string" },
+      connectedAt: { type: "string", format: "date-time" },
+      streams: {
+        type: "array",
+        items: {
+          type: "object",
+          properties: {
+            streamId: { type: "integer" },
+            hostname: { type: "string" },
+            port: { type: "integer" },
+            openedAt: { type: "string", format: "date-time" },
+            bytesSent: { type: "integer" },
+            bytesReceived: { type: "integer" },
+          },
+        },
+      },
+    },
+  },
+  Sharing: {
+    type: "object",
+    properties: {
+      sharedWith: { type: "array", items: { type: "string" } },
+    },
+  },
+  Success: {
+    type: "object",
+    properties: {
+      success: { type: "boolean" },
+      trashed: { type: "boolean" },
+    },
+  },
+  Settings: {
+    type: "object",
+    properties: {
+      uptime: { type: "integer" },
+      version: { type: "string" },
+      buildCommit: { type: "string" },
+      buildDate: { type: "string" },
+      port: { type: "integer" },
+      dataDir: { type: "string" },
+      publicBaseUrl: { type: "string" },
+      disableHttpsRedirect: { type: "boolean" },
+      readOnly: {
+        type: "boolean",
+        description: "Maintenance mode: downloads and packages are read-only",
+      },
+      autoCleanupDays: { type: "number" },
+      autoCleanupMaxMB: { type: "number" },
+      autoCleanupMaxPerAccount: { type: "integer" },
+      limits: {
+        type: "object",
+        properties: { maxDownloadSize: { type: "integer" } },
+      },
+      defaultStorefront: { type: "string" },
+      progressPollIntervalMs: { type: "integer" },
+      upstreams: { type: "object" },
+      stalledDownloads: { type: "object" },
+      storage: { type: "object" },
+      packageRoots: {
+        type: "array",
+        items: {
+          type: "object",
+          properties: {
+            path: { type: "string" },
+            packages: { type: "integer" },
+            packageBytes: { type: "integer" },
+            freeBytes: { type: ["integer", "null"] },
+            default: { type: "boolean" },
+          },
+        },
+      },
+    },
+  },
+  Migration: {
+    type: "object",
+    properties: {
+      status: { enum: ["running", "completed"] },
+      startedAt: { type: "string", format: "date-time" },
+      finishedAt: { type: "string", format: "date-time" },
+      deleteSource: { type: "boolean" },
+      totals: { type: "object" },
+      tasks: { type: "array", items: { type: "object" } },
+    },
+  },
+};
+
+const idPath = { in: "path", name: "id", required: true };
+
+const parameters: Record<string, Schema> = {
+  id: { ...idPath, schema: { type: "string" } },
+  accountHashesHeader: {
+    in: "header",
+    name: "X-Account-Hashes",
+    description: `Comma-separated account hashes (at most ${config.maxAccountHashes}); preferred over the query param`,
+    schema: { type: "string" },
+  },
+  accountHashes: {
+    in: "query",
+    name: "accountHashes",
+    description: `Comma-separated and/or repeated account hashes (at most ${config.maxAccountHashes}); rejected with STRICT_ACCOUNT_HEADER`,
+    deprecated: true,
+    schema: { type: "string" },
+  },
+  limit: {
+    in: "query",
+    name: "limit",
+    schema: { type: "integer", minimum: 1, maximum: MAX_PAGE_SIZE },
+  },
+  cursor: {
+    in: "query",
+    name: "cursor",
+    description: "From the X-Next-Cursor header of the previous page",
+    schema: { type: "string" },
+  },
+  includeTrashed: {
+    in: "query",
+    name: "includeTrashed",
+    schema: { type: "boolean" },
+  },
+  since: {
+    in: "query",
+    name: "since",
+    description:
+      "Only tasks created or updated at or after this time (ISO date or epoch milliseconds), e.g. the previous response's X-Server-Time. Deleted tasks are not reported",
+    schema: { type: "string" },
+  },
+  includeWarnings: {
+    in: "query",
+    name: "includeWarnings",
+    description:
+      "Answer { packages, warnings } instead of an array, listing packages left out because their file is missing or unreadable",
+    schema: { type: "boolean" },
+  },
+  installToken: {
+    in: "query",
+    name: "token",
+    required: true,
+    description: "From GET /install/{id}/url",
+    schema: { type: "string" },
+  },
+  country: {
+    in: "query",
+    name: "country",
+    description: "ISO 3166-1 alpha-2 storefront",
+    schema: { type: "string" },
+  },
+};
+
+const LIST = [
+  param("accountHashesHeader"),
+  param("accountHashes"),
+  param("limit"),
+  param("cursor"),
+];
+const PACKAGE_LIST = {
+  oneOf: [
+    { type: "array", items: ref("Package") },
+    {
+      type: "object",
+      required: ["packages", "warnings"],
+      properties: {
+        packages: { type: "array", items: ref("Package") },
+        warnings: { type: "array", items: ref("PackageWarning") },
+      },
+    },
+  ],
+};
+const IPA = { "application/octet-stream": { schema: { type: "string" } } };
+const PNG = { "image/png": { schema: { type: "string" } } };
+
+const paths: Record<string, Record<string, Schema>> = {
+  "/openapi.json": {
+    get: {
+      summary: "This document",
+      responses: { 200: ok("OpenAPI document", { type: "object" }) },
+    },
+  },
+  "/settings": {
+    get: {
+      summary: "Server settings and health",
+      responses: { 200: ok("Settings", ref("Settings")) },
+    },
+  },
+  "/session": {
+    post: {
+      summary: "Exchange an account hash for a session token",
+      description:
+        "Send the token as `Authorization: Session <token>` instead of the account hash until it expires (SESSION_TTL_SECONDS).",
+      requestBody: body({
+        type: "object",
+        required: ["accountHash"],
+        properties: { accountHash: { type: "string" } },
+      }),
+      responses: {
+        201: ok("Session", {
+          type: "object",
+          properties: {
+            token: { type: "string" },
+            expiresAt: { type: "string", format: "date-time" },
+          },
+        }),
+        ...errors(400, 503),
+      },
+    },
+    delete: {
+      summary: "Revoke the session in Authorization",
+      security: [{ session: [] }],
+      responses: {
+        204: { description: "Revoked" },
+        ...errors(400, 404),
+      },
+    },
+  },
+  "/search": {
+    get: {
+      summary: "Search the App Store (iTunes Search API passthrough)",
+      parameters: [
+        param("country"),
+        { in: "query", name: "term", schema: { type: "string" } },
+      ],
+      responses: {
+        200: ok("Matching apps", { type: "array", items: ref("Software") }),
+        ...errors(400, 500, 503),
+      },
+    },
+  },
+  "/lookup": {
+    get: {
+      summary: "Look up one app (iTunes Lookup API passthrough)",
+      parameters: [
+        param("country"),
+        { in: "query", name: "id", schema: { type: "string" } },
+        { in: "query", name: "bundleId", schema: { type: "string" } },
+      ],
+      responses: {
+        200: ok("The app, or null", {
+          oneOf: [ref("Software"), { type: "null" }],
+        }),
+        ...errors(400, 500, 503),
+      },
+    },
+  },
+  "/bag": {
+    get: {
+      summary: "Apple's init bag for a device GUID",
+      parameters: [
+        {
+          in: "query",
+          name: "guid",
+          required: true,
+          schema: { type: "string", pattern: "^[0-9a-fA-F]+$" },
+        },
+      ],
+      responses: {
+        200: {
+          description: "Bag plist",
+          content: { "application/xml": { schema: { type: "string" } } },
+        },
+        ...errors(400, 502, 503),
+      },
+    },
+  },
+  "/downloads": {
+    get: {
+      summary: "List downloads of the given accounts",
+      parameters: [
+        ...LIST,
+        param("includeTrashed"),
+        param("since"),
+        { in: "query", name: "summary", schema: { type: "boolean" } },
+      ],
+      responses: {
+        200: ok("Tasks (TaskSummary with ?summary=true)", {
+          type: "array",
+          items: { oneOf: [ref("Task"), ref("TaskSummary")] },
+        }),
+        ...errors(400),
+      },
+    },
+    post: {
+      summary: "Start a download",
+      requestBody: body(ref("CreateDownload")),
+      responses: {
+        200: ok("Existing task this request duplicates", ref("Task")),
+        201: ok("Created task", ref("Task")),
+        ...errors(400, 410, 507),
+      },
+    },
+  },
+  "/downloads/{id}": {
+    get: {
+      summary: "One download with its event history",
+      security: ACCOUNT,
+      parameters: [
+        param("id"),
+        { in: "query", name: "verbose", schema: { type: "boolean" } },
+      ],
+      responses: { 200: ok("Task", ref("Task")), ...errors(400, 403, 404) },
+    },
+    patch: {
+      summary: "Replace an expired download ticket and restart (owner only)",
+      security: ACCOUNT,
+      parameters: [param("id")],
+      requestBody: body({
+        type: "object",
+        required: ["downloadURL"],
+        properties: {
+          downloadURL: { type: "string", format: "uri" },
+          sinfs: { type: "array", items: ref("Sinf") },
+          iTunesMetadata: { type: "string" },
+          headers: { type: "object" },
+        },
+      }),
+      responses: {
+        200: ok("Task", ref("Task")),
+        ...errors(400, 403, 404, 409),
+      },
+    },
+    delete: {
+      summary: "Delete a download (completed packages go to the trash)",
+      security: ACCOUNT,
+      parameters: [
+        param("id"),
+        { in: "query", name: "purge", schema: { type: "boolean" } },
+      ],
+      responses: {
+        200: ok("Deleted", ref("Success")),
+        ...errors(400, 403, 404),
+      },
+    },
+  },
+  "/downloads/{id}/progress": {
+    get: {
+      summary: "Progress stream",
+      description:
+        "Server-sent events; every `data:` line is a JSON Task. The current state is sent first.",
+      security: ACCOUNT,
+      parameters: [param("id")],
+      responses: {
+        200: {
+          description: "Event stream",
+          content: { "text/event-stream": { schema: { type: "string" } } },
+        },
+        ...errors(400, 403, 404, 503),
+      },
+    },
+  },
+  "/downloads/{id}/pause": {
+    post: {
+      summary: "Pause a download (owner only)",
+      security: ACCOUNT,
+      parameters: [param("id")],
+      responses: {
+        200: ok("Task", ref("Task")),
+        ...errors(400, 403, 404, 409),
+      },
+    },
+  },
+  "/downloads/{id}/resume": {
+    post: {
+      summary: "Resume a paused download (owner only)",
+      security: ACCOUNT,
+      parameters: [param("id")],
+      responses: {
+        200: ok("Task", ref("Task")),
+        ...errors(400, 403, 404, 409),
+      },
+    },
+  },
+  "/downloads/{id}/priority": {
+    post: {
+      summary: "Reorder a queued download (owner only)",
+      security: ACCOUNT,
+      parameters: [param("id")],
+      requestBody: body({
+        type: "object",
+        required: ["priority"],
+        properties: { priority: { enum: ["high", "normal", "low"] } },
+      }),
+      responses: {
+        200: ok("Task", ref("Task")),
+        ...errors(400, 403, 404, 409),
+      },
+    },
+  },
+  "/downloads/{id}/sharing": {
+    get: {
+      summary: "Accounts the download is shared with (owner only)",
+      security: ACCOUNT,
+      parameters: [param("id")],
+      responses: {
+        200: ok("Sharing", ref("Sharing")),
+        ...errors(400, 403, 404),
+      },
+    },
+    post: {
+      summary: "Grant or revoke access (owner only)",
+      security: ACCOUNT,
+      parameters: [param("id")],
+      requestBody: body({
+        type: "object",
+        properties: {
+          grant: { type: "array", items: { type: "string" } },
+          revoke: { type: "array", items: { type: "string" } },
+        },
+      }),
+      responses: {
+        200: ok("Sharing", ref("Sharing")),
+        ...errors(400, 403, 404),
+      },
+    },
+  },
+  "/packages": {
+    get: {
+      summary: "List completed packages of the given accounts",
+      parameters: [
+        ...LIST,
+        param("includeTrashed"),
+        param("since"),
+        param("includeWarnings"),
+      ],
+      responses: {
+        200: ok("Packages", PACKAGE_LIST),
+        ...errors(400),
+      },
+    },
+  },
+  "/packages/search": {
+    get: {
+      summary: "Search packages by name or bundle ID",
+      parameters: [
+        ...LIST,
+        { in: "query", name: "q", schema: { type: "string" } },
+        { in: "query", name: "minSize", schema: { type: "integer" } },
+        { in: "query", name: "from", schema: { type: "string" } },
+        { in: "query", name: "to", schema: { type: "string" } },
+        { in: "query", name: "version", schema: { type: "string" } },
+        param("includeWarnings"),
+      ],
+      responses: {
+        200: ok("Packages", PACKAGE_LIST),
+        ...errors(400),
+      },
+    },
+  },
+  "/packages/{id}": {
+    delete: {
+      summary: "Delete a package (owner only)",
+      security: ACCOUNT,
+      parameters: [param("id")],
+      responses: {
+        200: ok("Deleted", ref("Success")),
+        ...errors(400, 403, 404),
+      },
+    },
+  },
+  "/packages/{id}/file": {
+    get: {
+      summary: "Download the IPA (HEAD supported)",
+      security: ACCOUNT,
+      parameters: [param("id")],
+      responses: {
+        200: { description: "IPA file", content: IPA },
+        ...errors(400, 403, 404),
+      },
+    },
+  },
+  "/packages/{id}/contents": {
+    get: {
+      summary: "List the files in a package (owner only)",
+      security: ACCOUNT,
+      parameters: [
+        param("id"),
+        { in: "query", name: "prefix", schema: { type: "string" } },
+        {
+          in: "query",
+          name: "limit",
+          schema: {
+            type: "integer",
+            minimum: 1,
+            maximum: MAX_PACKAGE_CONTENTS_ENTRIES,
+          },
+        },
+      ],
+      responses: {
+        200: ok("Central directory entries", ref("PackageContents")),
+        ...errors(400, 403, 404, 422),
+      },
+    },
+  },
+  "/packages/{id}/verify": {
+    get: {
+      summary: "Result of the last verification",
+      security: ACCOUNT,
+      parameters: [param("id")],
+      responses: {
+        200: ok("Finished verification", { type: "object" }),
+        202: ok("Still running", { type: "object" }),
+        ...errors(400, 403, 404),
+      },
+    },
+    post: {
+      summary: "Re-hash and re-open a package",
+      security: ACCOUNT,
+      parameters: [param("id")],
+      responses: {
+        200: ok("Report", ref("PackageHealth")),
+        202: ok("Running in the background; poll GET", { type: "object" }),
+        ...errors(400, 403, 404, 500),
+      },
+    },
+  },
+  "/packages/{id}/reinject": {
+    post: {
+      summary: "Inject new sinfs or iTunesMetadata into a package",
+      security: ACCOUNT,
+      parameters: [param("id")],
+      requestBody: body({
+        type: "object",
+        required: ["sinfs"],
+        properties: {
+          sinfs: { type: "array", items: ref("Sinf") },
+          iTunesMetadata: { type: "string" },
+          metadataCompression: { enum: ["stored", "deflated"] },
+        },
+      }),
+      responses: {
+        200: ok("Injected", { type: "object" }),
+        ...errors(400, 403, 404, 409, 422),
+      },
+    },
+  },
+  "/packages/{id}/restore": {
+    post: {
+      summary: "Restore a trashed package (owner only)",
+      security: ACCOUNT,
+      parameters: [param("id")],
+      responses: {
+        200: ok("Restored", ref("Success")),
+        ...errors(400, 403, 404, 409, 410),
+      },
+    },
+  },
+  "/packages/upload": {
+    post: {
+      summary: "Upload an IPA in one request as a completed package",
+      description:
+        "Metadata is base64 JSON (UploadMetadata) in X-Upload-Metadata. The IPA is validated, injected and owned by the account.",
+      security: ACCOUNT,
+      parameters: [
+        {
+          in: "header",
+          name: "X-Upload-Metadata",
+          schema: { type: "string" },
+        },
+      ],
+      requestBody: { required: true, content: IPA },
+      responses: {
+        201: ok("Package created", ref("Task")),
+        ...errors(400, 413, 422, 503, 507),
+      },
+    },
+  },
+  "/packages/uploads": {
+    post: {
+      summary: "Start a chunked upload",
+      security: ACCOUNT,
+      requestBody: body({
+        allOf: [
+          ref("UploadMetadata"),
+          {
+            type: "object",
+            required: ["size"],
+            properties: { size: { type: "integer" } },
+          },
+        ],
+      }),
+      responses: {
+        201: ok("Upload started", ref("Upload")),
+        ...errors(400, 413, 503, 507),
+      },
+    },
+  },
+  "/packages/uploads/{id}": {
+    get: {
+      summary: "Progress of a chunked upload",
+      security: ACCOUNT,
+      parameters: [param("id")],
+      responses: {
+        200: ok("Upload", ref("Upload")),
+        ...errors(403, 404),
+      },
+    },
+    put: {
+      summary: "Append bytes to a chunked upload",
+      description:
+        "Upload-Offset must equal received; after a dropped request, GET the upload and resume from there.",
+      security: ACCOUNT,
+      parameters: [
+        param("id"),
+        {
+          in: "header",
+          name: "Upload-Offset",
+          required: true,
+          schema: { type: "integer" },
+        },
+      ],
+      requestBody: { required: true, content: IPA },
+      responses: {
+        200: ok("Bytes stored", ref("Upload")),
+        ...errors(400, 403, 404, 409, 413, 507),
+      },
+    },
+    delete: {
+      summary: "Abandon a chunked upload",
+      security: ACCOUNT,
+      parameters: [param("id")],
+      responses: {
+        204: { description: "Deleted" },
+        ...errors(403, 404),
+      },
+    },
+  },
+  "/packages/uploads/{id}/complete": {
+    post: {
+      summary: "Turn a fully received upload into a completed package",
+      security: ACCOUNT,
+      parameters: [param("id")],
+      responses: {
+        201: ok("Package created", ref("Task")),
+        ...errors(403, 404, 409, 422),
+      },
+    },
+  },
+  "/install/preview": {
+    get: {
+      summary: "Preview the install manifest for given metadata",
+      description:
+        "Needs no task: asset URLs are placeholders unless iconUrl is given. name defaults to bundleID.",
+      parameters: [
+        { in: "query", name: "bundleID", schema: { type: "string" } },
+        { in: "query", name: "version", schema: { type: "string" } },
+        { in: "query", name: "name", schema: { type: "string" } },
+        { in: "query", name: "iconUrl", schema: { type: "string" } },
+      ],
+      responses: {
+        200: {
+          description: "Manifest",
+          content: { "application/xml": { schema: { type: "string" } } },
+        },
+        ...errors(400),
+      },
+    },
+  },
+  "/install/{id}/url": {
+    get: {
+      summary: "Mint a tokenized itms-services install link",
+      security: ACCOUNT,
+      parameters: [param("id")],
+      responses: {
+        200: ok("Install link", {
+          type: "object",
+          properties: {
+            installUrl: { type: "string" },
+            manifestUrl: { type: "string" },
+          },
+        }),
+        ...errors(400, 403, 404),
+      },
+    },
+  },
+  "/install/{id}/manifest.plist": {
+    get: {
+      summary: "Install manifest, fetched by iOS",
+      parameters: [param("id"), param("installToken")],
+      responses: {
+        200: {
+          description: "Manifest",
+          content: { "application/xml": { schema: { type: "string" } } },
+        },
+        ...errors(400, 403, 404),
+      },
+    },
+  },
+  "/install/{id}/payload.ipa": {
+    get: {
+      summary: "IPA for installation, fetched by iOS",
+      parameters: [param("id"), param("installToken")],
+      responses: {
+        200: { description: "IPA file", content: IPA },
+        ...errors(403, 404),
+      },
+    },
+  },
+  "/install/{id}/icon-small.png": {
+    get: {
+      summary: "Placeholder icon (57x57)",
+      parameters: [param("id")],
+      responses: { 200: { description: "PNG", content: PNG } },
+    },
+  },
+  "/install/{id}/icon-large.png": {
+    get: {
+      summary: "Placeholder icon (512x512)",
+      parameters: [param("id")],
+      responses: { 200: { description: "PNG", content: PNG } },
+    },
+  },
+  "/admin/config": {
+    get: {
+      summary: "Runtime settings in effect",
+      security: ADMIN,
+      responses: { 200: ok("Settings", { type: "object" }), ...errors(401) },
+    },
+  },
+  "/admin/config/reload": {
+    post: {
+      summary: "Re-read CONFIG_FILE",
+      security: ADMIN,
+      responses: {
+        200: ok("Reloaded", { type: "object" }),
+        ...errors(400, 401),
+      },
+    },
+  },
+  "/admin/migrate-storage": {
+    post: {
+      summary: "Copy completed packages to the MIGRATE_S3_* bucket",
+      security: ADMIN,
+      requestBody: body(
+        {
+          type: "object",
+          properties: { deleteSource: { type: "boolean" } },
+        },
+        false,
+      ),
+      responses: {
+        202: ok("Started", ref("Migration")),
+        ...errors(400, 401, 409),
+      },
+    },
+  },
+  "/admin/migrate-storage/status": {
+    get: {
+      summary: "Progress of the current or last migration",
+      security: ADMIN,
+      responses: {
+        200: ok("Migration", ref("Migration")),
+        ...errors(401, 404),
+      },
+    },
+  },
+  "/admin/tasks/{id}/force": {
+    post: {
+      summary: "Force a wedged download to failed, completed or paused",
+      security: ADMIN,
+      parameters: [param("id")],
+      requestBody: body({
+        type: "object",
+        required: ["target"],
+        properties: { target: { enum: ["fail", "complete", "pause"] } },
+      }),
+      responses: {
+        200: ok("Task with its event history", ref("Task")),
+        ...errors(400, 401, 404, 409),
+      },
+    },
+  },
+  "/admin/read-only": {
+    put: {
+      summary: "Turn read-only maintenance mode on or off",
+      description:
+        "While on, writes to /downloads and /packages answer 503 READ_ONLY. It can't be lifted (409 TASK_STORE_UNREADABLE) after a startup that could not read tasks.json.",
+      security: ADMIN,
+      requestBody: body({
+        type: "object",
+        required: ["enabled"],
+        properties: { enabled: { type: "boolean" } },
+      }),
+      responses: {
+        200: ok("Mode in effect", {
+          type: "object",
+          properties: { readOnly: { type: "boolean" } },
+        }),
+        ...errors(400, 401, 409),
+      },
+    },
+  },
+  "/admin/wisp/connections": {
+    get: {
+      summary: "Open Wisp connections and their streams",
+      security: ADMIN,
+      responses: {
+        200: ok("Connections", {
+          type: "array",
+          items: ref("WispConnection"),
+        }),
+        ...errors(401),
+      },
+    },
+  },
+  "/admin/wisp/connections/{id}": {
+    delete: {
+      summary: "Close a Wisp connection",
+      description:
+        "Sends CLOSE for each open stream, then closes the WebSocket and the target sockets.",
+      security: ADMIN,
+      parameters: [param("id")],
+      responses: {
+        204: { description: "Closed" },
+        ...errors(401, 404),
+      },
+    },
+  },
+  "/admin/export": {
+    get: {
+      summary: "Export every task as JSON lines",
+      description:
+        "One stored task per line, without download tickets or other secrets.",
+      security: ADMIN,
+      responses: {
+        200: {
+          description: "Task records",
+          content: { "application/x-ndjson": { schema: { type: "string" } } },
+        },
+        ...errors(401),
+      },
+    },
+  },
+  "/admin/import": {
+    post: {
+      summary: "Merge tasks from an export",
+      description:
+        "Skips invalid records, ids that already exist and tasks whose package file is missing.",
+      security: ADMIN,
+      requestBody: {
+        required: true,
+        content: { "application/x-ndjson": { schema: { type: "string" } } },
+      },
+      responses: {
+        200: ok("Import outcome", {
+          type: "object",
+          properties: {
+            imported: { type: "integer" },
+            skipped: {
+              type: "array",
+              items: {
+                type: "object",
+                properties: {
+                  index: { type: "integer" },
+                  id: { type: "string" },
+                  reason: { type: "string" },
+                },
+              },
+            },
+          },
+        }),
+        ...errors(400, 401),
+      },
+    },
+  },
+  "/dev/chaos": {
+    post: {
+      summary: "Arm development faults (only with CHAOS_ENABLED)",
+      description:
+        "Armed faults fire once, except sseDelayMs; null disarms a fault.",
+      requestBody: body({
+        type: "object",
+        properties: {
+          failDownloadAtPercent: { type: ["integer", "null"] },
+          sseDelayMs: { type: ["integer", "null"] },
+          failInjection: { type: ["boolean", "null"] },
+          failRoute: {
+            type: ["string", "null"],
+            description: 'Method and path under /api, e.g. "GET /packages"',
+          },
+        },
+      }),
+      responses: {
+        200: ok("Faults still armed", { type: "object" }),
+        ...errors(400),
+      },
+    },
+  },
+  "/admin/docs": {
+    get: {
+      summary: "Swagger UI for this document",
+      security: ADMIN,
+      responses: {
+        200: {
+          description: "HTML page",
+          content: { "text/html": { schema: { type: "string" } } },
+        },
+        ...errors(401),
+      },
+    },
+  },
+};
+
+/** The document, with the server URL under the configured BASE_PATH. */
+export function buildOpenApiDocument() {
+  return {
+    openapi: "3.1.0",
+    info: {
+      title: "AssppWeb API",
+      version: config.buildCommit,
+      description:
+        "Endpoints marked with accountHash security also accept the hash as `X-Account-Hash` or an `accountHash` query/body field. List endpoints page with `limit`/`cursor` and return the next cursor in `X-Next-Cursor`. The downloads and packages lists also send `X-Server-Time`, to pass as `since` on the next poll.",
+    },
+    servers: [{ url: `${config.basePath}/api` }],
+    paths,
+    components: {
+      schemas,
+      parameters,
+      responses: Object.fromEntries(
+        Object.entries(ERROR_DESCRIPTIONS).map(([status, description]) => [
+          `Error${status}`,
+          { description, content: json(ref("ApiError")) },
+        ]),
+      ),
+      securitySchemes: {
+        accountHash: {
+          type: "apiKey",
+          in: "header",
+          name: "X-Account-Hash",
+          description:
+            "Or Authorization: Bearer <hash>; ?accountHash= is deprecated",
+        },
+        session: {
+          type: "apiKey",
+          in: "header",
+          name: "Authorization",
+          description: "Session <token> from POST /session",
+        },
+        adminToken: { type: "http", scheme: "bearer" },
+      },
+    },
+  };
+}
+
+router.get("/openapi.json", (_req: Request, res: Response) => {
+  res.json(buildOpenApiDocument());
+});
+
+export default router;
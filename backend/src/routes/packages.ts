@@ -1,14 +1,59 @@
 import { Router, Request, Response } from "express";
 import fs from "fs";
 import path from "path";
-import { config } from "../config.js";
-import { MIN_ACCOUNT_HASH_LENGTH } from "../config.js";
-import { getAllTasks } from "../services/downloadManager.js";
-import { getIdParam } from "../utils/route.js";
-import type { PackageInfo } from "../types/index.js";
+import {
+  config,
+  MAX_PACKAGE_CONTENTS_ENTRIES,
+  MAX_SEARCH_QUERY_LENGTH,
+  VERIFY_INLINE_MAX_BYTES,
+} from "../config.js";
+import { rejectWritesWhenReadOnly } from "../middleware/readOnly.js";
+import {
+  deleteTask,
+  getAllTasks,
+  getVerificationJob,
+  packageRelativePath,
+  purgePackageFile,
+  reinjectTask,
+  restoreTrashedTask,
+  trashTask,
+  verifyTaskPackage,
+} from "../services/downloadManager.js";
+import { getPackageContents } from "../services/packageContents.js";
+import { packageRootOf } from "../services/packageRoots.js";
+import { isValidMetadataCompression } from "../services/sinfInjector.js";
+import {
+  canAccessTask,
+  getIdParam,
+  INVALID_SINFS,
+  isValidSinfs,
+  parseAccountHashes,
+  requireAccountHash,
+  sendError,
+  sendPackageFile,
+  verifyTaskOwner,
+  verifyTaskOwnership,
+} from "../utils/route.js";
+import {
+  changedSince,
+  paginate,
+  parsePageParams,
+  parseSince,
+  setNextCursor,
+  setServerTime,
+} from "../utils/pagination.js";
+import type {
+  PackageContentsResponse,
+  PackageListResponse,
+  PackageResponse,
+  PackageWarning,
+} from "../types/api.js";
+import type { DownloadTask } from "../types/index.js";
 
 const router = Router();
 
+router.use("/packages", rejectWritesWhenReadOnly);
+
 // Sanitize filename for Content-Disposition to prevent header injection
 function sanitizeFilename(name: string): string {
   // Remove control characters, quotes, backslashes, and non-ASCII
@@ -19,47 +64,181 @@ function sanitizeFilename(name: string): string {
     .slice(0, 200);
 }
 
-// List packages filtered by account hashes
-router.get("/packages", (req: Request, res: Response) => {
-  const hashesParam = req.query.accountHashes;
-  if (!hashesParam || typeof hashesParam !== "string") {
-    res.json([]);
-    return;
+// Listing/search record for a package, or why it can't be listed
+function toPackageInfo(task: DownloadTask): PackageResponse | PackageWarning {
+  if (!task.filePath || !fs.existsSync(task.filePath)) {
+    return { id: task.id, reason: "file_missing" };
+  }
+  let stats: fs.Stats;
+  try {
+    stats = fs.statSync(task.filePath);
+  } catch (err) {
+    console.warn(
+      `Package ${task.id} unreadable:`,
+      err instanceof Error ? err.message : err,
+    );
+    return { id: task.id, reason: "file_unreadable" };
+  }
+  return {
+    id: task.id,
+    software: task.software,
+    accountHash: task.accountHash,
+    relativePath: packageRelativePath(task.filePath),
+    fileSize: stats.size,
+    corrupt: task.corrupt,
+    trashedAt: task.trashedAt,
+    createdAt: task.createdAt,
+    updatedAt: task.updatedAt,
+  };
+}
+
+// A page of packages: a plain array, or with ?includeWarnings=true an
+// object that also says which packages were left out and why
+function sendPackages(req: Request, res: Response, tasks: DownloadTask[]) {
+  const body: PackageListResponse = { packages: [], warnings: [] };
+  for (const task of tasks) {
+    const info = toPackageInfo(task);
+    if ("reason" in info) body.warnings.push(info);
+    else body.packages.push(info);
+  }
+  res.json(req.query.includeWarnings === "true" ? body : body.packages);
+}
+
+interface SearchFilters {
+  q: string;
+  minSize?: number;
+  from?: number;
+  to?: number;
+  version?: string;
+}
+
+// Returns the filters, or the message for a 400
+function parseSearchFilters(query: Request["query"]): SearchFilters | string {
+  const { q = "", minSize, from, to, version } = query;
+  if (typeof q !== "string" || q.length > MAX_SEARCH_QUERY_LENGTH) {
+    return `q must be at most ${MAX_SEARCH_QUERY_LENGTH} characters`;
+  }
+  const filters: SearchFilters = { q: q.trim().toLowerCase() };
+  if (minSize !== undefined) {
+    if (typeof minSize !== "string" || !/^\d+$/.test(minSize)) {
+      return "minSize must be a non-negative integer";
+    }
+    filters.minSize = Number(minSize);
   }
-  const hashes = new Set(hashesParam.split(",").filter(Boolean));
+  for (const [name, value] of [
+    ["from", from],
+    ["to", to],
+  ] as const) {
+    if (value === undefined) continue;
+    const time = typeof value === "string" ? Date.parse(value) : NaN;
+    if (isNaN(time)) return `${name} must be an ISO 8601 date`;
+    filters[name] = time;
+  }
+  if (version !== undefined) {
+    if (typeof version !== "string") return "version must be a string";
+    filters.version = version.toLowerCase();
+  }
+  return filters;
+}
+
+// Relevance of a match (lower is better), or null when filtered out
+function matchPackage(
+  task: DownloadTask,
+  filters: SearchFilters,
+): number | null {
+  const { software } = task;
+  if (
+    filters.version &&
+    !software.version.toLowerCase().includes(filters.version)
+  ) {
+    return null;
+  }
+  const created = Date.parse(task.createdAt);
+  if (filters.from !== undefined && created < filters.from) return null;
+  if (filters.to !== undefined && created > filters.to) return null;
+  const size = task.fileSize ?? 0;
+  if (filters.minSize !== undefined && size < filters.minSize) return null;
+
+  const { q } = filters;
+  if (!q) return 0;
+  const bundleID = software.bundleID.toLowerCase();
+  const name = (software.name ?? "").toLowerCase();
+  if (bundleID === q) return 0;
+  if (name.startsWith(q)) return 1;
+  if (name.includes(q) || bundleID.includes(q)) return 2;
+  return null;
+}
+
+// List packages filtered by account hashes (?includeTrashed=true adds
+// trashed packages, marked with trashedAt; ?includeWarnings=true reports
+// the ones left out, see sendPackages; ?since= as for downloads)
+router.get("/packages", (req: Request, res: Response) => {
+  const hashes = parseAccountHashes(req, res);
+  if (!hashes) return;
   if (hashes.size === 0) {
-    res.json([]);
+    sendPackages(req, res, []);
     return;
   }
 
-  const packages: Omit<PackageInfo, "filePath">[] = [];
-  const completedTasks = getAllTasks().filter(
-    (t) => t.status === "completed" && t.filePath && hashes.has(t.accountHash),
+  const pageParams = parsePageParams(req, res);
+  if (!pageParams) return;
+  const since = parseSince(req, res);
+  if (since === null) return;
+  const includeTrashed = req.query.includeTrashed === "true";
+  const visible = (t: { status: string }) =>
+    t.status === "completed" || (includeTrashed && t.status === "trashed");
+  setServerTime(res);
+  const completedTasks = changedSince(
+    getAllTasks().filter(
+      (t) => visible(t) && t.filePath && canAccessTask(t, hashes),
+    ),
+    since,
   );
 
-  for (const task of completedTasks) {
-    if (!task.filePath || !fs.existsSync(task.filePath)) continue;
+  // Only the requested page is stat'ed; a page may come back short when
+  // files vanished, but the cursor still moves past them
+  const page = paginate(completedTasks, pageParams);
+  setNextCursor(res, page.nextCursor);
+  sendPackages(req, res, page.items);
+});
+
+// Search completed packages by name or bundle ID (case-insensitive), with
+// optional minSize (bytes), from/to (created date) and version filters.
+// Results come back exact bundle ID first, then name prefix, then other
+// substring matches; oldest first within each group.
+router.get("/packages/search", (req: Request, res: Response) => {
+  const hashes = parseAccountHashes(req, res);
+  if (!hashes) return;
+  const pageParams = parsePageParams(req, res);
+  if (!pageParams) return;
+
+  const filters = parseSearchFilters(req.query);
+  if (typeof filters === "string") {
+    sendError(res, 400, filters, "INVALID_SEARCH");
+    return;
+  }
 
-    const stats = fs.statSync(task.filePath);
-    packages.push({
-      id: task.id,
-      software: task.software,
-      accountHash: task.accountHash,
-      fileSize: stats.size,
-      createdAt: task.createdAt,
-    });
+  const ranks = new Map<string, number>();
+  const matches: DownloadTask[] = [];
+  for (const task of getAllTasks()) {
+    if (task.status !== "completed" || !task.filePath) continue;
+    if (!canAccessTask(task, hashes)) continue;
+    const rank = matchPackage(task, filters);
+    if (rank === null) continue;
+    ranks.set(task.id, rank);
+    matches.push(task);
   }
 
-  res.json(packages);
+  const page = paginate(matches, pageParams, (t) => ranks.get(t.id)!);
+  setNextCursor(res, page.nextCursor);
+  sendPackages(req, res, page.items);
 });
 
-// Stream IPA file (requires accountHash)
+// Stream IPA file (requires accountHash). Express also routes HEAD here.
+// Opened as a plain download link, so ?accountHash= is always accepted.
 router.get("/packages/:id/file", (req: Request, res: Response) => {
-  const accountHash = req.query.accountHash as string;
-  if (!accountHash || accountHash.length < MIN_ACCOUNT_HASH_LENGTH) {
-    res.status(400).json({ error: "Missing or invalid accountHash" });
-    return;
-  }
+  const accountHash = requireAccountHash(req, res, { allowQuery: true });
+  if (!accountHash) return;
 
   const id = getIdParam(req);
   const task = getAllTasks().find(
@@ -67,20 +246,16 @@ router.get("/packages/:id/file", (req: Request, res: Response) => {
   );
 
   if (!task || !task.filePath || !fs.existsSync(task.filePath)) {
-    res.status(404).json({ error: "Package not found" });
+    sendError(res, 404, "Package not found");
     return;
   }
 
-  if (task.accountHash !== accountHash) {
-    res.status(403).json({ error: "Access denied" });
-    return;
-  }
+  if (!verifyTaskOwnership(task, accountHash, res)) return;
 
-  // Verify file path is within packages directory
-  const packagesBase = path.resolve(path.join(config.dataDir, "packages"));
+  // Verify file path is within a package root
   const resolvedPath = path.resolve(task.filePath);
-  if (!resolvedPath.startsWith(packagesBase + path.sep)) {
-    res.status(403).json({ error: "Access denied" });
+  if (!packageRootOf(resolvedPath)) {
+    sendError(res, 403, "Access denied");
     return;
   }
 
@@ -88,61 +263,269 @@ router.get("/packages/:id/file", (req: Request, res: Response) => {
   const safeVersion = sanitizeFilename(task.software.version);
   const fileName = `${safeName}_${safeVersion}.ipa`;
   res.setHeader("Content-Disposition", `attachment; filename="${fileName}"`);
-  res.setHeader("Content-Type", "application/octet-stream");
+  sendPackageFile(req, res, resolvedPath);
+});
+
+// List the files inside a package from its zip central directory, without
+// reading any entry data (owner only). ?prefix= filters by path, ?limit=
+// caps the entries returned (default and maximum MAX_PACKAGE_CONTENTS_ENTRIES).
+router.get("/packages/:id/contents", async (req: Request, res: Response) => {
+  const accountHash = requireAccountHash(req, res);
+  if (!accountHash) return;
+
+  const { prefix = "", limit } = req.query;
+  if (typeof prefix !== "string") {
+    sendError(res, 400, "prefix must be a string");
+    return;
+  }
+  let max = MAX_PACKAGE_CONTENTS_ENTRIES;
+  if (limit !== undefined) {
+    const value = typeof limit === "string" ? Number(limit) : NaN;
+    if (
+      !Number.isInteger(value) ||
+      value < 1 ||
+      value > MAX_PACKAGE_CONTENTS_ENTRIES
+    ) {
+      sendError(
+        res,
+        400,
+        `limit must be an integer 1-${MAX_PACKAGE_CONTENTS_ENTRIES}`,
+      );
+      return;
+    }
+    max = value;
+  }
+
+  const id = getIdParam(req);
+  const task = getAllTasks().find(
+    (t) => t.id === id && t.status === "completed",
+  );
+  if (!task || !task.filePath || !fs.existsSync(task.filePath)) {
+    sendError(res, 404, "Package not found");
+    return;
+  }
 
-  const stats = fs.statSync(resolvedPath);
-  res.setHeader("Content-Length", stats.size);
+  if (!verifyTaskOwner(task, accountHash, res)) return;
 
-  const stream = fs.createReadStream(resolvedPath);
-  stream.pipe(res);
+  let entries;
+  try {
+    entries = await getPackageContents(task.id, task.filePath);
+  } catch (err) {
+    console.error(
+      `Contents of ${id} unreadable:`,
+      err instanceof Error ? err.message : err,
+    );
+    sendError(res, 422, "Package is not a readable zip archive", "INVALID_ZIP");
+    return;
+  }
+  const matching = prefix
+    ? entries.filter((entry) => entry.path.startsWith(prefix))
+    : entries;
+  const body: PackageContentsResponse = {
+    entries: matching.slice(0, max),
+    total: matching.length,
+    truncated: matching.length > max,
+  };
+  res.json(body);
 });
 
-// Delete a package (requires accountHash)
-router.delete("/packages/:id", (req: Request, res: Response) => {
-  const accountHash = req.query.accountHash as string;
-  if (!accountHash || accountHash.length < MIN_ACCOUNT_HASH_LENGTH) {
-    res.status(400).json({ error: "Missing or invalid accountHash" });
+// Re-hash and re-open a package to check it before installing (owner only).
+// Failures flag the package as corrupt in the list. Large packages are
+// checked in the background: 202 points at GET below to poll for the report.
+router.post("/packages/:id/verify", async (req: Request, res: Response) => {
+  const accountHash = requireAccountHash(req, res);
+  if (!accountHash) return;
+
+  const id = getIdParam(req);
+  const task = getAllTasks().find(
+    (t) => t.id === id && t.status === "completed",
+  );
+  if (!task || !task.filePath || !fs.existsSync(task.filePath)) {
+    sendError(res, 404, "Package not found");
     return;
   }
 
+  if (!verifyTaskOwner(task, accountHash, res)) return;
+
+  const size = fs.statSync(task.filePath).size;
+  const verification = verifyTaskPackage(id);
+  if (size > VERIFY_INLINE_MAX_BYTES) {
+    // The outcome is recorded in the job state for polling
+    verification.catch((err) =>
+      console.error(
+        `Verify ${id} failed:`,
+        err instanceof Error ? err.message : err,
+      ),
+    );
+    res
+      .status(202)
+      .location(`${req.baseUrl}/packages/${id}/verify`)
+      .json(getVerificationJob(id));
+    return;
+  }
+
+  try {
+    const report = await verification;
+    if (!report) {
+      sendError(res, 404, "Package not found");
+      return;
+    }
+    res.json(report);
+  } catch (err) {
+    console.error(
+      `Verify ${id} failed:`,
+      err instanceof Error ? err.message : err,
+    );
+    sendError(res, 500, "Verification failed");
+  }
+});
+
+// Latest verification job for a package (owner only): 202 while running
+router.get("/packages/:id/verify", (req: Request, res: Response) => {
+  const accountHash = requireAccountHash(req, res);
+  if (!accountHash) return;
+
+  const id = getIdParam(req);
+  const task = getAllTasks().find((t) => t.id === id);
+  if (!task) {
+    sendError(res, 404, "Package not found");
+    return;
+  }
+
+  if (!verifyTaskOwner(task, accountHash, res)) return;
+
+  const job = getVerificationJob(id);
+  if (!job) {
+    sendError(res, 404, "No verification has run");
+    return;
+  }
+  res.status(job.status === "running" ? 202 : 200).json(job);
+});
+
+// Re-inject fresh sinfs/metadata into a completed package without
+// downloading it again (owner only)
+router.post("/packages/:id/reinject", async (req: Request, res: Response) => {
+  const accountHash = requireAccountHash(req, res);
+  if (!accountHash) return;
+
+  const { sinfs, iTunesMetadata, metadataCompression } = req.body ?? {};
+  if (!isValidSinfs(sinfs)) {
+    sendError(res, 400, INVALID_SINFS);
+    return;
+  }
+  if (iTunesMetadata !== undefined && typeof iTunesMetadata !== "string") {
+    sendError(res, 400, "iTunesMetadata must be a string");
+    return;
+  }
+  if (
+    metadataCompression !== undefined &&
+    !isValidMetadataCompression(metadataCompression)
+  ) {
+    sendError(res, 400, "metadataCompression must be stored or deflated");
+    return;
+  }
+  if (sinfs.length === 0 && !iTunesMetadata) {
+    sendError(res, 400, "Nothing to inject");
+    return;
+  }
+
+  const id = getIdParam(req);
+  const task = getAllTasks().find((t) => t.id === id);
+  if (!task) {
+    sendError(res, 404, "Package not found");
+    return;
+  }
+
+  if (!verifyTaskOwner(task, accountHash, res)) return;
+
+  try {
+    const result = await reinjectTask(
+      id,
+      sinfs,
+      iTunesMetadata,
+      metadataCompression,
+    );
+    if (typeof result === "string") {
+      sendError(res, 409, "Cannot re-inject this package", result);
+      return;
+    }
+    res.json({ success: true, ...result });
+  } catch (err) {
+    console.error(
+      `Reinject ${id} failed:`,
+      err instanceof Error ? err.message : err,
+    );
+    sendError(
+      res,
+      422,
+      "Injection failed; the package was left unchanged",
+      "INJECTION_FAILED",
+    );
+  }
+});
+
+// Delete a package (owner only). Completed packages go to the trash unless
+// ?purge=true or the trash is disabled; deleting a trashed package purges it.
+router.delete("/packages/:id", (req: Request, res: Response) => {
+  const accountHash = requireAccountHash(req, res);
+  if (!accountHash) return;
+
   const id = getIdParam(req);
-  const packagesDir = path.join(config.dataDir, "packages");
-  const packagesBase = path.resolve(packagesDir);
 
   const task = getAllTasks().find((t) => t.id === id);
   if (!task || !task.filePath) {
-    res.status(404).json({ error: "Package not found" });
+    sendError(res, 404, "Package not found");
+    return;
+  }
+
+  if (!verifyTaskOwner(task, accountHash, res)) return;
+
+  if (task.status === "trashed") {
+    deleteTask(id);
+    res.json({ success: true });
     return;
   }
 
-  if (task.accountHash !== accountHash) {
-    res.status(403).json({ error: "Access denied" });
+  const purge = req.query.purge === "true" || config.trashRetentionHours <= 0;
+  if (!purge && trashTask(id)) {
+    res.json({ success: true, trashed: true });
     return;
   }
 
-  // Verify file path is within packages directory
-  const resolvedPath = path.resolve(task.filePath);
-  if (!resolvedPath.startsWith(packagesBase + path.sep)) {
-    res.status(403).json({ error: "Access denied" });
+  // Verify file path is within a package root
+  if (!packageRootOf(path.resolve(task.filePath))) {
+    sendError(res, 403, "Access denied");
     return;
   }
+  purgePackageFile(id);
 
-  if (fs.existsSync(resolvedPath)) {
-    fs.unlinkSync(resolvedPath);
+  res.json({ success: true });
+});
 
-    // Clean up empty parent directories
-    let dir = path.dirname(resolvedPath);
-    while (dir !== packagesBase && dir.startsWith(packagesBase)) {
-      const contents = fs.readdirSync(dir);
-      if (contents.length === 0) {
-        fs.rmdirSync(dir);
-        dir = path.dirname(dir);
-      } else {
-        break;
-      }
-    }
+// Restore a trashed package within the retention window (owner only)
+router.post("/packages/:id/restore", (req: Request, res: Response) => {
+  const accountHash = requireAccountHash(req, res);
+  if (!accountHash) return;
+
+  const id = getIdParam(req);
+  const task = getAllTasks().find((t) => t.id === id);
+  if (!task) {
+    sendError(res, 404, "Package not found");
+    return;
   }
 
+  if (!verifyTaskOwner(task, accountHash, res)) return;
+
+  const rejected = restoreTrashedTask(id);
+  if (rejected) {
+    sendError(
+      res,
+      rejected === "RETENTION_EXPIRED" ? 410 : 409,
+      "Cannot restore this package",
+      rejected,
+    );
+    return;
+  }
   res.json({ success: true });
 });
 
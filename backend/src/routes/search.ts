@@ -1,53 +1,94 @@
 import { Router, Request, Response } from "express";
+import { config } from "../config.js";
+import { itunesBreaker } from "../services/circuitBreaker.js";
+import { fetchItunes, mapSoftware } from "../services/itunesClient.js";
+import { rejectIfCircuitOpen, sendError } from "../utils/route.js";
+import { SingleFlight } from "../utils/singleFlight.js";
+import { normalizeCountryCode } from "../utils/storefront.js";
 
 const router = Router();
 
-// Map iTunes API fields to our Software type, matching Swift CodingKeys
-function mapSoftware(item: Record<string, any>) {
-  return {
-    id: item.trackId,
-    bundleID: item.bundleId,
-    name: item.trackName,
-    version: item.version,
-    price: item.price,
-    artistName: item.artistName,
-    sellerName: item.sellerName,
-    description: item.description,
-    averageUserRating: item.averageUserRating,
-    userRatingCount: item.userRatingCount,
-    artworkUrl: item.artworkUrl512,
-    screenshotUrls: item.screenshotUrls ?? [],
-    minimumOsVersion: item.minimumOsVersion,
-    fileSizeBytes: item.fileSizeBytes,
-    releaseDate: item.currentVersionReleaseDate ?? item.releaseDate,
-    releaseNotes: item.releaseNotes,
-    formattedPrice: item.formattedPrice,
-    primaryGenreName: item.primaryGenreName,
-  };
+// Concurrent identical queries share one upstream request
+const flights = new SingleFlight<any>();
+
+// Keep searches to apps unless the client asks for other media. Lookups
+// are left alone: `entity` there adds related items to the results.
+const SEARCH_DEFAULTS = { media: "software", entity: "software,iPadSoftware" };
+
+// Canonical query string: sorted keys, trimmed values, case-folded term
+function normalizeQuery(query: Request["query"]): string {
+  const params = new URLSearchParams();
+  for (const key of Object.keys(query).sort()) {
+    const raw = query[key];
+    if (typeof raw !== "string") continue;
+    const value = raw.trim().replace(/\s+/g, " ");
+    params.set(key, key === "term" ? value.toLowerCase() : value);
+  }
+  return params.toString();
+}
+
+// Validate `country` or fall back to DEFAULT_STOREFRONT. Sends 400 and
+// returns null for codes Apple would answer with an HTML error page.
+function resolveStorefront(req: Request, res: Response): string | null {
+  const raw = req.query.country;
+  if (raw === undefined || raw === "") return config.defaultStorefront;
+  const country = typeof raw === "string" ? normalizeCountryCode(raw) : null;
+  if (!country) {
+    sendError(
+      res,
+      400,
+      "country must be an ISO 3166-1 alpha-2 code",
+      "INVALID_COUNTRY",
+    );
+  }
+  return country;
+}
+
+// Fetch an iTunes endpoint, joining an identical in-flight request if any.
+// Joiners skip the circuit breaker check since they add no upstream load.
+// The storefront queried is echoed in the X-Storefront response header.
+async function coalescedItunes(
+  endpoint: "search" | "lookup",
+  req: Request,
+  res: Response,
+): Promise<any | undefined> {
+  const country = resolveStorefront(req, res);
+  if (!country) return undefined;
+  const defaults = endpoint === "search" ? SEARCH_DEFAULTS : {};
+  const query = normalizeQuery({ ...defaults, ...req.query, country });
+  const url = `https://itunes.apple.com/${endpoint}?${query}`;
+  res.set("X-Storefront", country);
+  if (!flights.has(url) && rejectIfCircuitOpen(itunesBreaker, res)) {
+    return undefined;
+  }
+  return flights.run(url, () => fetchItunes(url));
+}
+
+// Let browsers/CDNs reuse identical queries. Express derives a weak ETag
+// from the serialized body and answers matching If-None-Match with 304.
+function setCacheHeaders(res: Response) {
+  const ttl = config.searchCacheTtlSeconds;
+  res.set("Cache-Control", ttl > 0 ? `public, max-age=${ttl}` : "no-cache");
 }
 
 router.get("/search", async (req: Request, res: Response) => {
   try {
-    const params = new URLSearchParams(req.query as Record<string, string>);
-    const response = await fetch(
-      `https://itunes.apple.com/search?${params.toString()}`,
-    );
-    const data = await response.json();
+    const data = await coalescedItunes("search", req, res);
+    if (data === undefined) return;
     const results = (data.results ?? []).map(mapSoftware);
+    setCacheHeaders(res);
     res.json(results);
   } catch (err) {
     console.error("Search error:", err instanceof Error ? err.message : err);
-    res.status(500).json({ error: "Search request failed" });
+    sendError(res, 500, "Search request failed");
   }
 });
 
 router.get("/lookup", async (req: Request, res: Response) => {
   try {
-    const params = new URLSearchParams(req.query as Record<string, string>);
-    const response = await fetch(
-      `https://itunes.apple.com/lookup?${params.toString()}`,
-    );
-    const data = await response.json();
+    const data = await coalescedItunes("lookup", req, res);
+    if (data === undefined) return;
+    setCacheHeaders(res);
     if (!data.resultCount || !data.results?.length) {
       res.json(null);
       return;
@@ -55,7 +96,7 @@ router.get("/lookup", async (req: Request, res: Response) => {
     res.json(mapSoftware(data.results[0]));
   } catch (err) {
     console.error("Lookup error:", err instanceof Error ? err.message : err);
-    res.status(500).json({ error: "Lookup request failed" });
+    sendError(res, 500, "Lookup request failed");
   }
 });
 
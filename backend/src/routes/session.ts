@@ -0,0 +1,49 @@
+import { Router, Request, Response } from "express";
+import { createSession, revokeSession } from "../services/sessions.js";
+import type { SessionResponse } from "../types/api.js";
+import { isValidAccountHash } from "../utils/accountHash.js";
+import { sendError, sessionToken } from "../utils/route.js";
+
+const router = Router();
+
+// Exchange an account hash for a short-lived session token, so shared
+// devices need not keep the hash or put it in URLs
+router.post("/session", (req: Request, res: Response) => {
+  const accountHash = req.body?.accountHash;
+  if (typeof accountHash !== "string" || !isValidAccountHash(accountHash)) {
+    sendError(
+      res,
+      400,
+      "Missing or invalid accountHash parameter",
+      "INVALID_ACCOUNT_HASH",
+    );
+    return;
+  }
+  const session = createSession(accountHash);
+  if (!session) {
+    sendError(res, 503, "Too many active sessions", "TOO_MANY_SESSIONS");
+    return;
+  }
+  const { token, expiresAt } = session;
+  const body: SessionResponse = {
+    token,
+    expiresAt: new Date(expiresAt).toISOString(),
+  };
+  res.status(201).json(body);
+});
+
+// Sign out: revoke the session named in Authorization
+router.delete("/session", (req: Request, res: Response) => {
+  const token = sessionToken(req);
+  if (!token) {
+    sendError(res, 400, "Send Authorization: Session <token>");
+    return;
+  }
+  if (!revokeSession(token)) {
+    sendError(res, 404, "Session not found", "INVALID_SESSION");
+    return;
+  }
+  res.status(204).end();
+});
+
+export default router;
@@ -1,21 +1,58 @@
 import { Router, Request, Response } from "express";
-import { config } from "../config.js";
+import { config, PROGRESS_POLL_INTERVAL_MS } from "../config.js";
+import { bagBreaker, itunesBreaker } from "../services/circuitBreaker.js";
+import {
+  getAllTasks,
+  getStallCounters,
+} from "../services/downloadManager.js";
+import { getPackageRootUsage } from "../services/packageRoots.js";
+import { getStorageStatus } from "../services/storageMonitor.js";
+import type { SettingsResponse } from "../types/api.js";
 
 const router = Router();
 const startedAt = Date.now();
 
 router.get("/settings", (_req: Request, res: Response) => {
-  res.json({
+  const settings: SettingsResponse = {
     uptime: Math.floor((Date.now() - startedAt) / 1000),
+    version: config.version,
     buildCommit: config.buildCommit,
     buildDate: config.buildDate,
     port: config.port,
     dataDir: config.dataDir,
     publicBaseUrl: config.publicBaseUrl,
     disableHttpsRedirect: config.disableHttpsRedirect,
+    // Maintenance mode: only reads are accepted for downloads and packages
+    readOnly: config.readOnly,
     autoCleanupDays: config.autoCleanupDays,
     autoCleanupMaxMB: config.autoCleanupMaxMB,
-  });
+    autoCleanupMaxPerAccount: config.autoCleanupMaxPerAccount,
+    limits: {
+      maxDownloadSize: config.maxDownloadSize,
+    },
+    // Storefront search and lookup use when a request omits `country`
+    defaultStorefront: config.defaultStorefront,
+    // Recommended interval for clients polling GET /api/downloads/:id
+    progressPollIntervalMs: PROGRESS_POLL_INTERVAL_MS,
+    // Circuit breaker state for Apple upstreams
+    upstreams: {
+      itunes: itunesBreaker.snapshot(),
+      bag: bagBreaker.snapshot(),
+    },
+    // Downloads the stall watchdog restarted or gave up on
+    stalledDownloads: getStallCounters(),
+    // Free space on the data volume and whether new downloads are refused
+    storage: getStorageStatus(),
+    // Package directories with their totals; new downloads go to `default`
+    packageRoots: getPackageRootUsage(
+      getAllTasks().flatMap((t) =>
+        t.status === "completed" && t.filePath
+          ? [{ filePath: t.filePath, fileSize: t.fileSize }]
+          : [],
+      ),
+    ),
+  };
+  res.json(settings);
 });
 
 export default router;
@@ -0,0 +1,261 @@
+import { Router, Request, Response } from "express";
+import { config, MAX_UPLOAD_METADATA_BYTES } from "../config.js";
+import { rejectWritesWhenReadOnly } from "../middleware/readOnly.js";
+import {
+  sanitizeTaskForResponse,
+  type UploadedPackage,
+} from "../services/downloadManager.js";
+import {
+  appendUpload,
+  cancelUpload,
+  createUpload,
+  finishUpload,
+  getUpload,
+  parseUploadMetadata,
+  type Upload,
+  type UploadErrorCode,
+} from "../services/packageUploads.js";
+import { isUnderStoragePressure } from "../services/storageMonitor.js";
+import {
+  getIdParam,
+  requireAccountHash,
+  sendError,
+  verifyTaskOwner,
+} from "../utils/route.js";
+import type { UploadResponse } from "../types/api.js";
+
+const router = Router();
+
+router.use(["/packages/upload", "/packages/uploads"], rejectWritesWhenReadOnly);
+
+const UPLOAD_ERRORS: Record<UploadErrorCode, [number, string]> = {
+  UPLOAD_BUSY: [409, "Another request is writing to this upload"],
+  UPLOAD_OFFSET_MISMATCH: [409, "Upload-Offset does not match the upload"],
+  UPLOAD_TOO_LARGE: [413, "Upload exceeds its declared size"],
+  UPLOAD_INCOMPLETE: [409, "Upload has not received all of its bytes"],
+};
+
+function sendUploadError(res: Response, code: UploadErrorCode) {
+  const [status, error] = UPLOAD_ERRORS[code];
+  sendError(res, status, error, code);
+}
+
+function uploadResponse(upload: Upload): UploadResponse {
+  return {
+    id: upload.id,
+    size: upload.size ?? upload.received,
+    received: upload.received,
+    expiresAt: new Date(upload.expiresAt).toISOString(),
+  };
+}
+
+// 507 while the disk is nearly full, like new downloads
+function rejectUnderStoragePressure(res: Response): boolean {
+  if (!isUnderStoragePressure()) return false;
+  sendError(
+    res,
+    507,
+    "Server is low on disk space; try again later",
+    "INSUFFICIENT_STORAGE",
+  );
+  return true;
+}
+
+// A declared size must fit MAX_DOWNLOAD_SIZE_MB; returns false once answered
+function checkDeclaredSize(size: number | undefined, res: Response): boolean {
+  if (size === undefined || size <= config.maxDownloadSize) return true;
+  sendError(
+    res,
+    413,
+    `Upload of ${size} bytes exceeds the ${config.maxDownloadSize} byte limit`,
+    "UPLOAD_TOO_LARGE",
+  );
+  return false;
+}
+
+function startUpload(
+  res: Response,
+  metadata: UploadedPackage,
+  size?: number,
+): Upload | null {
+  const upload = createUpload(metadata, size);
+  if (typeof upload === "string") {
+    res.setHeader("Retry-After", 60);
+    sendError(
+      res,
+      503,
+      upload === "TOO_MANY_ACCOUNT_UPLOADS"
+        ? "Too many uploads in progress for this account"
+        : "Too many uploads in progress",
+      upload,
+    );
+    return null;
+  }
+  return upload;
+}
+
+// Look up an upload of the requesting account; null once answered
+function requireUpload(req: Request, res: Response): Upload | null {
+  const accountHash = requireAccountHash(req, res);
+  if (!accountHash) return null;
+  const upload = getUpload(getIdParam(req));
+  if (!upload) {
+    sendError(res, 404, "Upload not found");
+    return null;
+  }
+  return verifyTaskOwner(upload, accountHash, res) ? upload : null;
+}
+
+async function completeUpload(res: Response, upload: Upload) {
+  try {
+    const result = await finishUpload(upload);
+    if (typeof result === "string") {
+      sendUploadError(res, result);
+    } else if ("code" in result) {
+      sendError(res, 422, result.error, result.code);
+    } else {
+      res.status(201).json(sanitizeTaskForResponse(result));
+    }
+  } catch (err) {
+    console.error(
+      `[Upload] ${upload.id} failed:`,
+      err instanceof Error ? err.message : err,
+    );
+    sendError(
+      res,
+      422,
+      "Injection failed; the upload was discarded",
+      "INJECTION_FAILED",
+    );
+  }
+}
+
+// Store a request body in an upload; false once an error was answered
+async function receive(
+  req: Request,
+  res: Response,
+  upload: Upload,
+  offset: number,
+): Promise<boolean> {
+  try {
+    const failed = await appendUpload(upload, offset, req);
+    if (failed) sendUploadError(res, failed);
+    return !failed;
+  } catch (err) {
+    console.warn(
+      `[Upload] ${upload.id} interrupted:`,
+      err instanceof Error ? err.message : err,
+    );
+    if (!res.headersSent) sendError(res, 400, "Upload interrupted");
+    return false;
+  }
+}
+
+// Upload a whole IPA in one request. Metadata (software, sinfs,
+// iTunesMetadata, metadataCompression) comes as base64 JSON in
+// X-Upload-Metadata; large metadata needs the chunked uploads below.
+router.post("/packages/upload", async (req: Request, res: Response) => {
+  const accountHash = requireAccountHash(req, res);
+  if (!accountHash) return;
+
+  const header = req.headers["x-upload-metadata"];
+  let body: Record<string, any> = {};
+  if (typeof header === "string" && header) {
+    if (header.length > MAX_UPLOAD_METADATA_BYTES) {
+      sendError(res, 400, "X-Upload-Metadata is too large; upload in chunks");
+      return;
+    }
+    try {
+      body = JSON.parse(Buffer.from(header, "base64").toString("utf-8"));
+    } catch {
+      sendError(res, 400, "X-Upload-Metadata must be base64-encoded JSON");
+      return;
+    }
+    if (typeof body !== "object" || body === null) {
+      sendError(res, 400, "X-Upload-Metadata must be a JSON object");
+      return;
+    }
+  }
+  const parsed = parseUploadMetadata(body, accountHash);
+  if (!parsed.ok) {
+    sendError(res, 400, parsed.error);
+    return;
+  }
+  const length = req.headers["content-length"];
+  const size = length ? parseInt(length, 10) : undefined;
+  if (!checkDeclaredSize(size, res)) return;
+  if (rejectUnderStoragePressure(res)) return;
+
+  const upload = startUpload(res, parsed.metadata, size);
+  if (!upload) return;
+  if (!(await receive(req, res, upload, 0))) {
+    cancelUpload(upload.id);
+    return;
+  }
+  await completeUpload(res, upload);
+});
+
+// Start a chunked upload: declare the size and send the metadata as JSON
+router.post("/packages/uploads", (req: Request, res: Response) => {
+  const accountHash = requireAccountHash(req, res);
+  if (!accountHash) return;
+
+  const { size, ...body } = req.body ?? {};
+  if (!Number.isSafeInteger(size) || size <= 0) {
+    sendError(res, 400, "size must be a positive integer");
+    return;
+  }
+  if (!checkDeclaredSize(size, res)) return;
+  const parsed = parseUploadMetadata(body, accountHash);
+  if (!parsed.ok) {
+    sendError(res, 400, parsed.error);
+    return;
+  }
+  if (rejectUnderStoragePressure(res)) return;
+
+  const upload = startUpload(res, parsed.metadata, size);
+  if (!upload) return;
+  res.status(201).json(uploadResponse(upload));
+});
+
+// How much of an upload arrived, to resume after a dropped connection
+router.get("/packages/uploads/:id", (req: Request, res: Response) => {
+  const upload = requireUpload(req, res);
+  if (!upload) return;
+  res.json(uploadResponse(upload));
+});
+
+// Append the request body at Upload-Offset, which must equal `received`
+router.put("/packages/uploads/:id", async (req: Request, res: Response) => {
+  const upload = requireUpload(req, res);
+  if (!upload) return;
+  const offset = Number(req.headers["upload-offset"]);
+  if (!Number.isSafeInteger(offset) || offset < 0) {
+    sendError(res, 400, "Upload-Offset must be a non-negative integer");
+    return;
+  }
+  if (rejectUnderStoragePressure(res)) return;
+
+  if (!(await receive(req, res, upload, offset))) return;
+  res.json(uploadResponse(upload));
+});
+
+// Validate, inject and register a fully received upload as a package
+router.post(
+  "/packages/uploads/:id/complete",
+  async (req: Request, res: Response) => {
+    const upload = requireUpload(req, res);
+    if (!upload) return;
+    await completeUpload(res, upload);
+  },
+);
+
+// Abandon an upload and delete what was received
+router.delete("/packages/uploads/:id", (req: Request, res: Response) => {
+  const upload = requireUpload(req, res);
+  if (!upload) return;
+  cancelUpload(upload.id);
+  res.status(204).end();
+});
+
+export default router;
@@ -0,0 +1,84 @@
+import type { TaskErrorCode } from "../types/index.js";
+
+// Development-only fault injection (CHAOS_ENABLED), armed through
+// POST /api/dev/chaos so frontend error handling can be exercised without
+// pulling cables. Callers check config.chaosEnabled before every hook, so
+// with chaos off none of this runs.
+
+export interface ChaosFaults {
+  // Fail the next download once it reaches this percentage
+  failDownloadAtPercent?: number;
+  // Hold back every SSE progress event this long
+  sseDelayMs?: number;
+  // Make the next sinf injection throw
+  failInjection?: boolean;
+  // "METHOD /path" under /api answered once with a 500
+  failRoute?: string;
+}
+
+/** A failure raised by an armed fault, reported with a real task code. */
+export class ChaosError extends Error {
+  constructor(
+    readonly code: TaskErrorCode,
+    message: string,
+  ) {
+    super(`Chaos: ${message}`);
+    this.name = "ChaosError";
+  }
+}
+
+let faults: ChaosFaults = {};
+
+export function getChaosFaults(): ChaosFaults {
+  return { ...faults };
+}
+
+/** Arm the given faults; null disarms one, omitted ones stay as they are. */
+export function armChaosFaults(next: {
+  [K in keyof ChaosFaults]?: ChaosFaults[K] | null;
+}): ChaosFaults {
+  const armed: Record<string, unknown> = { ...faults };
+  for (const [key, value] of Object.entries(next)) {
+    if (value === null) delete armed[key];
+    else if (value !== undefined) armed[key] = value;
+  }
+  faults = armed as ChaosFaults;
+  return getChaosFaults();
+}
+
+export function resetChaosFaults() {
+  faults = {};
+}
+
+/** Download progress hook: throws once the armed percentage is reached. */
+export function chaosDownloadFault(progress: number) {
+  const at = faults.failDownloadAtPercent;
+  if (at === undefined || progress < at) return;
+  delete faults.failDownloadAtPercent;
+  throw new ChaosError("network", `connection dropped at ${progress}%`);
+}
+
+/** Injection hook: throws for the next injection when armed. */
+export function chaosInjectionFault() {
+  if (!faults.failInjection) return;
+  delete faults.failInjection;
+  throw new ChaosError("injection_failed", "injection failed");
+}
+
+/** Route hook: whether this request should get the armed 500. */
+export function takeChaosRouteFault(method: string, path: string): boolean {
+  if (faults.failRoute !== `${method} ${path}`) return false;
+  delete faults.failRoute;
+  return true;
+}
+
+/** Delay a progress listener by the armed SSE delay. */
+export function withChaosDelay<T>(
+  listener: (value: T) => void,
+): (value: T) => void {
+  return (value) => {
+    const delay = faults.sseDelayMs ?? 0;
+    if (delay > 0) setTimeout(() => listener(value), delay);
+    else listener(value);
+  };
+}
@@ -0,0 +1,225 @@
+import fs from "fs";
+import type { FileHandle } from "fs/promises";
+
+const CHUNK_MAX_ATTEMPTS = 3;
+
+// How requests are made; the download manager passes a DNS-pinned fetch
+export type Fetcher = (url: string, init: RequestInit) => Promise<Response>;
+
+// fetch() transparently decompresses encoded bodies, which would make byte
+// counts disagree with Content-Length/Content-Range and the size limit.
+export class ContentEncodingError extends Error {
+  constructor(encoding: string) {
+    super(`Upstream sent an encoded body (Content-Encoding: ${encoding})`);
+    this.name = "ContentEncodingError";
+  }
+}
+
+// The transfer went past MAX_DOWNLOAD_SIZE (thrown from the byte counter);
+// a retry would only fetch the same bytes again.
+export class DownloadSizeError extends Error {
+  constructor(message: string) {
+    super(message);
+    this.name = "DownloadSizeError";
+  }
+}
+
+/**
+ * GET a download URL asking for the raw bytes (`Accept-Encoding: identity`)
+ * and reject responses that are encoded anyway.
+ */
+export async function fetchIdentity(
+  url: string,
+  signal: AbortSignal,
+  headers: Record<string, string> = {},
+  fetcher: Fetcher = fetch,
+): Promise<Response> {
+  const response = await fetcher(url, {
+    signal,
+    redirect: "follow",
+    headers: { ...headers, "Accept-Encoding": "identity" },
+  });
+  const encoding = response.headers.get("content-encoding");
+  if (encoding && encoding.toLowerCase() !== "identity") {
+    await response.body?.cancel();
+    throw new ContentEncodingError(encoding);
+  }
+  return response;
+}
+
+export interface RangeProbe {
+  status: number;
+  // Total size when the upstream honours byte ranges, otherwise null
+  total: number | null;
+  // Content-Length of a successful response that ignored the range
+  length: number | null;
+  // Answered 206, or advertised Accept-Ranges: bytes
+  acceptsRanges: boolean;
+}
+
+/** Probe whether the upstream supports ranged GETs via a `bytes=0-0` request. */
+export async function probeRangeSupport(
+  url: string,
+  signal: AbortSignal,
+  headers: Record<string, string> = {},
+  fetcher: Fetcher = fetch,
+): Promise<RangeProbe> {
+  const response = await fetchIdentity(
+    url,
+    signal,
+    { ...headers, Range: "bytes=0-0" },
+    fetcher,
+  );
+  await response.body?.cancel();
+
+  const acceptsRanges =
+    response.status === 206 ||
+    response.headers.get("accept-ranges")?.toLowerCase() === "bytes";
+  if (response.status !== 206) {
+    const length = parseInt(response.headers.get("content-length") || "", 10);
+    return {
+      status: response.status,
+      total: null,
+      length: response.ok && length > 0 ? length : null,
+      acceptsRanges,
+    };
+  }
+  // Content-Range: bytes 0-0/<total>
+  const match = /\/(\d+)$/.exec(response.headers.get("content-range") || "");
+  const total = match ? parseInt(match[1], 10) : 0;
+  return {
+    status: response.status,
+    total: total > 0 ? total : null,
+    length: null,
+    acceptsRanges,
+  };
+}
+
+export interface ChunkedDownloadOptions {
+  url: string;
+  filePath: string;
+  total: number;
+  connections: number;
+  signal: AbortSignal;
+  onBytes: (bytes: number) => void;
+  // A chunk is retrying; `attempt` is the one that failed
+  onRetry?: (attempt: number) => void;
+  // Sent on every range request
+  headers?: Record<string, string>;
+  fetcher?: Fetcher;
+}
+
+// One chunk: bytes `start` to `end` (inclusive) written into `handle`
+interface RangeOptions
+  extends Omit<ChunkedDownloadOptions, "filePath" | "total" | "connections"> {
+  handle: FileHandle;
+  start: number;
+  end: number;
+}
+
+/**
+ * Download `total` bytes of `url` into `filePath` using `connections`
+ * concurrent ranged requests written at their offsets in a pre-allocated
+ * file. Each chunk retries from where it stopped (reported via `onRetry`);
+ * the first chunk that gives up aborts the rest. `headers` go on every
+ * range request.
+ */
+export async function downloadInChunks(
+  options: ChunkedDownloadOptions,
+): Promise<void> {
+  const { filePath, total, connections, signal } = options;
+  const chunkController = new AbortController();
+  const onAbort = () => chunkController.abort();
+  signal.addEventListener("abort", onAbort);
+
+  const handle = await fs.promises.open(filePath, "w");
+  try {
+    await handle.truncate(total);
+
+    const chunkSize = Math.ceil(total / connections);
+    const ranges: [number, number][] = [];
+    for (let start = 0; start < total; start += chunkSize) {
+      ranges.push([start, Math.min(start + chunkSize, total) - 1]);
+    }
+
+    // The chunk that gave up first; the others then fail with aborts
+    let failure: unknown;
+    const results = await Promise.allSettled(
+      ranges.map(([start, end]) =>
+        downloadRange({
+          ...options,
+          handle,
+          start,
+          end,
+          signal: chunkController.signal,
+        }).catch((err) => {
+          if (!chunkController.signal.aborted) failure = err;
+          chunkController.abort();
+          throw err;
+        }),
+      ),
+    );
+    const failed = results.find((r) => r.status === "rejected");
+    if (failed) throw failure ?? (failed as PromiseRejectedResult).reason;
+  } finally {
+    signal.removeEventListener("abort", onAbort);
+    await handle.close();
+  }
+}
+
+async function downloadRange({
+  url,
+  handle,
+  start,
+  end,
+  signal,
+  onBytes,
+  onRetry,
+  headers = {},
+  fetcher = fetch,
+}: RangeOptions): Promise<void> {
+  let position = start;
+  for (let attempt = 1; ; attempt++) {
+    try {
+      const response = await fetchIdentity(
+        url,
+        signal,
+        { ...headers, Range: `bytes=${position}-${end}` },
+        fetcher,
+      );
+      if (response.status !== 206 || !response.body) {
+        await response.body?.cancel();
+        throw new Error(`Range request failed: HTTP ${response.status}`);
+      }
+
+      const reader = response.body.getReader();
+      while (position <= end) {
+        const { done, value } = await reader.read();
+        if (done) break;
+        // Never write past the end of this chunk
+        const length = Math.min(value.byteLength, end + 1 - position);
+        await handle.write(value, 0, length, position);
+        position += length;
+        onBytes(length);
+      }
+      if (position <= end) {
+        throw new Error("Range response ended early");
+      }
+      return;
+    } catch (err) {
+      if (
+        signal.aborted ||
+        err instanceof ContentEncodingError ||
+        err instanceof DownloadSizeError ||
+        attempt >= CHUNK_MAX_ATTEMPTS
+      ) {
+        throw err;
+      }
+      console.warn(
+        `Chunk ${start}-${end} failed (attempt ${attempt}), retrying from ${position}:`,
+        err instanceof Error ? err.message : err,
+      );
+      onRetry?.(attempt);
+    }
+  }
+}
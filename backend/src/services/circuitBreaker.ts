@@ -0,0 +1,72 @@
+import { UPSTREAM_COOLDOWN_MS, UPSTREAM_FAILURE_THRESHOLD } from "../config.js";
+
+export type CircuitState = "closed" | "open" | "half-open";
+
+/**
+ * Fail fast while an upstream is down. After `threshold` consecutive
+ * failures the circuit opens for `cooldownMs`; the first request after
+ * that is let through as a probe, and its outcome closes or re-opens it.
+ */
+export class CircuitBreaker {
+  private failures = 0;
+  private openedAt: number | null = null;
+  private probing = false;
+
+  constructor(
+    private readonly threshold = UPSTREAM_FAILURE_THRESHOLD,
+    private readonly cooldownMs = UPSTREAM_COOLDOWN_MS,
+    private readonly now: () => number = Date.now,
+  ) {}
+
+  get state(): CircuitState {
+    if (this.openedAt === null) return "closed";
+    return this.now() - this.openedAt >= this.cooldownMs ? "half-open" : "open";
+  }
+
+  /** Milliseconds until a probe is allowed (0 when closed). */
+  get retryAfterMs(): number {
+    if (this.openedAt === null) return 0;
+    return Math.max(0, this.openedAt + this.cooldownMs - this.now());
+  }
+
+  /** Whether a request may go upstream now. Claims the probe slot when half-open. */
+  tryAcquire(): boolean {
+    const state = this.state;
+    if (state === "closed") return true;
+    if (state === "open" || this.probing) return false;
+    this.probing = true;
+    return true;
+  }
+
+  recordSuccess(): void {
+    this.failures = 0;
+    this.openedAt = null;
+    this.probing = false;
+  }
+
+  recordFailure(): void {
+    this.failures++;
+    if (this.probing || this.failures >= this.threshold) {
+      this.openedAt = this.now();
+    }
+    this.probing = false;
+  }
+
+  snapshot() {
+    return {
+      state: this.state,
+      consecutiveFailures: this.failures,
+      retryAfterMs: this.retryAfterMs,
+    };
+  }
+}
+
+// Only server-side trouble counts; a 4xx means the upstream is answering
+export function isUpstreamFailureStatus(status: number): boolean {
+  return status >= 500 || status === 429;
+}
+
+// Shared per upstream host: itunes.apple.com (search/lookup) and
+// init.itunes.apple.com (bag)
+export const itunesBreaker = new CircuitBreaker();
+export const bagBreaker = new CircuitBreaker();
@@ -0,0 +1,113 @@
+import fs from "fs";
+import {
+  config,
+  parseRuntimeSettings,
+  RUNTIME_SETTING_FORMATS,
+} from "../config.js";
+import type { RuntimeSettings } from "../config.js";
+
+export type ReloadResult =
+  | { ok: true; settings: RuntimeSettings }
+  | { ok: false; error: string };
+
+const listeners = new Set<() => void>();
+const RUNTIME_KEYS = Object.keys(
+  parseRuntimeSettings({}),
+) as (keyof RuntimeSettings)[];
+let lastReloadAt: string | null = null;
+
+/** Called after every successful reload, with the new values in `config`. */
+export function onConfigReload(listener: () => void) {
+  listeners.add(listener);
+  return () => listeners.delete(listener);
+}
+
+/** The runtime settings currently in effect. */
+export function getRuntimeSettings(): RuntimeSettings {
+  const settings = {} as Record<string, unknown>;
+  for (const key of RUNTIME_KEYS) settings[key] = config[key];
+  return settings as RuntimeSettings;
+}
+
+export function getLastReloadAt(): string | null {
+  return lastReloadAt;
+}
+
+// KEY=VALUE lines; blank lines and # comments are skipped, and a value may
+// be wrapped in double quotes
+export function parseConfigFile(text: string): Record<string, string> {
+  const values: Record<string, string> = {};
+  for (const [index, raw] of text.split(/\r?\n/).entries()) {
+    const line = raw.trim();
+    if (!line || line.startsWith("#")) continue;
+    const match = /^([A-Z0-9_]+)\s*=\s*(.*)$/.exec(line);
+    if (!match) throw new Error(`Line ${index + 1} is not KEY=VALUE`);
+    values[match[1]] = match[2].replace(/^"(.*)"$/, "$1");
+  }
+  return values;
+}
+
+// Returns the first problem with the file's values, if any
+function validateValues(values: Record<string, string>): string | null {
+  for (const [key, value] of Object.entries(values)) {
+    const format = RUNTIME_SETTING_FORMATS[key];
+    if (!format) return `${key} cannot be changed without a restart`;
+    if (format === "integer" && !/^\d+$/.test(value)) {
+      return `${key} must be a non-negative integer`;
+    }
+    if (format === "boolean" && value !== "true" && value !== "false") {
+      return `${key} must be true or false`;
+    }
+    if (Array.isArray(format) && !format.includes(value)) {
+      return `${key} must be one of ${format.join(", ")}`;
+    }
+    if (
+      format === "limits" &&
+      value !== "" &&
+      !/^\s*[\w-]+\s*=\s*\d+\s*(,\s*[\w-]+\s*=\s*\d+\s*)*$/.test(value)
+    ) {
+      return `${key} must be comma-separated hash=count pairs`;
+    }
+  }
+  return null;
+}
+
+/**
+ * Re-read CONFIG_FILE over the environment and apply its runtime settings.
+ * A file that cannot be read or fails validation changes nothing. The new
+ * values are assigned in one synchronous step, so no request or timer ever
+ * sees a mix of old and new settings.
+ */
+export function reloadConfig(): ReloadResult {
+  if (!config.configFile) {
+    return { ok: false, error: "CONFIG_FILE is not set" };
+  }
+  let values: Record<string, string>;
+  try {
+    values = parseConfigFile(fs.readFileSync(config.configFile, "utf-8"));
+  } catch (err) {
+    return {
+      ok: false,
+      error: err instanceof Error ? err.message : "Cannot read CONFIG_FILE",
+    };
+  }
+  const invalid = validateValues(values);
+  if (invalid) return { ok: false, error: invalid };
+
+  Object.assign(config, parseRuntimeSettings({ ...process.env, ...values }));
+  lastReloadAt = new Date().toISOString();
+  for (const listener of listeners) listener();
+  return { ok: true, settings: getRuntimeSettings() };
+}
+
+/** Reload on SIGHUP, logging the outcome. */
+export function reloadConfigOnSignal() {
+  process.on("SIGHUP", () => {
+    const result = reloadConfig();
+    if (result.ok) {
+      console.log(`[Config] Reloaded ${config.configFile}`);
+    } else {
+      console.warn(`[Config] Reload rejected: ${result.error}`);
+    }
+  });
+}
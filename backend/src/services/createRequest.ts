@@ -0,0 +1,201 @@
+import { MAX_DOWNLOAD_MIRRORS } from "../config.js";
+import { isValidPriority, validateDownloadURL } from "./downloadManager.js";
+import { isValidMetadataCompression } from "./sinfInjector.js";
+import { isValidAccountHash } from "../utils/accountHash.js";
+import {
+  INVALID_DOWNLOAD_HEADERS,
+  INVALID_SINFS,
+  isValidDownloadHeaders,
+  isValidSinfs,
+} from "../utils/route.js";
+import type {
+  ExpectedDigests,
+  MetadataCompression,
+  Sinf,
+  Software,
+  TaskPriority,
+} from "../types/index.js";
+
+/** A validated, normalized `POST /api/downloads` body. */
+export interface CreateDownloadRequest {
+  software: Software;
+  accountHash: string;
+  // Candidate URLs in the order to try them; never empty
+  downloadURLs: string[];
+  sinfs: Sinf[];
+  iTunesMetadata?: string;
+  // Overrides METADATA_COMPRESSION for this download
+  metadataCompression?: MetadataCompression;
+  headers?: Record<string, string>;
+  priority: TaskPriority;
+  expectedDigests?: ExpectedDigests;
+  // Supersede a completed package of the same version once this completes
+  replace: boolean;
+  // Create without probing the first URL for its size and validity
+  skipProbe: boolean;
+}
+
+export type ParsedCreateRequest =
+  | { ok: true; request: CreateDownloadRequest }
+  | { ok: false; error: string; code?: string };
+
+// Shape checks for a create request; returns the error message, if any
+function validateShape(body: Record<string, any>): string | null {
+  const { software, accountHash, downloadURL, downloadURLs, sinfs } = body;
+  if (!software || !accountHash || !(downloadURL || downloadURLs) || !sinfs) {
+    return "Missing required fields: software, accountHash, downloadURL, sinfs";
+  }
+  if (
+    typeof software !== "object" ||
+    typeof software.bundleID !== "string" ||
+    typeof software.version !== "string"
+  ) {
+    return "software must include string bundleID and version";
+  }
+  if (downloadURL !== undefined && typeof downloadURL !== "string") {
+    return "downloadURL must be a string";
+  }
+  if (
+    downloadURLs !== undefined &&
+    (!Array.isArray(downloadURLs) ||
+      downloadURLs.length === 0 ||
+      downloadURLs.length > MAX_DOWNLOAD_MIRRORS ||
+      !downloadURLs.every((url) => typeof url === "string"))
+  ) {
+    return `downloadURLs must be an array of 1 to ${MAX_DOWNLOAD_MIRRORS} strings`;
+  }
+  if (!isValidSinfs(sinfs)) return INVALID_SINFS;
+  const { iTunesMetadata } = body;
+  if (iTunesMetadata !== undefined && typeof iTunesMetadata !== "string") {
+    return "iTunesMetadata must be a string";
+  }
+  if (
+    body.metadataCompression !== undefined &&
+    !isValidMetadataCompression(body.metadataCompression)
+  ) {
+    return "metadataCompression must be stored or deflated";
+  }
+  if (body.headers !== undefined && !isValidDownloadHeaders(body.headers)) {
+    return INVALID_DOWNLOAD_HEADERS;
+  }
+  if (body.priority !== undefined && !isValidPriority(body.priority)) {
+    return "priority must be high, normal or low";
+  }
+  if (body.replace !== undefined && typeof body.replace !== "boolean") {
+    return "replace must be a boolean";
+  }
+  if (body.skipProbe !== undefined && typeof body.skipProbe !== "boolean") {
+    return "skipProbe must be a boolean";
+  }
+  const { expectedMd5, expectedSha1 } = body;
+  if (
+    expectedMd5 !== undefined &&
+    (typeof expectedMd5 !== "string" || !/^[0-9a-f]{32}$/i.test(expectedMd5))
+  ) {
+    return "expectedMd5 must be 32 hex characters";
+  }
+  if (
+    expectedSha1 !== undefined &&
+    (typeof expectedSha1 !== "string" || !/^[0-9a-f]{40}$/i.test(expectedSha1))
+  ) {
+    return "expectedSha1 must be 40 hex characters";
+  }
+  const { externalVersionId, purchaseDate } = body;
+  if (
+    externalVersionId !== undefined &&
+    ((typeof externalVersionId !== "number" &&
+      typeof externalVersionId !== "string") ||
+      !/^\d{1,20}$/.test(String(externalVersionId)))
+  ) {
+    return "externalVersionId must be numeric";
+  }
+  if (
+    purchaseDate !== undefined &&
+    (typeof purchaseDate !== "string" || isNaN(Date.parse(purchaseDate)))
+  ) {
+    return "purchaseDate must be an ISO 8601 date";
+  }
+  return null;
+}
+
+/**
+ * Validate a create body and build the request the download manager
+ * acts on. Every invariant of a new download is checked here, so the
+ * route and any other caller construct tasks from the same rules.
+ */
+export function parseCreateRequest(
+  body: Record<string, any>,
+): ParsedCreateRequest {
+  const invalid = validateShape(body);
+  if (invalid) return { ok: false, error: invalid };
+
+  if (!isValidAccountHash(body.accountHash)) {
+    return {
+      ok: false,
+      error: "Missing or invalid accountHash parameter",
+      code: "INVALID_ACCOUNT_HASH",
+    };
+  }
+  // downloadURL alone is a one-element list; given both, it goes first
+  const downloadURLs: string[] = [
+    ...new Set([
+      ...(body.downloadURL ? [body.downloadURL] : []),
+      ...(body.downloadURLs ?? []),
+    ]),
+  ];
+  if (downloadURLs.length > MAX_DOWNLOAD_MIRRORS) {
+    return {
+      ok: false,
+      error: `At most ${MAX_DOWNLOAD_MIRRORS} download URLs may be given`,
+    };
+  }
+  try {
+    for (const url of downloadURLs) validateDownloadURL(url);
+  } catch (err) {
+    return {
+      ok: false,
+      error: err instanceof Error ? err.message : "Invalid download URL",
+    };
+  }
+
+  // Purchase details identify the exact historical build the package is;
+  // only the validated top-level fields end up on software
+  const software = {
+    ...body.software,
+    externalVersionId:
+      body.externalVersionId !== undefined
+        ? String(body.externalVersionId)
+        : undefined,
+    purchaseDate:
+      body.purchaseDate !== undefined
+        ? new Date(body.purchaseDate).toISOString()
+        : undefined,
+  };
+
+  // Absent digests skip the check
+  const { expectedMd5, expectedSha1 } = body;
+  const expectedDigests =
+    expectedMd5 || expectedSha1
+      ? {
+          md5: expectedMd5?.toLowerCase(),
+          sha1: expectedSha1?.toLowerCase(),
+        }
+      : undefined;
+
+  return {
+    ok: true,
+    request: {
+      software,
+      accountHash: body.accountHash,
+      downloadURLs,
+      sinfs: body.sinfs,
+      iTunesMetadata: body.iTunesMetadata,
+      metadataCompression: body.metadataCompression,
+      headers: body.headers,
+      priority: body.priority ?? "normal",
+      expectedDigests,
+      replace: body.replace ?? false,
+      skipProbe: body.skipProbe ?? false,
+    },
+  };
+}
@@ -0,0 +1,161 @@
+import dns from "dns";
+import net from "net";
+import { server as wisp } from "@mercuryworkshop/wisp-js/server";
+import { config, DNS_CACHE_MAX_ENTRIES, DOH_TIMEOUT_MS } from "../config.js";
+import { SingleFlight } from "../utils/singleFlight.js";
+
+export type Resolver = (hostname: string) => Promise<string>;
+
+// Ranges the relay refuses unless wisp.options allow them. Checked on every
+// lookup, cached or not, so widening or narrowing the options applies to
+// entries resolved before the change.
+const loopbackRanges = new net.BlockList();
+loopbackRanges.addSubnet("127.0.0.0", 8, "ipv4");
+loopbackRanges.addAddress("::1", "ipv6");
+
+const privateRanges = new net.BlockList();
+for (const [network, prefix] of [
+  ["0.0.0.0", 8],
+  ["10.0.0.0", 8],
+  ["100.64.0.0", 10],
+  ["169.254.0.0", 16],
+  ["172.16.0.0", 12],
+  ["192.168.0.0", 16],
+  ["198.18.0.0", 15],
+] as const) {
+  privateRanges.addSubnet(network, prefix, "ipv4");
+}
+privateRanges.addSubnet("fc00::", 7, "ipv6");
+privateRanges.addSubnet("fe80::", 10, "ipv6");
+
+/**
+ * Whether an address is loopback, private or unspecified, whatever
+ * wisp.options allow. IPv4-mapped IPv6 addresses are checked as IPv4.
+ */
+export function isNonPublicAddress(address: string): boolean {
+  const mapped = /^::ffff:(\d+\.\d+\.\d+\.\d+)$/i.exec(address);
+  const ip = mapped ? mapped[1] : address;
+  const family = net.isIPv6(ip) ? "ipv6" : "ipv4";
+  return (
+    ip === "::" ||
+    loopbackRanges.check(ip, family) ||
+    privateRanges.check(ip, family)
+  );
+}
+
+function assertAllowedAddress(hostname: string, address: string) {
+  if (!net.isIP(address)) {
+    throw new Error(`Resolver returned no address for ${hostname}`);
+  }
+  const family = net.isIPv6(address) ? "ipv6" : "ipv4";
+  if (
+    !wisp.options.allow_loopback_ips &&
+    loopbackRanges.check(address, family)
+  ) {
+    throw new Error(`${hostname} resolved to loopback address ${address}`);
+  }
+  if (!wisp.options.allow_private_ips && privateRanges.check(address, family)) {
+    throw new Error(`${hostname} resolved to private address ${address}`);
+  }
+}
+
+/** The system resolver (getaddrinfo), which wisp-js uses by default. */
+export const systemResolver: Resolver = async (hostname) =>
+  (await dns.promises.lookup(hostname)).address;
+
+/**
+ * Resolve A records through a DNS-over-HTTPS endpoint speaking the JSON
+ * API (e.g. https://cloudflare-dns.com/dns-query).
+ */
+export function dohResolver(endpoint: string): Resolver {
+  return async (hostname) => {
+    const url = new URL(endpoint);
+    url.searchParams.set("name", hostname);
+    url.searchParams.set("type", "A");
+    const response = await fetch(url, {
+      headers: { Accept: "application/dns-json" },
+      signal: AbortSignal.timeout(DOH_TIMEOUT_MS),
+    });
+    if (!response.ok) {
+      throw new Error(`DoH lookup of ${hostname} failed: ${response.status}`);
+    }
+    const body = (await response.json()) as {
+      Answer?: { type: number; data: string }[];
+    };
+    // Skip CNAMEs (type 5) to the first A record
+    const record = body.Answer?.find(
+      (a) => a.type === 1 && net.isIPv4(a.data),
+    );
+    if (!record) throw new Error(`No A record for ${hostname}`);
+    return record.data;
+  };
+}
+
+/**
+ * Per-hostname address cache in front of a resolver. Entries live for the
+ * TTL current when they are stored (read on every store, so a config reload
+ * applies to new entries); concurrent misses for one host share a lookup.
+ */
+export class DnsCache {
+  private readonly entries = new Map<
+    string,
+    { address: string; expiresAt: number }
+  >();
+  private readonly lookups = new SingleFlight<string>();
+
+  constructor(
+    private readonly resolve: Resolver,
+    private readonly ttlMs: () => number,
+  ) {}
+
+  get size(): number {
+    return this.entries.size;
+  }
+
+  async lookup(hostname: string, now = Date.now()): Promise<string> {
+    const key = hostname.toLowerCase();
+    const cached = this.entries.get(key);
+    let address: string;
+    if (cached && cached.expiresAt > now) {
+      address = cached.address;
+    } else {
+      address = await this.lookups.run(key, () => this.resolve(key));
+      this.store(key, address, now);
+    }
+    assertAllowedAddress(hostname, address);
+    return address;
+  }
+
+  /** Drop entries whose TTL has passed. Returns how many were removed. */
+  evictExpired(now = Date.now()): number {
+    let removed = 0;
+    for (const [key, entry] of this.entries) {
+      if (entry.expiresAt <= now) {
+        this.entries.delete(key);
+        removed++;
+      }
+    }
+    return removed;
+  }
+
+  private store(key: string, address: string, now: number) {
+    const ttlMs = this.ttlMs();
+    if (ttlMs <= 0) return;
+    this.entries.delete(key);
+    if (this.entries.size >= DNS_CACHE_MAX_ENTRIES) {
+      this.evictExpired(now);
+    }
+    // Still full: drop the oldest insertion
+    if (this.entries.size >= DNS_CACHE_MAX_ENTRIES) {
+      const oldest = this.entries.keys().next().value;
+      if (oldest !== undefined) this.entries.delete(oldest);
+    }
+    this.entries.set(key, { address, expiresAt: now + ttlMs });
+  }
+}
+
+/** Resolver and cache used for Wisp CONNECT targets. */
+export const wispDns = new DnsCache(
+  config.wispDnsResolver ? dohResolver(config.wispDnsResolver) : systemResolver,
+  () => config.wispDnsTtlSeconds * 1000,
+);
@@ -1,18 +1,121 @@
+import crypto from "crypto";
 import fs from "fs";
 import path from "path";
 import { Readable } from "stream";
 import { pipeline } from "stream/promises";
 import { v4 as uuidv4 } from "uuid";
-import { config, MAX_DOWNLOAD_SIZE, DOWNLOAD_TIMEOUT_MS } from "../config.js";
-import { inject } from "./sinfInjector.js";
-import type { DownloadTask, Software, Sinf } from "../types/index.js";
+import {
+  config,
+  CREATE_PROBE_TIMEOUT_MS,
+  DOWNLOAD_TIMEOUT_MS,
+  MAX_SHARED_ACCOUNTS,
+  MAX_STALL_RECOVERIES,
+  PAUSE_SWEEP_INTERVAL_MS,
+  SPACE_CHECK_INTERVAL_MS,
+  STALE_PROGRESS_MS,
+  STALL_CHECK_INTERVAL_MS,
+  TRASH_SWEEP_INTERVAL_MS,
+} from "../config.js";
+import { ChaosError, chaosDownloadFault } from "./chaos.js";
+import { onConfigReload } from "./configReload.js";
+import { enrichSoftware, readBundleInfo } from "./metadataEnricher.js";
+import {
+  createPinnedFetch,
+  RedirectRefusedError,
+  UnsafeAddressError,
+} from "./pinnedFetch.js";
+import { inject, InjectionVerificationError } from "./sinfInjector.js";
+import {
+  ContentEncodingError,
+  downloadInChunks,
+  DownloadSizeError,
+  fetchIdentity,
+  probeRangeSupport,
+  type Fetcher,
+} from "./chunkedDownload.js";
+import { hashFile, verifyPackage } from "./packageVerifier.js";
+import {
+  getPackageRoots,
+  moveFile,
+  packageRootOf,
+  selectPackageRoot,
+} from "./packageRoots.js";
+import { ProgressAccumulator } from "./progressAccumulator.js";
+import {
+  checkStorage,
+  isUnderStoragePressure,
+  onStoragePressureChange,
+} from "./storageMonitor.js";
+import { recordTaskEvent, restoreTaskEvents } from "./taskEvents.js";
+import { createFileTaskStore } from "./taskStore.js";
+import {
+  accountHashesEqual,
+  isValidAccountHash,
+} from "../utils/accountHash.js";
+import { preallocate, syncFileAndParentDir } from "../utils/durable.js";
+import { Semaphore } from "../utils/semaphore.js";
+import { SingleFlight } from "../utils/singleFlight.js";
+import {
+  getEncryptionKeys,
+  openSecrets,
+  sealSecrets,
+} from "../utils/persistEncryption.js";
+import type {
+  DownloadTask,
+  DownloadStats,
+  Software,
+  ExpectedDigests,
+  MetadataCompression,
+  RemovalReason,
+  Sinf,
+  TaskErrorCategory,
+  TaskErrorCode,
+  TaskPriority,
+  TaskSummary,
+} from "../types/index.js";
+import type { TaskResponse } from "../types/api.js";
+import type { PackageHealth } from "./packageVerifier.js";
+import type { TaskStore } from "./taskStore.js";
 
 const tasks = new Map<string, DownloadTask>();
 const abortControllers = new Map<string, AbortController>();
+
+// Transient per-task counters, folded into task.stats on completion
+interface StatsTracker {
+  bytes: number;
+  activeMs: number;
+  peakBytesPerSec: number;
+  retries: number;
+  pauseResumeCycles: number;
+  stallRecoveries: number;
+}
+const statsTrackers = new Map<string, StatsTracker>();
+// When each downloading task last received bytes, for the stall watchdog
+const lastProgressAt = new Map<string, number>();
+// Attempts abandoned by the watchdog or forceTask; their late settlement is
+// ignored
+const supersededAttempts = new WeakSet<AbortController>();
+const stallCounters = { recovered: 0, failed: 0 };
+// Tasks waiting for a download slot, with when they started waiting
+const queuedAt = new Map<string, number>();
+// Download requests, pinned to addresses checked to be public
+const downloadFetch = createPinnedFetch(validateDownloadURL);
+// Imported/adopted/uploaded tasks waiting to have their software record rebuilt
+const enrichmentQueue = new Set<string>();
+let enrichmentRunning = false;
 const progressListeners = new Map<string, Set<(task: DownloadTask) => void>>();
 
-const PACKAGES_DIR = path.join(config.dataDir, "packages");
-const TASKS_FILE = path.join(config.dataDir, "tasks.json");
+// Absolute, so in-memory task paths are too
+const DATA_DIR = path.resolve(config.dataDir);
+const TRASH_DIR = path.join(DATA_DIR, "trash");
+let taskStore: TaskStore = createFileTaskStore(
+  path.join(config.dataDir, "tasks.json"),
+  config.durableWrites,
+  config.compressTaskStore,
+);
+// Set when the persisted tasks could not be read on startup: nothing is
+// saved over them and no package is treated as orphaned until a restart
+let taskStoreUnreadable = false;
 // Legacy file from old code — cleaned up on startup
 const LEGACY_DOWNLOADS_FILE = path.join(config.dataDir, "downloads.json");
 
@@ -59,43 +162,425 @@ export function validateDownloadURL(url: string): void {
   }
 }
 
+// Apple's signed CDN URLs expire; the CDN then answers 403 or 410
+class DownloadURLExpiredError extends Error {
+  constructor(status: number) {
+    super(`Download URL rejected with HTTP ${status} (likely expired)`);
+    this.name = "DownloadURLExpiredError";
+  }
+}
+
+// The downloaded bytes don't match a digest the client supplied
+class ChecksumMismatchError extends Error {
+  constructor(readonly algorithm: string) {
+    super(`Downloaded file failed ${algorithm} verification`);
+    this.name = "ChecksumMismatchError";
+  }
+}
+
+// A replace=true download finished but its package did not verify, so the
+// package it was meant to replace is kept
+class ReplacementInvalidError extends Error {
+  constructor(readonly problems: string[]) {
+    super(`Replacement package failed verification: ${problems.join("; ")}`);
+    this.name = "ReplacementInvalidError";
+  }
+}
+
 // --- Security: sanitize task for API responses ---
+// Failure codes/details are only included for requests that passed the
+// ownership check; download statistics only in verbose (single-task) views;
+// the event history only in the single-task view.
+export interface SanitizeOptions {
+  owner?: boolean;
+  verbose?: boolean;
+  history?: boolean;
+}
+
 export function sanitizeTaskForResponse(
   task: DownloadTask,
-): Omit<
-  DownloadTask,
-  "downloadURL" | "sinfs" | "iTunesMetadata" | "filePath"
-> & { hasFile?: boolean } {
-  const { downloadURL, sinfs, iTunesMetadata, filePath, ...safe } = task;
+  options: SanitizeOptions = {},
+): TaskResponse {
+  const {
+    downloadURL,
+    fallbackURLs,
+    sinfs,
+    iTunesMetadata,
+    downloadHeaders,
+    filePath,
+    trashedFrom,
+    sharedWith,
+    stats,
+    injectionSource,
+    events,
+    errorCode,
+    errorDetail,
+    etaSeconds,
+    ...safe
+  } = task;
+  // Speed/ETA are only refreshed when bytes arrive; don't let a stalled
+  // download keep reporting its last sample to polling clients
+  const stalled =
+    task.status === "downloading" &&
+    !!task.updatedAt &&
+    Date.now() - new Date(task.updatedAt).getTime() > STALE_PROGRESS_MS;
   return {
     ...safe,
+    ...(stalled ? { speed: "0 B/s" } : { etaSeconds }),
+    ...(errorCode ? { errorCategory: ERROR_CATEGORIES[errorCode] } : {}),
+    ...(options.owner ? { errorCode, errorDetail } : {}),
+    ...(options.verbose && stats ? { stats } : {}),
+    ...(options.verbose && injectionSource ? { injectionSource } : {}),
+    ...(options.owner && options.history && events ? { events } : {}),
+    ...(queuedAt.has(task.id)
+      ? { queuePosition: getQueuePosition(task.id) }
+      : {}),
+    ...(options.owner && task.status === "paused"
+      ? { pauseExpiresAt: pauseDeadline(task) }
+      : {}),
     hasFile: !!filePath && fs.existsSync(filePath),
   };
 }
 
-// --- Persistence: save only completed task metadata (no secrets) ---
+// Failures a different CDN edge might not have; anything else (disk,
+// size, injection) would fail the same way on every mirror
+const MIRROR_FALLBACK_CODES = new Set<TaskErrorCode>([
+  "download_url_expired",
+  "http_status",
+  "network",
+  "unexpected_encoding",
+  "blocked_address",
+  "redirect_rejected",
+  "checksum_mismatch",
+]);
+
+// Category shown to anyone who can see the task; codes without an obvious
+// remedy (blocked_address, redirect_rejected, invalid_path, forced,
+// unknown) have none
+const ERROR_CATEGORIES: Record<TaskErrorCode, TaskErrorCategory | undefined> =
+  {
+    download_url_expired: "URL_EXPIRED",
+    http_status: "NETWORK",
+    network: "NETWORK",
+    timeout: "NETWORK",
+    stalled: "NETWORK",
+    unexpected_encoding: "NETWORK",
+    blocked_address: undefined,
+    redirect_rejected: undefined,
+    too_large: "TOO_LARGE",
+    disk_full: "DISK_FULL",
+    zip_invalid: "CHECKSUM",
+    checksum_mismatch: "CHECKSUM",
+    injection_failed: "INJECTION",
+    invalid_path: undefined,
+    forced: undefined,
+    unknown: undefined,
+  };
+
+const NETWORK_ERRNOS = new Set([
+  "ECONNREFUSED",
+  "ECONNRESET",
+  "EHOSTUNREACH",
+  "ENETUNREACH",
+  "ENOTFOUND",
+  "EAI_AGAIN",
+  "EPIPE",
+  "ETIMEDOUT",
+]);
+
+// Map an internal failure to an owner-visible code and a short detail.
+// Details must never carry URLs or other secrets; full errors stay in logs.
+function classifyError(err: unknown): {
+  code: TaskErrorCode;
+  detail?: string;
+} {
+  if (err instanceof DownloadURLExpiredError) {
+    return { code: "download_url_expired" };
+  }
+  if (err instanceof InjectionVerificationError) {
+    return {
+      code: "injection_failed",
+      detail: "Injected files failed verification",
+    };
+  }
+  if (err instanceof ChecksumMismatchError) {
+    return {
+      code: "checksum_mismatch",
+      detail: `${err.algorithm} mismatch`,
+    };
+  }
+  if (err instanceof ContentEncodingError) {
+    return { code: "unexpected_encoding", detail: err.message };
+  }
+  if (err instanceof DownloadSizeError) {
+    return { code: "too_large" };
+  }
+  if (err instanceof UnsafeAddressError) {
+    return {
+      code: "blocked_address",
+      detail: "Download host resolved to a non-public address",
+    };
+  }
+  if (err instanceof RedirectRefusedError) {
+    return { code: "redirect_rejected", detail: err.message };
+  }
+  if (err instanceof ReplacementInvalidError) {
+    return { code: "zip_invalid", detail: err.problems[0] };
+  }
+  if (err instanceof ChaosError) {
+    return { code: err.code, detail: err.message };
+  }
+  const errno = (err as NodeJS.ErrnoException)?.code;
+  if (errno === "ENOSPC") {
+    return { code: "disk_full" };
+  }
+
+  const message = err instanceof Error ? err.message : String(err);
+  const http = /HTTP (\d{3})/.exec(message);
+  if (http) return { code: "http_status", detail: `HTTP ${http[1]}` };
+  if (/bundle name|manifest or info plist|central directory/i.test(message)) {
+    return { code: "zip_invalid" };
+  }
+  // fetch() rejects with a TypeError for DNS/connection failures, the
+  // pinned https transport with a socket errno
+  if (err instanceof TypeError || (errno && NETWORK_ERRNOS.has(errno))) {
+    return { code: "network" };
+  }
+  return { code: "unknown" };
+}
+
+// Persisted paths are relative to DATA_DIR ("packages/...", "trash/...") so
+// the data directory can be moved or remounted without losing the library.
+// Packages in another PACKAGE_DIRS root keep their absolute path, which
+// records the root they live under.
+function toPersistedPath(filePath: string | undefined): string | undefined {
+  if (!filePath) return undefined;
+  const relative = path.relative(DATA_DIR, filePath).split(path.sep).join("/");
+  return /^(packages|trash)\//.test(relative) ? relative : filePath;
+}
+
+function insideDataDir(filePath: string): string | undefined {
+  const relative = path.relative(DATA_DIR, filePath);
+  if (!relative || relative.startsWith("..") || path.isAbsolute(relative)) {
+    return undefined;
+  }
+  return filePath;
+}
+
+// Resolve a persisted path against the current DATA_DIR. Older records hold
+// the path as created (absolute, or relative to the working directory); if
+// that is no longer under DATA_DIR it is re-rooted at its packages/ or
+// trash/ segment, where the files are after moving the data directory.
+function resolvePersistedPath(value: unknown): string | undefined {
+  if (typeof value !== "string" || !value) return undefined;
+  const segments = value.split(/[\\/]+/);
+  if (
+    !path.isAbsolute(value) &&
+    (segments[0] === "packages" || segments[0] === "trash")
+  ) {
+    return insideDataDir(path.resolve(DATA_DIR, ...segments));
+  }
+
+  const legacy = path.resolve(value);
+  if (insideDataDir(legacy)) return legacy;
+  if (path.isAbsolute(value) && packageRootOf(legacy)) return legacy;
+  // packages/<account>/<bundle>/<version>/<id>.ipa, a descriptively named
+  // packages/<name>.ipa, or trash/<id>.ipa
+  const n = segments.length;
+  if (segments[n - 5] === "packages") {
+    return insideDataDir(path.resolve(DATA_DIR, ...segments.slice(n - 5)));
+  }
+  if (segments[n - 2] === "packages" || segments[n - 2] === "trash") {
+    return insideDataDir(path.resolve(DATA_DIR, ...segments.slice(n - 2)));
+  }
+  return undefined;
+}
+
+// --- Persistence: save completed, trashed, failed and removed tasks ---
+// Failed and removed tasks are kept so their event history survives a
+// restart; trashed ones so they can still be restored. Unfinished tasks are
+// kept only with PERSIST_ENCRYPTION_KEY, their download ticket encrypted,
+// and come back paused so they can be resumed after a restart.
+const RESUMABLE_STATUSES = new Set(["pending", "downloading", "paused"]);
+
+function isResumable(t: DownloadTask): boolean {
+  return RESUMABLE_STATUSES.has(t.status) && !!t.downloadURL;
+}
+
+function isPersisted(t: DownloadTask, sealKey?: Buffer): boolean {
+  return (
+    ((t.status === "completed" || t.status === "trashed") && !!t.filePath) ||
+    t.status === "failed" ||
+    t.status === "removed" ||
+    (!!sealKey && isResumable(t))
+  );
+}
+
+// The stored form of a task; without sealKey no download ticket is kept
+function toPersistedTask(t: DownloadTask, sealKey: Buffer | undefined) {
+  const unfinished = t.status === "failed" || RESUMABLE_STATUSES.has(t.status);
+  return {
+    id: t.id,
+    software: t.software,
+    accountHash: t.accountHash,
+    sharedWith: t.sharedWith,
+    downloadURL: "",
+    sinfs: [],
+    status: t.status,
+    progress: t.progress,
+    speed: t.speed,
+    error: t.error,
+    errorCode: t.errorCode,
+    errorDetail: t.errorDetail,
+    mirrorIndex: t.mirrorIndex,
+    metadataCompression: t.metadataCompression,
+    // Partial downloads are not kept across restarts
+    filePath:
+      t.status === "completed" || t.status === "trashed"
+        ? toPersistedPath(t.filePath)
+        : undefined,
+    fileSize: t.fileSize,
+    sha256: t.sha256,
+    injectedSinfs: t.injectedSinfs,
+    injectionSource: t.injectionSource,
+    corrupt: t.corrupt,
+    origin: t.origin,
+    metadataEnrichedAt: t.metadataEnrichedAt,
+    trashedAt: t.trashedAt,
+    trashedFrom: toPersistedPath(t.trashedFrom),
+    removedAt: t.removedAt,
+    removedReason: t.removedReason,
+    expectedDigests: unfinished ? t.expectedDigests : undefined,
+    replaces: unfinished ? t.replaces : undefined,
+    pausedAt: t.status === "paused" ? t.pausedAt : undefined,
+    secrets:
+      sealKey && isResumable(t)
+        ? sealSecrets(
+            t.id,
+            {
+              downloadURL: t.downloadURL,
+              fallbackURLs: t.fallbackURLs,
+              sinfs: t.sinfs,
+              iTunesMetadata: t.iTunesMetadata,
+              headers: t.downloadHeaders,
+            },
+            sealKey,
+          )
+        : undefined,
+    stats: t.stats,
+    events: t.events,
+    createdAt: t.createdAt,
+    updatedAt: t.updatedAt,
+  };
+}
+
 function persistTasks() {
-  const completed = Array.from(tasks.values())
-    .filter((t) => t.status === "completed" && t.filePath)
-    .map((t) => ({
-      id: t.id,
-      software: t.software,
-      accountHash: t.accountHash,
-      downloadURL: "",
-      sinfs: [],
-      status: t.status,
-      progress: t.progress,
-      speed: t.speed,
-      filePath: t.filePath,
-      createdAt: t.createdAt,
-    }));
-  fs.writeFileSync(TASKS_FILE, JSON.stringify(completed, null, 2));
+  if (taskStoreUnreadable) return;
+  const [sealKey] = getEncryptionKeys();
+  const persisted = Array.from(tasks.values())
+    .filter((t) => isPersisted(t, sealKey))
+    .map((t) => toPersistedTask(t, sealKey));
+  taskStore.save(persisted);
+}
+
+/**
+ * Every stored task in its persisted form, for GET /api/admin/export. Never
+ * includes download tickets, sealed or not.
+ */
+export function* exportTasks() {
+  for (const task of tasks.values()) {
+    if (isPersisted(task)) yield toPersistedTask(task, undefined);
+  }
+}
+
+const IMPORTABLE_STATUSES = new Set([
+  "completed",
+  "trashed",
+  "failed",
+  "removed",
+]);
+
+export interface ImportResult {
+  imported: number;
+  // Records not taken, by position in the import (0-based)
+  skipped: { index: number; id?: string; reason: string }[];
+}
+
+// Why an exported record can't be taken as is, or null
+function invalidImport(item: Record<string, unknown>): string | null {
+  if (typeof item.id !== "string" || !item.id) return "missing id";
+  if (
+    typeof item.accountHash !== "string" ||
+    !isValidAccountHash(item.accountHash)
+  ) {
+    return "invalid accountHash";
+  }
+  const software = item.software as Record<string, unknown> | undefined;
+  if (
+    typeof software !== "object" ||
+    software === null ||
+    typeof software.bundleID !== "string" ||
+    typeof software.version !== "string"
+  ) {
+    return "invalid software";
+  }
+  if (
+    typeof item.status !== "string" ||
+    !IMPORTABLE_STATUSES.has(item.status)
+  ) {
+    return "unsupported status";
+  }
+  if (
+    typeof item.createdAt !== "string" ||
+    isNaN(Date.parse(item.createdAt))
+  ) {
+    return "invalid createdAt";
+  }
+  return null;
+}
+
+/**
+ * Merge exported task records (POST /api/admin/import). Tasks already
+ * present are left alone, and completed or trashed ones are only taken
+ * when their package file exists here (under DATA_DIR or PACKAGE_DIRS)
+ * and no other task claims it. Imported records carry no download ticket.
+ */
+export function importTasks(records: unknown[]): ImportResult {
+  const result: ImportResult = { imported: 0, skipped: [] };
+  records.forEach((record, index) => {
+    const item =
+      typeof record === "object" && record !== null
+        ? (record as Record<string, unknown>)
+        : {};
+    const id = typeof item.id === "string" ? item.id : undefined;
+    const skip = (reason: string) =>
+      result.skipped.push({ index, id, reason });
+
+    const invalid = invalidImport(item);
+    if (invalid) return skip(invalid);
+    if (tasks.has(id!)) return skip("already exists");
+
+    restoreTasks([{ ...item, secrets: undefined }]);
+    const task = tasks.get(id!);
+    if (!task) return skip("package file not found");
+    const claimed = [task.filePath, task.trashedFrom].some(
+      (p) => p && packagePathOwner(p, task),
+    );
+    if (claimed) {
+      tasks.delete(task.id);
+      return skip("package file belongs to another task");
+    }
+    scheduleEnrichment(task);
+    result.imported++;
+  });
+  if (result.imported > 0) persistTasks();
+  return result;
 }
 
 // Auto-cleanup: delete completed files older than configured days
 export function runTimeCleanup() {
   const { autoCleanupDays } = config;
-  if (autoCleanupDays <= 0) return;
+  if (autoCleanupDays <= 0 || config.readOnly) return;
   const cutoff = Date.now() - autoCleanupDays * 24 * 60 * 60 * 1000;
 
   // Collect IDs first to avoid mutating the map during iteration
@@ -114,34 +599,63 @@ export function runTimeCleanup() {
       } catch {
         // File inaccessible — skip
       }
+    } else if (
+      task.status === "failed" &&
+      new Date(task.createdAt).getTime() < cutoff
+    ) {
+      // Persisted failures only carry history; expire them the same way
+      expiredIds.push(task.id);
+    } else if (
+      task.status === "removed" &&
+      new Date(task.removedAt ?? task.createdAt).getTime() < cutoff
+    ) {
+      // So do the records of packages cleanup already removed
+      expiredIds.push(task.id);
     }
   }
 
   for (const id of expiredIds) {
-    console.log(`[Cleanup] Deleting expired task: ${id}`);
-    deleteTask(id);
+    const task = tasks.get(id)!;
+    if (task.status === "completed") {
+      console.log(`[Cleanup] Removing expired package: ${id}`);
+      removeByCleanup(task, "age");
+    } else {
+      console.log(`[Cleanup] Deleting expired task: ${id}`);
+      deleteTask(id);
+    }
   }
 }
 
-// Auto-cleanup: evict oldest completed files when total size exceeds limit
+// Auto-cleanup: evict trashed, then oldest completed files when total size
+// exceeds limit
 export function runSpaceCleanup() {
   const { autoCleanupMaxMB } = config;
-  if (autoCleanupMaxMB <= 0) return;
+  if (autoCleanupMaxMB <= 0 || config.readOnly) return;
   const maxBytes = autoCleanupMaxMB * 1024 * 1024;
 
   let totalBytes = 0;
-  const fileTasks: { id: string; size: number; mtimeMs: number }[] = [];
+  const fileTasks: {
+    id: string;
+    size: number;
+    mtimeMs: number;
+    trashed: boolean;
+  }[] = [];
 
   for (const task of tasks.values()) {
     if (
-      task.status === "completed" &&
+      (task.status === "completed" || task.status === "trashed") &&
       task.filePath &&
       fs.existsSync(task.filePath)
     ) {
       try {
         const stat = fs.statSync(task.filePath);
         totalBytes += stat.size;
-        fileTasks.push({ id: task.id, size: stat.size, mtimeMs: stat.mtimeMs });
+        fileTasks.push({
+          id: task.id,
+          size: stat.size,
+          mtimeMs: stat.mtimeMs,
+          trashed: task.status === "trashed",
+        });
       } catch {
         // File inaccessible — skip
       }
@@ -150,15 +664,62 @@ export function runSpaceCleanup() {
 
   if (totalBytes <= maxBytes) return;
 
-  fileTasks.sort((a, b) => a.mtimeMs - b.mtimeMs);
+  fileTasks.sort(
+    (a, b) => Number(b.trashed) - Number(a.trashed) || a.mtimeMs - b.mtimeMs,
+  );
   for (const ft of fileTasks) {
     console.log(`[Cleanup] Space limit exceeded, deleting task: ${ft.id}`);
-    deleteTask(ft.id);
+    // Trashed packages were already deleted by their owner
+    if (ft.trashed) deleteTask(ft.id);
+    else removeByCleanup(tasks.get(ft.id)!, "space");
     totalBytes -= ft.size;
     if (totalBytes <= maxBytes) break;
   }
 }
 
+// Auto-cleanup: keep only the newest completed packages of each account
+// When a package finished: its latest "completed" event, else (history
+// trimmed or never recorded) when the task was created
+function completedAt(task: DownloadTask): string {
+  const events = task.events ?? [];
+  for (let i = events.length - 1; i >= 0; i--) {
+    if (events[i].type === "completed") return events[i].at;
+  }
+  return task.createdAt;
+}
+
+export function runCountCleanup() {
+  const { autoCleanupMaxPerAccount, autoCleanupAccountLimits } = config;
+  if (autoCleanupMaxPerAccount <= 0 && autoCleanupAccountLimits.size === 0) {
+    return;
+  }
+  if (config.readOnly) return;
+
+  const byAccount = new Map<string, DownloadTask[]>();
+  for (const task of tasks.values()) {
+    if (task.status !== "completed") continue;
+    const owned = byAccount.get(task.accountHash) ?? [];
+    owned.push(task);
+    byAccount.set(task.accountHash, owned);
+  }
+
+  for (const [accountHash, owned] of byAccount) {
+    const limit =
+      autoCleanupAccountLimits.get(accountHash) ?? autoCleanupMaxPerAccount;
+    if (limit <= 0 || owned.length <= limit) continue;
+
+    // Newest packages first: a slow download started long ago still counts
+    // as new once it finishes
+    owned.sort((a, b) => completedAt(b).localeCompare(completedAt(a)));
+    for (const task of owned.slice(limit)) {
+      console.log(
+        `[Cleanup] Account package limit (${limit}) exceeded, deleting task: ${task.id} (${task.software.bundleID} ${task.software.version})`,
+      );
+      removeByCleanup(task, "account_limit");
+    }
+  }
+}
+
 // Schedule daily time-based cleanup at midnight (self-correcting to avoid drift)
 function scheduleDailyCleanup() {
   function msUntilMidnight(): number {
@@ -176,61 +737,345 @@ function scheduleDailyCleanup() {
 
   function tick() {
     runTimeCleanup();
+    runCountCleanup();
     setTimeout(tick, msUntilMidnight());
   }
 
   setTimeout(tick, msUntilMidnight());
 }
 
+// Rebuild in-memory tasks from persisted records
+function restoreTasks(data: unknown[]) {
+  const keys = getEncryptionKeys();
+  for (const item of data as Record<string, any>[]) {
+    const filePath = resolvePersistedPath(item.filePath);
+    const trashedFrom = resolvePersistedPath(item.trashedFrom);
+
+    // Failed tasks are restored without a file or ticket, so only their
+    // error and history remain; the client can PATCH a new ticket
+    if (item.id && item.status === "failed") {
+      tasks.set(item.id, {
+        id: item.id,
+        software: item.software,
+        accountHash: item.accountHash,
+        sharedWith: Array.isArray(item.sharedWith)
+          ? item.sharedWith
+          : undefined,
+        downloadURL: "",
+        mirrorIndex: item.mirrorIndex,
+        sinfs: [],
+        metadataCompression: item.metadataCompression,
+        expectedDigests: item.expectedDigests,
+        replaces: Array.isArray(item.replaces) ? item.replaces : undefined,
+        status: "failed",
+        progress: item.progress ?? 0,
+        speed: "0 B/s",
+        error: item.error,
+        errorCode: item.errorCode,
+        errorDetail: item.errorDetail,
+        events: restoreTaskEvents(item.events),
+        createdAt: item.createdAt,
+        updatedAt: item.updatedAt,
+      });
+      continue;
+    }
+
+    // Unfinished tasks come back paused with their ticket (if it decrypts)
+    // and start over on resume; without a ticket the client can PATCH one
+    if (item.id && RESUMABLE_STATUSES.has(item.status)) {
+      const secrets = item.secrets
+        ? openSecrets(item.id, item.secrets, keys)
+        : null;
+      if (item.secrets && !secrets) {
+        console.warn(
+          `Task ${item.id}: could not decrypt its download ticket; dropping it`,
+        );
+      }
+      const wasPaused = item.status === "paused";
+      const now = new Date().toISOString();
+      tasks.set(item.id, {
+        id: item.id,
+        software: item.software,
+        accountHash: item.accountHash,
+        sharedWith: Array.isArray(item.sharedWith)
+          ? item.sharedWith
+          : undefined,
+        downloadURL: secrets?.downloadURL ?? "",
+        fallbackURLs: secrets?.fallbackURLs,
+        mirrorIndex: item.mirrorIndex,
+        sinfs: secrets?.sinfs ?? [],
+        iTunesMetadata: secrets?.iTunesMetadata,
+        metadataCompression: item.metadataCompression,
+        downloadHeaders: secrets?.headers,
+        expectedDigests: item.expectedDigests,
+        replaces: Array.isArray(item.replaces) ? item.replaces : undefined,
+        status: "paused",
+        pausedAt: (wasPaused && item.pausedAt) || now,
+        progress: 0,
+        speed: "0 B/s",
+        events: restoreTaskEvents(item.events),
+        createdAt: item.createdAt,
+        updatedAt: wasPaused ? item.updatedAt : now,
+      });
+      continue;
+    }
+
+    // Packages removed by cleanup: history only, like failed tasks
+    if (item.id && item.status === "removed") {
+      tasks.set(item.id, {
+        id: item.id,
+        software: item.software,
+        accountHash: item.accountHash,
+        sharedWith: Array.isArray(item.sharedWith)
+          ? item.sharedWith
+          : undefined,
+        downloadURL: "",
+        sinfs: [],
+        status: "removed",
+        progress: 100,
+        speed: "0 B/s",
+        fileSize: item.fileSize,
+        sha256: item.sha256,
+        origin: item.origin,
+        removedAt: item.removedAt,
+        removedReason: item.removedReason,
+        stats: item.stats,
+        events: restoreTaskEvents(item.events),
+        createdAt: item.createdAt,
+        updatedAt: item.updatedAt,
+      });
+      continue;
+    }
+
+    // Trashed packages keep their trash location and original path
+    if (
+      item.id &&
+      item.status === "trashed" &&
+      filePath &&
+      trashedFrom &&
+      fs.existsSync(filePath)
+    ) {
+      tasks.set(item.id, {
+        id: item.id,
+        software: item.software,
+        accountHash: item.accountHash,
+        sharedWith: Array.isArray(item.sharedWith)
+          ? item.sharedWith
+          : undefined,
+        downloadURL: "",
+        sinfs: [],
+        status: "trashed",
+        progress: 100,
+        speed: "0 B/s",
+        filePath,
+        fileSize: item.fileSize,
+        sha256: item.sha256,
+        injectedSinfs: item.injectedSinfs,
+        injectionSource: item.injectionSource,
+        corrupt: item.corrupt,
+        origin: item.origin,
+        metadataEnrichedAt: item.metadataEnrichedAt,
+        trashedAt: item.trashedAt,
+        trashedFrom,
+        stats: item.stats,
+        events: restoreTaskEvents(item.events),
+        createdAt: item.createdAt,
+        updatedAt: item.updatedAt,
+      });
+      continue;
+    }
+
+    // Only restore completed tasks whose IPA file still exists
+    if (
+      item.id &&
+      item.status === "completed" &&
+      filePath &&
+      fs.existsSync(filePath)
+    ) {
+      const task: DownloadTask = {
+        id: item.id,
+        software: item.software,
+        accountHash: item.accountHash,
+        sharedWith: Array.isArray(item.sharedWith)
+          ? item.sharedWith
+          : undefined,
+        downloadURL: "",
+        sinfs: [],
+        status: "completed",
+        progress: 100,
+        speed: "0 B/s",
+        filePath,
+        fileSize: item.fileSize,
+        sha256: item.sha256,
+        injectedSinfs: item.injectedSinfs,
+        injectionSource: item.injectionSource,
+        mirrorIndex: item.mirrorIndex,
+        metadataCompression: item.metadataCompression,
+        corrupt: item.corrupt,
+        origin: item.origin,
+        metadataEnrichedAt: item.metadataEnrichedAt,
+        stats: item.stats,
+        events: restoreTaskEvents(item.events),
+        createdAt: item.createdAt,
+        updatedAt: item.updatedAt,
+      };
+      tasks.set(task.id, task);
+    }
+  }
+}
+
+/**
+ * Swap the task metadata store and reload tasks from it, dropping any
+ * in-flight state. Tests use this with MemoryTaskStore.
+ */
+export function setTaskStore(store: TaskStore) {
+  for (const controller of abortControllers.values()) controller.abort();
+  abortControllers.clear();
+  tasks.clear();
+  statsTrackers.clear();
+  lastProgressAt.clear();
+  queuedAt.clear();
+  enrichmentQueue.clear();
+  progressListeners.clear();
+  taskStore = store;
+  taskStoreUnreadable = false;
+  restoreTasks(store.load());
+  for (const task of tasks.values()) scheduleEnrichment(task);
+}
+
 function initOnStartup() {
   // Remove legacy downloads.json from old code
   if (fs.existsSync(LEGACY_DOWNLOADS_FILE)) {
     fs.unlinkSync(LEGACY_DOWNLOADS_FILE);
   }
 
-  // Ensure packages dir exists
-  fs.mkdirSync(PACKAGES_DIR, { recursive: true });
+  // Ensure package roots exist
+  for (const root of getPackageRoots()) {
+    fs.mkdirSync(root, { recursive: true });
+  }
+
+  // Load completed, trashed and failed tasks from previous run
+  try {
+    restoreTasks(taskStore.load());
+  } catch (err) {
+    // Corrupted or newer store (a downgrade): every package would look
+    // orphaned and the next save would replace the file, so keep both as
+    // they are and stay read-only until an operator fixes it and restarts
+    console.error(
+      "Could not load persisted tasks, starting read-only:",
+      err instanceof Error ? err.message : err,
+    );
+    taskStoreUnreadable = true;
+    setReadOnly(true);
+  }
+
+  // Temp files a crash left behind, then orphaned IPA files (files without
+  // a task). The trash lives outside the package roots and is only emptied by
+  // sweepTrash(). Like every cleanup, skipped in read-only mode.
+  if (!taskStoreUnreadable && !config.readOnly) {
+    sweepTempFiles();
+    cleanOrphanedPackages();
+  }
+
+  for (const task of tasks.values()) scheduleEnrichment(task);
+
+  // Run time-based cleanup once on startup, then schedule daily
+  runTimeCleanup();
+  runCountCleanup();
+  scheduleDailyCleanup();
+
+  sweepTrash();
+  setInterval(sweepTrash, TRASH_SWEEP_INTERVAL_MS).unref();
+  setInterval(checkStalledDownloads, STALL_CHECK_INTERVAL_MS).unref();
+  setInterval(expirePausedTasks, PAUSE_SWEEP_INTERVAL_MS).unref();
+
+  onStoragePressureChange(handleStoragePressure);
+  checkStorage();
+  setInterval(checkStorage, SPACE_CHECK_INTERVAL_MS).unref();
+
+  // A raised concurrency limit or changed floor applies right away
+  onConfigReload(() => {
+    drainQueue();
+    checkStorage();
+  });
+}
 
-  // Load completed tasks from previous run
-  if (fs.existsSync(TASKS_FILE)) {
+// Work files written next to a package: the copy a re-injection rewrites,
+// and atomic writes not yet renamed into place
+const TEMP_SUFFIXES = [".reinject", ".tmp"];
+
+/**
+ * Delete temp files left by a crash mid-injection whose package is still
+ * intact (present, and the size recorded on completion). Temp files of a
+ * missing or damaged package are left to orphan cleanup. Returns how many
+ * were removed.
+ */
+export function sweepTempFiles(): number {
+  const intact = new Set<string>();
+  for (const task of tasks.values()) {
+    if (task.status !== "completed" || !task.filePath) continue;
+    const filePath = path.resolve(task.filePath);
     try {
-      const data = JSON.parse(fs.readFileSync(TASKS_FILE, "utf-8"));
-      if (Array.isArray(data)) {
-        for (const item of data) {
-          // Only restore completed tasks whose IPA file still exists
-          if (
-            item.id &&
-            item.status === "completed" &&
-            item.filePath &&
-            fs.existsSync(item.filePath)
-          ) {
-            const task: DownloadTask = {
-              id: item.id,
-              software: item.software,
-              accountHash: item.accountHash,
-              downloadURL: "",
-              sinfs: [],
-              status: "completed",
-              progress: 100,
-              speed: "0 B/s",
-              filePath: item.filePath,
-              createdAt: item.createdAt,
-            };
-            tasks.set(task.id, task);
-          }
-        }
+      const { size } = fs.statSync(filePath);
+      if (task.fileSize === undefined || size === task.fileSize) {
+        intact.add(filePath);
       }
     } catch {
-      // Corrupted file — start fresh
+      // Package gone; nothing to recover it from
+    }
+  }
+
+  let removed = 0;
+  let bytes = 0;
+  function walk(dir: string) {
+    if (!fs.existsSync(dir)) return;
+    for (const entry of fs.readdirSync(dir, { withFileTypes: true })) {
+      const fullPath = path.join(dir, entry.name);
+      if (entry.isDirectory()) {
+        walk(fullPath);
+        continue;
+      }
+      const suffix = TEMP_SUFFIXES.find((s) => entry.name.endsWith(s));
+      if (!suffix || !entry.isFile()) continue;
+      const finalPath = path.resolve(fullPath.slice(0, -suffix.length));
+      if (!intact.has(finalPath)) continue;
+      // One unreadable or locked file must not stop the sweep
+      try {
+        const { size } = fs.statSync(fullPath);
+        fs.unlinkSync(fullPath);
+        bytes += size;
+        removed++;
+      } catch (err) {
+        console.warn(
+          `[Cleanup] Could not remove temp file ${fullPath}: ${(err as Error).message}`,
+        );
+      }
     }
   }
+  for (const root of getPackageRoots()) walk(root);
 
-  // Clean up orphaned IPA files (files without a task)
-  cleanOrphanedPackages();
+  if (removed > 0) {
+    console.log(
+      `[Cleanup] Removed ${removed} leftover temp file(s), ${Math.round(bytes / 1024 / 1024)} MB`,
+    );
+  }
+  return removed;
+}
 
-  // Run time-based cleanup once on startup, then schedule daily
-  runTimeCleanup();
-  scheduleDailyCleanup();
+// Whether a file, as its path segments under a package root, is one this
+// server writes: `<account>/<bundle>/<version>/<id>.ipa` or a descriptive
+// `<name>.ipa` at the top (either with a temp suffix), or an upload's
+// `.uploads/<id>.part`. A root can be a directory on a shared disk, so
+// anything else in it is left alone.
+function isOwnPackageFile(segments: string[]): boolean {
+  if (!segments.every((s) => SAFE_SEGMENT_RE.test(s))) return false;
+  const name = segments[segments.length - 1];
+  if (segments.length === 2 && segments[0] === ".uploads") {
+    return name.endsWith(".part");
+  }
+  if (segments.length !== 1 && segments.length !== 4) return false;
+  const suffix = TEMP_SUFFIXES.find((s) => name.endsWith(s)) ?? "";
+  return name.slice(0, name.length - suffix.length).endsWith(".ipa");
 }
 
 function cleanOrphanedPackages() {
@@ -241,33 +1086,47 @@ function cleanOrphanedPackages() {
     }
   }
 
-  const packagesBase = path.resolve(PACKAGES_DIR);
-
-  function walkAndClean(dir: string) {
-    if (!fs.existsSync(dir)) return;
+  // Returns whether anything was removed below `dir`
+  function walkAndClean(dir: string, segments: string[]): boolean {
+    if (!fs.existsSync(dir)) return false;
+    let removed = false;
     const entries = fs.readdirSync(dir, { withFileTypes: true });
     for (const entry of entries) {
       const fullPath = path.join(dir, entry.name);
+      const entrySegments = [...segments, entry.name];
       if (entry.isDirectory()) {
-        walkAndClean(fullPath);
-        // Remove empty directories
+        if (!walkAndClean(fullPath, entrySegments)) continue;
+        removed = true;
+        // Only directories the removals emptied, not empty ones of others
         if (fs.readdirSync(fullPath).length === 0) {
           fs.rmdirSync(fullPath);
         }
-      } else if (entry.isFile() && !knownPaths.has(path.resolve(fullPath))) {
+      } else if (
+        entry.isFile() &&
+        isOwnPackageFile(entrySegments) &&
+        !knownPaths.has(path.resolve(fullPath))
+      ) {
         // Orphaned file — remove
         fs.unlinkSync(fullPath);
+        removed = true;
       }
     }
+    return removed;
   }
 
-  walkAndClean(packagesBase);
+  for (const root of getPackageRoots()) walkAndClean(root, []);
 }
 
 // Initialize on startup
 initOnStartup();
 
 function notifyProgress(task: DownloadTask) {
+  task.updatedAt = new Date().toISOString();
+  if (task.status !== "downloading") {
+    task.etaSeconds = undefined;
+    task.indeterminate = undefined;
+    task.downloadedBytes = undefined;
+  }
   const listeners = progressListeners.get(task.id);
   if (listeners) {
     for (const listener of listeners) {
@@ -301,6 +1160,20 @@ export function removeProgressListener(
   }
 }
 
+/**
+ * Compact record for list views. Built from memory only: no file checks,
+ * so large listings cost no disk I/O; size is the one recorded on completion.
+ */
+export function summarizeTask(task: DownloadTask): TaskSummary {
+  return {
+    id: task.id,
+    name: task.software.name,
+    version: task.software.version,
+    status: task.status,
+    fileSize: task.fileSize,
+  };
+}
+
 export function getAllTasks(): DownloadTask[] {
   return Array.from(tasks.values());
 }
@@ -312,6 +1185,9 @@ export function getTask(id: string): DownloadTask | undefined {
 export function deleteTask(id: string): boolean {
   const task = tasks.get(id);
   if (!task) return false;
+  lastProgressAt.delete(id);
+  queuedAt.delete(id);
+  verificationJobs.delete(id);
 
   // Abort if downloading
   const controller = abortControllers.get(id);
@@ -320,242 +1196,1538 @@ export function deleteTask(id: string): boolean {
     abortControllers.delete(id);
   }
 
-  // Remove file if exists, with path safety check
-  if (task.filePath) {
-    const resolved = path.resolve(task.filePath);
-    const packagesBase = path.resolve(PACKAGES_DIR);
-    if (
-      resolved.startsWith(packagesBase + path.sep) &&
-      fs.existsSync(resolved)
-    ) {
-      fs.unlinkSync(resolved);
-
-      // Clean up empty parent directories
-      let dir = path.dirname(resolved);
-      while (dir !== packagesBase && dir.startsWith(packagesBase)) {
-        const contents = fs.readdirSync(dir);
-        if (contents.length === 0) {
-          fs.rmdirSync(dir);
-          dir = path.dirname(dir);
-        } else {
-          break;
-        }
-      }
-    }
-  }
-
+  removePackageFile(task);
   tasks.delete(id);
   progressListeners.delete(id);
+  statsTrackers.delete(id);
   persistTasks();
   return true;
 }
 
-export function pauseTask(id: string): boolean {
+/**
+ * Delete a task's package file and the directories it leaves empty, keeping
+ * the task itself. Files outside the package roots and trash are left alone.
+ */
+export function purgePackageFile(id: string): void {
   const task = tasks.get(id);
-  if (!task || task.status !== "downloading") return false;
+  if (task) removePackageFile(task);
+}
 
-  const controller = abortControllers.get(id);
-  if (controller) {
-    controller.abort();
-    abortControllers.delete(id);
+// Remove the task's file if it exists, with path safety check (packages or
+// trash)
+function removePackageFile(task: DownloadTask) {
+  if (!task.filePath) return;
+  const resolved = path.resolve(task.filePath);
+  const trashBase = path.resolve(TRASH_DIR);
+  const base =
+    packageRootOf(resolved) ??
+    (resolved.startsWith(trashBase + path.sep) ? trashBase : undefined);
+  if (base && fs.existsSync(resolved)) {
+    fs.unlinkSync(resolved);
+    removeEmptyParents(path.dirname(resolved), base);
+  }
+}
+
+/**
+ * Detach the completed tasks a finished replace=true download supersedes.
+ * They are marked removed ("replaced") for anyone still watching and
+ * dropped from the store; the caller deletes their files once the new
+ * package is persisted. Tasks trashed or deleted meanwhile are skipped.
+ */
+function takeReplacedTasks(task: DownloadTask): DownloadTask[] {
+  const replaced: DownloadTask[] = [];
+  for (const id of task.replaces ?? []) {
+    const old = tasks.get(id);
+    if (!old || old.status !== "completed") continue;
+    verificationJobs.delete(id);
+    old.status = "removed";
+    old.removedAt = new Date().toISOString();
+    old.removedReason = "replaced";
+    recordTaskEvent(old, "removed", `replaced by ${task.id}`);
+    notifyProgress(old);
+    tasks.delete(id);
+    progressListeners.delete(id);
+    replaced.push(old);
   }
+  task.replaces = undefined;
+  return replaced;
+}
 
-  task.status = "paused";
+/**
+ * Delete a completed package on behalf of a retention policy. Unlike
+ * deleteTask, the task stays as "removed" and progress listeners are
+ * notified, so clients still holding it learn right away rather than from a
+ * later 404. Explicit deletions go through deleteTask/trashTask instead.
+ */
+function removeByCleanup(task: DownloadTask, reason: RemovalReason) {
+  verificationJobs.delete(task.id);
+  removePackageFile(task);
+  task.filePath = undefined;
+  task.corrupt = undefined;
+  task.status = "removed";
+  task.removedAt = new Date().toISOString();
+  task.removedReason = reason;
+  recordTaskEvent(task, "removed", reason);
   notifyProgress(task);
-  return true;
+  persistTasks();
 }
 
-export function resumeTask(id: string): boolean {
-  const task = tasks.get(id);
-  if (!task || task.status !== "paused") return false;
+// Remove now-empty directories from `dir` up to (not including) `base`
+function removeEmptyParents(dir: string, base: string) {
+  while (dir !== base && dir.startsWith(base + path.sep)) {
+    if (fs.readdirSync(dir).length > 0) break;
+    fs.rmdirSync(dir);
+    dir = path.dirname(dir);
+  }
+}
 
-  startDownload(task);
+/**
+ * Soft-delete a completed package: move its file into the trash, where it
+ * can be restored until TRASH_RETENTION_HOURS elapse. Returns false when
+ * the task has no package to trash.
+ */
+export function trashTask(id: string): boolean {
+  const task = tasks.get(id);
+  if (!task || task.status !== "completed" || !task.filePath) return false;
+
+  const resolved = path.resolve(task.filePath);
+  const root = packageRootOf(resolved);
+  if (!root) return false;
+  if (!fs.existsSync(resolved)) return false;
+
+  fs.mkdirSync(TRASH_DIR, { recursive: true });
+  const trashPath = path.join(TRASH_DIR, `${task.id}.ipa`);
+  moveFile(resolved, trashPath);
+  removeEmptyParents(path.dirname(resolved), root);
+
+  task.trashedFrom = task.filePath;
+  task.filePath = trashPath;
+  task.trashedAt = new Date().toISOString();
+  task.status = "trashed";
+  recordTaskEvent(task, "trashed");
+  notifyProgress(task);
+  persistTasks();
   return true;
 }
 
-export function createTask(
-  software: Software,
-  accountHash: string,
-  downloadURL: string,
-  sinfs: Sinf[],
-  iTunesMetadata?: string,
-): DownloadTask {
-  // Validate download URL
-  validateDownloadURL(downloadURL);
-
-  // Validate path segments
-  safePathSegment(accountHash, "accountHash");
-  safePathSegment(software.bundleID, "bundleID");
-  safePathSegment(software.version, "version");
-
-  const task: DownloadTask = {
-    id: uuidv4(),
-    software,
-    accountHash,
-    downloadURL,
-    sinfs,
-    iTunesMetadata,
-    status: "pending",
-    progress: 0,
-    speed: "0 B/s",
-    createdAt: new Date().toISOString(),
-  };
+export type RestoreErrorCode = "NOT_TRASHED" | "RETENTION_EXPIRED";
 
-  tasks.set(task.id, task);
-  startDownload(task);
-  return task;
+function trashExpired(task: DownloadTask): boolean {
+  const retentionMs = config.trashRetentionHours * 60 * 60 * 1000;
+  return (
+    !task.trashedAt ||
+    Date.now() - new Date(task.trashedAt).getTime() > retentionMs
+  );
 }
 
-async function startDownload(task: DownloadTask) {
-  // Pre-download cleanup: expire old files + enforce space limit
-  runTimeCleanup();
-  runSpaceCleanup();
-
-  const controller = new AbortController();
-  abortControllers.set(task.id, controller);
+/** Move a trashed package back. Returns null on success. */
+export function restoreTrashedTask(id: string): RestoreErrorCode | null {
+  const task = tasks.get(id);
+  if (!task || task.status !== "trashed" || !task.filePath) {
+    return "NOT_TRASHED";
+  }
+  if (!task.trashedFrom || trashExpired(task)) return "RETENTION_EXPIRED";
 
-  // Set a global timeout for the entire download
-  const timeout = setTimeout(() => controller.abort(), DOWNLOAD_TIMEOUT_MS);
+  fs.mkdirSync(path.dirname(task.trashedFrom), { recursive: true });
+  moveFile(task.filePath, task.trashedFrom);
 
-  task.status = "downloading";
-  task.progress = 0;
-  task.speed = "0 B/s";
-  task.error = undefined;
+  task.filePath = task.trashedFrom;
+  task.trashedFrom = undefined;
+  task.trashedAt = undefined;
+  task.status = "completed";
+  recordTaskEvent(task, "restored");
   notifyProgress(task);
+  persistTasks();
+  return null;
+}
 
-  // Sanitize path segments
-  const safeAccountHash = safePathSegment(task.accountHash, "accountHash");
-  const safeBundleID = safePathSegment(task.software.bundleID, "bundleID");
-  const safeVersion = safePathSegment(task.software.version, "version");
+/**
+ * Permanently delete trashed packages past the retention window, plus any
+ * trash files no task refers to.
+ */
+export function sweepTrash() {
+  if (config.readOnly) return;
+  const expiredIds: string[] = [];
+  const knownPaths = new Set<string>();
+  for (const task of tasks.values()) {
+    if (task.status !== "trashed") continue;
+    if (trashExpired(task)) {
+      expiredIds.push(task.id);
+    } else if (task.filePath) {
+      knownPaths.add(path.resolve(task.filePath));
+    }
+  }
 
-  const dir = path.join(
-    PACKAGES_DIR,
-    safeAccountHash,
-    safeBundleID,
-    safeVersion,
-  );
+  for (const id of expiredIds) {
+    console.log(`[Cleanup] Purging trashed task: ${id}`);
+    deleteTask(id);
+  }
 
-  // Verify the resolved path is within PACKAGES_DIR
-  const resolvedDir = path.resolve(dir);
-  const packagesBase = path.resolve(PACKAGES_DIR);
-  if (!resolvedDir.startsWith(packagesBase + path.sep)) {
-    task.status = "failed";
-    task.error = "Invalid path";
-    clearTimeout(timeout);
-    notifyProgress(task);
-    return;
+  if (!fs.existsSync(TRASH_DIR)) return;
+  for (const entry of fs.readdirSync(TRASH_DIR, { withFileTypes: true })) {
+    const fullPath = path.join(TRASH_DIR, entry.name);
+    if (entry.isFile() && !knownPaths.has(path.resolve(fullPath))) {
+      fs.unlinkSync(fullPath);
+    }
   }
+}
 
-  fs.mkdirSync(dir, { recursive: true });
+// Reason codes for rejected pause/resume requests (surfaced as 409 Conflict)
+export type TransitionErrorCode =
+  | "ALREADY_PAUSED"
+  | "ALREADY_COMPLETED"
+  | "NOT_ACTIVE";
 
-  const filePath = path.join(dir, `${task.id}.ipa`);
-  task.filePath = filePath;
+function transitionError(task: DownloadTask | undefined): TransitionErrorCode {
+  if (task?.status === "paused") return "ALREADY_PAUSED";
+  if (task?.status === "completed") return "ALREADY_COMPLETED";
+  return "NOT_ACTIVE";
+}
+
+/** Pause a downloading or queued task. Returns null on success, or the reason it was rejected. */
+export function pauseTask(id: string): TransitionErrorCode | null {
+  const task = tasks.get(id);
+  if (!task || (task.status !== "downloading" && !queuedAt.has(id))) {
+    return transitionError(task);
+  }
+  queuedAt.delete(id);
+
+  const controller = abortControllers.get(id);
+  if (controller) {
+    controller.abort();
+    abortControllers.delete(id);
+  }
+
+  task.status = "paused";
+  task.pausedAt = new Date().toISOString();
+  const tracker = statsTrackers.get(id);
+  if (tracker) tracker.pauseResumeCycles++;
+  recordTaskEvent(task, "paused");
+  notifyProgress(task);
+  persistTasks();
+  return null;
+}
+
+/** Resume a paused task. Returns null on success, or the reason it was rejected. */
+export function resumeTask(id: string): TransitionErrorCode | null {
+  const task = tasks.get(id);
+  if (!task || task.status !== "paused") return transitionError(task);
+
+  recordTaskEvent(task, "resumed");
+  scheduleDownload(task);
+  return null;
+}
+
+// Downloads paused because the data volume ran low, resumed on recovery
+const pausedForSpace = new Set<string>();
+
+/**
+ * Storage monitor hook: with PAUSE_ON_LOW_SPACE, pause running downloads
+ * when free space drops below the floor and resume the same ones (if still
+ * paused) once it recovers.
+ */
+export function handleStoragePressure(pressure: boolean) {
+  if (!config.pauseOnLowSpace) return;
+  if (pressure) {
+    for (const task of tasks.values()) {
+      if (task.status === "downloading" && pauseTask(task.id) === null) {
+        pausedForSpace.add(task.id);
+      }
+    }
+    return;
+  }
+  for (const id of pausedForSpace) {
+    if (tasks.get(id)?.status === "paused") resumeTask(id);
+  }
+  pausedForSpace.clear();
+  drainQueue();
+}
+
+// When a paused task will be resumed or expired by expirePausedTasks
+function pauseDeadline(task: DownloadTask): string | undefined {
+  const hours = config.pauseMaxAgeHours;
+  if (hours <= 0 || !task.pausedAt) return undefined;
+  return new Date(
+    new Date(task.pausedAt).getTime() + hours * 60 * 60 * 1000,
+  ).toISOString();
+}
+
+/**
+ * Sweeper pass for PAUSE_MAX_AGE_HOURS. A task paused longer than that is
+ * resumed while its URL may still work, or failed as download_url_expired
+ * once the CDN has refused it (or it no longer validates), so the client
+ * refreshes the ticket instead of resuming into a certain 403. Tasks held
+ * for low disk space are left to the storage monitor.
+ */
+export function expirePausedTasks(now = Date.now()) {
+  const maxAgeMs = config.pauseMaxAgeHours * 60 * 60 * 1000;
+  if (maxAgeMs <= 0 || config.readOnly) return;
+
+  for (const task of tasks.values()) {
+    if (task.status !== "paused" || !task.pausedAt) continue;
+    if (pausedForSpace.has(task.id)) continue;
+    if (now - new Date(task.pausedAt).getTime() < maxAgeMs) continue;
+
+    let usable =
+      !!task.downloadURL && task.errorCode !== "download_url_expired";
+    try {
+      validateDownloadURL(task.downloadURL);
+    } catch {
+      usable = false;
+    }
+    if (usable) {
+      console.log(`[Pause] Resuming ${task.id} after PAUSE_MAX_AGE_HOURS`);
+      recordTaskEvent(task, "resumed", "pause expired");
+      scheduleDownload(task);
+      continue;
+    }
+
+    console.log(`[Pause] Expiring ${task.id}; its download URL expired`);
+    task.status = "failed";
+    task.pausedAt = undefined;
+    task.error = "Download URL expired";
+    task.errorCode = "download_url_expired";
+    recordTaskEvent(task, "failed", "download_url_expired");
+    notifyProgress(task);
+    persistTasks();
+  }
+}
+
+/**
+ * Replace the download ticket of a paused or failed task (e.g. after the
+ * signed CDN URL expired) and restart it. Returns null on success.
+ */
+export function refreshDownloadURL(
+  id: string,
+  downloadURL: string,
+  sinfs?: Sinf[],
+  iTunesMetadata?: string,
+  downloadHeaders?: Record<string, string>,
+): TransitionErrorCode | null {
+  const task = tasks.get(id);
+  if (!task || (task.status !== "paused" && task.status !== "failed")) {
+    return task?.status === "completed" ? "ALREADY_COMPLETED" : "NOT_ACTIVE";
+  }
 
+  validateDownloadURL(downloadURL);
+
+  // A fresh ticket replaces the whole candidate list
+  task.downloadURL = downloadURL;
+  task.fallbackURLs = undefined;
+  task.mirrorIndex = undefined;
+  if (sinfs) task.sinfs = sinfs;
+  if (iTunesMetadata !== undefined) task.iTunesMetadata = iTunesMetadata;
+  if (downloadHeaders !== undefined) task.downloadHeaders = downloadHeaders;
+  recordTaskEvent(task, "resumed", "download ticket refreshed");
+  scheduleDownload(task);
+  return null;
+}
+
+/**
+ * Watchdog pass: a task still "downloading" that has received no bytes for
+ * STALL_TIMEOUT_SECONDS lost its worker (hung socket, dropped handle). It
+ * is restarted up to MAX_STALL_RECOVERIES times, then failed as "stalled".
+ * Paused, pending and injecting tasks are never touched.
+ */
+export function checkStalledDownloads(now = Date.now()) {
+  const windowMs = config.stallTimeoutSeconds * 1000;
+  if (windowMs <= 0) return;
+
+  for (const task of tasks.values()) {
+    if (task.status !== "downloading") continue;
+    const last = lastProgressAt.get(task.id);
+    if (last === undefined || now - last < windowMs) continue;
+
+    const controller = abortControllers.get(task.id);
+    if (controller) {
+      supersededAttempts.add(controller);
+      controller.abort();
+      abortControllers.delete(task.id);
+    }
+    lastProgressAt.delete(task.id);
+
+    const tracker = statsTrackers.get(task.id);
+    const stalledFor = Math.round((now - last) / 1000);
+    if (tracker && tracker.stallRecoveries < MAX_STALL_RECOVERIES) {
+      tracker.stallRecoveries++;
+      tracker.retries++;
+      stallCounters.recovered++;
+      console.warn(`Download ${task.id} stalled for ${stalledFor}s; restarting`);
+      recordTaskEvent(task, "retry", "stalled");
+      launchDownload(task);
+      continue;
+    }
+
+    stallCounters.failed++;
+    console.warn(`Download ${task.id} stalled for ${stalledFor}s; failing`);
+    task.status = "failed";
+    task.error = "Download stalled";
+    task.errorCode = "stalled";
+    recordTaskEvent(task, "failed", "stalled");
+    notifyProgress(task);
+    persistTasks();
+  }
+}
+
+/** Stall watchdog outcomes since startup, reported by /api/settings. */
+export function getStallCounters() {
+  return { ...stallCounters };
+}
+
+export type ForceTarget = "fail" | "complete" | "pause";
+export type ForceErrorCode = "NOT_FOUND" | "NOT_FORCIBLE" | "NO_PACKAGE_FILE";
+// Statuses with an attempt under way, the only ones a forced pause accepts
+const IN_FLIGHT_STATUSES = new Set(["pending", "downloading", "injecting"]);
+
+/**
+ * Operator escape hatch for wedged tasks (a hung injection, a task left
+ * "downloading" by a lost worker): abandon any in-flight attempt and move
+ * the task to the target state whatever it is in now. Completing requires
+ * the package file to exist; it is recorded as-is, without injection.
+ * Pausing applies only to work still in flight. Trashed and removed tasks
+ * have no work to unstick. Returns null on success.
+ */
+export function forceTask(
+  id: string,
+  target: ForceTarget,
+): ForceErrorCode | null {
+  const task = tasks.get(id);
+  if (!task) return "NOT_FOUND";
+  if (task.status === "trashed" || task.status === "removed") {
+    return "NOT_FORCIBLE";
+  }
+  if (target === "pause" && !IN_FLIGHT_STATUSES.has(task.status)) {
+    return "NOT_FORCIBLE";
+  }
+  let fileSize: number | undefined;
+  if (target === "complete") {
+    try {
+      fileSize = fs.statSync(task.filePath ?? "").size;
+    } catch {
+      return "NO_PACKAGE_FILE";
+    }
+  }
+
+  const controller = abortControllers.get(id);
+  if (controller) {
+    supersededAttempts.add(controller);
+    controller.abort();
+    abortControllers.delete(id);
+  }
+  queuedAt.delete(id);
+  lastProgressAt.delete(id);
+  pausedForSpace.delete(id);
+  task.injectionPhase = undefined;
+  task.speed = "0 B/s";
+
+  if (target === "pause") {
+    task.status = "paused";
+    task.pausedAt = new Date().toISOString();
+    recordTaskEvent(task, "paused", "forced");
+  } else if (target === "fail") {
+    task.status = "failed";
+    task.error = "Failed by an administrator";
+    task.errorCode = "forced";
+    task.errorDetail = undefined;
+    recordTaskEvent(task, "failed", "forced");
+  } else {
+    task.status = "completed";
+    task.progress = 100;
+    task.error = undefined;
+    task.errorCode = undefined;
+    task.errorDetail = undefined;
+    // Baseline for verification; the next verify adopts a hash
+    task.fileSize = fileSize;
+    task.sha256 = undefined;
+    const tracker = statsTrackers.get(id);
+    if (tracker) task.stats = buildStats(task, tracker);
+    statsTrackers.delete(id);
+    task.downloadURL = "";
+    task.fallbackURLs = undefined;
+    task.sinfs = [];
+    task.iTunesMetadata = undefined;
+    task.downloadHeaders = undefined;
+    recordTaskEvent(task, "completed", "forced");
+  }
+  console.warn(`Download ${id} forced to ${task.status} by an administrator`);
+  notifyProgress(task);
+  persistTasks();
+  drainQueue();
+  return null;
+}
+
+// --- Metadata enrichment ---
+// Packages that were not downloaded here (origin set) have their software
+// record rebuilt from the IPA once, one task at a time. Failures are
+// recorded in the task history and retried on the next start.
+
+/** Queue a task for enrichment if it is imported and not yet enriched. */
+export function scheduleEnrichment(task: DownloadTask) {
+  if (config.enrichMetadata === "off") return;
+  if (!task.origin || task.metadataEnrichedAt) return;
+  if (task.status !== "completed") return;
+  enrichmentQueue.add(task.id);
+  void runEnrichment();
+}
+
+async function runEnrichment() {
+  if (enrichmentRunning) return;
+  enrichmentRunning = true;
   try {
-    // Re-validate download URL before fetching
-    validateDownloadURL(task.downloadURL);
+    // Sets iterate entries added while the loop runs
+    for (const id of enrichmentQueue) {
+      enrichmentQueue.delete(id);
+      const task = tasks.get(id);
+      if (task?.status === "completed" && task.filePath) {
+        await enrichTask(task, task.filePath);
+      }
+    }
+  } finally {
+    enrichmentRunning = false;
+  }
+}
+
+async function enrichTask(task: DownloadTask, filePath: string) {
+  try {
+    const { software, detail } = await enrichSoftware(task.software, filePath);
+    // Deleted or trashed while we were reading it
+    if (tasks.get(task.id) !== task || task.status !== "completed") return;
+    task.software = software;
+    task.metadataEnrichedAt = new Date().toISOString();
+    recordTaskEvent(task, "metadata", detail);
+    notifyProgress(task);
+  } catch (err) {
+    const message = err instanceof Error ? err.message : String(err);
+    console.warn(`Task ${task.id}: metadata enrichment failed: ${message}`);
+    recordTaskEvent(task, "metadata", `failed: ${message}`);
+  }
+  persistTasks();
+}
+
+export type ReinjectErrorCode = "NOT_COMPLETED" | "IN_PROGRESS";
+const reinjecting = new Set<string>();
+
+// Each injection rewrites a whole package; bounding them keeps several
+// finishing downloads from saturating the disk at once
+const injectionSlots = new Semaphore(
+  () => config.maxConcurrentInjections,
+);
+
+/**
+ * Re-run sinf/metadata injection on a completed package with fresh signing
+ * data, without downloading it again. Works on a copy that replaces the
+ * package only once injection verified, so a failure (thrown) leaves the
+ * original untouched. Returns the new size and hash, or why it was refused.
+ * Metadata compression defaults to the task's own choice.
+ */
+export async function reinjectTask(
+  id: string,
+  sinfs: Sinf[],
+  iTunesMetadata?: string,
+  metadataCompression?: MetadataCompression,
+): Promise<ReinjectErrorCode | { fileSize: number; sha256: string }> {
+  const task = tasks.get(id);
+  if (task?.status !== "completed" || !task.filePath) return "NOT_COMPLETED";
+  if (!fs.existsSync(task.filePath)) return "NOT_COMPLETED";
+  if (reinjecting.has(id)) return "IN_PROGRESS";
+
+  const filePath = task.filePath;
+  // Inside the package dir, so a crash leaves it to orphan cleanup
+  const tmpPath = `${filePath}.reinject`;
+  reinjecting.add(id);
+  try {
+    await fs.promises.copyFile(filePath, tmpPath);
+    const injectionSource = await injectionSlots.run(() =>
+      inject(
+        sinfs,
+        tmpPath,
+        iTunesMetadata,
+        metadataCompression ?? task.metadataCompression,
+      ),
+    );
+    if (config.durableWrites) await syncFileAndParentDir(tmpPath);
+    const fileSize = (await fs.promises.stat(tmpPath)).size;
+    const sha256 = await hashFile(tmpPath);
+
+    // Deleted or trashed while we were working
+    if (tasks.get(id) !== task || task.filePath !== filePath) {
+      return "NOT_COMPLETED";
+    }
+    await fs.promises.rename(tmpPath, filePath);
+    task.fileSize = fileSize;
+    task.sha256 = sha256;
+    if (injectionSource.kind === "not_encrypted") task.injectedSinfs = 0;
+    else if (sinfs.length > 0) task.injectedSinfs = sinfs.length;
+    task.injectionSource = injectionSource;
+    // The package now carries what this reinject wrote
+    if (metadataCompression) task.metadataCompression = metadataCompression;
+    task.corrupt = undefined;
+    recordTaskEvent(task, "injecting", "reinjected");
+    notifyProgress(task);
+    persistTasks();
+    return { fileSize, sha256 };
+  } finally {
+    reinjecting.delete(id);
+    await fs.promises.rm(tmpPath, { force: true });
+  }
+}
+
+const verifications = new SingleFlight<PackageHealth>();
 
-    const response = await fetch(task.downloadURL, {
-      signal: controller.signal,
-      redirect: "follow",
-    });
-    if (!response.ok) {
-      throw new Error(`HTTP ${response.status}: ${response.statusText}`);
+// Latest verification per task, polled by clients for large packages
+export type VerificationJob =
+  | { status: "running"; startedAt: string }
+  | {
+      status: "done";
+      startedAt: string;
+      finishedAt: string;
+      report: PackageHealth;
     }
-    if (!response.body) {
-      throw new Error("No response body");
+  | { status: "error"; startedAt: string; finishedAt: string; error: string };
+const verificationJobs = new Map<string, VerificationJob>();
+
+export function getVerificationJob(id: string): VerificationJob | undefined {
+  return verificationJobs.get(id);
+}
+
+/**
+ * Re-check a completed package on disk against its recorded size, hash and
+ * sinf count, flagging it corrupt on failure. Packages completed before
+ * hashes were recorded adopt the current ones as baseline if the archive is
+ * sound. The job is marked running synchronously, so callers may respond
+ * before awaiting it and let clients poll `getVerificationJob`.
+ * Returns null when there is no completed package file to verify.
+ */
+export async function verifyTaskPackage(
+  id: string,
+): Promise<PackageHealth | null> {
+  const task = tasks.get(id);
+  if (task?.status !== "completed" || !task.filePath) return null;
+  if (!fs.existsSync(task.filePath)) return null;
+
+  const filePath = task.filePath;
+  const report = await verifications.run(id, async () => {
+    const startedAt = new Date().toISOString();
+    verificationJobs.set(id, { status: "running", startedAt });
+    try {
+      const report = await verifyPackage(filePath, {
+        fileSize: task.fileSize,
+        sha256: task.sha256,
+        sinfs: task.injectedSinfs,
+      });
+      verificationJobs.set(id, {
+        status: "done",
+        startedAt,
+        finishedAt: new Date().toISOString(),
+        report,
+      });
+      return report;
+    } catch (err) {
+      verificationJobs.set(id, {
+        status: "error",
+        startedAt,
+        finishedAt: new Date().toISOString(),
+        error: "Verification failed",
+      });
+      throw err;
     }
+  });
 
-    // Check content length against max
-    const contentLength = parseInt(
-      response.headers.get("content-length") || "0",
+  // The task may have been deleted or trashed while we were reading
+  if (tasks.get(id) !== task || task.filePath !== filePath) return report;
+  const corrupt = report.ok ? undefined : true;
+  if (task.corrupt !== corrupt) {
+    task.corrupt = corrupt;
+    notifyProgress(task);
+  }
+  if (report.ok && task.sha256 === undefined) {
+    task.fileSize = report.size;
+    task.sha256 = report.sha256;
+  }
+  persistTasks();
+  return report;
+}
+
+/**
+ * Grant and/or revoke access to a task for other account hashes.
+ * Returns the updated list; throws on invalid hashes or too many grants.
+ */
+export function updateTaskSharing(
+  id: string,
+  grant: string[],
+  revoke: string[],
+): string[] {
+  const task = tasks.get(id);
+  if (!task) throw new Error("Download not found");
+
+  for (const hash of grant) {
+    if (!isValidAccountHash(hash)) {
+      throw new Error("Invalid account hash");
+    }
+  }
+
+  const shared = new Set(task.sharedWith ?? []);
+  for (const hash of grant) {
+    if (hash !== task.accountHash) shared.add(hash);
+  }
+  for (const hash of revoke) shared.delete(hash);
+  if (shared.size > MAX_SHARED_ACCOUNTS) {
+    throw new Error(
+      `A download can be shared with at most ${MAX_SHARED_ACCOUNTS} accounts`,
     );
-    if (contentLength > MAX_DOWNLOAD_SIZE) {
-      throw new Error(
-        `File too large: ${contentLength} bytes exceeds ${MAX_DOWNLOAD_SIZE} byte limit`,
+  }
+
+  task.sharedWith = shared.size > 0 ? Array.from(shared) : undefined;
+  notifyProgress(task);
+  persistTasks();
+  return task.sharedWith ?? [];
+}
+
+/**
+ * An existing task for the same account, bundle and version that a new
+ * request would duplicate: anything in flight, or a sound completed package
+ * (unless the request is to replace it).
+ */
+export function findDuplicateTask(
+  accountHash: string,
+  bundleID: string,
+  version: string,
+  replace = false,
+): DownloadTask | undefined {
+  for (const task of tasks.values()) {
+    if (
+      !accountHashesEqual(task.accountHash, accountHash) ||
+      task.software.bundleID !== bundleID ||
+      task.software.version !== version
+    ) {
+      continue;
+    }
+    if (
+      task.status === "failed" ||
+      task.status === "trashed" ||
+      task.status === "removed"
+    ) {
+      continue;
+    }
+    if (
+      task.status === "completed" &&
+      (replace || task.corrupt || !task.filePath)
+    ) {
+      continue;
+    }
+    return task;
+  }
+  return undefined;
+}
+
+export interface NewTask {
+  software: Software;
+  accountHash: string;
+  downloadURL: string;
+  sinfs: Sinf[];
+  iTunesMetadata?: string;
+  downloadHeaders?: Record<string, string>;
+  priority?: TaskPriority;
+  expectedDigests?: ExpectedDigests;
+  // Mirrors tried in order after downloadURL fails
+  fallbackURLs?: string[];
+  // Delete completed tasks of the same version once this one completes
+  replace?: boolean;
+  metadataCompression?: MetadataCompression;
+  // What probeDownloadURL found, if the upstream was checked
+  probe?: UpstreamProbe | null;
+}
+
+export function createTask({
+  software,
+  accountHash,
+  downloadURL,
+  sinfs,
+  iTunesMetadata,
+  downloadHeaders,
+  priority = "normal",
+  expectedDigests,
+  fallbackURLs = [],
+  replace = false,
+  metadataCompression,
+  probe,
+}: NewTask): DownloadTask {
+  // Validate download URLs
+  validateDownloadURL(downloadURL);
+  for (const url of fallbackURLs) validateDownloadURL(url);
+
+  if (!isValidAccountHash(accountHash)) {
+    throw new Error("Invalid account hash");
+  }
+
+  // Validate path segments
+  safePathSegment(accountHash, "accountHash");
+  safePathSegment(software.bundleID, "bundleID");
+  safePathSegment(software.version, "version");
+
+  // Completed packages of the same version this download supersedes
+  const replaces = replace
+    ? Array.from(tasks.values())
+        .filter(
+          (t) =>
+            t.status === "completed" &&
+            accountHashesEqual(t.accountHash, accountHash) &&
+            t.software.bundleID === software.bundleID &&
+            t.software.version === software.version,
+        )
+        .map((t) => t.id)
+    : [];
+
+  const task: DownloadTask = {
+    id: uuidv4(),
+    software,
+    accountHash,
+    downloadURL,
+    ...(fallbackURLs.length > 0 ? { fallbackURLs, mirrorIndex: 0 } : {}),
+    sinfs,
+    iTunesMetadata,
+    metadataCompression,
+    downloadHeaders,
+    expectedDigests,
+    ...(replaces.length > 0 ? { replaces } : {}),
+    ...(probe
+      ? { totalBytes: probe.totalBytes, resumable: probe.resumable }
+      : {}),
+    status: "pending",
+    priority,
+    progress: 0,
+    speed: "0 B/s",
+    createdAt: new Date().toISOString(),
+  };
+
+  recordTaskEvent(task, "created");
+  tasks.set(task.id, task);
+  scheduleDownload(task);
+  persistTasks();
+  return task;
+}
+
+export interface UpstreamProbe {
+  // Upstream answered 403/410: the signed URL has expired
+  expired: boolean;
+  totalBytes?: number;
+  resumable: boolean;
+}
+
+/**
+ * Best-effort look at a download URL before its task is created: a
+ * `bytes=0-0` GET (some CDNs reject HEAD) through the pinned fetch,
+ * given CREATE_PROBE_TIMEOUT_MS. Null when it failed or took too long,
+ * in which case the download goes ahead unprobed.
+ */
+export async function probeDownloadURL(
+  url: string,
+  headers?: Record<string, string>,
+): Promise<UpstreamProbe | null> {
+  try {
+    const probe = await probeRangeSupport(
+      url,
+      AbortSignal.timeout(CREATE_PROBE_TIMEOUT_MS),
+      headers,
+      downloadFetch,
+    );
+    return {
+      expired: probe.status === 403 || probe.status === 410,
+      totalBytes: probe.total ?? probe.length ?? undefined,
+      resumable: probe.acceptsRanges,
+    };
+  } catch {
+    return null;
+  }
+}
+
+// --- Uploaded packages ---
+
+/** What a client sends along with an IPA it uploads instead. */
+export interface UploadedPackage {
+  accountHash: string;
+  // Store fields the client knows; bundleID and version, if given, must
+  // match the IPA's Info.plist
+  software?: Partial<Software>;
+  sinfs: Sinf[];
+  iTunesMetadata?: string;
+  metadataCompression?: MetadataCompression;
+}
+
+export interface UploadRejection {
+  code: "INVALID_IPA" | "BUNDLE_MISMATCH";
+  error: string;
+}
+
+/**
+ * Register an IPA uploaded to `sourcePath` as a completed task of its
+ * account. The archive must hold an app bundle whose Info.plist names a
+ * bundle ID and version, which place the package like a download's. The
+ * file is moved into its package path and injected there; if injection
+ * fails (thrown) the file and task are dropped again.
+ */
+export async function addUploadedPackage(
+  sourcePath: string,
+  upload: UploadedPackage,
+): Promise<DownloadTask | UploadRejection> {
+  const health = await verifyPackage(sourcePath, {});
+  if (!health.ok) {
+    return { code: "INVALID_IPA", error: health.problems.join("; ") };
+  }
+  const bundle = await readBundleInfo(sourcePath).catch(() => null);
+  if (!bundle?.bundleID || !bundle.version) {
+    return {
+      code: "INVALID_IPA",
+      error: "Info.plist has no bundle identifier or version",
+    };
+  }
+  for (const key of ["bundleID", "version"] as const) {
+    const claimed = upload.software?.[key];
+    if (claimed !== undefined && claimed !== bundle[key]) {
+      return {
+        code: "BUNDLE_MISMATCH",
+        error: `software.${key} is ${claimed} but the IPA has ${bundle[key]}`,
+      };
+    }
+  }
+  try {
+    safePathSegment(bundle.bundleID, "bundleID");
+    safePathSegment(bundle.version, "version");
+  } catch (err) {
+    const message = err instanceof Error ? err.message : String(err);
+    return { code: "INVALID_IPA", error: message };
+  }
+
+  const task: DownloadTask = {
+    id: uuidv4(),
+    software: {
+      id: 0,
+      name: bundle.name ?? bundle.bundleID,
+      ...upload.software,
+      bundleID: bundle.bundleID,
+      version: bundle.version,
+    } as Software,
+    accountHash: upload.accountHash,
+    downloadURL: "",
+    sinfs: upload.sinfs,
+    iTunesMetadata: upload.iTunesMetadata,
+    metadataCompression: upload.metadataCompression,
+    status: "injecting",
+    priority: "normal",
+    progress: 100,
+    speed: "0 B/s",
+    origin: "uploaded",
+    createdAt: new Date().toISOString(),
+  };
+  const filePath = buildPackagePath(task);
+  fs.mkdirSync(path.dirname(filePath), { recursive: true });
+  moveFile(sourcePath, filePath);
+  task.filePath = filePath;
+  recordTaskEvent(task, "created", "uploaded");
+  tasks.set(task.id, task);
+  notifyProgress(task);
+
+  try {
+    let { size, sha256 } = health;
+    if (task.sinfs.length > 0 || task.iTunesMetadata) {
+      task.injectionSource = await injectionSlots.run(() =>
+        inject(
+          task.sinfs,
+          filePath,
+          task.iTunesMetadata,
+          task.metadataCompression,
+        ),
       );
+      recordTaskEvent(task, "injected");
+      size = (await fs.promises.stat(filePath)).size;
+      sha256 = await hashFile(filePath);
     }
+    if (config.durableWrites) await syncFileAndParentDir(filePath);
+    task.fileSize = size;
+    task.sha256 = sha256;
+  } catch (err) {
+    tasks.delete(task.id);
+    await fs.promises.rm(filePath, { force: true });
+    throw err;
+  }
 
-    let downloaded = 0;
-    let lastTime = Date.now();
-    let lastBytes = 0;
+  task.injectedSinfs =
+    task.injectionSource?.kind === "not_encrypted" ? 0 : task.sinfs.length;
+  task.status = "completed";
+  task.sinfs = [];
+  task.iTunesMetadata = undefined;
+  recordTaskEvent(task, "completed");
+  notifyProgress(task);
+  persistTasks();
+  scheduleEnrichment(task);
+  runCountCleanup();
+  return task;
+}
 
-    const writeStream = fs.createWriteStream(filePath);
-    const reader = response.body.getReader();
+// --- Download queue ---
+// With MAX_CONCURRENT_DOWNLOADS set, tasks beyond the limit wait as
+// "pending" and start in (priority, createdAt) order as slots free up.
+// Waiting tasks move up one priority level per PRIORITY_AGING_SECONDS so
+// a steady stream of high-priority work cannot starve them.
 
-    const readable = new Readable({
-      async read() {
-        try {
-          const { done, value } = await reader.read();
-          if (done) {
-            this.push(null);
-            return;
-          }
-          downloaded += value.byteLength;
+const PRIORITY_RANK: Record<TaskPriority, number> = {
+  high: 0,
+  normal: 1,
+  low: 2,
+};
 
-          // Enforce max download size even without Content-Length
-          if (downloaded > MAX_DOWNLOAD_SIZE) {
-            this.destroy(new Error("Download exceeded maximum size"));
-            return;
-          }
+export function isValidPriority(value: unknown): value is TaskPriority {
+  return typeof value === "string" && value in PRIORITY_RANK;
+}
 
-          // Calculate speed every 500ms
-          const now = Date.now();
-          const elapsed = now - lastTime;
-          if (elapsed >= 500) {
-            const bytesPerSec = ((downloaded - lastBytes) / elapsed) * 1000;
-            task.speed = formatSpeed(bytesPerSec);
-            lastTime = now;
-            lastBytes = downloaded;
-          }
+function effectiveRank(task: DownloadTask, now: number): number {
+  const rank = PRIORITY_RANK[task.priority ?? "normal"];
+  const agingMs = config.priorityAgingSeconds * 1000;
+  if (agingMs <= 0) return rank;
+  const waited = now - (queuedAt.get(task.id) ?? now);
+  return Math.max(0, rank - Math.floor(waited / agingMs));
+}
+
+// Queued tasks in the order they will start
+function queueOrder(now = Date.now()): DownloadTask[] {
+  const queued: DownloadTask[] = [];
+  for (const id of queuedAt.keys()) {
+    const task = tasks.get(id);
+    if (task) queued.push(task);
+  }
+  return queued.sort(
+    (a, b) =>
+      effectiveRank(a, now) - effectiveRank(b, now) ||
+      a.createdAt.localeCompare(b.createdAt),
+  );
+}
+
+/** 1-based position of a queued task, or undefined if it is not waiting. */
+export function getQueuePosition(id: string): number | undefined {
+  if (!queuedAt.has(id)) return undefined;
+  return queueOrder().findIndex((t) => t.id === id) + 1;
+}
+
+function hasFreeSlot(): boolean {
+  const limit = config.maxConcurrentDownloads;
+  if (limit <= 0) return true;
+  let active = 0;
+  for (const task of tasks.values()) {
+    if (task.status === "downloading" || task.status === "injecting") active++;
+  }
+  return active < limit;
+}
+
+// Run an attempt and hand its slot to the next queued task when it ends
+function launchDownload(task: DownloadTask) {
+  startDownload(task).finally(drainQueue);
+}
+
+// Start queued tasks while slots are free. Nothing starts while running
+// downloads are held paused for low disk space.
+function drainQueue() {
+  if (config.readOnly) return;
+  if (config.pauseOnLowSpace && isUnderStoragePressure()) return;
+  while (queuedAt.size > 0 && hasFreeSlot()) {
+    const [next] = queueOrder();
+    if (!next) break;
+    queuedAt.delete(next.id);
+    launchDownload(next);
+  }
+}
+
+/**
+ * Enter or leave read-only maintenance mode (READ_ONLY). Downloads already
+ * running finish; queued ones wait and start once the mode is lifted. It
+ * can't be lifted while the persisted tasks are unreadable.
+ */
+export function setReadOnly(enabled: boolean) {
+  if (config.readOnly === enabled) return;
+  if (!enabled && taskStoreUnreadable) return;
+  config.readOnly = enabled;
+  console.log(`[Maintenance] Read-only mode ${enabled ? "on" : "off"}`);
+  if (!enabled) drainQueue();
+}
+
+/** Whether startup could not read the persisted tasks (see initOnStartup). */
+export function isTaskStoreUnreadable(): boolean {
+  return taskStoreUnreadable;
+}
+
+// Queue a new, resumed or retried task and start it if its turn has come
+function scheduleDownload(task: DownloadTask) {
+  task.status = "pending";
+  task.pausedAt = undefined;
+  task.speed = "0 B/s";
+  queuedAt.set(task.id, Date.now());
+  drainQueue();
+  if (queuedAt.has(task.id)) notifyProgress(task);
+}
+
+/**
+ * Change the priority of a task still waiting for a slot. Returns null on
+ * success; running and finished tasks keep the priority they started with.
+ */
+export function setTaskPriority(
+  id: string,
+  priority: TaskPriority,
+): "NOT_QUEUED" | null {
+  const task = tasks.get(id);
+  if (!task || !queuedAt.has(id)) return "NOT_QUEUED";
+  task.priority = priority;
+  notifyProgress(task);
+  return null;
+}
+
+// --- Package file naming (PACKAGE_NAMING) ---
+// "uuid": packages/<account>/<bundle>/<version>/<id>.ipa
+// "descriptive": packages/<bundle>_<version>_<account8>.ipa, with _2, _3...
+// appended while the name is taken. Either way every part has passed
+// safePathSegment. Retries keep the path of their first attempt.
+
+// Another task whose package (or trashed package's origin) is this file
+function packagePathOwner(
+  filePath: string,
+  task: DownloadTask,
+): DownloadTask | undefined {
+  const resolved = path.resolve(filePath);
+  for (const other of tasks.values()) {
+    if (other === task) continue;
+    for (const owned of [other.filePath, other.trashedFrom]) {
+      if (owned && path.resolve(owned) === resolved) return other;
+    }
+  }
+  return undefined;
+}
+
+function isPackagePathTaken(filePath: string, task: DownloadTask): boolean {
+  return fs.existsSync(filePath) || !!packagePathOwner(filePath, task);
+}
+
+function buildPackagePath(task: DownloadTask): string {
+  const safeAccountHash = safePathSegment(task.accountHash, "accountHash");
+  const safeBundleID = safePathSegment(task.software.bundleID, "bundleID");
+  const safeVersion = safePathSegment(task.software.version, "version");
+
+  // A restarted download stays in the root its partial file is in
+  const root =
+    (task.filePath && packageRootOf(task.filePath)) || selectPackageRoot();
+  if (config.packageNaming !== "descriptive") {
+    return path.join(
+      root,
+      safeAccountHash,
+      safeBundleID,
+      safeVersion,
+      `${task.id}.ipa`,
+    );
+  }
+  if (task.filePath) return task.filePath;
+
+  const base = `${safeBundleID}_${safeVersion}_${safeAccountHash.slice(0, 8)}`;
+  let filePath = path.resolve(root, `${base}.ipa`);
+  for (let n = 2; isPackagePathTaken(filePath, task); n++) {
+    filePath = path.resolve(root, `${base}_${n}.ipa`);
+  }
+  return filePath;
+}
+
+/** Location of a package relative to its package root, if it is in one. */
+export function packageRelativePath(filePath: string): string | undefined {
+  const root = packageRootOf(filePath);
+  if (!root) return undefined;
+  const relative = path.relative(root, path.resolve(filePath));
+  return relative.split(path.sep).join("/");
+}
+
+async function startDownload(task: DownloadTask) {
+  // Pre-download cleanup: expire old files + enforce space limit
+  runTimeCleanup();
+  runSpaceCleanup();
+
+  const controller = new AbortController();
+  abortControllers.set(task.id, controller);
+
+  // Set a global timeout for the entire download
+  const timeout = setTimeout(() => controller.abort(), DOWNLOAD_TIMEOUT_MS);
+
+  task.status = "downloading";
+  task.progress = 0;
+  task.speed = "0 B/s";
+  task.error = undefined;
+  task.errorCode = undefined;
+  task.errorDetail = undefined;
+  recordTaskEvent(task, "started");
+  notifyProgress(task);
+
+  const tracker = statsTrackers.get(task.id) ?? {
+    bytes: 0,
+    activeMs: 0,
+    peakBytesPerSec: 0,
+    retries: 0,
+    pauseResumeCycles: 0,
+    stallRecoveries: 0,
+  };
+  statsTrackers.set(task.id, tracker);
+  const attemptStartedAt = Date.now();
+  lastProgressAt.set(task.id, attemptStartedAt);
+
+  const filePath = buildPackagePath(task);
+
+  // Verify the resolved path is within a package root
+  if (!packageRootOf(filePath)) {
+    task.status = "failed";
+    task.error = "Invalid path";
+    task.errorCode = "invalid_path";
+    clearTimeout(timeout);
+    recordTaskEvent(task, "failed", "invalid_path");
+    notifyProgress(task);
+    persistTasks();
+    return;
+  }
+
+  // Never write over another task's package. Each task gets its own file
+  // (uuid names, or the next free descriptive name), so this only trips if
+  // a naming change made two tasks resolve to one path; a re-download of a
+  // completed version is a separate file until it replaces the old one.
+  const owner = packagePathOwner(filePath, task);
+  if (owner) {
+    console.error(`Download ${task.id}: ${filePath} belongs to ${owner.id}`);
+    task.status = "failed";
+    task.error = "Invalid path";
+    task.errorCode = "invalid_path";
+    task.errorDetail = "Package path belongs to another download";
+    clearTimeout(timeout);
+    recordTaskEvent(task, "failed", "invalid_path");
+    notifyProgress(task);
+    persistTasks();
+    return;
+  }
+
+  fs.mkdirSync(path.dirname(filePath), { recursive: true });
+  task.filePath = filePath;
+
+  try {
+    // Re-validate download URL before fetching
+    validateDownloadURL(task.downloadURL);
+
+    const { maxDownloadSize } = config;
+    const accumulator = new ProgressAccumulator();
+    let milestone = 0;
+    // Where upstream redirects led, recorded once per URL and without the
+    // query string, which carries the signature
+    let redirectedTo: string | undefined;
+    const fetcher: Fetcher = async (url, init) => {
+      const response = await downloadFetch(url, init);
+      if (response.redirected) {
+        const final = new URL(response.url);
+        const target = `${final.origin}${final.pathname}`;
+        if (target !== redirectedTo) {
+          redirectedTo = target;
+          recordTaskEvent(task, "redirected", target);
+        }
+      }
+      return response;
+    };
+
+    // Shared progress accounting for both single-stream and chunked paths.
+    // Listeners are only notified when percent or speed actually changed.
+    const onBytes = (bytes: number, total: number) => {
+      const update = accumulator.add(bytes, total);
+      lastProgressAt.set(task.id, Date.now());
+
+      // Enforce max download size even without Content-Length
+      if (accumulator.downloaded > maxDownloadSize) {
+        throw new DownloadSizeError("Download exceeded maximum size");
+      }
+
+      if (!update) return;
+      // Unknown-length responses never move progress off 0; publish the
+      // byte count so the UI has something live to show
+      task.indeterminate = total <= 0;
+      task.downloadedBytes = accumulator.downloaded;
+      if (update.bytesPerSec !== null) {
+        task.speed = formatSpeed(update.bytesPerSec);
+        task.etaSeconds =
+          total > 0 && update.bytesPerSec > 0
+            ? Math.ceil((total - accumulator.downloaded) / update.bytesPerSec)
+            : undefined;
+        tracker.peakBytesPerSec = Math.max(
+          tracker.peakBytesPerSec,
+          update.bytesPerSec,
+        );
+      }
+      if (total > 0) {
+        task.progress = update.progress;
+        // Record 25/50/75% milestones once per attempt
+        const reached = Math.floor(update.progress / 25) * 25;
+        if (reached > milestone && reached < 100) {
+          milestone = reached;
+          recordTaskEvent(task, "progress", `${reached}%`);
+        }
+        if (config.chaosEnabled) chaosDownloadFault(update.progress);
+      }
+
+      notifyProgress(task);
+    };
+
+    const checkSize = (size: number) => {
+      if (size > maxDownloadSize) {
+        throw new DownloadSizeError(
+          `File too large: ${size} bytes exceeds ${maxDownloadSize} byte limit`,
+        );
+      }
+    };
+
+    // Split into concurrent ranged requests when enabled and supported
+    const probe =
+      config.downloadConnections > 1
+        ? await probeRangeSupport(
+            task.downloadURL,
+            controller.signal,
+            task.downloadHeaders,
+            fetcher,
+          )
+        : null;
+    if (probe && (probe.status === 403 || probe.status === 410)) {
+      throw new DownloadURLExpiredError(probe.status);
+    }
 
-          if (contentLength > 0) {
-            task.progress = Math.round((downloaded / contentLength) * 100);
+    if (probe?.total) {
+      const total = probe.total;
+      checkSize(total);
+      // Ranges arrive out of order, so expectedDigests can't be checked
+      // incrementally here; chunked downloads skip the verification
+      await downloadInChunks({
+        url: task.downloadURL,
+        filePath,
+        total,
+        connections: config.downloadConnections,
+        signal: controller.signal,
+        onBytes: (bytes) => onBytes(bytes, total),
+        onRetry: (attempt) => {
+          tracker.retries++;
+          recordTaskEvent(task, "retry", `#${attempt} scheduled`);
+        },
+        headers: task.downloadHeaders,
+        fetcher,
+      });
+    } else {
+      const response = await fetchIdentity(
+        task.downloadURL,
+        controller.signal,
+        task.downloadHeaders,
+        fetcher,
+      );
+      if (response.status === 403 || response.status === 410) {
+        throw new DownloadURLExpiredError(response.status);
+      }
+      if (!response.ok) {
+        throw new Error(`HTTP ${response.status}: ${response.statusText}`);
+      }
+      if (!response.body) {
+        throw new Error("No response body");
+      }
+
+      // Check content length against max
+      const contentLength = parseInt(
+        response.headers.get("content-length") || "0",
+      );
+      checkSize(contentLength);
+
+      // With a known length, reserve the space and write into it in place
+      const preallocated = config.durableWrites && contentLength > 0;
+      if (preallocated) await preallocate(filePath, contentLength);
+      const writeStream = fs.createWriteStream(filePath, {
+        flags: preallocated ? "r+" : "w",
+      });
+      const reader = response.body.getReader();
+      // Hashed as the bytes stream past, so memory stays flat
+      const digests = Object.entries(task.expectedDigests ?? {})
+        .filter(([, expected]) => expected)
+        .map(([algorithm, expected]) => ({
+          algorithm,
+          expected,
+          hash: crypto.createHash(algorithm),
+        }));
+
+      const readable = new Readable({
+        async read() {
+          try {
+            const { done, value } = await reader.read();
+            if (done) {
+              this.push(null);
+              return;
+            }
+            onBytes(value.byteLength, contentLength);
+            for (const { hash } of digests) hash.update(value);
+            this.push(Buffer.from(value));
+          } catch (err) {
+            this.destroy(err instanceof Error ? err : new Error(String(err)));
           }
+        },
+      });
 
-          notifyProgress(task);
-          this.push(Buffer.from(value));
-        } catch (err) {
-          this.destroy(err instanceof Error ? err : new Error(String(err)));
+      await pipeline(readable, writeStream);
+
+      for (const { algorithm, expected, hash } of digests) {
+        if (hash.digest("hex") !== expected) {
+          throw new ChecksumMismatchError(algorithm.toUpperCase());
         }
-      },
-    });
+      }
 
-    await pipeline(readable, writeStream);
+      // Upstream sent less than advertised: drop the unused reservation
+      if (preallocated && accumulator.downloaded !== contentLength) {
+        await fs.promises.truncate(filePath, accumulator.downloaded);
+      }
+    }
 
-    abortControllers.delete(task.id);
     clearTimeout(timeout);
+    if (supersededAttempts.has(controller)) return;
+    tracker.bytes += accumulator.downloaded;
+    tracker.activeMs += Date.now() - attemptStartedAt;
 
     // Inject sinfs
     if (task.sinfs.length > 0) {
       task.status = "injecting";
       task.progress = 100;
+      task.injectionPhase = injectionSlots.full ? "queued" : "running";
+      recordTaskEvent(
+        task,
+        "injecting",
+        task.injectionPhase === "queued"
+          ? "waiting for injection slot"
+          : undefined,
+      );
       notifyProgress(task);
 
-      await inject(task.sinfs, filePath, task.iTunesMetadata);
+      const injectionSource = await injectionSlots.run(async () => {
+        // Forced to another state while waiting for a slot
+        if (supersededAttempts.has(controller)) return undefined;
+        if (task.injectionPhase === "queued") {
+          task.injectionPhase = "running";
+          notifyProgress(task);
+        }
+        const startedAt = Date.now();
+        const source = await inject(
+          task.sinfs,
+          filePath,
+          task.iTunesMetadata,
+          task.metadataCompression,
+        );
+        const elapsed = `${Date.now() - startedAt} ms`;
+        recordTaskEvent(
+          task,
+          "injected",
+          source.kind === "not_encrypted"
+            ? `not encrypted, sinfs skipped (${elapsed})`
+            : elapsed,
+        );
+        return source;
+      });
+      // Forced to another state by an operator while injecting
+      if (supersededAttempts.has(controller)) return;
+      task.injectionSource = injectionSource;
+      task.injectionPhase = undefined;
+    }
+    abortControllers.delete(task.id);
+
+    // Make sure data and directory entry are on disk before we persist
+    if (config.durableWrites) {
+      await syncFileAndParentDir(filePath);
+    }
+
+    // Baseline for later integrity checks of the final (injected) file
+    task.fileSize = (await fs.promises.stat(filePath)).size;
+    task.sha256 = await hashFile(filePath);
+    task.injectedSinfs =
+      task.injectionSource?.kind === "not_encrypted" ? 0 : task.sinfs.length;
+
+    // A replacement must be a sound package before the old one goes
+    if (task.replaces) {
+      const health = await verifyPackage(filePath, {
+        sinfs: task.injectedSinfs,
+      });
+      if (!health.ok) throw new ReplacementInvalidError(health.problems);
+      if (supersededAttempts.has(controller)) return;
     }
 
+    // Replaced tasks leave the store in the same write that records this
+    // package, and their files are deleted only after it: a crash at any
+    // point leaves at least one complete package on record
+    const replaced = takeReplacedTasks(task);
+
     task.status = "completed";
     task.progress = 100;
+    task.stats = buildStats(task, tracker);
+    statsTrackers.delete(task.id);
+    recordTaskEvent(
+      task,
+      "completed",
+      replaced.length > 0
+        ? `replaced ${replaced.map((t) => t.id).join(", ")}`
+        : undefined,
+    );
 
     // Strip sensitive data after successful compile
     task.downloadURL = "";
+    task.fallbackURLs = undefined;
     task.sinfs = [];
     task.iTunesMetadata = undefined;
+    task.downloadHeaders = undefined;
 
     // Persist completed task metadata (no secrets)
-    persistTasks();
     notifyProgress(task);
+    persistTasks();
+    for (const old of replaced) removePackageFile(old);
+
+    // A new package may push its account over the retention limit
+    runCountCleanup();
   } catch (err) {
-    abortControllers.delete(task.id);
     clearTimeout(timeout);
+    // Restarted by the stall watchdog (or forced elsewhere); the task is no
+    // longer this attempt's
+    if (supersededAttempts.has(controller)) return;
+    abortControllers.delete(task.id);
+    task.injectionPhase = undefined;
 
     if (err instanceof Error && err.name === "AbortError") {
       // Status may have been changed to "paused" externally by pauseTask()
       if ((task.status as string) === "paused") return;
       task.status = "failed";
       task.error = "Download timed out";
+      task.errorCode = "timeout";
+      recordTaskEvent(task, "failed", "timeout");
       notifyProgress(task);
+      persistTasks();
+      return;
+    }
+
+    // The CDN edge refused or broke the transfer: move on to the next
+    // mirror before giving up on the download
+    const { code, detail } = classifyError(err);
+    if (task.fallbackURLs?.length && MIRROR_FALLBACK_CODES.has(code)) {
+      console.warn(
+        `Download ${task.id} failed on mirror ${task.mirrorIndex} (${code}); trying the next`,
+      );
+      task.downloadURL = task.fallbackURLs.shift()!;
+      task.mirrorIndex = (task.mirrorIndex ?? 0) + 1;
+      tracker.retries++;
+      recordTaskEvent(task, "retry", `mirror ${task.mirrorIndex}`);
+      launchDownload(task);
+      return;
+    }
+
+    // Expired signed URL: keep the task paused so the client can PATCH a
+    // fresh ticket instead of starting over
+    if (err instanceof DownloadURLExpiredError) {
+      console.warn(`Download ${task.id}: ${err.message}`);
+      task.status = "paused";
+      task.pausedAt = new Date().toISOString();
+      task.error = "Download URL expired";
+      task.errorCode = "download_url_expired";
+      recordTaskEvent(task, "paused", "download_url_expired");
+      notifyProgress(task);
+      persistTasks();
       return;
     }
 
@@ -565,10 +2737,27 @@ async function startDownload(task: DownloadTask) {
       err instanceof Error ? err.message : err,
     );
     task.error = "Download failed";
+    task.errorCode = code;
+    task.errorDetail = detail;
+    recordTaskEvent(task, "failed", code);
     notifyProgress(task);
+    persistTasks();
   }
 }
 
+function buildStats(task: DownloadTask, tracker: StatsTracker): DownloadStats {
+  return {
+    averageBytesPerSec:
+      tracker.activeMs > 0
+        ? Math.round((tracker.bytes / tracker.activeMs) * 1000)
+        : 0,
+    peakBytesPerSec: Math.round(tracker.peakBytesPerSec),
+    retries: tracker.retries,
+    pauseResumeCycles: tracker.pauseResumeCycles,
+    totalTimeMs: Date.now() - new Date(task.createdAt).getTime(),
+  };
+}
+
 function formatSpeed(bytesPerSec: number): string {
   if (bytesPerSec < 1024) return `${Math.round(bytesPerSec)} B/s`;
   if (bytesPerSec < 1024 * 1024)
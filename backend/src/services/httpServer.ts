@@ -0,0 +1,46 @@
+import fs from "fs";
+import { createServer, RequestListener, Server as HttpServer } from "http";
+import {
+  createServer as createHttpsServer,
+  Server as HttpsServer,
+} from "https";
+import { config } from "../config.js";
+
+/** Whether the built-in TLS is on: both TLS_CERT_PATH and TLS_KEY_PATH set. */
+export function tlsEnabled(): boolean {
+  return !!(config.tlsCertPath && config.tlsKeyPath);
+}
+
+/** The app's server: HTTPS when a certificate is configured, else HTTP. */
+export function createAppServer(
+  app: RequestListener,
+): HttpServer | HttpsServer {
+  if (!tlsEnabled()) return createServer(app);
+  return createHttpsServer(
+    {
+      cert: fs.readFileSync(config.tlsCertPath),
+      key: fs.readFileSync(config.tlsKeyPath),
+    },
+    app,
+  );
+}
+
+/**
+ * Plain-HTTP server for HTTP_REDIRECT_PORT that only answers 301 to the
+ * same path and query on the HTTPS port.
+ */
+export function createRedirectServer(): HttpServer {
+  return createServer((req, res) => {
+    // Strip the port and any unsafe characters from the Host header
+    const hostname = (req.headers["host"] || "")
+      .replace(/:\d+$/, "")
+      .replace(/[^\w.\-]/g, "");
+    if (!hostname) {
+      res.writeHead(400).end();
+      return;
+    }
+    const port = config.port === 443 ? "" : `:${config.port}`;
+    res.writeHead(301, { Location: `https://${hostname}${port}${req.url}` });
+    res.end();
+  });
+}
@@ -0,0 +1,40 @@
+import { isUpstreamFailureStatus, itunesBreaker } from "./circuitBreaker.js";
+import type { Software } from "../types/index.js";
+
+// Fetch JSON from the iTunes API, feeding the outcome to the circuit breaker
+export async function fetchItunes(url: string) {
+  const response = await fetch(url).catch((err) => {
+    itunesBreaker.recordFailure();
+    throw err;
+  });
+  if (isUpstreamFailureStatus(response.status)) {
+    itunesBreaker.recordFailure();
+    throw new Error(`iTunes returned HTTP ${response.status}`);
+  }
+  itunesBreaker.recordSuccess();
+  return response.json();
+}
+
+// Map iTunes API fields to our Software type, matching Swift CodingKeys
+export function mapSoftware(item: Record<string, any>): Software {
+  return {
+    id: item.trackId,
+    bundleID: item.bundleId,
+    name: item.trackName,
+    version: item.version,
+    price: item.price,
+    artistName: item.artistName,
+    sellerName: item.sellerName,
+    description: item.description,
+    averageUserRating: item.averageUserRating,
+    userRatingCount: item.userRatingCount,
+    artworkUrl: item.artworkUrl512,
+    screenshotUrls: item.screenshotUrls ?? [],
+    minimumOsVersion: item.minimumOsVersion,
+    fileSizeBytes: item.fileSizeBytes,
+    releaseDate: item.currentVersionReleaseDate ?? item.releaseDate,
+    releaseNotes: item.releaseNotes,
+    formattedPrice: item.formattedPrice,
+    primaryGenreName: item.primaryGenreName,
+  };
+}
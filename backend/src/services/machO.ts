@@ -0,0 +1,121 @@
+// Just enough Mach-O parsing to tell whether an app binary is FairPlay
+// encrypted: the cryptid of its LC_ENCRYPTION_INFO(_64) load command. Only
+// the headers and load commands of each architecture slice are read.
+
+const MH_MAGIC = 0xfeedface;
+const MH_MAGIC_64 = 0xfeedfacf;
+const FAT_MAGIC = 0xcafebabe;
+const FAT_MAGIC_64 = 0xcafebabf;
+const LC_ENCRYPTION_INFO = 0x21;
+const LC_ENCRYPTION_INFO_64 = 0x2c;
+// Header plus load commands of one slice; real apps stay far below this
+const SLICE_HEADER_BYTES = 256 * 1024;
+const MAX_FAT_ARCHS = 64;
+
+export type EncryptionState = "encrypted" | "not_encrypted" | "unknown";
+
+/**
+ * Reads windows at increasing offsets from a stream, keeping only the
+ * current window in memory. A window may start where the previous one did.
+ */
+class StreamWindow {
+  private base = 0;
+  private buffered = Buffer.alloc(0);
+  private done = false;
+
+  constructor(private readonly chunks: AsyncIterator<Buffer>) {}
+
+  async read(start: number, length: number): Promise<Buffer> {
+    if (start < this.base) throw new Error("Windows must not go backwards");
+    for (;;) {
+      const skip = Math.min(start - this.base, this.buffered.length);
+      this.buffered = this.buffered.subarray(skip);
+      this.base += skip;
+      if (this.done) break;
+      if (this.base === start && this.buffered.length >= length) break;
+      const next = await this.chunks.next();
+      if (next.done) this.done = true;
+      else this.buffered = Buffer.concat([this.buffered, next.value]);
+    }
+    return this.base === start
+      ? this.buffered.subarray(0, length)
+      : Buffer.alloc(0);
+  }
+}
+
+// Whether one thin image is encrypted; null when it can't be parsed
+function imageEncrypted(image: Buffer): boolean | null {
+  if (image.length < 28) return null;
+  const magic = image.readUInt32LE(0);
+  const headerSize =
+    magic === MH_MAGIC ? 28 : magic === MH_MAGIC_64 ? 32 : undefined;
+  if (!headerSize) return null;
+  const ncmds = image.readUInt32LE(16);
+  const end = headerSize + image.readUInt32LE(20);
+  if (end > image.length) return null;
+
+  let offset = headerSize;
+  for (let i = 0; i < ncmds; i++) {
+    if (offset + 8 > end) return null;
+    const cmd = image.readUInt32LE(offset);
+    const cmdsize = image.readUInt32LE(offset + 4);
+    if (cmdsize < 8) return null;
+    if (cmd === LC_ENCRYPTION_INFO || cmd === LC_ENCRYPTION_INFO_64) {
+      // cmd, cmdsize, cryptoff, cryptsize, cryptid
+      if (offset + 20 > end) return null;
+      return image.readUInt32LE(offset + 16) !== 0;
+    }
+    offset += cmdsize;
+  }
+  // No encryption command at all: nothing to decrypt with a sinf
+  return false;
+}
+
+/**
+ * Inspect a Mach-O binary (thin or universal) streamed from the IPA. A
+ * universal binary counts as encrypted if any slice is; anything that
+ * doesn't parse is "unknown".
+ */
+export async function readEncryptionState(
+  chunks: AsyncIterable<Buffer>,
+): Promise<EncryptionState> {
+  const iterator = chunks[Symbol.asyncIterator]();
+  try {
+    const window = new StreamWindow(iterator);
+    const head = await window.read(0, SLICE_HEADER_BYTES);
+    if (head.length < 8) return "unknown";
+
+    const fatMagic = head.readUInt32BE(0);
+    let slices: number[];
+    if (fatMagic === FAT_MAGIC || fatMagic === FAT_MAGIC_64) {
+      const count = head.readUInt32BE(4);
+      const entrySize = fatMagic === FAT_MAGIC ? 20 : 32;
+      if (count === 0 || count > MAX_FAT_ARCHS) return "unknown";
+      if (8 + count * entrySize > head.length) return "unknown";
+      slices = [];
+      for (let i = 0; i < count; i++) {
+        const at = 8 + i * entrySize + 8;
+        slices.push(
+          fatMagic === FAT_MAGIC
+            ? head.readUInt32BE(at)
+            : Number(head.readBigUInt64BE(at)),
+        );
+      }
+      slices.sort((a, b) => a - b);
+    } else {
+      slices = [0];
+    }
+
+    let encrypted = false;
+    for (const offset of slices) {
+      const image = await window.read(offset, SLICE_HEADER_BYTES);
+      const result = imageEncrypted(image);
+      if (result === null) return "unknown";
+      encrypted ||= result;
+    }
+    return encrypted ? "encrypted" : "not_encrypted";
+  } finally {
+    // Stop decompressing the rest of the binary
+    await iterator.return?.();
+  }
+}
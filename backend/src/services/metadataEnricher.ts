@@ -0,0 +1,109 @@
+import { open as openZip } from "yauzl-promise";
+import { config, ENRICH_LOOKUP_INTERVAL_MS } from "../config.js";
+import { itunesBreaker } from "./circuitBreaker.js";
+import { fetchItunes, mapSoftware } from "./itunesClient.js";
+import { parsePlistBuffer, streamToBuffer } from "./sinfInjector.js";
+import type { Software } from "../types/index.js";
+
+export interface BundleInfo {
+  bundleID?: string;
+  name?: string;
+  version?: string;
+}
+
+// Store fields copied from the lookup. Version-specific ones (version,
+// minimum OS, release date) describe the current store build, not this IPA.
+const STORE_FIELDS = [
+  "id",
+  "name",
+  "artistName",
+  "sellerName",
+  "description",
+  "averageUserRating",
+  "userRatingCount",
+  "artworkUrl",
+  "screenshotUrls",
+  "primaryGenreName",
+] as const;
+
+/** Identity fields from the main app's Info.plist, or null if unreadable. */
+export async function readBundleInfo(
+  ipaPath: string,
+): Promise<BundleInfo | null> {
+  const zip = await openZip(ipaPath);
+  try {
+    for await (const entry of zip) {
+      // The app bundle itself, not nested extensions or Watch apps
+      if (!/^Payload\/[^/]+\.app\/Info\.plist$/.test(entry.filename)) continue;
+      const info = parsePlistBuffer(
+        await streamToBuffer(await entry.openReadStream()),
+      );
+      if (!info) return null;
+      const text = (key: string) => {
+        const value = info[key];
+        return typeof value === "string" && value ? value : undefined;
+      };
+      return {
+        bundleID: text("CFBundleIdentifier"),
+        name: text("CFBundleDisplayName") ?? text("CFBundleName"),
+        version: text("CFBundleShortVersionString") ?? text("CFBundleVersion"),
+      };
+    }
+    return null;
+  } finally {
+    await zip.close();
+  }
+}
+
+// Lookups are spaced ENRICH_LOOKUP_INTERVAL_MS apart, however many
+// packages are waiting
+let nextLookupAt = 0;
+
+async function lookupBundle(bundleID: string): Promise<Software | null> {
+  const now = Date.now();
+  const wait = Math.max(0, nextLookupAt - now);
+  nextLookupAt = now + wait + ENRICH_LOOKUP_INTERVAL_MS;
+  if (wait > 0) await new Promise((resolve) => setTimeout(resolve, wait));
+
+  if (!itunesBreaker.tryAcquire()) throw new Error("iTunes unavailable");
+  const params = new URLSearchParams({
+    bundleId: bundleID,
+    country: config.defaultStorefront,
+  });
+  const data = await fetchItunes(`https://itunes.apple.com/lookup?${params}`);
+  return data?.results?.length ? mapSoftware(data.results[0]) : null;
+}
+
+/**
+ * Rebuild the software record of a package that did not come through a
+ * download: name, bundle ID and version from its Info.plist, then (with
+ * ENRICH_METADATA=itunes) artwork, seller and other store fields from an
+ * iTunes lookup. Returns the new record and a note for the event history.
+ */
+export async function enrichSoftware(
+  software: Software,
+  ipaPath: string,
+): Promise<{ software: Software; detail: string }> {
+  const bundle = await readBundleInfo(ipaPath);
+  if (!bundle) throw new Error("Info.plist not found");
+  const enriched: Software = {
+    ...software,
+    ...(bundle.bundleID ? { bundleID: bundle.bundleID } : {}),
+    ...(bundle.name ? { name: bundle.name } : {}),
+    ...(bundle.version ? { version: bundle.version } : {}),
+  };
+  if (config.enrichMetadata !== "itunes") {
+    return { software: enriched, detail: "from Info.plist" };
+  }
+
+  const store = await lookupBundle(enriched.bundleID);
+  if (!store) {
+    return { software: enriched, detail: "from Info.plist; not in store" };
+  }
+  for (const field of STORE_FIELDS) {
+    if (store[field] !== undefined) {
+      (enriched as unknown as Record<string, unknown>)[field] = store[field];
+    }
+  }
+  return { software: enriched, detail: "from Info.plist and iTunes" };
+}
@@ -0,0 +1,44 @@
+import fs from "fs";
+import { PACKAGE_CONTENTS_CACHE_ENTRIES } from "../config.js";
+import { SingleFlight } from "../utils/singleFlight.js";
+import { walkPackageEntries, type PackageEntry } from "./packageVerifier.js";
+
+// Central directory listings by task id. A completed package only changes
+// when it is rewritten (reinject), so an entry stays valid while the file's
+// path, size and mtime match what was read.
+const listings = new Map<
+  string,
+  { filePath: string; size: number; mtimeMs: number; entries: PackageEntry[] }
+>();
+const reads = new SingleFlight<PackageEntry[]>();
+
+/** Every entry of a task's package; cached while the file is unchanged. */
+export async function getPackageContents(
+  taskId: string,
+  filePath: string,
+): Promise<PackageEntry[]> {
+  const { size, mtimeMs } = await fs.promises.stat(filePath);
+  const cached = listings.get(taskId);
+  if (
+    cached &&
+    cached.filePath === filePath &&
+    cached.size === size &&
+    cached.mtimeMs === mtimeMs
+  ) {
+    return cached.entries;
+  }
+
+  const entries = await reads.run(`${taskId}:${mtimeMs}`, async () => {
+    const list: PackageEntry[] = [];
+    await walkPackageEntries(filePath, (entry) => list.push(entry));
+    return list;
+  });
+  listings.delete(taskId);
+  // Full: drop the oldest insertion
+  if (listings.size >= PACKAGE_CONTENTS_CACHE_ENTRIES) {
+    const oldest = listings.keys().next().value;
+    if (oldest !== undefined) listings.delete(oldest);
+  }
+  listings.set(taskId, { filePath, size, mtimeMs, entries });
+  return entries;
+}
@@ -0,0 +1,99 @@
+import fs from "fs";
+import path from "path";
+import { config } from "../config.js";
+
+export interface PackageRootUsage {
+  path: string;
+  packages: number;
+  packageBytes: number;
+  // Free bytes on the root's volume (null if it cannot be measured)
+  freeBytes: number | null;
+  // Where new downloads currently go
+  default: boolean;
+}
+
+/**
+ * Directories package files may live in: PACKAGE_DIRS in configured order,
+ * then DATA_DIR/packages (always a root, so packages downloaded before
+ * PACKAGE_DIRS was set stay reachable).
+ */
+export function getPackageRoots(): string[] {
+  const roots = [
+    ...config.packageDirs,
+    path.join(config.dataDir, "packages"),
+  ].map((dir) => path.resolve(dir));
+  return [...new Set(roots)];
+}
+
+/** The configured root a file lives under, if any. */
+export function packageRootOf(filePath: string): string | undefined {
+  const resolved = path.resolve(filePath);
+  return getPackageRoots().find((root) =>
+    resolved.startsWith(root + path.sep),
+  );
+}
+
+function freeBytesAt(dir: string): number | null {
+  try {
+    const stats = fs.statfsSync(dir);
+    return stats.bavail * stats.bsize;
+  } catch {
+    return null;
+  }
+}
+
+/**
+ * Root for a new package: the first one, or with PACKAGE_DIR_POLICY
+ * "most-free" the one with the most free space (roots that cannot be
+ * measured are skipped).
+ */
+export function selectPackageRoot(): string {
+  const roots = getPackageRoots();
+  if (config.packageDirPolicy !== "most-free") return roots[0];
+  let best = roots[0];
+  let bestFree = -1;
+  for (const root of roots) {
+    const free = freeBytesAt(root);
+    if (free !== null && free > bestFree) {
+      best = root;
+      bestFree = free;
+    }
+  }
+  return best;
+}
+
+/** Per-root package totals and free space for GET /api/settings. */
+export function getPackageRootUsage(
+  files: { filePath: string; fileSize?: number }[],
+): PackageRootUsage[] {
+  const selected = selectPackageRoot();
+  const usage = getPackageRoots().map((root) => ({
+    path: root,
+    packages: 0,
+    packageBytes: 0,
+    freeBytes: freeBytesAt(root),
+    default: root === selected,
+  }));
+  for (const file of files) {
+    const root = packageRootOf(file.filePath);
+    const entry = usage.find((u) => u.path === root);
+    if (!entry) continue;
+    entry.packages++;
+    entry.packageBytes += file.fileSize ?? 0;
+  }
+  return usage;
+}
+
+/**
+ * Rename a file, copying it instead when the destination is on another
+ * volume (a package root and the trash under DATA_DIR may differ).
+ */
+export function moveFile(from: string, to: string) {
+  try {
+    fs.renameSync(from, to);
+  } catch (err) {
+    if ((err as NodeJS.ErrnoException).code !== "EXDEV") throw err;
+    fs.copyFileSync(from, to);
+    fs.unlinkSync(from);
+  }
+}
@@ -0,0 +1,246 @@
+import fs from "fs";
+import path from "path";
+import { Readable, Transform } from "stream";
+import { pipeline } from "stream/promises";
+import { v4 as uuidv4 } from "uuid";
+import {
+  config,
+  MAX_UPLOADS,
+  MAX_UPLOADS_PER_ACCOUNT,
+  UPLOAD_IDLE_TIMEOUT_MS,
+} from "../config.js";
+import {
+  addUploadedPackage,
+  type UploadedPackage,
+  type UploadRejection,
+} from "./downloadManager.js";
+import { selectPackageRoot } from "./packageRoots.js";
+import { isValidMetadataCompression } from "./sinfInjector.js";
+import { accountHashesEqual } from "../utils/accountHash.js";
+import { INVALID_SINFS, isValidSinfs } from "../utils/route.js";
+import type { DownloadTask } from "../types/index.js";
+
+// IPAs sent by clients instead of downloaded here. Bytes go to a part file
+// in the package root's .uploads directory until the upload is completed.
+// Uploads are held in memory only: a restart drops them, and startup
+// orphan cleanup deletes their part files.
+
+export interface Upload {
+  id: string;
+  accountHash: string;
+  // Declared total; unknown for a single request without Content-Length
+  size?: number;
+  received: number;
+  expiresAt: number;
+  partPath: string;
+  metadata: UploadedPackage;
+}
+
+export type UploadErrorCode =
+  | "UPLOAD_BUSY"
+  | "UPLOAD_OFFSET_MISMATCH"
+  | "UPLOAD_TOO_LARGE"
+  | "UPLOAD_INCOMPLETE";
+
+export type UploadLimitCode = "TOO_MANY_UPLOADS" | "TOO_MANY_ACCOUNT_UPLOADS";
+
+const uploads = new Map<string, Upload>();
+// Uploads a request is currently writing to or completing
+const busy = new Set<string>();
+
+export type ParsedUploadMetadata =
+  | { ok: true; metadata: UploadedPackage }
+  | { ok: false; error: string };
+
+/**
+ * Validate the metadata sent with an upload: optional software fields,
+ * sinfs (default none), iTunesMetadata and metadataCompression.
+ */
+export function parseUploadMetadata(
+  body: Record<string, any>,
+  accountHash: string,
+): ParsedUploadMetadata {
+  const { software, sinfs = [], iTunesMetadata, metadataCompression } = body;
+  if (
+    software !== undefined &&
+    (typeof software !== "object" ||
+      software === null ||
+      Array.isArray(software) ||
+      (software.bundleID !== undefined &&
+        typeof software.bundleID !== "string") ||
+      (software.version !== undefined && typeof software.version !== "string"))
+  ) {
+    return {
+      ok: false,
+      error: "software must be an object with string bundleID and version",
+    };
+  }
+  if (!isValidSinfs(sinfs)) return { ok: false, error: INVALID_SINFS };
+  if (iTunesMetadata !== undefined && typeof iTunesMetadata !== "string") {
+    return { ok: false, error: "iTunesMetadata must be a string" };
+  }
+  if (
+    metadataCompression !== undefined &&
+    !isValidMetadataCompression(metadataCompression)
+  ) {
+    return {
+      ok: false,
+      error: "metadataCompression must be stored or deflated",
+    };
+  }
+  return {
+    ok: true,
+    metadata: {
+      accountHash,
+      software,
+      sinfs,
+      iTunesMetadata,
+      metadataCompression,
+    },
+  };
+}
+
+function accountUploads(accountHash: string): number {
+  let count = 0;
+  for (const upload of uploads.values()) {
+    if (accountHashesEqual(upload.accountHash, accountHash)) count++;
+  }
+  return count;
+}
+
+/**
+ * Start an upload, or say which limit is reached: MAX_UPLOADS in progress
+ * overall, or MAX_UPLOADS_PER_ACCOUNT for this account.
+ */
+export function createUpload(
+  metadata: UploadedPackage,
+  size?: number,
+  now = Date.now(),
+): Upload | UploadLimitCode {
+  if (
+    uploads.size >= MAX_UPLOADS ||
+    accountUploads(metadata.accountHash) >= MAX_UPLOADS_PER_ACCOUNT
+  ) {
+    sweepUploads(now);
+  }
+  if (uploads.size >= MAX_UPLOADS) return "TOO_MANY_UPLOADS";
+  if (accountUploads(metadata.accountHash) >= MAX_UPLOADS_PER_ACCOUNT) {
+    return "TOO_MANY_ACCOUNT_UPLOADS";
+  }
+
+  const id = uuidv4();
+  const partPath = path.join(selectPackageRoot(), ".uploads", `${id}.part`);
+  fs.mkdirSync(path.dirname(partPath), { recursive: true });
+  fs.writeFileSync(partPath, "");
+  const upload: Upload = {
+    id,
+    accountHash: metadata.accountHash,
+    size,
+    received: 0,
+    expiresAt: now + UPLOAD_IDLE_TIMEOUT_MS,
+    partPath,
+    metadata,
+  };
+  uploads.set(id, upload);
+  return upload;
+}
+
+/** A live upload, or undefined when unknown or expired. */
+export function getUpload(id: string, now = Date.now()): Upload | undefined {
+  const upload = uploads.get(id);
+  if (upload && upload.expiresAt <= now && !busy.has(id)) {
+    cancelUpload(id);
+    return undefined;
+  }
+  return upload;
+}
+
+/**
+ * Append `body` to an upload whose stored bytes end at `offset`. Bytes
+ * beyond the declared size (or MAX_DOWNLOAD_SIZE_MB) are refused; whatever
+ * was written before a failure stays, so the client resumes from
+ * `received`. Stream errors (a dropped request) are thrown.
+ */
+export async function appendUpload(
+  upload: Upload,
+  offset: number,
+  body: Readable,
+): Promise<UploadErrorCode | null> {
+  if (busy.has(upload.id)) return "UPLOAD_BUSY";
+  if (offset !== upload.received) return "UPLOAD_OFFSET_MISMATCH";
+
+  const limit = upload.size ?? config.maxDownloadSize;
+  let written = upload.received;
+  let tooLarge = false;
+  // Past the limit the rest of the body is read and dropped, so the
+  // request stays intact for the error response
+  const limiter = new Transform({
+    transform(chunk: Buffer, _encoding, callback) {
+      written += chunk.length;
+      if (written > limit) tooLarge = true;
+      callback(null, tooLarge ? undefined : chunk);
+    },
+  });
+
+  busy.add(upload.id);
+  try {
+    await pipeline(
+      body,
+      limiter,
+      fs.createWriteStream(upload.partPath, { flags: "a" }),
+    );
+    return tooLarge ? "UPLOAD_TOO_LARGE" : null;
+  } finally {
+    busy.delete(upload.id);
+    // Unless it was cancelled meanwhile
+    if (uploads.get(upload.id) === upload) {
+      upload.received = (await fs.promises.stat(upload.partPath)).size;
+      upload.expiresAt = Date.now() + UPLOAD_IDLE_TIMEOUT_MS;
+    }
+  }
+}
+
+/**
+ * Turn a fully received upload into a completed task (see
+ * addUploadedPackage). The upload ends either way, except while another
+ * request is still writing to it or bytes are missing.
+ */
+export async function finishUpload(
+  upload: Upload,
+): Promise<DownloadTask | UploadRejection | UploadErrorCode> {
+  if (busy.has(upload.id)) return "UPLOAD_BUSY";
+  if (upload.size !== undefined && upload.received !== upload.size) {
+    return "UPLOAD_INCOMPLETE";
+  }
+  busy.add(upload.id);
+  uploads.delete(upload.id);
+  try {
+    return await addUploadedPackage(upload.partPath, upload.metadata);
+  } finally {
+    busy.delete(upload.id);
+    await fs.promises.rm(upload.partPath, { force: true });
+  }
+}
+
+/** Abandon an upload and delete its bytes; false when unknown. */
+export function cancelUpload(id: string): boolean {
+  const upload = uploads.get(id);
+  if (!upload) return false;
+  uploads.delete(id);
+  fs.rmSync(upload.partPath, { force: true });
+  return true;
+}
+
+/** Drop uploads idle past UPLOAD_IDLE_TIMEOUT_MS; returns how many. */
+export function sweepUploads(now = Date.now()): number {
+  let removed = 0;
+  for (const upload of [...uploads.values()]) {
+    if (upload.expiresAt <= now && !busy.has(upload.id)) {
+      cancelUpload(upload.id);
+      removed++;
+    }
+  }
+  return removed;
+}
+
+setInterval(sweepUploads, UPLOAD_IDLE_TIMEOUT_MS / 4).unref();
@@ -0,0 +1,129 @@
+import crypto from "crypto";
+import fs from "fs";
+import { open as openZip } from "yauzl-promise";
+
+// Every IPA carries exactly one app bundle under Payload/
+const APP_ENTRY_RE = /^Payload\/[^/]+\.app\//;
+const SINF_ENTRY_RE = /^Payload\/[^/]+\.app\/SC_Info\/[^/]+\.sinf$/;
+
+export interface PackageHealth {
+  ok: boolean;
+  size: number;
+  expectedSize?: number;
+  sha256: string;
+  expectedSha256?: string;
+  validZip: boolean;
+  entries: number;
+  sinfEntries: number;
+  // Human-readable reasons verification failed (empty when ok)
+  problems: string[];
+}
+
+/** Stream a file through SHA-256 and return the hex digest. */
+export async function hashFile(filePath: string): Promise<string> {
+  const hash = crypto.createHash("sha256");
+  for await (const chunk of fs.createReadStream(filePath)) {
+    hash.update(chunk as Buffer);
+  }
+  return hash.digest("hex");
+}
+
+export interface PackageEntry {
+  path: string;
+  compressedSize: number;
+  uncompressedSize: number;
+  // "stored", "deflated", or "method <n>" for anything else
+  method: string;
+}
+
+function methodName(method: number): string {
+  if (method === 0) return "stored";
+  if (method === 8) return "deflated";
+  return `method ${method}`;
+}
+
+/**
+ * Visit every entry of a package's central directory. Only the directory
+ * is read; no entry data is decompressed, so this is cheap for any size.
+ */
+export async function walkPackageEntries(
+  filePath: string,
+  visit: (entry: PackageEntry) => void,
+): Promise<void> {
+  const zip = await openZip(filePath);
+  try {
+    for await (const entry of zip) {
+      visit({
+        path: entry.filename,
+        compressedSize: entry.compressedSize,
+        uncompressedSize: entry.uncompressedSize,
+        method: methodName(entry.compressionMethod),
+      });
+    }
+  } finally {
+    await zip.close();
+  }
+}
+
+// Walk the central directory and confirm it describes an app bundle
+async function inspectIpa(
+  filePath: string,
+): Promise<{ entries: number; sinfEntries: number; problem?: string }> {
+  let entries = 0;
+  let sinfEntries = 0;
+  let hasApp = false;
+  try {
+    await walkPackageEntries(filePath, (entry) => {
+      entries++;
+      if (APP_ENTRY_RE.test(entry.path)) hasApp = true;
+      if (SINF_ENTRY_RE.test(entry.path)) sinfEntries++;
+    });
+  } catch (err) {
+    const message = err instanceof Error ? err.message : String(err);
+    return {
+      entries,
+      sinfEntries,
+      problem: `Not a readable zip archive: ${message}`,
+    };
+  }
+  return hasApp
+    ? { entries, sinfEntries }
+    : { entries, sinfEntries, problem: "Archive has no Payload/*.app bundle" };
+}
+
+/**
+ * Re-read a package from disk and check it against what was recorded when
+ * the download completed. Missing expectations are skipped, not failed.
+ */
+export async function verifyPackage(
+  filePath: string,
+  expected: { fileSize?: number; sha256?: string; sinfs?: number },
+): Promise<PackageHealth> {
+  const problems: string[] = [];
+  const size = (await fs.promises.stat(filePath)).size;
+  const sha256 = await hashFile(filePath);
+  const zip = await inspectIpa(filePath);
+
+  if (expected.fileSize !== undefined && size !== expected.fileSize) {
+    problems.push(`Size is ${size} bytes, expected ${expected.fileSize}`);
+  }
+  if (expected.sha256 !== undefined && sha256 !== expected.sha256) {
+    problems.push("SHA-256 does not match the completed download");
+  }
+  if (zip.problem) problems.push(zip.problem);
+  if (!zip.problem && expected.sinfs && zip.sinfEntries === 0) {
+    problems.push("No SC_Info/*.sinf entry although sinfs were injected");
+  }
+
+  return {
+    ok: problems.length === 0,
+    size,
+    expectedSize: expected.fileSize,
+    sha256,
+    expectedSha256: expected.sha256,
+    validZip: !zip.problem,
+    entries: zip.entries,
+    sinfEntries: zip.sinfEntries,
+    problems,
+  };
+}
@@ -0,0 +1,159 @@
+import dns from "dns";
+import https from "https";
+import net from "net";
+import { Readable } from "stream";
+import { MAX_DOWNLOAD_REDIRECTS } from "../config.js";
+import { isNonPublicAddress } from "./dnsCache.js";
+
+// Download URLs are checked by hostname, but a name can resolve (or be
+// rebound between checks) to an internal address. Requests made here
+// resolve the host once, refuse it if any address is not public, and
+// connect to exactly that address. Redirects are followed by hand so every
+// hop goes through the same checks; like fetch(), a redirected response
+// reports where it ended up in `url` and `redirected`.
+
+const REDIRECT_STATUSES = new Set([301, 302, 303, 307, 308]);
+// Dropped when a redirect leaves the origin, as fetch() does
+const CREDENTIAL_HEADERS = ["authorization", "cookie", "proxy-authorization"];
+
+export class UnsafeAddressError extends Error {
+  constructor(hostname: string, address: string) {
+    super(`${hostname} resolved to non-public address ${address}`);
+    this.name = "UnsafeAddressError";
+  }
+}
+
+// A redirect the download may not follow: off the allowed hosts, not
+// https, or one hop too many
+export class RedirectRefusedError extends Error {
+  constructor(target: string, reason: string) {
+    super(`Redirect to ${target} refused: ${reason}`);
+    this.name = "RedirectRefusedError";
+  }
+}
+
+export type AddressResolver = (hostname: string) => Promise<string[]>;
+
+/** Performs one request to `url`, connected to `address`. */
+export type PinnedTransport = (
+  url: URL,
+  address: string,
+  init: { signal?: AbortSignal; headers: Record<string, string> },
+) => Promise<Response>;
+
+const systemResolver: AddressResolver = async (hostname) =>
+  (await dns.promises.lookup(hostname, { all: true, verbatim: true })).map(
+    (a) => a.address,
+  );
+
+// https.request with a lookup that only ever returns the validated address;
+// TLS still verifies the certificate against the hostname
+const httpsTransport: PinnedTransport = (url, address, init) =>
+  new Promise((resolve, reject) => {
+    const family = net.isIPv6(address) ? 6 : 4;
+    const req = https.request(
+      url,
+      {
+        headers: init.headers,
+        signal: init.signal,
+        lookup: (_hostname, options, callback) => {
+          if (options.all) callback(null, [{ address, family }]);
+          else callback(null, address, family);
+        },
+      },
+      (res) => {
+        const headers = new Headers();
+        for (const [name, value] of Object.entries(res.headers)) {
+          for (const v of Array.isArray(value) ? value : [value]) {
+            if (v !== undefined) headers.append(name, v);
+          }
+        }
+        const status = res.statusCode ?? 502;
+        const empty = status === 204 || status === 304;
+        if (empty) res.resume();
+        resolve(
+          new Response(
+            empty ? null : (Readable.toWeb(res) as ReadableStream<Uint8Array>),
+            { status, statusText: res.statusMessage, headers },
+          ),
+        );
+      },
+    );
+    req.on("error", reject);
+    req.end();
+  });
+
+let resolveAddresses = systemResolver;
+let transport = httpsTransport;
+
+/**
+ * Replace the resolver and transport (both restored when omitted). Tests
+ * use this to stay offline and serve downloads from a mocked fetch.
+ */
+export function setPinnedNetwork(
+  resolver: AddressResolver = systemResolver,
+  connect: PinnedTransport = httpsTransport,
+) {
+  resolveAddresses = resolver;
+  transport = connect;
+}
+
+async function resolvePublic(hostname: string): Promise<string> {
+  const addresses = await resolveAddresses(hostname);
+  if (addresses.length === 0) {
+    throw new Error(`No addresses found for ${hostname}`);
+  }
+  for (const address of addresses) {
+    if (!net.isIP(address) || isNonPublicAddress(address)) {
+      throw new UnsafeAddressError(hostname, address);
+    }
+  }
+  return addresses[0];
+}
+
+/**
+ * A fetch for untrusted URLs: `validateURL` runs on the URL and on every
+ * redirect target, and each request is pinned to a resolved public address.
+ */
+export function createPinnedFetch(validateURL: (url: string) => void) {
+  return async (input: string, init: RequestInit = {}): Promise<Response> => {
+    const headers = Object.fromEntries(new Headers(init.headers));
+    const signal = init.signal ?? undefined;
+    let url = new URL(input);
+    for (let redirects = 0; ; redirects++) {
+      try {
+        validateURL(url.href);
+      } catch (err) {
+        if (redirects === 0) throw err;
+        throw new RedirectRefusedError(
+          url.host,
+          err instanceof Error ? err.message : String(err),
+        );
+      }
+      const address = await resolvePublic(url.hostname);
+      const response = await transport(url, address, { signal, headers });
+      const location = response.headers.get("location");
+      if (!REDIRECT_STATUSES.has(response.status) || !location) {
+        if (redirects > 0) {
+          Object.defineProperties(response, {
+            url: { value: url.href },
+            redirected: { value: true },
+          });
+        }
+        return response;
+      }
+      await response.body?.cancel();
+      const next = new URL(location, url);
+      if (redirects >= MAX_DOWNLOAD_REDIRECTS) {
+        throw new RedirectRefusedError(
+          next.host,
+          `more than ${MAX_DOWNLOAD_REDIRECTS} redirects`,
+        );
+      }
+      if (next.origin !== url.origin) {
+        for (const name of CREDENTIAL_HEADERS) delete headers[name];
+      }
+      url = next;
+    }
+  };
+}
@@ -0,0 +1,47 @@
+// Speed is sampled over windows of this length
+const SPEED_SAMPLE_MS = 500;
+
+export interface ProgressUpdate {
+  progress: number;
+  // Set when a new speed sample window completed
+  bytesPerSec: number | null;
+}
+
+/**
+ * Accumulates downloaded bytes locally and decides when an update is worth
+ * publishing: when the whole-percent progress changes or a speed sample
+ * window elapses. Per-chunk work is a few arithmetic operations, so the
+ * shared task and its listeners are only touched when something visible
+ * changed.
+ */
+export class ProgressAccumulator {
+  downloaded = 0;
+  private lastTime: number;
+  private lastBytes = 0;
+  private lastProgress = -1;
+
+  constructor(private readonly now: () => number = Date.now) {
+    this.lastTime = now();
+  }
+
+  /** Record received bytes; returns an update to publish, or null. */
+  add(bytes: number, total: number): ProgressUpdate | null {
+    this.downloaded += bytes;
+
+    let bytesPerSec: number | null = null;
+    const now = this.now();
+    const elapsed = now - this.lastTime;
+    if (elapsed >= SPEED_SAMPLE_MS) {
+      bytesPerSec = ((this.downloaded - this.lastBytes) / elapsed) * 1000;
+      this.lastTime = now;
+      this.lastBytes = this.downloaded;
+    }
+
+    const progress =
+      total > 0 ? Math.round((this.downloaded / total) * 100) : 0;
+    if (bytesPerSec === null && progress === this.lastProgress) return null;
+
+    this.lastProgress = progress;
+    return { progress, bytesPerSec };
+  }
+}
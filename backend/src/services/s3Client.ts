@@ -0,0 +1,261 @@
+import crypto from "crypto";
+
+// Just enough of the S3 API (path-style, SigV4) to copy packages into an
+// S3-compatible bucket such as R2 with resumable multipart uploads
+
+export interface S3Target {
+  endpoint: string;
+  bucket: string;
+  region: string;
+  accessKeyId: string;
+  secretAccessKey: string;
+}
+
+export interface UploadedPart {
+  partNumber: number;
+  etag: string;
+  size: number;
+}
+
+export class S3Error extends Error {
+  constructor(
+    readonly status: number,
+    message: string,
+  ) {
+    super(message);
+    this.name = "S3Error";
+  }
+}
+
+function sha256Hex(data: string | Buffer): string {
+  return crypto.createHash("sha256").update(data).digest("hex");
+}
+
+function hmac(key: string | Buffer, data: string): Buffer {
+  return crypto.createHmac("sha256", key).update(data).digest();
+}
+
+// RFC 3986 encoding, which SigV4 requires and encodeURIComponent misses
+function encodeRfc3986(value: string): string {
+  return encodeURIComponent(value).replace(
+    /[!'()*]/g,
+    (c) => `%${c.charCodeAt(0).toString(16).toUpperCase()}`,
+  );
+}
+
+function objectUrl(
+  target: S3Target,
+  key: string,
+  query: Record<string, string> = {},
+): URL {
+  const encodedKey = key.split("/").map(encodeRfc3986).join("/");
+  const url = new URL(
+    `${target.endpoint.replace(/\/+$/, "")}/${encodeRfc3986(target.bucket)}/${encodedKey}`,
+  );
+  for (const [name, value] of Object.entries(query)) {
+    url.searchParams.set(name, value);
+  }
+  return url;
+}
+
+function sign(
+  target: S3Target,
+  method: string,
+  url: URL,
+  payloadHash: string,
+): Record<string, string> {
+  const amzDate = new Date().toISOString().replace(/[-:]|\.\d{3}/g, "");
+  const date = amzDate.slice(0, 8);
+  const headers: Record<string, string> = {
+    "x-amz-content-sha256": payloadHash,
+    "x-amz-date": amzDate,
+  };
+  // fetch sets Host itself, but it must be signed
+  const signed: Record<string, string> = { host: url.host, ...headers };
+  const names = Object.keys(signed).sort();
+  const query = [...url.searchParams]
+    .map(([k, v]) => `${encodeRfc3986(k)}=${encodeRfc3986(v)}`)
+    .sort()
+    .join("&");
+  const canonicalRequest = [
+    method,
+    url.pathname,
+    query,
+    names.map((n) => `${n}:${signed[n]}\n`).join(""),
+    names.join(";"),
+    payloadHash,
+  ].join("\n");
+  const scope = `${date}/${target.region}/s3/aws4_request`;
+  const stringToSign = [
+    "AWS4-HMAC-SHA256",
+    amzDate,
+    scope,
+    sha256Hex(canonicalRequest),
+  ].join("\n");
+  let key = hmac(`AWS4${target.secretAccessKey}`, date);
+  for (const part of [target.region, "s3", "aws4_request"]) {
+    key = hmac(key, part);
+  }
+  const signature = crypto
+    .createHmac("sha256", key)
+    .update(stringToSign)
+    .digest("hex");
+  return {
+    ...headers,
+    authorization: `AWS4-HMAC-SHA256 Credential=${target.accessKeyId}/${scope}, SignedHeaders=${names.join(";")}, Signature=${signature}`,
+  };
+}
+
+async function send(
+  target: S3Target,
+  method: string,
+  url: URL,
+  body?: string | Buffer,
+): Promise<Response> {
+  const res = await fetch(url, {
+    method,
+    headers: sign(target, method, url, sha256Hex(body ?? "")),
+    body,
+  });
+  if (!res.ok && !(method === "HEAD" && res.status === 404)) {
+    const text = method === "HEAD" ? "" : await res.text();
+    const code = /<Code>([^<]+)<\/Code>/.exec(text)?.[1];
+    throw new S3Error(
+      res.status,
+      `S3 ${method} failed: HTTP ${res.status}${code ? ` ${code}` : ""}`,
+    );
+  }
+  return res;
+}
+
+function xmlValue(xml: string, tag: string): string | undefined {
+  return new RegExp(`<${tag}>([^<]*)</${tag}>`).exec(xml)?.[1];
+}
+
+/** Size and ETag of an object, or null if it does not exist. */
+export async function headObject(
+  target: S3Target,
+  key: string,
+): Promise<{ size: number; etag: string } | null> {
+  const res = await send(target, "HEAD", objectUrl(target, key));
+  if (res.status === 404) return null;
+  return {
+    size: parseInt(res.headers.get("content-length") ?? "0", 10),
+    etag: res.headers.get("etag") ?? "",
+  };
+}
+
+export async function createMultipartUpload(
+  target: S3Target,
+  key: string,
+): Promise<string> {
+  const res = await send(
+    target,
+    "POST",
+    objectUrl(target, key, { uploads: "" }),
+  );
+  const uploadId = xmlValue(await res.text(), "UploadId");
+  if (!uploadId) throw new S3Error(res.status, "S3 returned no UploadId");
+  return uploadId;
+}
+
+export async function uploadPart(
+  target: S3Target,
+  key: string,
+  uploadId: string,
+  partNumber: number,
+  body: Buffer,
+): Promise<string> {
+  const res = await send(
+    target,
+    "PUT",
+    objectUrl(target, key, { partNumber: String(partNumber), uploadId }),
+    body,
+  );
+  return res.headers.get("etag") ?? "";
+}
+
+/**
+ * Parts already stored for an upload, or null when the upload no longer
+ * exists (completed, aborted or expired).
+ */
+export async function listParts(
+  target: S3Target,
+  key: string,
+  uploadId: string,
+): Promise<UploadedPart[] | null> {
+  const parts: UploadedPart[] = [];
+  let marker = "0";
+  for (;;) {
+    let xml: string;
+    try {
+      const res = await send(
+        target,
+        "GET",
+        objectUrl(target, key, { uploadId, "part-number-marker": marker }),
+      );
+      xml = await res.text();
+    } catch (err) {
+      if (err instanceof S3Error && err.status === 404) return null;
+      throw err;
+    }
+    for (const [, part] of xml.matchAll(/<Part>([\s\S]*?)<\/Part>/g)) {
+      parts.push({
+        partNumber: parseInt(xmlValue(part, "PartNumber") ?? "0", 10),
+        etag: (xmlValue(part, "ETag") ?? "").replace(/&quot;/g, '"'),
+        size: parseInt(xmlValue(part, "Size") ?? "0", 10),
+      });
+    }
+    if (xmlValue(xml, "IsTruncated") !== "true") return parts;
+    marker = xmlValue(xml, "NextPartNumberMarker") ?? "";
+    if (!marker) return parts;
+  }
+}
+
+/**
+ * Abort a multipart upload so its stored parts are freed. An upload that
+ * no longer exists counts as aborted.
+ */
+export async function abortMultipartUpload(
+  target: S3Target,
+  key: string,
+  uploadId: string,
+): Promise<void> {
+  try {
+    await send(target, "DELETE", objectUrl(target, key, { uploadId }));
+  } catch (err) {
+    if (err instanceof S3Error && err.status === 404) return;
+    throw err;
+  }
+}
+
+export async function completeMultipartUpload(
+  target: S3Target,
+  key: string,
+  uploadId: string,
+  parts: { partNumber: number; etag: string }[],
+): Promise<void> {
+  const body =
+    "<CompleteMultipartUpload>" +
+    parts
+      .map(
+        (p) =>
+          `<Part><PartNumber>${p.partNumber}</PartNumber><ETag>${p.etag}</ETag></Part>`,
+      )
+      .join("") +
+    "</CompleteMultipartUpload>";
+  const res = await send(
+    target,
+    "POST",
+    objectUrl(target, key, { uploadId }),
+    body,
+  );
+  // S3 can answer 200 with an error document
+  const text = await res.text();
+  if (text.includes("<Error>")) {
+    throw new S3Error(
+      res.status,
+      `S3 complete failed: ${xmlValue(text, "Code") ?? "unknown error"}`,
+    );
+  }
+}
@@ -0,0 +1,55 @@
+import crypto from "crypto";
+import { config, MAX_SESSIONS, SESSION_SWEEP_INTERVAL_MS } from "../config.js";
+
+// Opaque session tokens standing in for an account hash on shared devices.
+// Held in memory only: a restart signs everyone out.
+const sessions = new Map<string, { accountHash: string; expiresAt: number }>();
+
+/**
+ * Mint a token for `accountHash` valid for SESSION_TTL_SECONDS. At
+ * MAX_SESSIONS, expired sessions are dropped first; if none were, null.
+ * Live sessions are never evicted, so minting many cannot sign others out.
+ */
+export function createSession(
+  accountHash: string,
+  now = Date.now(),
+): { token: string; expiresAt: number } | null {
+  if (sessions.size >= MAX_SESSIONS) sweepSessions(now);
+  if (sessions.size >= MAX_SESSIONS) return null;
+  const token = crypto.randomBytes(32).toString("base64url");
+  const expiresAt = now + config.sessionTtlSeconds * 1000;
+  sessions.set(token, { accountHash, expiresAt });
+  return { token, expiresAt };
+}
+
+/** The account hash behind a live session token, or null. */
+export function resolveSession(token: string, now = Date.now()): string | null {
+  const session = sessions.get(token);
+  if (!session) return null;
+  if (session.expiresAt <= now) {
+    sessions.delete(token);
+    return null;
+  }
+  return session.accountHash;
+}
+
+/** End a session; false when the token was unknown or already expired. */
+export function revokeSession(token: string, now = Date.now()): boolean {
+  const live = resolveSession(token, now) !== null;
+  sessions.delete(token);
+  return live;
+}
+
+/** Drop expired sessions; returns how many were removed. */
+export function sweepSessions(now = Date.now()): number {
+  let removed = 0;
+  for (const [token, session] of sessions) {
+    if (session.expiresAt <= now) {
+      sessions.delete(token);
+      removed++;
+    }
+  }
+  return removed;
+}
+
+setInterval(sweepSessions, SESSION_SWEEP_INTERVAL_MS).unref();
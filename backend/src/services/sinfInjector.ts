@@ -8,41 +8,97 @@ import type { Readable } from "stream";
 import bplistParser from "bplist-parser";
 import bplistCreator from "bplist-creator";
 import plist from "plist";
-import type { Sinf } from "../types/index.js";
+import { config } from "../config.js";
+import { chaosInjectionFault } from "./chaos.js";
+import { readEncryptionState, type EncryptionState } from "./machO.js";
+import {
+  flagUtf8Names,
+  isAsciiName,
+  rawEntryName,
+  toRawName,
+  UTF8_NAME_FLAG,
+} from "./zipEntryNames.js";
+import type {
+  InjectionSource,
+  MetadataCompression,
+  Sinf,
+} from "../types/index.js";
 
 const execFile = promisify(execFileCb);
 
+// A file to write into the IPA; only iTunesMetadata.plist may be deflated.
+// entryPath is a raw name (see zipEntryNames.ts).
+interface InjectedFile {
+  entryPath: string;
+  data: Buffer;
+  deflate?: boolean;
+}
+
+export function isValidMetadataCompression(
+  value: unknown,
+): value is MetadataCompression {
+  return value === "stored" || value === "deflated";
+}
+
 interface IpaMetadata {
-  bundleName: string;
+  // "Payload/<name>.app" as stored, and whether it is flagged UTF-8
+  bundleDir: string;
+  utf8Names: boolean;
   manifest: { sinfPaths: string[] } | null;
   info: { bundleExecutable: string } | null;
 }
 
+/**
+ * Write sinfs (and iTunesMetadata) into the IPA. Returns which source the
+ * sinf paths were taken from, for diagnosing unusual bundles. Sinfs are
+ * skipped (kind "not_encrypted") when the main binary is not encrypted.
+ * Sinfs are always stored uncompressed, as iOS expects of SC_Info;
+ * `metadataCompression` only applies to iTunesMetadata.plist.
+ */
 export async function inject(
   sinfs: Sinf[],
   ipaPath: string,
   iTunesMetadata?: string,
-): Promise<void> {
-  const { bundleName, manifest, info } = await readIpaMetadata(ipaPath);
+  metadataCompression: MetadataCompression = config.metadataCompression,
+): Promise<InjectionSource> {
+  if (config.chaosEnabled) chaosInjectionFault();
+  const { bundleDir, utf8Names, manifest, info } =
+    await readIpaMetadata(ipaPath);
 
   // Collect all files to inject
-  const filesToInject: { entryPath: string; data: Buffer }[] = [];
+  const filesToInject: InjectedFile[] = [];
+  let source: InjectionSource;
 
-  if (manifest) {
+  // A decrypted (or never encrypted) binary has nothing for a sinf to
+  // unlock, and an IPA carrying one anyway is malformed. Binaries that
+  // can't be parsed are injected as before.
+  const encryption =
+    sinfs.length > 0 && info
+      ? await readExecutableEncryption(
+          ipaPath,
+          bundleDir,
+          info.bundleExecutable,
+        )
+      : "unknown";
+
+  if (info && encryption === "not_encrypted") {
+    source = { kind: "not_encrypted", executable: info.bundleExecutable };
+  } else if (manifest) {
+    source = { kind: "manifest", sinfPaths: manifest.sinfPaths.length };
     for (let i = 0; i < manifest.sinfPaths.length; i++) {
       if (i >= sinfs.length) continue;
       const sinfPath = manifest.sinfPaths[i];
-      const fullPath = `Payload/${bundleName}.app/${sinfPath}`;
       filesToInject.push({
-        entryPath: fullPath,
+        entryPath: `${bundleDir}/${toRawName(sinfPath)}`,
         data: Buffer.from(sinfs[i].sinf, "base64"),
       });
     }
   } else if (info) {
+    source = { kind: "info", executable: info.bundleExecutable };
     if (sinfs.length > 0) {
-      const sinfPath = `Payload/${bundleName}.app/SC_Info/${info.bundleExecutable}.sinf`;
+      const sinfPath = `SC_Info/${info.bundleExecutable}.sinf`;
       filesToInject.push({
-        entryPath: sinfPath,
+        entryPath: `${bundleDir}/${toRawName(sinfPath)}`,
         data: Buffer.from(sinfs[0].sinf, "base64"),
       });
     }
@@ -66,15 +122,68 @@ export async function inject(
     filesToInject.push({
       entryPath: "iTunesMetadata.plist",
       data: metadataBuffer,
+      deflate: metadataCompression === "deflated",
     });
   }
 
   if (filesToInject.length > 0) {
     await addFilesToZip(ipaPath, filesToInject);
+    // Injected names next to UTF-8 ones are flagged the same
+    if (utf8Names) {
+      await flagUtf8Names(
+        ipaPath,
+        new Set(
+          filesToInject
+            .map((f) => f.entryPath)
+            .filter((name) => !isAsciiName(name)),
+        ),
+      );
+    }
+    await verifyInjectedFiles(ipaPath, filesToInject);
+  }
+  return source;
+}
+
+export class InjectionVerificationError extends Error {
+  constructor(message: string) {
+    super(message);
+    this.name = "InjectionVerificationError";
   }
 }
 
-async function streamToBuffer(stream: Readable): Promise<Buffer> {
+// Re-open the rewritten archive and confirm every planned file landed at the
+// expected path with the expected size. Catches injection bugs that would
+// otherwise produce an IPA that installs but refuses to launch.
+async function verifyInjectedFiles(
+  ipaPath: string,
+  files: InjectedFile[],
+): Promise<void> {
+  const expected = new Map(files.map((f) => [f.entryPath, f.data.length]));
+  const zip = await openZip(ipaPath, { decodeStrings: false });
+  try {
+    for await (const entry of zip) {
+      const name = rawEntryName(entry);
+      const size = expected.get(name);
+      if (size === undefined) continue;
+      if (entry.uncompressedSize !== size) {
+        throw new InjectionVerificationError(
+          `Injected entry ${name} has size ${entry.uncompressedSize}, expected ${size}`,
+        );
+      }
+      expected.delete(name);
+    }
+  } finally {
+    await zip.close();
+  }
+
+  if (expected.size > 0) {
+    throw new InjectionVerificationError(
+      `Injected entries missing from IPA: ${Array.from(expected.keys()).join(", ")}`,
+    );
+  }
+}
+
+export async function streamToBuffer(stream: Readable): Promise<Buffer> {
   const chunks: Buffer[] = [];
   for await (const chunk of stream) {
     chunks.push(chunk as Buffer);
@@ -82,26 +191,50 @@ async function streamToBuffer(stream: Readable): Promise<Buffer> {
   return Buffer.concat(chunks);
 }
 
+// Encryption of the app's main executable. A second pass over the archive,
+// since Info.plist (which names the executable) may come after it.
+async function readExecutableEncryption(
+  ipaPath: string,
+  bundleDir: string,
+  executable: string,
+): Promise<EncryptionState> {
+  const target = `${bundleDir}/${toRawName(executable)}`;
+  const zip = await openZip(ipaPath, { decodeStrings: false });
+  try {
+    for await (const entry of zip) {
+      if (rawEntryName(entry) !== target) continue;
+      return await readEncryptionState(await entry.openReadStream());
+    }
+    return "unknown";
+  } finally {
+    await zip.close();
+  }
+}
+
 async function readIpaMetadata(ipaPath: string): Promise<IpaMetadata> {
-  const zip = await openZip(ipaPath);
+  const zip = await openZip(ipaPath, { decodeStrings: false });
   try {
-    let bundleName: string | null = null;
+    let bundleDir: string | null = null;
+    let utf8Names = false;
     let manifestData: Buffer | null = null;
     let infoPlistData: Buffer | null = null;
 
     for await (const entry of zip) {
-      const filename = entry.filename;
+      // Raw, so the bundle directory is reused byte for byte; the parts
+      // matched here are ASCII in any encoding
+      const filename = rawEntryName(entry);
 
       // Find bundle name from .app directory
       if (
-        !bundleName &&
+        !bundleDir &&
         filename.includes(".app/Info.plist") &&
         !filename.includes("/Watch/")
       ) {
         const components = filename.split("/");
         for (const component of components) {
           if (component.endsWith(".app")) {
-            bundleName = component.slice(0, -4);
+            bundleDir = `Payload/${component}`;
+            utf8Names = (entry.generalPurposeBitFlag & UTF8_NAME_FLAG) !== 0;
             break;
           }
         }
@@ -124,7 +257,7 @@ async function readIpaMetadata(ipaPath: string): Promise<IpaMetadata> {
       }
     }
 
-    if (!bundleName) {
+    if (!bundleDir) {
       throw new Error("Could not read bundle name");
     }
 
@@ -152,45 +285,72 @@ async function readIpaMetadata(ipaPath: string): Promise<IpaMetadata> {
       }
     }
 
-    return { bundleName, manifest, info };
+    return { bundleDir, utf8Names, manifest, info };
   } finally {
     await zip.close();
   }
 }
 
+// A raw path under `dir`, as the bytes the filesystem should see
+function onDisk(dir: string, rawPath: string): Buffer {
+  return Buffer.concat([
+    Buffer.from(dir + path.sep),
+    Buffer.from(rawPath, "latin1"),
+  ]);
+}
+
 async function addFilesToZip(
   ipaPath: string,
-  files: { entryPath: string; data: Buffer }[],
+  files: InjectedFile[],
 ): Promise<void> {
   const tmpDir = await fs.promises.mkdtemp(path.join(os.tmpdir(), "sinf-"));
-  const resolvedTmpDir = path.resolve(tmpDir);
   try {
-    // Write files to temp dir preserving ZIP path structure
-    const relativePaths: string[] = [];
+    // One directory per compression method, holding the files at their ZIP
+    // paths. Names are written as raw bytes and zip stores them as found,
+    // so it never has to be given (and re-encode) a non-ASCII name.
+    const methods = [
+      ["-0", "stored"],
+      ["-6", "deflated"],
+    ].map(([level, name]) => ({
+      level,
+      dir: path.resolve(tmpDir, name),
+      roots: new Set<string>(),
+    }));
     for (const file of files) {
+      const method = methods[file.deflate ? 1 : 0];
       // Guard against path traversal from IPA-derived entry paths
-      const fullPath = path.resolve(tmpDir, file.entryPath);
-      if (!fullPath.startsWith(resolvedTmpDir + path.sep)) {
+      const fullPath = path.resolve(method.dir, file.entryPath);
+      if (!fullPath.startsWith(method.dir + path.sep)) {
         throw new Error(`Path traversal detected in entry: ${file.entryPath}`);
       }
-      await fs.promises.mkdir(path.dirname(fullPath), { recursive: true });
-      await fs.promises.writeFile(fullPath, file.data);
-      relativePaths.push(file.entryPath);
+      const relative = fullPath.slice(method.dir.length + 1);
+      await fs.promises.mkdir(onDisk(method.dir, path.dirname(relative)), {
+        recursive: true,
+      });
+      await fs.promises.writeFile(onDisk(method.dir, relative), file.data);
+      // "Payload" or "iTunesMetadata.plist"
+      method.roots.add(relative.split("/")[0]);
     }
 
-    // Use zip to update the archive in-place
-    // -0: store without compression (SINF/plist files are tiny)
+    // Use zip to update the archive in-place, one pass per method
+    // -0: store without compression (sinfs must be; they are tiny anyway)
+    // -6: deflate (zip still stores a file that doesn't shrink)
+    // -r -D: add the files under each root, without directory entries
     // "--" after archive name prevents file args from being parsed as flags
-    await execFile("zip", ["-0", ipaPath, "--", ...relativePaths], {
-      cwd: tmpDir,
-      maxBuffer: 1024 * 1024,
-    });
+    for (const { level, dir, roots } of methods) {
+      if (roots.size === 0) continue;
+      await execFile(
+        "zip",
+        [level, "-r", "-D", ipaPath, "--", ...roots],
+        { cwd: dir, maxBuffer: 1024 * 1024 },
+      );
+    }
   } finally {
     await fs.promises.rm(tmpDir, { recursive: true, force: true });
   }
 }
 
-function parsePlistBuffer(data: Buffer): Record<string, unknown> | null {
+export function parsePlistBuffer(data: Buffer): Record<string, unknown> | null {
   // Try binary plist first
   try {
     const parsed = bplistParser.parseBuffer(data);
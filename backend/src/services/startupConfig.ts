@@ -0,0 +1,12 @@
+import { config } from "../config.js";
+import { reloadConfig } from "./configReload.js";
+
+// Runtime settings from CONFIG_FILE. index.ts imports this module before
+// anything else, so the values are in `config` before other modules act
+// on them while loading (the download manager's startup cleanup, for one).
+if (config.configFile) {
+  const loaded = reloadConfig();
+  if (!loaded.ok) {
+    console.warn(`[Config] Ignoring CONFIG_FILE: ${loaded.error}`);
+  }
+}
@@ -0,0 +1,388 @@
+import crypto from "crypto";
+import fs from "fs";
+import path from "path";
+import {
+  config,
+  MIGRATE_PART_BYTES,
+  MIGRATE_PART_RETRIES,
+} from "../config.js";
+import {
+  deleteTask,
+  getAllTasks,
+  getTask,
+  packageRelativePath,
+} from "./downloadManager.js";
+import {
+  abortMultipartUpload,
+  completeMultipartUpload,
+  createMultipartUpload,
+  headObject,
+  listParts,
+  uploadPart,
+  type S3Target,
+} from "./s3Client.js";
+import { writeFileAtomicSync } from "../utils/durable.js";
+import type { DownloadTask } from "../types/index.js";
+
+// Copies completed packages into an S3-compatible bucket (e.g. R2) one at
+// a time, so a standalone library can move to object storage without
+// downloading anything from Apple again. The job and its cursor live in
+// DATA_DIR/storage-migration.json and pick up where they left off after a
+// restart, including a half-uploaded package.
+
+export interface MigrationEntry {
+  taskId: string;
+  key: string;
+  size: number;
+  status: "pending" | "uploading" | "migrated" | "skipped" | "failed";
+  uploadedBytes: number;
+  // Multipart upload in progress, reused to resume
+  uploadId?: string;
+  error?: string;
+  migratedAt?: string;
+}
+
+export interface MigrationState {
+  status: "running" | "completed";
+  startedAt: string;
+  finishedAt?: string;
+  deleteSource: boolean;
+  partSize: number;
+  // Index of the next entry to process
+  cursor: number;
+  entries: MigrationEntry[];
+}
+
+const STATE_FILE = path.join(config.dataDir, "storage-migration.json");
+
+let state: MigrationState | null = null;
+let running = false;
+
+/** The configured destination, or null without MIGRATE_S3_* settings. */
+export function getMigrationTarget(): S3Target | null {
+  const {
+    migrateS3Endpoint,
+    migrateS3Bucket,
+    migrateS3Region,
+    migrateS3AccessKeyId,
+    migrateS3SecretAccessKey,
+  } = config;
+  if (
+    !migrateS3Endpoint ||
+    !migrateS3Bucket ||
+    !migrateS3AccessKeyId ||
+    !migrateS3SecretAccessKey
+  ) {
+    return null;
+  }
+  return {
+    endpoint: migrateS3Endpoint,
+    bucket: migrateS3Bucket,
+    region: migrateS3Region,
+    accessKeyId: migrateS3AccessKeyId,
+    secretAccessKey: migrateS3SecretAccessKey,
+  };
+}
+
+function readStateFile(): MigrationState | null {
+  try {
+    return JSON.parse(fs.readFileSync(STATE_FILE, "utf-8"));
+  } catch {
+    return null;
+  }
+}
+
+function saveState() {
+  fs.mkdirSync(path.dirname(STATE_FILE), { recursive: true });
+  writeFileAtomicSync(
+    STATE_FILE,
+    JSON.stringify(state, null, 2),
+    config.durableWrites,
+  );
+}
+
+export function getMigrationState(): MigrationState | null {
+  return state ?? readStateFile();
+}
+
+export function isMigrationRunning(): boolean {
+  return running;
+}
+
+/**
+ * Start a migration of every completed package. Uploads left unfinished by
+ * an earlier job are resumed rather than restarted.
+ */
+export function startStorageMigration(deleteSource: boolean): MigrationState {
+  const last = getMigrationState();
+  const previous = new Map(
+    (last?.entries ?? []).map((e) => [`${e.taskId}:${e.key}`, e]),
+  );
+  const partSize = MIGRATE_PART_BYTES;
+  const reusable = last?.partSize === partSize;
+  const entries: MigrationEntry[] = [];
+  const tasks = getAllTasks()
+    .filter((t) => t.status === "completed" && t.filePath)
+    .sort((a, b) => a.createdAt.localeCompare(b.createdAt));
+  for (const task of tasks) {
+    const relative = packageRelativePath(task.filePath!);
+    if (!relative) continue;
+    const key = config.migrateS3Prefix + relative;
+    const before = previous.get(`${task.id}:${key}`);
+    entries.push({
+      taskId: task.id,
+      key,
+      size: task.fileSize ?? 0,
+      status: "pending",
+      uploadedBytes: 0,
+      uploadId: reusable ? before?.uploadId : undefined,
+    });
+  }
+  state = {
+    status: "running",
+    startedAt: new Date().toISOString(),
+    deleteSource,
+    partSize,
+    cursor: 0,
+    entries,
+  };
+  saveState();
+  void runMigration();
+  return state;
+}
+
+/** Continue a job interrupted by a restart. Called once at startup. */
+export function resumeStorageMigration() {
+  state = readStateFile();
+  if (state?.status !== "running") return;
+  if (!getMigrationTarget()) {
+    console.warn("[Migration] MIGRATE_S3_* not configured; not resuming");
+    return;
+  }
+  console.log(
+    `[Migration] Resuming at package ${state.cursor + 1} of ${state.entries.length}`,
+  );
+  void runMigration();
+}
+
+// The package was deleted or trashed while its copy was uploading
+class PackageRemovedError extends Error {}
+
+// Free the parts of an upload that will not be completed: failed and
+// cancelled entries are not retried, and stored parts are billed until
+// the bucket's own lifecycle rules (if any) expire them
+async function abortUpload(target: S3Target, entry: MigrationEntry) {
+  if (!entry.uploadId) return;
+  try {
+    await abortMultipartUpload(target, entry.key, entry.uploadId);
+    entry.uploadId = undefined;
+  } catch (err) {
+    console.warn(
+      `[Migration] ${entry.taskId}: could not abort upload:`,
+      err instanceof Error ? err.message : err,
+    );
+  }
+}
+
+async function runMigration() {
+  const target = getMigrationTarget();
+  if (running || !state || !target) return;
+  running = true;
+  try {
+    while (state.cursor < state.entries.length) {
+      const entry = state.entries[state.cursor];
+      try {
+        await migrateEntry(target, state, entry);
+      } catch (err) {
+        await abortUpload(target, entry);
+        entry.status =
+          err instanceof PackageRemovedError ? "skipped" : "failed";
+        entry.error = err instanceof Error ? err.message : String(err);
+        console.warn(`[Migration] ${entry.taskId}: ${entry.error}`);
+      }
+      state.cursor++;
+      saveState();
+    }
+    state.status = "completed";
+    state.finishedAt = new Date().toISOString();
+    saveState();
+  } finally {
+    running = false;
+  }
+}
+
+async function readRange(
+  file: fs.promises.FileHandle,
+  start: number,
+  length: number,
+): Promise<Buffer> {
+  const buffer = Buffer.alloc(length);
+  let read = 0;
+  while (read < length) {
+    const { bytesRead } = await file.read(
+      buffer,
+      read,
+      length - read,
+      start + read,
+    );
+    if (bytesRead === 0) throw new Error("Package changed while migrating");
+    read += bytesRead;
+  }
+  return buffer;
+}
+
+async function withRetries<T>(fn: () => Promise<T>): Promise<T> {
+  for (let attempt = 1; ; attempt++) {
+    try {
+      return await fn();
+    } catch (err) {
+      if (attempt > MIGRATE_PART_RETRIES) throw err;
+      await new Promise((resolve) => setTimeout(resolve, 1000 * attempt));
+    }
+  }
+}
+
+function isAvailable(
+  task: DownloadTask | undefined,
+): task is DownloadTask & { filePath: string } {
+  return (
+    task?.status === "completed" &&
+    !!task.filePath &&
+    fs.existsSync(task.filePath)
+  );
+}
+
+const unquote = (etag: string) => etag.replace(/"/g, "").toLowerCase();
+
+async function migrateEntry(
+  target: S3Target,
+  job: MigrationState,
+  entry: MigrationEntry,
+) {
+  const task = getTask(entry.taskId);
+  if (!isAvailable(task)) {
+    entry.status = "skipped";
+    entry.error = "package no longer available";
+    return;
+  }
+  entry.size = fs.statSync(task.filePath).size;
+  entry.status = "uploading";
+  entry.error = undefined;
+
+  // Parts a previous attempt already stored, if that upload still exists
+  let stored = entry.uploadId
+    ? await listParts(target, entry.key, entry.uploadId)
+    : null;
+  if (!stored) {
+    entry.uploadId = await createMultipartUpload(target, entry.key);
+    stored = [];
+  }
+  const uploadId = entry.uploadId!;
+  saveState();
+
+  const partCount = Math.max(1, Math.ceil(entry.size / job.partSize));
+  const parts: { partNumber: number; etag: string }[] = [];
+  const partDigests: Buffer[] = [];
+  entry.uploadedBytes = 0;
+  const file = await fs.promises.open(task.filePath, "r");
+  try {
+    for (let partNumber = 1; partNumber <= partCount; partNumber++) {
+      // Stop at the next part once the package is deleted, rather than
+      // finishing a copy nobody will claim
+      if (!isAvailable(getTask(entry.taskId))) {
+        throw new PackageRemovedError("package deleted during migration");
+      }
+      const start = (partNumber - 1) * job.partSize;
+      const body = await readRange(
+        file,
+        start,
+        Math.min(job.partSize, entry.size - start),
+      );
+      const md5 = crypto.createHash("md5").update(body).digest();
+      const md5Hex = md5.toString("hex");
+      partDigests.push(md5);
+
+      // Reuse a stored part only if it holds exactly these bytes
+      const existing = stored.find((p) => p.partNumber === partNumber);
+      let etag =
+        existing &&
+        existing.size === body.length &&
+        unquote(existing.etag) === md5Hex
+          ? existing.etag
+          : undefined;
+      if (!etag) {
+        etag = await withRetries(() =>
+          uploadPart(target, entry.key, uploadId, partNumber, body),
+        );
+        const returned = unquote(etag);
+        if (/^[0-9a-f]{32}$/.test(returned) && returned !== md5Hex) {
+          throw new Error(`Part ${partNumber} checksum mismatch`);
+        }
+      }
+      parts.push({ partNumber, etag });
+      entry.uploadedBytes += body.length;
+      saveState();
+    }
+  } finally {
+    await file.close();
+  }
+
+  await withRetries(() =>
+    completeMultipartUpload(target, entry.key, uploadId, parts),
+  );
+  entry.uploadId = undefined;
+
+  // Only trust the copy once the destination agrees on size and, when it
+  // reports a multipart MD5 ETag, on content
+  const head = await headObject(target, entry.key);
+  if (!head || head.size !== entry.size) {
+    throw new Error(
+      `Destination size ${head?.size ?? "missing"} does not match ${entry.size}`,
+    );
+  }
+  const expectedEtag = `${crypto
+    .createHash("md5")
+    .update(Buffer.concat(partDigests))
+    .digest("hex")}-${partCount}`;
+  const etag = unquote(head.etag);
+  if (/^[0-9a-f]{32}-\d+$/.test(etag) && etag !== expectedEtag) {
+    throw new Error("Destination checksum does not match");
+  }
+
+  entry.status = "migrated";
+  entry.migratedAt = new Date().toISOString();
+  if (job.deleteSource) {
+    console.log(`[Migration] Deleting migrated package: ${entry.taskId}`);
+    deleteTask(entry.taskId);
+  }
+}
+
+/** Job state for the status endpoint, with per-status totals. */
+export function summarizeMigration(job: MigrationState) {
+  const count = (status: MigrationEntry["status"]) =>
+    job.entries.filter((e) => e.status === status).length;
+  return {
+    status: job.status,
+    startedAt: job.startedAt,
+    finishedAt: job.finishedAt,
+    deleteSource: job.deleteSource,
+    totals: {
+      packages: job.entries.length,
+      migrated: count("migrated"),
+      failed: count("failed"),
+      skipped: count("skipped"),
+      pending: count("pending") + count("uploading"),
+      bytes: job.entries.reduce((sum, e) => sum + e.size, 0),
+      uploadedBytes: job.entries.reduce((sum, e) => sum + e.uploadedBytes, 0),
+    },
+    tasks: job.entries.map((e) => ({
+      taskId: e.taskId,
+      key: e.key,
+      size: e.size,
+      status: e.status,
+      uploadedBytes: e.uploadedBytes,
+      error: e.error,
+      migratedAt: e.migratedAt,
+    })),
+  };
+}
@@ -0,0 +1,72 @@
+import fs from "fs";
+import { config } from "../config.js";
+
+export interface StorageStatus {
+  // Free bytes on the DATA_DIR volume at the last check (null before it)
+  freeBytes: number | null;
+  minFreeBytes: number;
+  // True while free space is below MIN_FREE_SPACE_MB
+  pressure: boolean;
+  checkedAt?: string;
+}
+
+type PressureListener = (pressure: boolean) => void;
+
+let status: StorageStatus = {
+  freeBytes: null,
+  minFreeBytes: 0,
+  pressure: false,
+};
+const listeners = new Set<PressureListener>();
+
+export function getStorageStatus(): StorageStatus {
+  return status;
+}
+
+/** Whether new downloads should be refused for lack of disk space. */
+export function isUnderStoragePressure(): boolean {
+  return status.pressure;
+}
+
+/** Called with the new state whenever storage pressure starts or ends. */
+export function onStoragePressureChange(listener: PressureListener) {
+  listeners.add(listener);
+  return () => listeners.delete(listener);
+}
+
+/**
+ * Measure free space on the data volume and flip the pressure flag. A
+ * failed measurement keeps the previous state rather than guessing.
+ */
+export async function checkStorage(): Promise<StorageStatus> {
+  const minFreeBytes = config.minFreeSpaceMB * 1024 * 1024;
+  let freeBytes: number;
+  try {
+    const stats = await fs.promises.statfs(config.dataDir);
+    freeBytes = stats.bavail * stats.bsize;
+  } catch (err) {
+    console.warn(
+      "Free space check failed:",
+      err instanceof Error ? err.message : err,
+    );
+    return status;
+  }
+
+  const pressure = minFreeBytes > 0 && freeBytes < minFreeBytes;
+  const changed = pressure !== status.pressure;
+  status = {
+    freeBytes,
+    minFreeBytes,
+    pressure,
+    checkedAt: new Date().toISOString(),
+  };
+  if (changed) {
+    console.warn(
+      pressure
+        ? `[Storage] Free space ${freeBytes} bytes is below the ${minFreeBytes} byte floor; refusing new downloads`
+        : `[Storage] Free space recovered to ${freeBytes} bytes`,
+    );
+    for (const listener of listeners) listener(pressure);
+  }
+  return status;
+}
@@ -0,0 +1,30 @@
+import type { TaskEvent, TaskEventType } from "../types/index.js";
+
+// Oldest events are dropped once a task's history reaches this size
+export const MAX_TASK_EVENTS = 50;
+
+/** Append a timestamped event to the task's bounded history. */
+export function recordTaskEvent(
+  task: { events?: TaskEvent[] },
+  type: TaskEventType,
+  detail?: string,
+  now: Date = new Date(),
+): void {
+  const events = (task.events ??= []);
+  events.push({ type, at: now.toISOString(), ...(detail ? { detail } : {}) });
+  if (events.length > MAX_TASK_EVENTS) {
+    events.splice(0, events.length - MAX_TASK_EVENTS);
+  }
+}
+
+/** Restore a persisted history, dropping malformed entries. */
+export function restoreTaskEvents(value: unknown): TaskEvent[] | undefined {
+  if (!Array.isArray(value)) return undefined;
+  const events = value
+    .filter(
+      (e): e is TaskEvent =>
+        !!e && typeof e.type === "string" && typeof e.at === "string",
+    )
+    .slice(-MAX_TASK_EVENTS);
+  return events.length > 0 ? events : undefined;
+}
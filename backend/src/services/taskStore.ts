@@ -0,0 +1,141 @@
+import fs from "fs";
+import zlib from "zlib";
+import { writeFileAtomicSync } from "../utils/durable.js";
+import type { DownloadTask } from "../types/index.js";
+
+/**
+ * Where task metadata is persisted between restarts. `load` returns raw,
+ * untrusted records; the download manager validates them on restore.
+ */
+export interface TaskStore {
+  load(): unknown[];
+  save(tasks: DownloadTask[]): void;
+}
+
+/**
+ * Version of the persisted task file layout. Bump it with a migration in
+ * MIGRATIONS whenever a stored field is renamed or reshaped.
+ */
+export const TASKS_SCHEMA_VERSION = 2;
+
+// Upgrades records from the version they are keyed by to the next one
+const MIGRATIONS: Record<number, (records: unknown[]) => unknown[]> = {
+  // v1 was a bare array of tasks; v2 only wraps it with schemaVersion
+  1: (records) => records,
+};
+
+interface PersistedTaskFile {
+  schemaVersion: number;
+  tasks: unknown[];
+}
+
+/**
+ * Bring a parsed task file up to TASKS_SCHEMA_VERSION. Files written by a
+ * newer server are refused rather than read with fields this version
+ * doesn't know about.
+ */
+export function migrateTaskFile(data: unknown): unknown[] {
+  const file = data as Partial<PersistedTaskFile> | null;
+  const version = Array.isArray(data) ? 1 : file?.schemaVersion;
+  let records = Array.isArray(data) ? data : file?.tasks;
+  if (
+    typeof version !== "number" ||
+    !Number.isInteger(version) ||
+    version < 1 ||
+    !Array.isArray(records)
+  ) {
+    throw new Error("Unrecognized task file layout");
+  }
+  if (version > TASKS_SCHEMA_VERSION) {
+    throw new Error(
+      `Task file schema ${version} is newer than supported ${TASKS_SCHEMA_VERSION}`,
+    );
+  }
+  if (version < TASKS_SCHEMA_VERSION) {
+    console.log(
+      `[TaskStore] Migrating task file from schema ${version} to ${TASKS_SCHEMA_VERSION}`,
+    );
+  }
+  for (let v = version; v < TASKS_SCHEMA_VERSION; v++) {
+    records = MIGRATIONS[v](records);
+  }
+  return records;
+}
+
+/**
+ * JSON file store used in production (`DATA_DIR/tasks.json`). With
+ * `compress` it writes `tasks.json.gz` instead. Loading reads whichever of
+ * the two was written last, so toggling the option needs no migration.
+ */
+export function createFileTaskStore(
+  filePath: string,
+  durable: boolean,
+  compress = false,
+): TaskStore {
+  const gzPath = `${filePath}.gz`;
+  return {
+    load() {
+      const plain = statMtime(filePath);
+      const gz = statMtime(gzPath);
+      if (plain === null && gz === null) return [];
+      const source =
+        gz !== null && (plain === null || gz >= plain) ? gzPath : filePath;
+      try {
+        const raw = fs.readFileSync(source);
+        const json = source === gzPath ? zlib.gunzipSync(raw) : raw;
+        return migrateTaskFile(JSON.parse(json.toString("utf-8")));
+      } catch (err) {
+        // The next save replaces the file; keep what could not be read
+        fs.copyFileSync(source, `${source}.unreadable`);
+        throw err;
+      }
+    },
+    save(tasks) {
+      const file: PersistedTaskFile = {
+        schemaVersion: TASKS_SCHEMA_VERSION,
+        tasks,
+      };
+      if (compress) {
+        const data = zlib.gzipSync(JSON.stringify(file));
+        writeFileAtomicSync(gzPath, data, durable);
+        fs.rmSync(filePath, { force: true });
+      } else {
+        writeFileAtomicSync(filePath, JSON.stringify(file, null, 2), durable);
+        fs.rmSync(gzPath, { force: true });
+      }
+    },
+  };
+}
+
+function statMtime(filePath: string): number | null {
+  try {
+    return fs.statSync(filePath).mtimeMs;
+  } catch {
+    return null;
+  }
+}
+
+/**
+ * In-memory store for hermetic tests. Records are deep-copied through JSON
+ * so tests observe exactly what would have been written to disk.
+ */
+export class MemoryTaskStore implements TaskStore {
+  readonly records = new Map<string, DownloadTask>();
+
+  constructor(initial: DownloadTask[] = []) {
+    this.save(initial);
+  }
+
+  load(): unknown[] {
+    return Array.from(this.records.values(), (t) =>
+      JSON.parse(JSON.stringify(t)),
+    );
+  }
+
+  save(tasks: DownloadTask[]): void {
+    this.records.clear();
+    for (const task of tasks) {
+      this.records.set(task.id, JSON.parse(JSON.stringify(task)));
+    }
+  }
+}
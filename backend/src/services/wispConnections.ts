@@ -0,0 +1,342 @@
+import type { Duplex } from "stream";
+import { v4 as uuidv4 } from "uuid";
+import { config } from "../config.js";
+import {
+  parseFrameHeader,
+  WISP_PACKET_HEADER_BYTES,
+} from "./wispFrameGuard.js";
+
+// Operator view of the Wisp relay: who is connected and which streams they
+// have open. wisp-js exposes none of this, so it is read passively from the
+// WebSocket frames passing through each upgraded socket in both directions.
+// Closing a connection writes CLOSE packets and a close frame straight to
+// the socket and ends it; wisp-js then drops the streams' target sockets.
+// The same packets drive the lifecycle log (WISP_LOG_LEVEL).
+
+const PACKET_CONNECT = 0x01;
+const PACKET_DATA = 0x02;
+const PACKET_CLOSE = 0x04;
+const CLOSE_REASON_VOLUNTARY = 0x02;
+const WS_OPCODE_BINARY = 0x2;
+const WS_OPCODE_CLOSE = 0x8;
+const WS_CLOSE_POLICY_VIOLATION = 1008;
+// Enough of a message for a CONNECT packet with any DNS hostname
+const MAX_PACKET_HEAD_BYTES = 512;
+
+const CLOSE_REASONS: Record<number, string> = {
+  0x01: "unknown",
+  0x02: "voluntary",
+  0x03: "network_error",
+  0x41: "invalid_info",
+  0x42: "unreachable",
+  0x43: "timeout",
+  0x44: "refused",
+  0x47: "tcp_timeout",
+  0x48: "blocked",
+  0x49: "throttled",
+  0x81: "client_error",
+};
+
+// "p25-buy.itunes.apple.com" -> "*.apple.com" in privacy mode
+function logTarget(hostname: string, port: number): string {
+  if (!config.wispLogPrivacy) return `${hostname}:${port}`;
+  const labels = hostname.split(".");
+  const host = labels.length > 2 ? `*.${labels.slice(-2).join(".")}` : hostname;
+  return `${host}:${port}`;
+}
+
+// Client addresses are left out of the log in privacy mode
+function logRemote(address: string): string {
+  return config.wispLogPrivacy ? "*" : address;
+}
+
+// Hostnames come straight from CONNECT packets; anything beyond plain host
+// and address characters is JSON-quoted so one event stays one line
+function logValue(value: string | number): string {
+  const text = String(value);
+  return /^[\w.:*\-[\]]+$/.test(text) ? text : JSON.stringify(text);
+}
+
+// One key=value line per event, e.g. "[Wisp] stream.close conn=... ..."
+function logEvent(
+  level: "connections" | "streams",
+  event: string,
+  fields: Record<string, string | number>,
+) {
+  const wanted = config.wispLogLevel;
+  if (wanted === "off" || (level === "streams" && wanted !== "streams")) {
+    return;
+  }
+  const pairs = Object.entries(fields).map(
+    ([key, value]) => `${key}=${logValue(value)}`,
+  );
+  console.log(`[Wisp] ${event} ${pairs.join(" ")}`);
+}
+
+export interface WispStreamInfo {
+  streamId: number;
+  hostname: string;
+  port: number;
+  openedAt: string;
+  // DATA payload bytes from the client, and from the target back to it
+  bytesSent: number;
+  bytesReceived: number;
+}
+
+export interface WispConnectionInfo {
+  id: string;
+  remoteAddress: string;
+  connectedAt: string;
+  streams: WispStreamInfo[];
+}
+
+/**
+ * Splits one direction of a WebSocket byte stream into messages, passing
+ * the first bytes of each (unmasked) and its total length to `onMessage`.
+ * Control frames are skipped, also between fragments of a message.
+ */
+class MessageReader {
+  private pending: Buffer = Buffer.alloc(0);
+  private remaining = 0;
+  private mask: Buffer | null = null;
+  // Position in the current frame's payload, for unmasking
+  private framePosition = 0;
+  private control = false;
+  private fin = false;
+  private head: Buffer[] = [];
+  private headBytes = 0;
+  private messageBytes = 0;
+
+  constructor(
+    private readonly onMessage: (head: Buffer, length: number) => void,
+  ) {}
+
+  feed(chunk: Buffer) {
+    const data = this.pending.length
+      ? Buffer.concat([this.pending, chunk])
+      : chunk;
+    this.pending = Buffer.alloc(0);
+
+    let offset = 0;
+    while (offset < data.length) {
+      if (this.remaining > 0) {
+        const take = Math.min(this.remaining, data.length - offset);
+        if (!this.control) this.keep(data.subarray(offset, offset + take));
+        this.remaining -= take;
+        offset += take;
+        if (this.remaining === 0) this.endFrame();
+        continue;
+      }
+      const header = parseFrameHeader(data, offset);
+      if (!header) {
+        this.pending = Buffer.from(data.subarray(offset));
+        break;
+      }
+      const end = offset + header.headerLength;
+      this.mask = header.masked
+        ? Buffer.from(data.subarray(end - 4, end))
+        : null;
+      offset = end;
+      this.framePosition = 0;
+      this.control = header.opcode >= 0x8;
+      this.fin = header.fin;
+      this.remaining = header.payloadLength;
+      if (!this.control) this.messageBytes += header.payloadLength;
+      if (this.remaining === 0) this.endFrame();
+    }
+  }
+
+  private keep(bytes: Buffer) {
+    const room = MAX_PACKET_HEAD_BYTES - this.headBytes;
+    if (room > 0) {
+      const part = Buffer.from(bytes.subarray(0, room));
+      if (this.mask) {
+        for (let i = 0; i < part.length; i++) {
+          part[i] ^= this.mask[(this.framePosition + i) % 4];
+        }
+      }
+      this.head.push(part);
+      this.headBytes += part.length;
+    }
+    this.framePosition += bytes.length;
+  }
+
+  private endFrame() {
+    if (this.control || !this.fin) return;
+    this.onMessage(Buffer.concat(this.head), this.messageBytes);
+    this.head = [];
+    this.headBytes = 0;
+    this.messageBytes = 0;
+  }
+}
+
+class WispConnection {
+  readonly id = uuidv4();
+  readonly connectedAt = new Date().toISOString();
+  readonly streams = new Map<number, WispStreamInfo>();
+  // Totals over every stream, for the connection's close event
+  streamsOpened = 0;
+  bytesSent = 0;
+  bytesReceived = 0;
+  closedByAdmin = false;
+  readonly fromClient = new MessageReader((head, length) =>
+    this.onPacket(head, length, true),
+  );
+  readonly fromServer = new MessageReader((head, length) =>
+    this.onPacket(head, length, false),
+  );
+
+  constructor(
+    readonly socket: Duplex,
+    readonly remoteAddress: string,
+  ) {}
+
+  private onPacket(head: Buffer, length: number, fromClient: boolean) {
+    if (head.length < WISP_PACKET_HEADER_BYTES) return;
+    const type = head.readUInt8(0);
+    const streamId = head.readUInt32LE(1);
+    if (type === PACKET_CONNECT && fromClient && head.length >= 8) {
+      // Stream type (1 byte), port (2 bytes LE), hostname
+      const stream = {
+        streamId,
+        hostname: head.subarray(8).toString("utf-8"),
+        port: head.readUInt16LE(6),
+        openedAt: new Date().toISOString(),
+        bytesSent: 0,
+        bytesReceived: 0,
+      };
+      this.streams.set(streamId, stream);
+      this.streamsOpened++;
+      logEvent("streams", "stream.connect", {
+        conn: this.id,
+        stream: streamId,
+        target: logTarget(stream.hostname, stream.port),
+      });
+    } else if (type === PACKET_DATA) {
+      const stream = this.streams.get(streamId);
+      const bytes = length - WISP_PACKET_HEADER_BYTES;
+      if (stream && fromClient) {
+        stream.bytesSent += bytes;
+        this.bytesSent += bytes;
+      } else if (stream) {
+        stream.bytesReceived += bytes;
+        this.bytesReceived += bytes;
+      }
+    } else if (type === PACKET_CLOSE) {
+      const stream = this.streams.get(streamId);
+      if (!stream) return;
+      this.streams.delete(streamId);
+      const reason = head.length > WISP_PACKET_HEADER_BYTES ? head[5] : 0x01;
+      this.logStreamClose(stream, reason, fromClient ? "client" : "server");
+    }
+  }
+
+  logStreamClose(stream: WispStreamInfo, reason: number, by: string) {
+    logEvent("streams", "stream.close", {
+      conn: this.id,
+      stream: stream.streamId,
+      target: logTarget(stream.hostname, stream.port),
+      reason: CLOSE_REASONS[reason] ?? `0x${reason.toString(16)}`,
+      by,
+      sent: stream.bytesSent,
+      received: stream.bytesReceived,
+      ms: Date.now() - Date.parse(stream.openedAt),
+    });
+  }
+
+  // Streams still open when the socket went away end with it
+  logClose() {
+    for (const stream of this.streams.values()) {
+      this.logStreamClose(stream, 0x03, "connection");
+    }
+    logEvent("connections", "connection.close", {
+      conn: this.id,
+      remote: logRemote(this.remoteAddress),
+      by: this.closedByAdmin ? "admin" : "socket",
+      streams: this.streamsOpened,
+      sent: this.bytesSent,
+      received: this.bytesReceived,
+      ms: Date.now() - Date.parse(this.connectedAt),
+    });
+  }
+}
+
+const connections = new Map<string, WispConnection>();
+
+// An unmasked (server) frame with a payload of at most 125 bytes
+function frame(opcode: number, payload: Buffer): Buffer {
+  return Buffer.concat([Buffer.from([0x80 | opcode, payload.length]), payload]);
+}
+
+/**
+ * Start tracking an upgraded Wisp socket, including the bytes that came
+ * with the upgrade. Call before the relay is attached: wisp-js writes its
+ * frames through `socket.write`, which is wrapped to read them.
+ */
+export function trackWispConnection(
+  socket: Duplex,
+  remoteAddress: string,
+  head: Buffer,
+): string {
+  const connection = new WispConnection(socket, remoteAddress);
+  connections.set(connection.id, connection);
+  logEvent("connections", "connection.open", {
+    conn: connection.id,
+    remote: logRemote(remoteAddress),
+  });
+  socket.once("close", () => {
+    connections.delete(connection.id);
+    connection.logClose();
+  });
+
+  connection.fromClient.feed(head);
+  socket.on("data", (chunk: Buffer) => connection.fromClient.feed(chunk));
+  const write = socket.write;
+  socket.write = function (this: Duplex, chunk: unknown, ...rest: unknown[]) {
+    // The upgrade response is written as a string and is not a frame
+    if (chunk instanceof Uint8Array) {
+      connection.fromServer.feed(
+        Buffer.from(chunk.buffer, chunk.byteOffset, chunk.byteLength),
+      );
+    }
+    return write.apply(this, [chunk, ...rest] as Parameters<typeof write>);
+  } as typeof socket.write;
+  return connection.id;
+}
+
+/** Open Wisp connections with their streams. */
+export function listWispConnections(): WispConnectionInfo[] {
+  return Array.from(connections.values(), (connection) => ({
+    id: connection.id,
+    remoteAddress: connection.remoteAddress,
+    connectedAt: connection.connectedAt,
+    streams: Array.from(connection.streams.values(), (stream) => ({
+      ...stream,
+    })),
+  }));
+}
+
+/**
+ * Close a connection: a CLOSE packet for each open stream, then a
+ * WebSocket close frame, then the socket. False when the id is unknown.
+ */
+export function closeWispConnection(id: string): boolean {
+  const connection = connections.get(id);
+  if (!connection) return false;
+  connections.delete(id);
+  connection.closedByAdmin = true;
+
+  const { socket } = connection;
+  for (const streamId of [...connection.streams.keys()]) {
+    const packet = Buffer.alloc(WISP_PACKET_HEADER_BYTES + 1);
+    packet.writeUInt8(PACKET_CLOSE, 0);
+    packet.writeUInt32LE(streamId, 1);
+    packet.writeUInt8(CLOSE_REASON_VOLUNTARY, WISP_PACKET_HEADER_BYTES);
+    socket.write(frame(WS_OPCODE_BINARY, packet));
+  }
+  const status = Buffer.alloc(2);
+  status.writeUInt16BE(WS_CLOSE_POLICY_VIOLATION, 0);
+  socket.write(frame(WS_OPCODE_CLOSE, status));
+  socket.end(() => socket.destroy());
+  return true;
+}
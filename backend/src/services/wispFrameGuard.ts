@@ -0,0 +1,97 @@
+// Passive check of the WebSocket frames a Wisp client sends, run on the raw
+// upgraded socket ahead of the relay. wisp-js only sees a packet once its
+// whole message is buffered, so an oversized DATA packet is refused here,
+// from the frame header, before the payload is ever accumulated.
+
+// Wisp packet header: type (1 byte) + stream id (4 bytes)
+export const WISP_PACKET_HEADER_BYTES = 5;
+
+export interface FrameHeader {
+  fin: boolean;
+  opcode: number;
+  // Client frames are masked; the key is the last 4 header bytes
+  masked: boolean;
+  payloadLength: number;
+  headerLength: number;
+}
+
+// Parse a frame header at `offset`, or null if more bytes are needed
+export function parseFrameHeader(data: Buffer, offset: number): FrameHeader | null {
+  if (data.length - offset < 2) return null;
+  const first = data[offset];
+  const second = data[offset + 1];
+  const masked = (second & 0x80) !== 0;
+  let payloadLength = second & 0x7f;
+  let headerLength = 2;
+  if (payloadLength === 126) {
+    if (data.length - offset < 4) return null;
+    payloadLength = data.readUInt16BE(offset + 2);
+    headerLength = 4;
+  } else if (payloadLength === 127) {
+    if (data.length - offset < 10) return null;
+    const length = data.readBigUInt64BE(offset + 2);
+    payloadLength =
+      length > BigInt(Number.MAX_SAFE_INTEGER) ? Infinity : Number(length);
+    headerLength = 10;
+  }
+  if (masked) headerLength += 4;
+  if (data.length - offset < headerLength) return null;
+  return {
+    fin: (first & 0x80) !== 0,
+    opcode: first & 0x0f,
+    masked,
+    payloadLength,
+    headerLength,
+  };
+}
+
+/**
+ * Tracks frame boundaries across socket chunks and reports whether any
+ * message (a frame plus its continuations) exceeds `maxMessageBytes`.
+ */
+export class WispFrameGuard {
+  private pending: Buffer = Buffer.alloc(0);
+  private payloadRemaining = 0;
+  private messageBytes = 0;
+
+  constructor(private readonly maxMessageBytes: number) {}
+
+  /** Feed client bytes; false once a message is too large. */
+  feed(chunk: Buffer): boolean {
+    const data = this.pending.length
+      ? Buffer.concat([this.pending, chunk])
+      : chunk;
+    this.pending = Buffer.alloc(0);
+
+    let offset = 0;
+    while (offset < data.length) {
+      if (this.payloadRemaining > 0) {
+        const skip = Math.min(this.payloadRemaining, data.length - offset);
+        this.payloadRemaining -= skip;
+        offset += skip;
+        continue;
+      }
+      const header = parseFrameHeader(data, offset);
+      if (!header) {
+        // At most 14 header bytes are ever held back
+        this.pending = Buffer.from(data.subarray(offset));
+        break;
+      }
+      offset += header.headerLength;
+
+      // Control frames (close/ping/pong) are capped at 125 bytes by RFC 6455
+      if (header.opcode >= 0x8) {
+        if (header.payloadLength > 125) return false;
+      } else {
+        this.messageBytes =
+          header.opcode === 0x0
+            ? this.messageBytes + header.payloadLength
+            : header.payloadLength;
+        if (this.messageBytes > this.maxMessageBytes) return false;
+        if (header.fin) this.messageBytes = 0;
+      }
+      this.payloadRemaining = header.payloadLength;
+    }
+    return true;
+  }
+}
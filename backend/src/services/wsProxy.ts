@@ -1,5 +1,11 @@
 import { Server as HttpServer } from "http";
+import { Server as HttpsServer } from "https";
 import { server as wisp } from "@mercuryworkshop/wisp-js/server";
+import { config } from "../config.js";
+import { streamLimiter } from "../middleware/connectionLimit.js";
+import { wispDns } from "./dnsCache.js";
+import { trackWispConnection } from "./wispConnections.js";
+import { WISP_PACKET_HEADER_BYTES, WispFrameGuard } from "./wispFrameGuard.js";
 
 // Allow only Apple hosts required by bag/auth/purchase/version flows.
 wisp.options.hostname_whitelist = [
@@ -15,10 +21,41 @@ wisp.options.allow_direct_ip = false;
 // whitelist above is the primary security control.
 wisp.options.allow_private_ips = true;
 wisp.options.allow_loopback_ips = false;
+// Targets resolve through our cache (WISP_DNS_TTL_SECONDS, optional DoH
+// WISP_DNS_RESOLVER) instead of wisp-js's own, so there is one TTL to tune
+wisp.options.dns_method = (hostname) => wispDns.lookup(hostname);
+wisp.options.dns_ttl = 0;
 
-export function setupWsProxy(server: HttpServer) {
+export function setupWsProxy(server: HttpServer | HttpsServer) {
   server.on("upgrade", (req, socket, head) => {
-    if (req.url?.startsWith("/wisp")) {
+    if (req.url?.startsWith(`${config.basePath}/wisp`)) {
+      const remoteAddress = req.socket.remoteAddress || "unknown";
+      const release = streamLimiter.tryAcquire(remoteAddress);
+      if (!release) {
+        socket.write("HTTP/1.1 503 Service Unavailable\r\n\r\n");
+        socket.destroy();
+        return;
+      }
+      socket.once("close", release);
+
+      // Registered before the relay's own listener, so an oversized packet
+      // is cut off from its header instead of being buffered first
+      const guard = new WispFrameGuard(
+        config.wispMaxDataBytes + WISP_PACKET_HEADER_BYTES,
+      );
+      const checkFrames = (chunk: Buffer) => {
+        if (guard.feed(chunk)) return;
+        console.warn(
+          `[Wisp] Closing ${remoteAddress}: packet over ${config.wispMaxDataBytes} bytes`,
+        );
+        socket.destroy();
+      };
+      checkFrames(head);
+      if (socket.destroyed) return;
+      socket.on("data", checkFrames);
+
+      // Listed and closable under /api/admin/wisp/connections
+      trackWispConnection(socket, remoteAddress, head);
       wisp.routeRequest(req, socket, head);
     } else {
       socket.write("HTTP/1.1 404 Not Found\r\n\r\n");
@@ -0,0 +1,120 @@
+import fs from "fs";
+import type { Entry } from "yauzl-promise";
+
+// Entry names as stored in an archive. Older IPAs carry names in CP437 or
+// mangled UTF-8, which decoding would change; the injector instead opens
+// archives with `decodeStrings: false` and handles each name as a latin1
+// string, one char per byte, so paths it derives match byte for byte.
+
+const EOCD_SIGNATURE = 0x06054b50;
+const EOCD_BYTES = 22;
+const ZIP64_LOCATOR_SIGNATURE = 0x07064b50;
+const ZIP64_LOCATOR_BYTES = 20;
+const ZIP64_EXTRA_ID = 0x0001;
+const CENTRAL_HEADER_SIGNATURE = 0x02014b50;
+const CENTRAL_HEADER_BYTES = 46;
+const SATURATED = 0xffffffff;
+// General purpose bit 11: the name is UTF-8
+export const UTF8_NAME_FLAG = 0x800;
+
+/** The stored name of an entry read with `decodeStrings: false`. */
+export function rawEntryName(entry: Entry): string {
+  return (entry.filename as unknown as Buffer).toString("latin1");
+}
+
+/** A Unicode name (e.g. from a plist) as its UTF-8 bytes, latin1 style. */
+export function toRawName(name: string): string {
+  return Buffer.from(name, "utf-8").toString("latin1");
+}
+
+export function isAsciiName(raw: string): boolean {
+  return /^[\x00-\x7f]*$/.test(raw);
+}
+
+// Local header offset of the central directory entry at `pos`, from its
+// Zip64 extra field when the 32-bit one is saturated
+function localHeaderOffset(cd: Buffer, pos: number): number {
+  const offset = cd.readUInt32LE(pos + 42);
+  if (offset !== SATURATED) return offset;
+  const extraStart = pos + CENTRAL_HEADER_BYTES + cd.readUInt16LE(pos + 28);
+  const extraEnd = extraStart + cd.readUInt16LE(pos + 30);
+  for (let at = extraStart; at + 4 <= extraEnd; ) {
+    if (cd.readUInt16LE(at) === ZIP64_EXTRA_ID) {
+      // Only the saturated fields are present, in this order
+      let field = at + 4;
+      if (cd.readUInt32LE(pos + 24) === SATURATED) field += 8;
+      if (cd.readUInt32LE(pos + 20) === SATURATED) field += 8;
+      return Number(cd.readBigUInt64LE(field));
+    }
+    at += 4 + cd.readUInt16LE(at + 2);
+  }
+  throw new Error("Zip64 entry without a local header offset");
+}
+
+/**
+ * Set the UTF-8 flag on the entries with these raw names, in the central
+ * directory and in their local headers. Info-ZIP's zip stores names as
+ * the bytes it finds on disk and never sets the flag itself.
+ */
+export async function flagUtf8Names(
+  zipPath: string,
+  names: Set<string>,
+): Promise<void> {
+  if (names.size === 0) return;
+  const file = await fs.promises.open(zipPath, "r+");
+  try {
+    const { size } = await file.stat();
+    const tail = Buffer.alloc(Math.min(size, EOCD_BYTES + 0xffff));
+    await file.read(tail, 0, tail.length, size - tail.length);
+    let eocd = tail.length - EOCD_BYTES;
+    while (eocd >= 0 && tail.readUInt32LE(eocd) !== EOCD_SIGNATURE) eocd--;
+    if (eocd < 0) throw new Error("End of central directory not found");
+
+    let cdSize = tail.readUInt32LE(eocd + 12);
+    let cdOffset = tail.readUInt32LE(eocd + 16);
+    const locator = eocd - ZIP64_LOCATOR_BYTES;
+    if (
+      (cdSize === SATURATED || cdOffset === SATURATED) &&
+      locator >= 0 &&
+      tail.readUInt32LE(locator) === ZIP64_LOCATOR_SIGNATURE
+    ) {
+      const record = Buffer.alloc(56);
+      await file.read(
+        record,
+        0,
+        record.length,
+        Number(tail.readBigUInt64LE(locator + 8)),
+      );
+      cdSize = Number(record.readBigUInt64LE(40));
+      cdOffset = Number(record.readBigUInt64LE(48));
+    }
+
+    const cd = Buffer.alloc(cdSize);
+    await file.read(cd, 0, cdSize, cdOffset);
+    const flags = Buffer.alloc(2);
+    let pos = 0;
+    while (
+      pos + CENTRAL_HEADER_BYTES <= cd.length &&
+      cd.readUInt32LE(pos) === CENTRAL_HEADER_SIGNATURE
+    ) {
+      const nameLength = cd.readUInt16LE(pos + 28);
+      const nameStart = pos + CENTRAL_HEADER_BYTES;
+      const name = cd.toString("latin1", nameStart, nameStart + nameLength);
+      if (names.has(name)) {
+        flags.writeUInt16LE(cd.readUInt16LE(pos + 8) | UTF8_NAME_FLAG);
+        await file.write(flags, 0, 2, cdOffset + pos + 8);
+        const local = localHeaderOffset(cd, pos) + 6;
+        await file.read(flags, 0, 2, local);
+        flags.writeUInt16LE(flags.readUInt16LE(0) | UTF8_NAME_FLAG);
+        await file.write(flags, 0, 2, local);
+      }
+      pos +=
+        CENTRAL_HEADER_BYTES +
+        nameLength +
+        cd.readUInt16LE(pos + 30) +
+        cd.readUInt16LE(pos + 32);
+    }
+  } finally {
+    await file.close();
+  }
+}
@@ -0,0 +1,101 @@
+import type { CircuitBreaker } from "../services/circuitBreaker.js";
+import type { PackageRootUsage } from "../services/packageRoots.js";
+import type { PackageEntry } from "../services/packageVerifier.js";
+import type { StorageStatus } from "../services/storageMonitor.js";
+import type { DownloadTask, PackageInfo, TaskErrorCategory } from "./index.js";
+
+// Wire formats of the JSON API. Field names are camelCase throughout and
+// are what clients depend on; tests/apiSchema.test.ts pins the serialized
+// shapes so a rename here (or in the types these derive from) fails CI.
+
+/** Body of every 4xx/5xx JSON response. */
+export interface ApiError {
+  error: string;
+  // Stable UPPER_SNAKE identifier for clients to branch on
+  code?: string;
+}
+
+/** A download task as returned by the downloads routes. */
+export type TaskResponse = Omit<
+  DownloadTask,
+  | "downloadURL"
+  | "fallbackURLs"
+  | "sinfs"
+  | "iTunesMetadata"
+  | "downloadHeaders"
+  | "filePath"
+  | "trashedFrom"
+  | "sharedWith"
+  | "events"
+> & {
+  hasFile?: boolean;
+  errorCategory?: TaskErrorCategory;
+  queuePosition?: number;
+  // Owner view of a paused task, with PAUSE_MAX_AGE_HOURS set
+  pauseExpiresAt?: string;
+};
+
+/** A package in listings and search results. */
+export type PackageResponse = Omit<PackageInfo, "filePath">;
+
+/** A package left out of a listing because its file can't be read. */
+export interface PackageWarning {
+  id: string;
+  reason: "file_missing" | "file_unreadable";
+}
+
+/** GET /api/packages and /api/packages/search with ?includeWarnings=true */
+export interface PackageListResponse {
+  packages: PackageResponse[];
+  warnings: PackageWarning[];
+}
+
+/** GET /api/packages/:id/contents */
+export interface PackageContentsResponse {
+  entries: PackageEntry[];
+  // Entries matching the prefix; more than returned when truncated
+  total: number;
+  truncated: boolean;
+}
+
+/** POST /api/session */
+export interface SessionResponse {
+  // Sent back as `Authorization: Session <token>`
+  token: string;
+  expiresAt: string;
+}
+
+/** /api/packages/uploads and its PUT/GET responses */
+export interface UploadResponse {
+  id: string;
+  size: number;
+  // Bytes stored so far; the Upload-Offset of the next PUT
+  received: number;
+  expiresAt: string;
+}
+
+/** GET /api/settings */
+export interface SettingsResponse {
+  uptime: number;
+  version: string;
+  buildCommit: string;
+  buildDate: string;
+  port: number;
+  dataDir: string;
+  publicBaseUrl: string;
+  disableHttpsRedirect: boolean;
+  readOnly: boolean;
+  autoCleanupDays: number;
+  autoCleanupMaxMB: number;
+  autoCleanupMaxPerAccount: number;
+  limits: { maxDownloadSize: number };
+  defaultStorefront: string;
+  progressPollIntervalMs: number;
+  upstreams: {
+    itunes: ReturnType<CircuitBreaker["snapshot"]>;
+    bag: ReturnType<CircuitBreaker["snapshot"]>;
+  };
+  stalledDownloads: { recovered: number; failed: number };
+  storage: StorageStatus;
+  packageRoots: PackageRootUsage[];
+}
@@ -17,6 +17,9 @@ export interface Software {
   releaseNotes?: string;
   formattedPrice?: string;
   primaryGenreName: string;
+  // Build the license was purchased for, when the client knows it
+  externalVersionId?: string;
+  purchaseDate?: string;
 }
 
 export interface Sinf {
@@ -24,32 +27,190 @@ export interface Sinf {
   sinf: string; // base64 encoded
 }
 
+// Machine-readable failure reasons, shown only to the task's owner
+export type TaskErrorCode =
+  | "download_url_expired"
+  | "http_status"
+  | "network"
+  | "timeout"
+  | "stalled"
+  | "too_large"
+  | "unexpected_encoding"
+  | "blocked_address"
+  | "redirect_rejected"
+  | "disk_full"
+  | "zip_invalid"
+  | "checksum_mismatch"
+  | "injection_failed"
+  | "invalid_path"
+  | "forced"
+  | "unknown";
+
+// Coarse, client-facing grouping of TaskErrorCode by remedy: re-acquire
+// the URL, free space, retry, ...
+export type TaskErrorCategory =
+  | "NETWORK"
+  | "URL_EXPIRED"
+  | "DISK_FULL"
+  | "TOO_LARGE"
+  | "INJECTION"
+  | "CHECKSUM";
+
 export interface DownloadTask {
   id: string;
   software: Software;
   accountHash: string;
+  // Other account hashes granted access by the owner
+  sharedWith?: string[];
   downloadURL: string;
+  // Mirrors not tried yet, taken in order when downloadURL fails
+  fallbackURLs?: string[];
+  // Which of the client's candidate URLs downloadURL is (0 = the first);
+  // only set for tasks created with several
+  mirrorIndex?: number;
   sinfs: Sinf[];
   iTunesMetadata?: string;
+  // How iTunesMetadata.plist is written (default METADATA_COMPRESSION)
+  metadataCompression?: MetadataCompression;
+  // Extra request headers the CDN needs to authorize the fetch (a secret)
+  downloadHeaders?: Record<string, string>;
+  // Digests of the upstream file as the client's ticket reported them;
+  // checked against single-stream downloads before injection
+  expectedDigests?: ExpectedDigests;
+  // Completed tasks of the same version to delete once this one completes
+  // (created with replace=true)
+  replaces?: string[];
+  // Probed when the task was created (unless skipProbe): upstream size and
+  // whether it honours byte ranges
+  totalBytes?: number;
+  resumable?: boolean;
   status:
     | "pending"
     | "downloading"
     | "paused"
     | "injecting"
     | "completed"
-    | "failed";
+    | "failed"
+    | "trashed"
+    | "removed";
+  // When the task was last paused (cleared once it runs again)
+  pausedAt?: string;
+  // Queue ordering while waiting for a download slot (default "normal")
+  priority?: TaskPriority;
+  // While injecting: waiting for an injection slot, or rewriting the IPA
+  injectionPhase?: "queued" | "running";
   progress: number;
   speed: string;
+  // Estimated seconds remaining, while downloading with a known size
+  etaSeconds?: number;
+  // While downloading: set when the response has no usable Content-Length,
+  // so progress stays 0 and clients should show downloadedBytes instead
+  indeterminate?: boolean;
+  downloadedBytes?: number;
+  // Last time progress listeners were notified: any status, progress,
+  // sharing, metadata or corrupt change; ?since= filters lists on it
+  updatedAt?: string;
   error?: string;
+  errorCode?: TaskErrorCode;
+  errorDetail?: string;
   filePath?: string;
+  // Recorded on completion; checked by package verification
+  fileSize?: number;
+  sha256?: string;
+  // Number of sinfs injected, so verification can expect SC_Info entries
+  injectedSinfs?: number;
+  // Where the sinf paths came from during the last injection
+  injectionSource?: InjectionSource;
+  // Set when the last verification of the package failed
+  corrupt?: boolean;
+  // How the package got here when not downloaded by this server; such
+  // tasks get their software record rebuilt from the IPA once
+  origin?: "imported" | "adopted" | "uploaded";
+  metadataEnrichedAt?: string;
+  // Set while a deleted package sits in the trash awaiting restore/sweep
+  trashedAt?: string;
+  trashedFrom?: string;
+  // Set when automated cleanup deleted the package (status "removed")
+  removedAt?: string;
+  removedReason?: RemovalReason;
+  stats?: DownloadStats;
+  events?: TaskEvent[];
   createdAt: string;
 }
 
+export type TaskPriority = "high" | "normal" | "low";
+
+// Zip method for an injected iTunesMetadata.plist; sinfs are always stored
+export type MetadataCompression = "stored" | "deflated";
+
+// Which retention policy deleted a package
+export type RemovalReason = "age" | "space" | "account_limit" | "replaced";
+
+// Lowercase hex digests
+export interface ExpectedDigests {
+  md5?: string;
+  sha1?: string;
+}
+
+// SC_Info/Manifest.plist SinfPaths, or the Info.plist executable fallback
+export type InjectionSource =
+  | { kind: "manifest"; sinfPaths: number }
+  | { kind: "info"; executable: string }
+  // Sinfs skipped: the executable has no FairPlay encryption (cryptid 0)
+  | { kind: "not_encrypted"; executable: string };
+
+export type TaskEventType =
+  | "created"
+  | "started"
+  | "progress"
+  | "paused"
+  | "resumed"
+  | "retry"
+  | "redirected"
+  | "injecting"
+  | "injected"
+  | "metadata"
+  | "failed"
+  | "completed"
+  | "trashed"
+  | "restored"
+  | "removed";
+
+// Lightweight history entry for reconstructing what happened to a task
+export interface TaskEvent {
+  type: TaskEventType;
+  at: string;
+  detail?: string;
+}
+
+// Summary recorded when a download completes
+export interface DownloadStats {
+  averageBytesPerSec: number;
+  peakBytesPerSec: number;
+  retries: number;
+  pauseResumeCycles: number;
+  totalTimeMs: number;
+}
+
+// Compact list record (GET /api/downloads?summary=true)
+export interface TaskSummary {
+  id: string;
+  name: string;
+  version: string;
+  status: DownloadTask["status"];
+  fileSize?: number;
+}
+
 export interface PackageInfo {
   id: string;
   software: Software;
   accountHash: string;
   filePath: string;
+  // Path under DATA_DIR/packages, for tools syncing the directory
+  relativePath?: string;
   fileSize: number;
+  corrupt?: boolean;
+  trashedAt?: string;
   createdAt: string;
+  updatedAt?: string;
 }
@@ -17,7 +17,7 @@ declare module "@mercuryworkshop/wisp-js/server" {
     allow_udp_streams: boolean;
     allow_tcp_streams: boolean;
     dns_ttl: number;
-    dns_method: string;
+    dns_method: string | ((hostname: string) => Promise<string>);
     dns_servers: string[] | null;
     dns_result_order: string;
     parse_real_ip: boolean;
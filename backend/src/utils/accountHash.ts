@@ -0,0 +1,26 @@
+import crypto from "crypto";
+import { config, MIN_ACCOUNT_HASH_LENGTH } from "../config.js";
+
+// Clients send a lowercase hex SHA-256 of the Apple account identifier
+const ACCOUNT_HASH_RE = /^[0-9a-f]{32,64}$/;
+// Pre-validation rule, still accepted when ALLOW_LEGACY_ACCOUNT_HASHES is set
+const LEGACY_ACCOUNT_HASH_RE = /^[a-zA-Z0-9._-]+$/;
+
+/** Whether `value` is an acceptable account hash under the current config. */
+export function isValidAccountHash(value: unknown): value is string {
+  if (typeof value !== "string") return false;
+  if (ACCOUNT_HASH_RE.test(value)) return true;
+  return (
+    config.allowLegacyAccountHashes &&
+    value.length >= MIN_ACCOUNT_HASH_LENGTH &&
+    LEGACY_ACCOUNT_HASH_RE.test(value)
+  );
+}
+
+/** Constant-time comparison so ownership checks don't leak hash prefixes. */
+export function accountHashesEqual(a: string, b: string): boolean {
+  const left = Buffer.from(a);
+  const right = Buffer.from(b);
+  if (left.length !== right.length) return false;
+  return crypto.timingSafeEqual(left, right);
+}
@@ -0,0 +1,58 @@
+import fs from "fs";
+import path from "path";
+
+/** Reserve `size` bytes for a file up front to reduce fragmentation. */
+export async function preallocate(filePath: string, size: number) {
+  const handle = await fs.promises.open(filePath, "w");
+  try {
+    await handle.truncate(size);
+  } finally {
+    await handle.close();
+  }
+}
+
+/**
+ * fsync a file and its parent directory so both the data and the directory
+ * entry survive a power loss. Directory fsync is unsupported on some
+ * platforms/filesystems; that failure is ignored.
+ */
+export async function syncFileAndParentDir(filePath: string) {
+  const handle = await fs.promises.open(filePath, "r+");
+  try {
+    await handle.sync();
+  } finally {
+    await handle.close();
+  }
+  syncDirSync(path.dirname(filePath));
+}
+
+/** Write via temp file + rename, optionally fsyncing before and after. */
+export function writeFileAtomicSync(
+  filePath: string,
+  data: string | Buffer,
+  durable: boolean,
+) {
+  const tmpPath = `${filePath}.tmp`;
+  const fd = fs.openSync(tmpPath, "w");
+  try {
+    fs.writeFileSync(fd, data);
+    if (durable) fs.fsyncSync(fd);
+  } finally {
+    fs.closeSync(fd);
+  }
+  fs.renameSync(tmpPath, filePath);
+  if (durable) syncDirSync(path.dirname(filePath));
+}
+
+function syncDirSync(dir: string) {
+  try {
+    const fd = fs.openSync(dir, "r");
+    try {
+      fs.fsyncSync(fd);
+    } finally {
+      fs.closeSync(fd);
+    }
+  } catch {
+    // Directory fsync not supported here (e.g. Windows, some network FS)
+  }
+}
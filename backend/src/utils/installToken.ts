@@ -0,0 +1,46 @@
+import crypto from "crypto";
+import { config } from "../config.js";
+
+// Without INSTALL_TOKEN_SECRET, tokens are signed with a per-process key
+// and stop verifying after a restart
+const fallbackSecret = crypto.randomBytes(32);
+
+function secret(): Buffer | string {
+  return config.installTokenSecret || fallbackSecret;
+}
+
+// With INSTALL_BASE_URL set, tokens are bound to it, so links minted for
+// one install host stop working once it changes
+function sign(taskId: string, expiresAt: number): string {
+  const scope = config.installBaseUrl ? `.${config.installBaseUrl}` : "";
+  return crypto
+    .createHmac("sha256", secret())
+    .update(`${taskId}.${expiresAt}${scope}`)
+    .digest("base64url");
+}
+
+/**
+ * Mint a token authorizing the install manifest and payload of one task
+ * until INSTALL_TOKEN_TTL_SECONDS from `now`. Format: `<expiry>.<hmac>`.
+ */
+export function mintInstallToken(taskId: string, now = Date.now()): string {
+  const expiresAt = Math.floor(now / 1000) + config.installTokenTtlSeconds;
+  return `${expiresAt}.${sign(taskId, expiresAt)}`;
+}
+
+/** Whether `token` was minted for `taskId` and has not expired. */
+export function verifyInstallToken(
+  taskId: string,
+  token: unknown,
+  now = Date.now(),
+): boolean {
+  if (typeof token !== "string") return false;
+  const match = /^(\d{1,12})\.([\w-]{43})$/.exec(token);
+  if (!match) return false;
+  const expiresAt = Number(match[1]);
+  if (expiresAt * 1000 <= now) return false;
+
+  const expected = Buffer.from(sign(taskId, expiresAt));
+  const given = Buffer.from(match[2]);
+  return crypto.timingSafeEqual(expected, given);
+}
@@ -0,0 +1,166 @@
+import { Request, Response } from "express";
+import { MAX_PAGE_SIZE } from "../config.js";
+import { sendError } from "./route.js";
+
+type Pageable = { id: string; createdAt: string };
+
+interface Cursor {
+  createdAt: string;
+  id: string;
+  // Set for ranked lists (search results), which order by rank first
+  rank?: number;
+}
+
+export interface PageParams {
+  // Undefined when the client did not ask for pagination
+  limit?: number;
+  after?: Cursor;
+}
+
+// Opaque to clients: the (createdAt, id[, rank]) of the last item returned,
+// so a page boundary survives tasks being added or deleted in between
+function encodeCursor(cursor: Cursor): string {
+  const fields: (string | number)[] = [cursor.createdAt, cursor.id];
+  if (cursor.rank !== undefined) fields.push(cursor.rank);
+  return Buffer.from(JSON.stringify(fields)).toString("base64url");
+}
+
+function decodeCursor(value: string): Cursor | null {
+  try {
+    const decoded = JSON.parse(Buffer.from(value, "base64url").toString());
+    if (
+      Array.isArray(decoded) &&
+      typeof decoded[0] === "string" &&
+      typeof decoded[1] === "string" &&
+      (decoded[2] === undefined || Number.isInteger(decoded[2]))
+    ) {
+      return { createdAt: decoded[0], id: decoded[1], rank: decoded[2] };
+    }
+  } catch {
+    // Fall through to the invalid cursor response
+  }
+  return null;
+}
+
+/**
+ * Read `?limit=` and `?cursor=`. Sends 400 and returns null when either is
+ * malformed. Lists stay unpaginated unless `limit` is given.
+ */
+export function parsePageParams(
+  req: Request,
+  res: Response,
+): PageParams | null {
+  const { limit: rawLimit, cursor: rawCursor } = req.query;
+  const params: PageParams = {};
+
+  if (rawLimit !== undefined) {
+    const limit = Number(rawLimit);
+    if (!Number.isInteger(limit) || limit < 1 || limit > MAX_PAGE_SIZE) {
+      sendError(
+        res,
+        400,
+        `limit must be an integer between 1 and ${MAX_PAGE_SIZE}`,
+        "INVALID_LIMIT",
+      );
+      return null;
+    }
+    params.limit = limit;
+  }
+
+  if (rawCursor !== undefined) {
+    const after =
+      typeof rawCursor === "string" ? decodeCursor(rawCursor) : null;
+    if (!after) {
+      sendError(res, 400, "Invalid cursor", "INVALID_CURSOR");
+      return null;
+    }
+    params.after = after;
+  }
+  return params;
+}
+
+function compare(a: Cursor, b: Cursor): number {
+  const rank = (a.rank ?? 0) - (b.rank ?? 0);
+  if (rank !== 0) return rank;
+  if (a.createdAt !== b.createdAt) return a.createdAt < b.createdAt ? -1 : 1;
+  return a.id < b.id ? -1 : a.id > b.id ? 1 : 0;
+}
+
+/**
+ * Order items oldest first and cut the page after the cursor. `nextCursor`
+ * is set when more items follow. With `rankOf`, items are ordered by rank
+ * (lowest first) and only then by age.
+ */
+export function paginate<T extends Pageable>(
+  items: T[],
+  params: PageParams,
+  rankOf?: (item: T) => number,
+): { items: T[]; nextCursor?: string } {
+  const { after, limit } = params;
+  const keyOf = (item: T): Cursor => ({
+    createdAt: item.createdAt,
+    id: item.id,
+    rank: rankOf?.(item),
+  });
+  const sorted = [...items].sort((a, b) => compare(keyOf(a), keyOf(b)));
+  const start = after
+    ? sorted.filter((t) => compare(keyOf(t), after) > 0)
+    : sorted;
+  if (limit === undefined || start.length <= limit) return { items: start };
+  const page = start.slice(0, limit);
+  const last = keyOf(page[page.length - 1]);
+  return { items: page, nextCursor: encodeCursor(last) };
+}
+
+/** Announce the next page in `X-Next-Cursor` (list bodies stay arrays). */
+export function setNextCursor(res: Response, nextCursor?: string) {
+  if (nextCursor) res.set("X-Next-Cursor", nextCursor);
+}
+
+/**
+ * Read `?since=` (an ISO date or epoch milliseconds) for incremental sync.
+ * Undefined when absent; sends 400 and returns null when malformed.
+ */
+export function parseSince(
+  req: Request,
+  res: Response,
+): number | undefined | null {
+  const raw = req.query.since;
+  if (raw === undefined) return undefined;
+  const since =
+    typeof raw !== "string"
+      ? NaN
+      : /^\d+$/.test(raw)
+        ? Number(raw)
+        : Date.parse(raw);
+  if (!Number.isFinite(since)) {
+    sendError(
+      res,
+      400,
+      "since must be an ISO date or epoch milliseconds",
+      "INVALID_SINCE",
+    );
+    return null;
+  }
+  return since;
+}
+
+/**
+ * Items created or updated at or after `since` (all when undefined). At,
+ * not after: a change in the same millisecond as the previous response is
+ * sent twice rather than missed.
+ */
+export function changedSince<T extends Pageable & { updatedAt?: string }>(
+  items: T[],
+  since: number | undefined,
+): T[] {
+  if (since === undefined) return items;
+  return items.filter(
+    (item) => Date.parse(item.updatedAt ?? item.createdAt) >= since,
+  );
+}
+
+/** Stamp a list with `X-Server-Time`, the `since` of the next poll. */
+export function setServerTime(res: Response, now = new Date()) {
+  res.set("X-Server-Time", now.toISOString());
+}
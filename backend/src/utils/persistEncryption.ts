@@ -0,0 +1,125 @@
+import crypto from "crypto";
+import { config } from "../config.js";
+import type { Sinf } from "../types/index.js";
+
+// Node's crypto has no XChaCha20; ChaCha20-Poly1305 with a random 96-bit
+// nonce per record is safe at the few thousand records tasks.json holds
+const ALGORITHM = "chacha20-poly1305";
+const KEY_BYTES = 32;
+const NONCE_BYTES = 12;
+const TAG_BYTES = 16;
+
+/** Download ticket fields that must not sit in tasks.json in plaintext. */
+export interface TaskSecrets {
+  downloadURL: string;
+  fallbackURLs?: string[];
+  sinfs: Sinf[];
+  iTunesMetadata?: string;
+  headers?: Record<string, string>;
+}
+
+/** Encrypted `TaskSecrets` as persisted (all fields base64). */
+export interface SealedSecrets {
+  v: 1;
+  nonce: string;
+  data: string;
+  tag: string;
+}
+
+let cachedKeys: { source: string[]; keys: Buffer[] } | null = null;
+
+/**
+ * Decode PERSIST_ENCRYPTION_KEY entries. The first key encrypts, all of
+ * them are tried when decrypting so old keys can be rotated out. Entries
+ * that aren't 32 bytes of base64 are ignored with a warning.
+ */
+export function getEncryptionKeys(): Buffer[] {
+  const source = config.persistEncryptionKeys;
+  if (cachedKeys?.source === source) return cachedKeys.keys;
+
+  const keys: Buffer[] = [];
+  source.forEach((value, index) => {
+    const key = Buffer.from(value, "base64");
+    if (key.length === KEY_BYTES) {
+      keys.push(key);
+    } else {
+      console.warn(
+        `PERSIST_ENCRYPTION_KEY entry ${index + 1} is not 32 bytes of base64; ignoring it`,
+      );
+    }
+  });
+  cachedKeys = { source, keys };
+  return keys;
+}
+
+/** Encrypt a task's secrets, bound to its id so records can't be swapped. */
+export function sealSecrets(
+  taskId: string,
+  secrets: TaskSecrets,
+  key: Buffer,
+): SealedSecrets {
+  const nonce = crypto.randomBytes(NONCE_BYTES);
+  const cipher = crypto.createCipheriv(ALGORITHM, key, nonce, {
+    authTagLength: TAG_BYTES,
+  });
+  cipher.setAAD(Buffer.from(taskId));
+  const data = Buffer.concat([
+    cipher.update(JSON.stringify(secrets), "utf-8"),
+    cipher.final(),
+  ]);
+  return {
+    v: 1,
+    nonce: nonce.toString("base64"),
+    data: data.toString("base64"),
+    tag: cipher.getAuthTag().toString("base64"),
+  };
+}
+
+/**
+ * Decrypt secrets with the first key that authenticates them. Returns null
+ * when no key matches or the record is malformed.
+ */
+export function openSecrets(
+  taskId: string,
+  sealed: unknown,
+  keys: Buffer[],
+): TaskSecrets | null {
+  if (!sealed || typeof sealed !== "object") return null;
+  const { v, nonce, data, tag } = sealed as Record<string, unknown>;
+  if (
+    v !== 1 ||
+    typeof nonce !== "string" ||
+    typeof data !== "string" ||
+    typeof tag !== "string"
+  ) {
+    return null;
+  }
+
+  for (const key of keys) {
+    try {
+      const decipher = crypto.createDecipheriv(
+        ALGORITHM,
+        key,
+        Buffer.from(nonce, "base64"),
+        { authTagLength: TAG_BYTES },
+      );
+      decipher.setAAD(Buffer.from(taskId));
+      decipher.setAuthTag(Buffer.from(tag, "base64"));
+      const plain = Buffer.concat([
+        decipher.update(Buffer.from(data, "base64")),
+        decipher.final(),
+      ]).toString("utf-8");
+      const secrets = JSON.parse(plain);
+      if (
+        typeof secrets?.downloadURL !== "string" ||
+        !Array.isArray(secrets.sinfs)
+      ) {
+        return null;
+      }
+      return secrets as TaskSecrets;
+    } catch {
+      // Wrong key (auth failure) or corrupt record; try the next key
+    }
+  }
+  return null;
+}
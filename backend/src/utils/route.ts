@@ -1,34 +1,320 @@
 import { Request, Response } from "express";
+import fs from "fs";
+import { config, MAX_DOWNLOAD_HEADERS, MAX_SINFS } from "../config.js";
+import { accountHashesEqual, isValidAccountHash } from "./accountHash.js";
+import { resolveSession } from "../services/sessions.js";
+import type { CircuitBreaker } from "../services/circuitBreaker.js";
+import type { ApiError } from "../types/api.js";
 
-const MIN_ACCOUNT_HASH_LENGTH = 8;
+/** Send an ApiError body; every JSON error response goes through here. */
+export function sendError(
+  res: Response,
+  status: number,
+  error: string,
+  code?: string,
+): void {
+  const body: ApiError = code ? { error, code } : { error };
+  res.status(status).json(body);
+}
 
 export function getIdParam(req: Request): string {
   const id = req.params.id;
   return Array.isArray(id) ? id[0] : id;
 }
 
-export function requireAccountHash(req: Request, res: Response): string | null {
-  const hash =
-    (req.query.accountHash as string) || (req.body && req.body.accountHash);
+/** Account hash from `X-Account-Hash` or `Authorization: Bearer <hash>`. */
+function accountHashFromHeaders(req: Request): string | undefined {
+  const header = req.headers["x-account-hash"];
+  if (typeof header === "string" && header) return header;
+
+  const auth = req.headers["authorization"];
+  if (typeof auth === "string" && auth.startsWith("Bearer ")) {
+    return auth.slice("Bearer ".length).trim() || undefined;
+  }
+  return undefined;
+}
+
+/** Token from `Authorization: Session <token>`, if that scheme is used. */
+export function sessionToken(req: Request): string | undefined {
+  const auth = req.headers["authorization"];
+  if (typeof auth === "string" && auth.startsWith("Session ")) {
+    return auth.slice("Session ".length).trim() || undefined;
+  }
+  return undefined;
+}
+
+/**
+ * Account hash of the request's session (POST /api/session), undefined
+ * without one. Sends 401 and returns null for an unknown or expired token.
+ */
+export function sessionAccountHash(
+  req: Request,
+  res: Response,
+): string | null | undefined {
+  const token = sessionToken(req);
+  if (token === undefined) return undefined;
+  const hash = resolveSession(token);
+  if (!hash) {
+    sendError(res, 401, "Session expired or revoked", "INVALID_SESSION");
+    return null;
+  }
+  return hash;
+}
+
+/**
+ * Answer 400 when STRICT_ACCOUNT_HEADER is on and the request carries
+ * account hashes in its query string; returns true when it did.
+ */
+function rejectQueryAccountHash(
+  req: Request,
+  res: Response,
+  param: "accountHash" | "accountHashes",
+): boolean {
+  if (!config.strictAccountHeader || req.query[param] === undefined) {
+    return false;
+  }
+  const header =
+    param === "accountHash" ? "X-Account-Hash" : "X-Account-Hashes";
+  sendError(
+    res,
+    400,
+    `?${param}= is no longer accepted; send the ${header} header instead`,
+    "ACCOUNT_HASH_IN_QUERY",
+  );
+  return true;
+}
+
+export interface AccountHashOptions {
+  // Accept ?accountHash= even with STRICT_ACCOUNT_HEADER, for URLs opened
+  // as plain links (package file downloads) that cannot send headers
+  allowQuery?: boolean;
+}
+
+// A session or header is preferred over the query param so the hash stays
+// out of access logs and browser history; query/body remain for older
+// clients unless STRICT_ACCOUNT_HEADER rejects the query param.
+export function requireAccountHash(
+  req: Request,
+  res: Response,
+  options: AccountHashOptions = {},
+): string | null {
+  const session = sessionAccountHash(req, res);
+  if (session === null) return null;
+  const fromHeaders = session || accountHashFromHeaders(req);
   if (
-    !hash ||
-    typeof hash !== "string" ||
-    hash.length < MIN_ACCOUNT_HASH_LENGTH
+    !fromHeaders &&
+    !options.allowQuery &&
+    rejectQueryAccountHash(req, res, "accountHash")
   ) {
-    res.status(400).json({ error: "Missing or invalid accountHash parameter" });
+    return null;
+  }
+  const hash =
+    fromHeaders ||
+    (req.query.accountHash as string) ||
+    (req.body && req.body.accountHash);
+  if (!hash) {
+    sendError(
+      res,
+      400,
+      "Missing or invalid accountHash parameter",
+      "MISSING_ACCOUNT_HASH",
+    );
+    return null;
+  }
+  if (!isValidAccountHash(hash)) {
+    sendError(
+      res,
+      400,
+      "Missing or invalid accountHash parameter",
+      "INVALID_ACCOUNT_HASH",
+    );
     return null;
   }
   return hash;
 }
 
+export const INVALID_SINFS = `sinfs must be an array of at most ${MAX_SINFS} { id, sinf } entries`;
+
+// Request headers a client may attach to authorize the CDN fetch. Anything
+// that changes transport semantics (Host, Range, encodings) stays ours.
+const DOWNLOAD_HEADER_NAMES = new Set(["authorization", "cookie"]);
+const DOWNLOAD_HEADER_PREFIX = "x-apple-";
+
+export const INVALID_DOWNLOAD_HEADERS = `headers must map at most ${MAX_DOWNLOAD_HEADERS} allowed names (Authorization, Cookie, X-Apple-*) to strings`;
+
+/** Shape and allowlist check for client-supplied download headers. */
+export function isValidDownloadHeaders(
+  value: unknown,
+): value is Record<string, string> {
+  if (typeof value !== "object" || value === null || Array.isArray(value)) {
+    return false;
+  }
+  const entries = Object.entries(value);
+  return (
+    entries.length <= MAX_DOWNLOAD_HEADERS &&
+    entries.every(([name, v]) => {
+      const lower = name.toLowerCase();
+      return (
+        (DOWNLOAD_HEADER_NAMES.has(lower) ||
+          (lower.startsWith(DOWNLOAD_HEADER_PREFIX) &&
+            /^[a-z0-9-]+$/.test(lower))) &&
+        typeof v === "string" &&
+        v.length <= 8192 &&
+        !/[\r\n\0]/.test(v)
+      );
+    })
+  );
+}
+
+/** Shape check for client-supplied sinfs (create, refresh, reinject). */
+export function isValidSinfs(value: unknown): boolean {
+  return (
+    Array.isArray(value) &&
+    value.length <= MAX_SINFS &&
+    value.every(
+      (s) =>
+        typeof s === "object" &&
+        s !== null &&
+        typeof s.id === "number" &&
+        typeof s.sinf === "string",
+    )
+  );
+}
+
+type SharableTask = { accountHash: string; sharedWith?: string[] };
+
+/** True when any of the hashes owns the task or has been granted access. */
+export function canAccessTask(
+  task: SharableTask,
+  hashes: Set<string>,
+): boolean {
+  const allowed = [task.accountHash, ...(task.sharedWith ?? [])];
+  for (const hash of hashes) {
+    if (allowed.some((candidate) => accountHashesEqual(candidate, hash))) {
+      return true;
+    }
+  }
+  return false;
+}
+
+// Owner or shared account; use verifyTaskOwner for owner-only actions
 export function verifyTaskOwnership(
+  task: SharableTask,
+  accountHash: string,
+  res: Response,
+): boolean {
+  if (!canAccessTask(task, new Set([accountHash]))) {
+    sendError(res, 403, "Access denied");
+    return false;
+  }
+  return true;
+}
+
+export function verifyTaskOwner(
   task: { accountHash: string },
   accountHash: string,
   res: Response,
 ): boolean {
-  if (task.accountHash !== accountHash) {
-    res.status(403).json({ error: "Access denied" });
+  if (!accountHashesEqual(task.accountHash, accountHash)) {
+    sendError(res, 403, "Access denied");
     return false;
   }
   return true;
 }
+
+/**
+ * Parse the `X-Account-Hashes` header, or else the `accountHashes` query
+ * param (comma-separated, possibly repeated), into a set of valid,
+ * distinct hashes, plus the session's or X-Account-Hash account; empty
+ * when absent. Sends 400 and returns null when more than
+ * MAX_ACCOUNT_HASHES remain, since every list request scans all tasks
+ * once per hash.
+ */
+export function parseAccountHashes(
+  req: Request,
+  res: Response,
+): Set<string> | null {
+  const session = sessionAccountHash(req, res);
+  if (session === null) return null;
+  const single = session || accountHashFromHeaders(req);
+  const header = req.headers["x-account-hashes"];
+  let values: unknown[];
+  if (header !== undefined || single) {
+    // The header wins over the query param when both are sent
+    values = Array.isArray(header) ? header : [header];
+  } else {
+    if (rejectQueryAccountHash(req, res, "accountHashes")) return null;
+    const param = req.query.accountHashes;
+    values = Array.isArray(param) ? param : [param];
+  }
+  const hashes = new Set<string>(
+    single && isValidAccountHash(single) ? [single] : [],
+  );
+  for (const value of values) {
+    if (typeof value !== "string") continue;
+    for (const hash of value.split(",")) {
+      if (isValidAccountHash(hash)) hashes.add(hash);
+    }
+  }
+  if (hashes.size > config.maxAccountHashes) {
+    sendError(
+      res,
+      400,
+      `At most ${config.maxAccountHashes} accountHashes may be given`,
+      "TOO_MANY_ACCOUNT_HASHES",
+    );
+    return null;
+  }
+  return hashes;
+}
+
+/**
+ * Serve a package file with Content-Type, Content-Length and ETag headers.
+ * GET and HEAD share this path so both report the same size; HEAD gets no body.
+ * The file is read in FILE_READ_BUFFER_KB chunks.
+ */
+export function sendPackageFile(
+  req: Request,
+  res: Response,
+  filePath: string,
+): void {
+  const stats = fs.statSync(filePath);
+  res.setHeader("Content-Type", "application/octet-stream");
+  res.setHeader("Content-Length", stats.size);
+  res.setHeader(
+    "ETag",
+    `"${stats.size.toString(16)}-${Math.floor(stats.mtimeMs).toString(16)}"`,
+  );
+
+  if (req.method === "HEAD") {
+    res.end();
+    return;
+  }
+
+  const stream = fs.createReadStream(filePath, {
+    highWaterMark: config.fileReadBufferKB * 1024,
+  });
+  stream.pipe(res);
+}
+
+/**
+ * Answer 503 with Retry-After when the upstream's circuit is open.
+ * Returns true when the request was rejected.
+ */
+export function rejectIfCircuitOpen(
+  breaker: CircuitBreaker,
+  res: Response,
+): boolean {
+  if (breaker.tryAcquire()) return false;
+  res.setHeader(
+    "Retry-After",
+    Math.max(1, Math.ceil(breaker.retryAfterMs / 1000)),
+  );
+  sendError(
+    res,
+    503,
+    "Upstream temporarily unavailable",
+    "UPSTREAM_UNAVAILABLE",
+  );
+  return true;
+}
@@ -0,0 +1,46 @@
+/**
+ * Bounds how many callers run at once; the rest wait in FIFO order. The
+ * limit is read on every acquire/release (0 = unlimited), so a reloaded
+ * setting applies to the next waiter without replacing the semaphore.
+ */
+export class Semaphore {
+  private active = 0;
+  private readonly waiters: (() => void)[] = [];
+
+  constructor(private readonly limit: () => number) {}
+
+  /** True when `run` would have to wait. */
+  get full(): boolean {
+    const limit = this.limit();
+    return limit > 0 && this.active >= limit;
+  }
+
+  get waiting(): number {
+    return this.waiters.length;
+  }
+
+  async run<T>(fn: () => Promise<T>): Promise<T> {
+    if (this.full || this.waiters.length > 0) {
+      const turn = new Promise<void>((resolve) => this.waiters.push(resolve));
+      // A raised limit may already have room for the queue
+      this.release();
+      await turn;
+    } else {
+      this.active++;
+    }
+    try {
+      return await fn();
+    } finally {
+      this.active--;
+      this.release();
+    }
+  }
+
+  // Hand free slots to waiters; each woken waiter already holds its slot
+  private release() {
+    while (this.waiters.length > 0 && !this.full) {
+      this.active++;
+      this.waiters.shift()!();
+    }
+  }
+}
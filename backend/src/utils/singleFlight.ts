@@ -0,0 +1,23 @@
+/**
+ * Collapse concurrent calls with the same key into one in-flight promise.
+ * Every caller gets the same result or error; the key is released as soon
+ * as the call settles, so a failure never sticks to later requests.
+ */
+export class SingleFlight<T> {
+  private readonly inflight = new Map<string, Promise<T>>();
+
+  has(key: string): boolean {
+    return this.inflight.has(key);
+  }
+
+  run(key: string, fn: () => Promise<T>): Promise<T> {
+    const existing = this.inflight.get(key);
+    if (existing) return existing;
+
+    const promise = fn().finally(() => {
+      this.inflight.delete(key);
+    });
+    this.inflight.set(key, promise);
+    return promise;
+  }
+}
@@ -0,0 +1,57 @@
+import { Response } from "express";
+
+// Warn when a client has fallen this many updates behind before catching up
+const LAG_WARN_THRESHOLD = 100;
+
+/**
+ * Create an SSE writer for a client socket that may back up. Up to
+ * `capacity` updates are queued in order while the socket drains; once a
+ * client lags further than that, the queue is dropped and only the newest
+ * state is kept, to be flushed on drain as a full snapshot. With a capacity
+ * of 0 every backed-up update is coalesced this way. The stream is never
+ * closed because a client lags.
+ */
+export function createCoalescingSseWriter<T>(
+  res: Response,
+  serialize: (value: T) => unknown,
+  capacity = 0,
+): (value: T) => void {
+  // Serialized when queued: the value may be a live object that changes
+  const queued: string[] = [];
+  let latest: { value: T } | null = null;
+  let skipped = 0;
+
+  const frame = (value: T) => `data: ${JSON.stringify(serialize(value))}\n\n`;
+
+  res.on("drain", () => {
+    if (latest) {
+      if (skipped >= LAG_WARN_THRESHOLD) {
+        console.warn(`[SSE] Slow client skipped ${skipped} progress updates`);
+      }
+      const { value } = latest;
+      latest = null;
+      skipped = 0;
+      res.write(frame(value));
+      return;
+    }
+    // Stop early if the socket backs up again; the next drain continues
+    while (queued.length > 0 && !res.writableNeedDrain) {
+      res.write(queued.shift()!);
+    }
+  });
+
+  return (value: T) => {
+    if (!res.writableNeedDrain && queued.length === 0 && !latest) {
+      res.write(frame(value));
+      return;
+    }
+    if (latest || queued.length >= capacity) {
+      // Lagged: resync to the newest snapshot instead of replaying history
+      skipped += queued.length + 1;
+      queued.length = 0;
+      latest = { value };
+      return;
+    }
+    queued.push(frame(value));
+  };
+}
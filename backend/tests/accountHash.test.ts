@@ -0,0 +1,69 @@
+import { describe, it, expect, vi, afterEach } from "vitest";
+import crypto from "crypto";
+import { config } from "../src/config.js";
+import {
+  accountHashesEqual,
+  isValidAccountHash,
+} from "../src/utils/accountHash.js";
+
+const SHA256_HEX =
+  "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+describe("isValidAccountHash", () => {
+  afterEach(() => {
+    config.allowLegacyAccountHashes = false;
+  });
+
+  it("should accept lowercase hex between 32 and 64 characters", () => {
+    expect(isValidAccountHash(SHA256_HEX)).toBe(true);
+    expect(isValidAccountHash(SHA256_HEX.slice(0, 32))).toBe(true);
+  });
+
+  it("should reject uppercase hex", () => {
+    expect(isValidAccountHash(SHA256_HEX.toUpperCase())).toBe(false);
+  });
+
+  it("should reject short, long, non-hex and non-string values", () => {
+    expect(isValidAccountHash("00000000")).toBe(false);
+    expect(isValidAccountHash(SHA256_HEX.slice(0, 31))).toBe(false);
+    expect(isValidAccountHash(SHA256_HEX + "0")).toBe(false);
+    expect(isValidAccountHash("g".repeat(64))).toBe(false);
+    expect(isValidAccountHash(undefined)).toBe(false);
+    expect(isValidAccountHash(12345678)).toBe(false);
+  });
+
+  it("should accept older hash formats only in legacy mode", () => {
+    expect(isValidAccountHash("abcdef1234567890")).toBe(false);
+    config.allowLegacyAccountHashes = true;
+    expect(isValidAccountHash("abcdef1234567890")).toBe(true);
+    expect(isValidAccountHash("short")).toBe(false);
+    expect(isValidAccountHash("../../../etc")).toBe(false);
+  });
+});
+
+describe("accountHashesEqual", () => {
+  afterEach(() => {
+    vi.restoreAllMocks();
+  });
+
+  it("should compare case-sensitively", () => {
+    expect(accountHashesEqual(SHA256_HEX, SHA256_HEX)).toBe(true);
+    expect(accountHashesEqual(SHA256_HEX, SHA256_HEX.toUpperCase())).toBe(
+      false,
+    );
+  });
+
+  it("should use a timing-safe comparison for equal-length hashes", () => {
+    const spy = vi.spyOn(crypto, "timingSafeEqual");
+    accountHashesEqual(SHA256_HEX, SHA256_HEX.replace(/.$/, "0"));
+    expect(spy).toHaveBeenCalledTimes(1);
+  });
+
+  it("should reject different lengths without comparing contents", () => {
+    const spy = vi.spyOn(crypto, "timingSafeEqual");
+    expect(accountHashesEqual(SHA256_HEX, SHA256_HEX.slice(0, 32))).toBe(
+      false,
+    );
+    expect(spy).not.toHaveBeenCalled();
+  });
+});
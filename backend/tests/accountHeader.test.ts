@@ -0,0 +1,99 @@
+import { describe, it, expect, beforeEach, afterEach } from "vitest";
+import express from "express";
+import request from "supertest";
+import http from "http";
+import type { AddressInfo } from "net";
+import { config } from "../src/config.js";
+import downloadRoutes from "../src/routes/downloads.js";
+import packageRoutes from "../src/routes/packages.js";
+import { deleteTask, setTaskStore } from "../src/services/downloadManager.js";
+import { MemoryTaskStore } from "../src/services/taskStore.js";
+import { completedTask } from "./helpers/tasks.js";
+
+const ACCOUNT_HASH = "abcdef1234567890abcdef1234567890";
+const OTHER_HASH = "0987654321fedcba0987654321fedcba";
+
+describe("account hashes in headers", () => {
+  const app = express();
+  app.use(express.json());
+  app.use("/api", packageRoutes);
+  app.use("/api", downloadRoutes);
+
+  beforeEach(() => {
+    setTaskStore(new MemoryTaskStore([completedTask("header-pkg", {}, "ipa")]));
+  });
+
+  afterEach(() => {
+    config.strictAccountHeader = false;
+    deleteTask("header-pkg");
+  });
+
+  it("should list by X-Account-Hashes and prefer it over the query", async () => {
+    const listed = await request(app)
+      .get("/api/packages")
+      .set("X-Account-Hashes", `${OTHER_HASH},${ACCOUNT_HASH}`);
+    expect(listed.body).toHaveLength(1);
+
+    const preferred = await request(app)
+      .get(`/api/packages?accountHashes=${ACCOUNT_HASH}`)
+      .set("X-Account-Hashes", OTHER_HASH);
+    expect(preferred.body).toEqual([]);
+
+    const single = await request(app)
+      .get("/api/packages")
+      .set("X-Account-Hash", ACCOUNT_HASH);
+    expect(single.body).toHaveLength(1);
+  });
+
+  it("should refuse query hashes in strict mode", async () => {
+    config.strictAccountHeader = true;
+
+    const listed = await request(app).get(
+      `/api/packages?accountHashes=${ACCOUNT_HASH}`,
+    );
+    expect(listed.status).toBe(400);
+    expect(listed.body.code).toBe("ACCOUNT_HASH_IN_QUERY");
+    const contents = await request(app).get(
+      `/api/packages/header-pkg/contents?accountHash=${ACCOUNT_HASH}`,
+    );
+    expect(contents.status).toBe(400);
+    expect(contents.body.code).toBe("ACCOUNT_HASH_IN_QUERY");
+
+    const byHeader = await request(app)
+      .get(`/api/packages?accountHashes=${OTHER_HASH}`)
+      .set("X-Account-Hashes", ACCOUNT_HASH);
+    expect(byHeader.body).toHaveLength(1);
+  });
+
+  it("should keep package file links working in strict mode", async () => {
+    config.strictAccountHeader = true;
+    const res = await request(app).get(
+      `/api/packages/header-pkg/file?accountHash=${ACCOUNT_HASH}`,
+    );
+    expect(res.status).toBe(200);
+  });
+
+  it("should keep the progress stream working in strict mode", async () => {
+    config.strictAccountHeader = true;
+    // EventSource sends no custom headers and the stream never ends, so
+    // read just the response head
+    const server = app.listen(0);
+    try {
+      const { port } = server.address() as AddressInfo;
+      const res = await new Promise<http.IncomingMessage>((resolve, reject) =>
+        http
+          .get(
+            `http://127.0.0.1:${port}/api/downloads/header-pkg/progress?accountHash=${ACCOUNT_HASH}`,
+            resolve,
+          )
+          .on("error", reject),
+      );
+      res.destroy();
+      expect(res.statusCode).toBe(200);
+      expect(res.headers["content-type"]).toBe("text/event-stream");
+    } finally {
+      server.closeAllConnections();
+      await new Promise((resolve) => server.close(resolve));
+    }
+  });
+});
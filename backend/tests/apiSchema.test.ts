@@ -0,0 +1,166 @@
+import { describe, it, expect, beforeAll, afterAll } from "vitest";
+import fs from "fs";
+import path from "path";
+import express from "express";
+import request from "supertest";
+import { config } from "../src/config.js";
+import downloadRoutes from "../src/routes/downloads.js";
+import packageRoutes from "../src/routes/packages.js";
+import settingsRoutes from "../src/routes/settings.js";
+import {
+  sanitizeTaskForResponse,
+  setTaskStore,
+} from "../src/services/downloadManager.js";
+import { MemoryTaskStore } from "../src/services/taskStore.js";
+import type { DownloadTask, Software } from "../src/types/index.js";
+
+// Pins the JSON field names clients depend on. A failure here means a wire
+// format changed: update src/types/api.ts and the frontend types with it.
+
+const ACCOUNT = "abcdef1234567890abcdef1234567890";
+const dir = path.resolve(config.dataDir, "packages", ACCOUNT, "schema");
+const ipaPath = path.join(dir, "app.ipa");
+const software = {
+  id: 1,
+  bundleID: "com.example.schema",
+  name: "Schema",
+  version: "1.0",
+} as Software;
+
+// JSON as a client receives it: undefined fields dropped
+const wire = (value: unknown) => JSON.parse(JSON.stringify(value));
+
+function createApp() {
+  const app = express();
+  app.use(express.json());
+  app.use("/api", settingsRoutes);
+  app.use("/api", downloadRoutes);
+  app.use("/api", packageRoutes);
+  return app;
+}
+
+describe("API wire format", () => {
+  const app = createApp();
+
+  beforeAll(() => {
+    fs.mkdirSync(dir, { recursive: true });
+    fs.writeFileSync(ipaPath, "ipa");
+    setTaskStore(
+      new MemoryTaskStore([
+        {
+          id: "schema-package",
+          software,
+          accountHash: ACCOUNT,
+          downloadURL: "",
+          sinfs: [],
+          status: "completed",
+          progress: 100,
+          speed: "0 B/s",
+          filePath: ipaPath,
+          fileSize: 3,
+          createdAt: "2026-01-01T00:00:00.000Z",
+        },
+      ]),
+    );
+  });
+
+  afterAll(() => {
+    fs.rmSync(dir, { recursive: true, force: true });
+    setTaskStore(new MemoryTaskStore());
+  });
+
+  it("should serialize a task without its secrets", () => {
+    const task: DownloadTask = {
+      id: "schema-task",
+      software,
+      accountHash: ACCOUNT,
+      sharedWith: ["fedcba0987654321fedcba0987654321"],
+      downloadURL: "https://example.apple.com/app.ipa",
+      sinfs: [{ id: 0, sinf: "c2luZg==" }],
+      iTunesMetadata: "bWV0YWRhdGE=",
+      downloadHeaders: { Cookie: "secret" },
+      status: "failed",
+      priority: "high",
+      progress: 40,
+      speed: "0 B/s",
+      error: "Network error",
+      errorCode: "network",
+      errorDetail: "ECONNRESET",
+      createdAt: "2026-01-01T00:00:00.000Z",
+    };
+    expect(wire(sanitizeTaskForResponse(task, { owner: true }))).toEqual({
+      id: "schema-task",
+      software: wire(software),
+      accountHash: ACCOUNT,
+      status: "failed",
+      priority: "high",
+      progress: 40,
+      speed: "0 B/s",
+      error: "Network error",
+      errorCategory: "NETWORK",
+      errorCode: "network",
+      errorDetail: "ECONNRESET",
+      createdAt: "2026-01-01T00:00:00.000Z",
+      hasFile: false,
+    });
+  });
+
+  it("should serialize package listings", async () => {
+    const res = await request(app)
+      .get("/api/packages")
+      .query({ accountHashes: ACCOUNT });
+    expect(res.body).toEqual([
+      {
+        id: "schema-package",
+        software: wire(software),
+        accountHash: ACCOUNT,
+        relativePath: `${ACCOUNT}/schema/app.ipa`,
+        fileSize: 3,
+        createdAt: "2026-01-01T00:00:00.000Z",
+      },
+    ]);
+  });
+
+  it("should serialize errors as error and code", async () => {
+    const res = await request(app).get("/api/downloads/schema-package");
+    expect(res.status).toBe(400);
+    expect(res.body).toEqual({
+      error: "Missing or invalid accountHash parameter",
+      code: "MISSING_ACCOUNT_HASH",
+    });
+  });
+
+  it("should keep the settings field names", async () => {
+    const res = await request(app).get("/api/settings");
+    expect(Object.keys(res.body).sort()).toEqual([
+      "autoCleanupDays",
+      "autoCleanupMaxMB",
+      "autoCleanupMaxPerAccount",
+      "buildCommit",
+      "buildDate",
+      "dataDir",
+      "defaultStorefront",
+      "disableHttpsRedirect",
+      "limits",
+      "packageRoots",
+      "port",
+      "progressPollIntervalMs",
+      "publicBaseUrl",
+      "readOnly",
+      "stalledDownloads",
+      "storage",
+      "upstreams",
+      "uptime",
+      "version",
+    ]);
+    expect(Object.keys(res.body.upstreams.itunes).sort()).toEqual([
+      "consecutiveFailures",
+      "retryAfterMs",
+      "state",
+    ]);
+    expect(res.body.stalledDownloads).toEqual({
+      recovered: expect.any(Number),
+      failed: expect.any(Number),
+    });
+  });
+});
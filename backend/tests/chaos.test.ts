@@ -0,0 +1,84 @@
+import { describe, it, expect, vi, beforeEach, afterEach } from "vitest";
+import express from "express";
+import request from "supertest";
+import { config } from "../src/config.js";
+import devRoutes from "../src/routes/dev.js";
+import settingsRoutes from "../src/routes/settings.js";
+import { resetChaosFaults } from "../src/services/chaos.js";
+import {
+  deleteTask,
+  getTask,
+  setTaskStore,
+} from "../src/services/downloadManager.js";
+import { MemoryTaskStore } from "../src/services/taskStore.js";
+import { downloadsViaFetch } from "./helpers/downloadNetwork.js";
+import { startDownload } from "./helpers/tasks.js";
+
+describe("chaos faults", () => {
+  const app = express();
+  app.use(express.json());
+  app.use("/api", devRoutes);
+  app.use("/api", settingsRoutes);
+  let id: string | undefined;
+
+  function arm(faults: Record<string, unknown>) {
+    return request(app).post("/api/dev/chaos").send(faults);
+  }
+
+  beforeEach(() => {
+    config.chaosEnabled = true;
+    setTaskStore(new MemoryTaskStore());
+    downloadsViaFetch();
+    vi.spyOn(globalThis, "fetch").mockImplementation(
+      async () =>
+        new Response("x".repeat(100), {
+          headers: { "content-length": "100" },
+        }),
+    );
+  });
+
+  afterEach(() => {
+    config.chaosEnabled = false;
+    resetChaosFaults();
+    if (id) deleteTask(id);
+    vi.restoreAllMocks();
+  });
+
+  it("should validate and report armed faults", async () => {
+    expect((await arm({ failDownloadAtPercent: 150 })).status).toBe(400);
+    expect((await arm({ failRoute: "/settings" })).status).toBe(400);
+
+    const armed = await arm({ sseDelayMs: 200, failInjection: true });
+    expect(armed.body).toEqual({ sseDelayMs: 200, failInjection: true });
+    const disarmed = await arm({ failInjection: null });
+    expect(disarmed.body).toEqual({ sseDelayMs: 200 });
+  });
+
+  it("should fail the next download at the armed percentage", async () => {
+    await arm({ failDownloadAtPercent: 50 });
+    id = startDownload("com.example.chaos");
+    await vi.waitFor(() => expect(getTask(id!)?.status).toBe("failed"));
+    expect(getTask(id)?.errorCode).toBe("network");
+    expect(getTask(id)?.errorDetail).toMatch(/Chaos/);
+
+    // Fired once: the retry goes through
+    deleteTask(id);
+    id = startDownload("com.example.chaos");
+    await vi.waitFor(() => expect(getTask(id!)?.status).toBe("completed"));
+  });
+
+  it("should fail the next sinf injection", async () => {
+    await arm({ failInjection: true });
+    id = startDownload("com.example.chaos", [{ id: 0, sinf: "AAAA" }]);
+    await vi.waitFor(() => expect(getTask(id!)?.status).toBe("failed"));
+    expect(getTask(id)?.errorCode).toBe("injection_failed");
+  });
+
+  it("should answer the chosen route with one 500", async () => {
+    await arm({ failRoute: "GET /settings" });
+    const failed = await request(app).get("/api/settings");
+    expect(failed.status).toBe(500);
+    expect(failed.body.code).toBe("CHAOS");
+    expect((await request(app).get("/api/settings")).status).toBe(200);
+  });
+});
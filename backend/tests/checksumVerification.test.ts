@@ -0,0 +1,88 @@
+import { describe, it, expect, vi, beforeEach, afterEach } from "vitest";
+import crypto from "crypto";
+import express from "express";
+import request from "supertest";
+import downloadRoutes from "../src/routes/downloads.js";
+import {
+  createTask,
+  deleteTask,
+  getTask,
+  setTaskStore,
+} from "../src/services/downloadManager.js";
+import { MemoryTaskStore } from "../src/services/taskStore.js";
+import type { ExpectedDigests, Software } from "../src/types/index.js";
+import { downloadsViaFetch } from "./helpers/downloadNetwork.js";
+
+const ACCOUNT = "abcdef1234567890abcdef1234567890";
+const BODY = "upstream package bytes";
+const software = {
+  id: 1,
+  bundleID: "com.example.checksum",
+  name: "Checksum",
+  version: "1.0",
+} as Software;
+
+function digest(algorithm: string, data: string) {
+  return crypto.createHash(algorithm).update(data).digest("hex");
+}
+
+function download(expectedDigests?: ExpectedDigests) {
+  return createTask({
+    software,
+    accountHash: ACCOUNT,
+    downloadURL: "https://example.apple.com/app.ipa",
+    sinfs: [],
+    expectedDigests,
+  }).id;
+}
+
+describe("checksum verification", () => {
+  const ids: string[] = [];
+
+  beforeEach(() => {
+    setTaskStore(new MemoryTaskStore());
+    downloadsViaFetch();
+    vi.spyOn(globalThis, "fetch").mockImplementation(
+      async () => new Response(BODY),
+    );
+  });
+
+  afterEach(() => {
+    for (const id of ids.splice(0)) deleteTask(id);
+    vi.restoreAllMocks();
+  });
+
+  it("should complete when the supplied digests match", async () => {
+    const id = download({
+      md5: digest("md5", BODY),
+      sha1: digest("sha1", BODY),
+    });
+    ids.push(id);
+    await vi.waitFor(() => expect(getTask(id)?.status).toBe("completed"));
+  });
+
+  it("should fail with checksum_mismatch when a digest differs", async () => {
+    const id = download({ sha1: digest("sha1", "something else") });
+    ids.push(id);
+    await vi.waitFor(() => expect(getTask(id)?.status).toBe("failed"));
+    expect(getTask(id)?.errorCode).toBe("checksum_mismatch");
+    expect(getTask(id)?.errorDetail).toBe("SHA1 mismatch");
+  });
+
+  it("should reject malformed digests on create", async () => {
+    const app = express();
+    app.use(express.json());
+    app.use("/api", downloadRoutes);
+    const res = await request(app)
+      .post("/api/downloads")
+      .send({
+        software,
+        accountHash: ACCOUNT,
+        downloadURL: "https://example.apple.com/app.ipa",
+        sinfs: [],
+        expectedMd5: "not-a-digest",
+      });
+    expect(res.status).toBe(400);
+    expect(res.body.error).toMatch(/expectedMd5/);
+  });
+});
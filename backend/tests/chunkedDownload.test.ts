@@ -0,0 +1,212 @@
+import { describe, it, expect, beforeAll, afterAll } from "vitest";
+import { createServer, Server } from "http";
+import net from "net";
+import fs from "fs";
+import path from "path";
+import os from "os";
+import crypto from "crypto";
+import zlib from "zlib";
+import {
+  ContentEncodingError,
+  downloadInChunks,
+  DownloadSizeError,
+  fetchIdentity,
+  probeRangeSupport,
+} from "../src/services/chunkedDownload.js";
+
+const TEMP_DIR = path.join(os.tmpdir(), "chunked-download-test");
+const BODY = crypto.randomBytes(1024 * 1024 + 123);
+
+let server: Server;
+let baseUrl: string;
+let flakyFailures = 0;
+let lastAcceptEncoding: string | undefined;
+const cookies = new Set<string | undefined>();
+
+beforeAll(async () => {
+  fs.mkdirSync(TEMP_DIR, { recursive: true });
+  server = createServer((req, res) => {
+    lastAcceptEncoding = req.headers["accept-encoding"];
+    cookies.add(req.headers["cookie"]);
+    // Misbehaving upstream that compresses regardless of Accept-Encoding
+    if (req.url === "/gzip") {
+      const gzipped = zlib.gzipSync(BODY);
+      res.writeHead(req.headers.range ? 206 : 200, {
+        "Content-Encoding": "gzip",
+        "Content-Length": gzipped.length,
+        "Content-Range": `bytes 0-${BODY.length - 1}/${BODY.length}`,
+      });
+      res.end(gzipped);
+      return;
+    }
+    if (req.url === "/no-ranges") {
+      res.writeHead(200, { "Content-Length": BODY.length });
+      res.end(BODY);
+      return;
+    }
+    const match = /bytes=(\d+)-(\d+)/.exec(req.headers.range || "");
+    if (!match) {
+      res.writeHead(200, { "Content-Length": BODY.length });
+      res.end(BODY);
+      return;
+    }
+    const start = parseInt(match[1], 10);
+    const end = Math.min(parseInt(match[2], 10), BODY.length - 1);
+    const slice = BODY.subarray(start, end + 1);
+    res.writeHead(206, {
+      "Content-Length": slice.length,
+      "Content-Range": `bytes ${start}-${end}/${BODY.length}`,
+    });
+    // Cut the first response of a chunk short to exercise per-chunk retry
+    if (req.url === "/flaky" && start > 0 && flakyFailures < 1) {
+      flakyFailures++;
+      res.write(slice.subarray(0, 100));
+      res.destroy();
+      return;
+    }
+    res.end(slice);
+  });
+  await new Promise<void>((resolve) => server.listen(0, resolve));
+  baseUrl = `http://127.0.0.1:${(server.address() as net.AddressInfo).port}`;
+});
+
+afterAll(async () => {
+  await new Promise<void>((resolve) => server.close(() => resolve()));
+  fs.rmSync(TEMP_DIR, { recursive: true, force: true });
+});
+
+describe("chunkedDownload", () => {
+  it("reports total size when ranges are supported", async () => {
+    const probe = await probeRangeSupport(
+      `${baseUrl}/file`,
+      new AbortController().signal,
+    );
+    expect(probe).toEqual({
+      status: 206,
+      total: BODY.length,
+      length: null,
+      acceptsRanges: true,
+    });
+  });
+
+  it("reports null total when ranges are not supported", async () => {
+    const probe = await probeRangeSupport(
+      `${baseUrl}/no-ranges`,
+      new AbortController().signal,
+    );
+    expect(probe.total).toBeNull();
+    // The full response still tells the size
+    expect(probe.length).toBe(BODY.length);
+    expect(probe.acceptsRanges).toBe(false);
+  });
+
+  it("reassembles the body from concurrent chunks", async () => {
+    const filePath = path.join(TEMP_DIR, "chunked.ipa");
+    let reported = 0;
+    await downloadInChunks({
+      url: `${baseUrl}/file`,
+      filePath,
+      total: BODY.length,
+      connections: 4,
+      signal: new AbortController().signal,
+      onBytes: (bytes) => {
+        reported += bytes;
+      },
+    });
+    expect(reported).toBe(BODY.length);
+    expect(fs.readFileSync(filePath).equals(BODY)).toBe(true);
+  });
+
+  it("retries a chunk that ends early", async () => {
+    const filePath = path.join(TEMP_DIR, "flaky.ipa");
+    await downloadInChunks({
+      url: `${baseUrl}/flaky`,
+      filePath,
+      total: BODY.length,
+      connections: 3,
+      signal: new AbortController().signal,
+      onBytes: () => {},
+    });
+    expect(flakyFailures).toBe(1);
+    expect(fs.readFileSync(filePath).equals(BODY)).toBe(true);
+  });
+
+  it("sends caller headers on every range request", async () => {
+    const filePath = path.join(TEMP_DIR, "headers.ipa");
+    cookies.clear();
+    await downloadInChunks({
+      url: `${baseUrl}/file`,
+      filePath,
+      total: BODY.length,
+      connections: 3,
+      signal: new AbortController().signal,
+      onBytes: () => {},
+      headers: { Cookie: "session=abc" },
+    });
+    expect([...cookies]).toEqual(["session=abc"]);
+  });
+
+  it("asks the upstream for unencoded bytes", async () => {
+    const response = await fetchIdentity(
+      `${baseUrl}/no-ranges`,
+      new AbortController().signal,
+    );
+    await response.body?.cancel();
+    expect(lastAcceptEncoding).toBe("identity");
+  });
+
+  it("rejects gzip-encoded responses instead of decompressing them", async () => {
+    await expect(
+      fetchIdentity(`${baseUrl}/gzip`, new AbortController().signal),
+    ).rejects.toBeInstanceOf(ContentEncodingError);
+  });
+
+  it("fails a chunked download on an encoded range without retrying", async () => {
+    const filePath = path.join(TEMP_DIR, "gzip.ipa");
+    let reported = 0;
+    let retries = 0;
+    await expect(
+      downloadInChunks({
+        url: `${baseUrl}/gzip`,
+        filePath,
+        total: BODY.length,
+        connections: 2,
+        signal: new AbortController().signal,
+        onBytes: (bytes) => {
+          reported += bytes;
+        },
+        onRetry: () => {
+          retries++;
+        },
+      }),
+    ).rejects.toBeInstanceOf(ContentEncodingError);
+    // No decompressed bytes were ever counted against the expected size
+    expect(reported).toBe(0);
+    expect(retries).toBe(0);
+  });
+
+  it("fails a chunked download past the size limit without retrying", async () => {
+    const filePath = path.join(TEMP_DIR, "oversized.ipa");
+    let reported = 0;
+    let retries = 0;
+    await expect(
+      downloadInChunks({
+        url: `${baseUrl}/file`,
+        filePath,
+        total: BODY.length,
+        connections: 3,
+        signal: new AbortController().signal,
+        onBytes: (bytes) => {
+          reported += bytes;
+          if (reported > 1000) {
+            throw new DownloadSizeError("Download exceeded maximum size");
+          }
+        },
+        onRetry: () => {
+          retries++;
+        },
+      }),
+    ).rejects.toBeInstanceOf(DownloadSizeError);
+    expect(retries).toBe(0);
+  });
+});
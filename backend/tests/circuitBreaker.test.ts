@@ -0,0 +1,67 @@
+import { describe, it, expect } from "vitest";
+import { CircuitBreaker } from "../src/services/circuitBreaker.js";
+
+function createBreaker() {
+  let now = 0;
+  const breaker = new CircuitBreaker(3, 1000, () => now);
+  return {
+    breaker,
+    advance: (ms: number) => {
+      now += ms;
+    },
+  };
+}
+
+describe("CircuitBreaker", () => {
+  it("stays closed below the failure threshold", () => {
+    const { breaker } = createBreaker();
+    breaker.recordFailure();
+    breaker.recordFailure();
+    expect(breaker.state).toBe("closed");
+    expect(breaker.tryAcquire()).toBe(true);
+  });
+
+  it("resets the failure count on success", () => {
+    const { breaker } = createBreaker();
+    breaker.recordFailure();
+    breaker.recordFailure();
+    breaker.recordSuccess();
+    breaker.recordFailure();
+    expect(breaker.state).toBe("closed");
+  });
+
+  it("opens after consecutive failures and fast-fails during cooldown", () => {
+    const { breaker, advance } = createBreaker();
+    for (let i = 0; i < 3; i++) breaker.recordFailure();
+
+    expect(breaker.state).toBe("open");
+    expect(breaker.tryAcquire()).toBe(false);
+    advance(400);
+    expect(breaker.retryAfterMs).toBe(600);
+  });
+
+  it("lets a single probe through after the cooldown", () => {
+    const { breaker, advance } = createBreaker();
+    for (let i = 0; i < 3; i++) breaker.recordFailure();
+    advance(1000);
+
+    expect(breaker.state).toBe("half-open");
+    expect(breaker.tryAcquire()).toBe(true);
+    expect(breaker.tryAcquire()).toBe(false);
+
+    breaker.recordSuccess();
+    expect(breaker.state).toBe("closed");
+    expect(breaker.tryAcquire()).toBe(true);
+  });
+
+  it("re-opens when the probe fails", () => {
+    const { breaker, advance } = createBreaker();
+    for (let i = 0; i < 3; i++) breaker.recordFailure();
+    advance(1000);
+
+    expect(breaker.tryAcquire()).toBe(true);
+    breaker.recordFailure();
+    expect(breaker.state).toBe("open");
+    expect(breaker.retryAfterMs).toBe(1000);
+  });
+});
@@ -1,5 +1,5 @@
 import { describe, it, expect } from "vitest";
-import { config } from "../src/config.js";
+import { config, parseRuntimeSettings } from "../src/config.js";
 
 describe("config", () => {
   it("should have default port 8080", () => {
@@ -9,4 +9,18 @@ describe("config", () => {
   it("should have default data directory", () => {
     expect(config.dataDir).toBe("./data");
   });
+
+  it("should default max download size to 8 GB", () => {
+    expect(config.maxDownloadSize).toBe(8 * 1024 * 1024 * 1024);
+  });
+
+  it("should not take a negative max download size", () => {
+    const { maxDownloadSize } = parseRuntimeSettings({
+      MAX_DOWNLOAD_SIZE_MB: "-5",
+    });
+    expect(maxDownloadSize).toBe(8 * 1024 * 1024 * 1024);
+    expect(
+      parseRuntimeSettings({ MAX_DOWNLOAD_SIZE_MB: "100" }).maxDownloadSize,
+    ).toBe(100 * 1024 * 1024);
+  });
 });
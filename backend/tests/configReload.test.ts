@@ -0,0 +1,126 @@
+import { describe, it, expect, vi, beforeEach, afterEach } from "vitest";
+import fs from "fs";
+import os from "os";
+import path from "path";
+import express from "express";
+import request from "supertest";
+import { config } from "../src/config.js";
+import adminRoutes from "../src/routes/admin.js";
+import {
+  getRuntimeSettings,
+  parseConfigFile,
+  reloadConfig,
+} from "../src/services/configReload.js";
+
+const ADMIN_TOKEN = "test-admin-token";
+const saved = { ...config };
+let dir: string;
+let configFile: string;
+
+function writeConfig(text: string) {
+  fs.writeFileSync(configFile, text);
+}
+
+describe("config reload", () => {
+  beforeEach(() => {
+    dir = fs.mkdtempSync(path.join(os.tmpdir(), "asspp-config-"));
+    configFile = path.join(dir, "asspp.env");
+    config.configFile = configFile;
+  });
+
+  afterEach(() => {
+    Object.assign(config, saved);
+    vi.unstubAllEnvs();
+    fs.rmSync(dir, { recursive: true, force: true });
+  });
+
+  it("should apply CONFIG_FILE as soon as the startup module loads", async () => {
+    writeConfig("AUTO_CLEANUP_MAX_PER_ACCOUNT=3");
+    vi.stubEnv("CONFIG_FILE", configFile);
+    vi.resetModules();
+    await import("../src/services/startupConfig.js");
+    const fresh = await import("../src/config.js");
+    expect(fresh.config.autoCleanupMaxPerAccount).toBe(3);
+  });
+
+  it("should parse KEY=VALUE lines, comments and quotes", () => {
+    const text = '# retention\nAUTO_CLEANUP_DAYS=7\n\nMAX_STREAMS = "64"\n';
+    expect(parseConfigFile(text)).toEqual({
+      AUTO_CLEANUP_DAYS: "7",
+      MAX_STREAMS: "64",
+    });
+    expect(() => parseConfigFile("not a setting")).toThrow(/Line 1/);
+  });
+
+  it("should apply runtime settings from the file", () => {
+    writeConfig(
+      [
+        "AUTO_CLEANUP_DAYS=7",
+        "PAUSE_ON_LOW_SPACE=true",
+        "AUTO_CLEANUP_ACCOUNT_LIMITS=abc=2",
+        "WISP_LOG_LEVEL=streams",
+      ].join("\n"),
+    );
+    const result = reloadConfig();
+
+    expect(result.ok).toBe(true);
+    expect(config.autoCleanupDays).toBe(7);
+    expect(config.pauseOnLowSpace).toBe(true);
+    expect(config.autoCleanupAccountLimits.get("abc")).toBe(2);
+    expect(config.wispLogLevel).toBe("streams");
+    expect(getRuntimeSettings().autoCleanupDays).toBe(7);
+  });
+
+  it("should keep the old settings when the file is invalid", () => {
+    const before = getRuntimeSettings();
+    for (const text of [
+      "AUTO_CLEANUP_DAYS=7\nMAX_STREAMS=lots\n",
+      "PAUSE_ON_LOW_SPACE=yes\n",
+      "WISP_LOG_LEVEL=verbose\n",
+      "AUTO_CLEANUP_DAYS=7\nPORT=9090\n",
+    ]) {
+      writeConfig(text);
+      expect(reloadConfig().ok).toBe(false);
+    }
+    fs.rmSync(configFile);
+    expect(reloadConfig().ok).toBe(false);
+
+    expect(getRuntimeSettings()).toEqual(before);
+    expect(config.port).toBe(saved.port);
+  });
+
+  it("should reload and report settings through the admin API", async () => {
+    const app = express();
+    app.use("/api", adminRoutes);
+    writeConfig("MAX_CONCURRENT_DOWNLOADS=2\n");
+
+    config.adminToken = "";
+    const disabled = await request(app).post("/api/admin/config/reload");
+    expect(disabled.status).toBe(404);
+
+    config.adminToken = ADMIN_TOKEN;
+    const denied = await request(app)
+      .post("/api/admin/config/reload")
+      .set("Authorization", "Bearer wrong");
+    expect(denied.status).toBe(401);
+
+    const res = await request(app)
+      .post("/api/admin/config/reload")
+      .set("Authorization", `Bearer ${ADMIN_TOKEN}`);
+    expect(res.status).toBe(200);
+    expect(res.body.settings.maxConcurrentDownloads).toBe(2);
+
+    writeConfig("MAX_CONCURRENT_DOWNLOADS=-1\n");
+    const rejected = await request(app)
+      .post("/api/admin/config/reload")
+      .set("Authorization", `Bearer ${ADMIN_TOKEN}`);
+    expect(rejected.status).toBe(400);
+    expect(rejected.body.code).toBe("INVALID_CONFIG");
+
+    const current = await request(app)
+      .get("/api/admin/config")
+      .set("Authorization", `Bearer ${ADMIN_TOKEN}`);
+    expect(current.body.settings.maxConcurrentDownloads).toBe(2);
+    expect(current.body.reloadedAt).toBeTruthy();
+  });
+});
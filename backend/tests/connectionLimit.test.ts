@@ -0,0 +1,79 @@
+import { describe, it, expect, afterEach } from "vitest";
+import express from "express";
+import request from "supertest";
+import { config } from "../src/config.js";
+import {
+  ConnectionLimiter,
+  connectionLimit,
+} from "../src/middleware/connectionLimit.js";
+
+describe("ConnectionLimiter", () => {
+  it("enforces the global cap and frees slots on release", () => {
+    const limiter = new ConnectionLimiter(() => ({ max: 2, perIp: 0 }));
+    const a = limiter.tryAcquire("1.1.1.1");
+    const b = limiter.tryAcquire("2.2.2.2");
+    expect(a && b).toBeTruthy();
+    expect(limiter.tryAcquire("3.3.3.3")).toBeNull();
+
+    a!();
+    a!(); // releasing twice must not free a second slot
+    expect(limiter.size).toBe(1);
+    expect(limiter.tryAcquire("3.3.3.3")).not.toBeNull();
+    expect(limiter.tryAcquire("4.4.4.4")).toBeNull();
+  });
+
+  it("enforces the per-IP cap independently of other clients", () => {
+    const limiter = new ConnectionLimiter(() => ({ max: 0, perIp: 1 }));
+    expect(limiter.tryAcquire("1.1.1.1")).not.toBeNull();
+    expect(limiter.tryAcquire("1.1.1.1")).toBeNull();
+    expect(limiter.tryAcquire("2.2.2.2")).not.toBeNull();
+  });
+});
+
+describe("connectionLimit middleware", () => {
+  const original = config.maxConcurrentRequests;
+
+  afterEach(() => {
+    config.maxConcurrentRequests = original;
+  });
+
+  function createApp() {
+    const app = express();
+    app.use(connectionLimit);
+    app.get("/slow", (_req, res) => {
+      setTimeout(() => res.json({ ok: true }), 200);
+    });
+    app.get("/fast", (_req, res) => {
+      res.json({ ok: true });
+    });
+    app.get("/downloads/:id/progress", (_req, res) => {
+      res.json({ ok: true });
+    });
+    return app;
+  }
+
+  it("returns 503 when the request cap is reached", async () => {
+    config.maxConcurrentRequests = 1;
+    const app = createApp();
+
+    const slow = request(app).get("/slow").then((res) => res);
+    await new Promise((resolve) => setTimeout(resolve, 50));
+    const rejected = await request(app).get("/fast");
+
+    expect(rejected.status).toBe(503);
+    expect(rejected.body.code).toBe("TOO_MANY_CONNECTIONS");
+    expect((await slow).status).toBe(200);
+  });
+
+  it("does not count progress streams against the request cap", async () => {
+    config.maxConcurrentRequests = 1;
+    const app = createApp();
+
+    const slow = request(app).get("/slow").then((res) => res);
+    await new Promise((resolve) => setTimeout(resolve, 50));
+    const stream = await request(app).get("/downloads/abc/progress");
+
+    expect(stream.status).toBe(200);
+    await slow;
+  });
+});
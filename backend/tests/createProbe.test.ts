@@ -0,0 +1,108 @@
+import { describe, it, expect, vi, beforeEach, afterEach } from "vitest";
+import express from "express";
+import request from "supertest";
+import downloadRoutes from "../src/routes/downloads.js";
+import {
+  deleteTask,
+  getAllTasks,
+  setTaskStore,
+} from "../src/services/downloadManager.js";
+import { MemoryTaskStore } from "../src/services/taskStore.js";
+import { downloadsViaFetch } from "./helpers/downloadNetwork.js";
+
+const ACCOUNT_HASH = "abcdef1234567890abcdef1234567890";
+const BODY = {
+  software: { id: 1, bundleID: "com.example.probe", version: "1.0" },
+  accountHash: ACCOUNT_HASH,
+  downloadURL: "https://example.apple.com/app.ipa",
+  sinfs: [],
+};
+
+function rangeOf(init?: RequestInit) {
+  return new Headers(init?.headers).get("range");
+}
+
+describe("upstream probe on create", () => {
+  const app = express();
+  app.use(express.json());
+  app.use("/api", downloadRoutes);
+  const ids: string[] = [];
+
+  beforeEach(() => {
+    setTaskStore(new MemoryTaskStore());
+    downloadsViaFetch();
+  });
+
+  afterEach(() => {
+    for (const id of ids.splice(0)) deleteTask(id);
+    vi.restoreAllMocks();
+  });
+
+  it("should record the probed size and range support", async () => {
+    vi.spyOn(globalThis, "fetch").mockImplementation(async (_url, init) =>
+      rangeOf(init) === "bytes=0-0"
+        ? new Response("x", {
+            status: 206,
+            headers: { "Content-Range": "bytes 0-0/4096" },
+          })
+        : new Response("package"),
+    );
+
+    const res = await request(app).post("/api/downloads").send(BODY);
+    ids.push(res.body.id);
+
+    expect(res.status).toBe(201);
+    expect(res.body.totalBytes).toBe(4096);
+    expect(res.body.resumable).toBe(true);
+  });
+
+  it("should answer 410 for an expired URL without creating a task", async () => {
+    vi.spyOn(globalThis, "fetch").mockImplementation(
+      async () => new Response("Forbidden", { status: 403 }),
+    );
+
+    const res = await request(app).post("/api/downloads").send(BODY);
+
+    expect(res.status).toBe(410);
+    expect(res.body.code).toBe("DOWNLOAD_URL_EXPIRED");
+    expect(getAllTasks()).toEqual([]);
+  });
+
+  it("should not probe with skipProbe", async () => {
+    const fetchMock = vi
+      .spyOn(globalThis, "fetch")
+      .mockImplementation(async () => new Response("package"));
+
+    const res = await request(app)
+      .post("/api/downloads")
+      .send({ ...BODY, skipProbe: true });
+    ids.push(res.body.id);
+
+    expect(res.status).toBe(201);
+    expect(res.body.totalBytes).toBeUndefined();
+    const probes = fetchMock.mock.calls.filter(
+      ([, init]) => rangeOf(init) === "bytes=0-0",
+    );
+    expect(probes).toEqual([]);
+    expect(
+      (
+        await request(app)
+          .post("/api/downloads")
+          .send({ ...BODY, skipProbe: "yes" })
+      ).status,
+    ).toBe(400);
+  });
+
+  it("should still create the task when the probe fails", async () => {
+    vi.spyOn(globalThis, "fetch").mockImplementation(async (_url, init) => {
+      if (rangeOf(init) === "bytes=0-0") throw new TypeError("fetch failed");
+      return new Response("package");
+    });
+
+    const res = await request(app).post("/api/downloads").send(BODY);
+    ids.push(res.body.id);
+
+    expect(res.status).toBe(201);
+    expect(res.body.totalBytes).toBeUndefined();
+  });
+});
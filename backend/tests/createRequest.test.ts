@@ -0,0 +1,92 @@
+import { describe, it, expect } from "vitest";
+import { parseCreateRequest } from "../src/services/createRequest.js";
+
+const ACCOUNT = "abcdef1234567890abcdef1234567890";
+const body = {
+  software: { id: 1, bundleID: "com.example.app", version: "1.0" },
+  accountHash: ACCOUNT,
+  downloadURL: "https://example.apple.com/app.ipa",
+  sinfs: [],
+};
+
+describe("parseCreateRequest", () => {
+  it("should build a normalized request with defaults", () => {
+    const parsed = parseCreateRequest({
+      ...body,
+      externalVersionId: 123,
+      purchaseDate: "2024-01-02T03:04:05Z",
+      expectedSha1: "A".repeat(40),
+    });
+    expect(parsed.ok).toBe(true);
+    if (!parsed.ok) return;
+    expect(parsed.request.priority).toBe("normal");
+    expect(parsed.request.replace).toBe(false);
+    expect(parsed.request.software.externalVersionId).toBe("123");
+    expect(parsed.request.software.purchaseDate).toBe(
+      "2024-01-02T03:04:05.000Z",
+    );
+    expect(parsed.request.expectedDigests).toEqual({
+      md5: undefined,
+      sha1: "a".repeat(40),
+    });
+  });
+
+  it("should report the first invalid field", () => {
+    expect(parseCreateRequest({ ...body, sinfs: undefined })).toMatchObject({
+      ok: false,
+      error: expect.stringMatching(/Missing required fields/),
+    });
+    expect(
+      parseCreateRequest({ ...body, accountHash: "short" }),
+    ).toMatchObject({ ok: false, code: "INVALID_ACCOUNT_HASH" });
+    expect(
+      parseCreateRequest({ ...body, downloadURL: "https://example.com/a" }),
+    ).toMatchObject({ ok: false, error: expect.stringMatching(/Apple/) });
+    expect(parseCreateRequest({ ...body, priority: "urgent" })).toMatchObject({
+      ok: false,
+      error: "priority must be high, normal or low",
+    });
+    expect(parseCreateRequest({ ...body, replace: "yes" })).toMatchObject({
+      ok: false,
+      error: "replace must be a boolean",
+    });
+    // Arrays and objects that stringify to digits are still not IDs
+    for (const externalVersionId of [[123], { toString: () => "123" }]) {
+      expect(parseCreateRequest({ ...body, externalVersionId })).toMatchObject({
+        ok: false,
+        error: "externalVersionId must be numeric",
+      });
+    }
+  });
+
+  it("should accept an ordered list of mirrors", () => {
+    const mirror = "https://mirror.apple.com/app.ipa";
+    expect(parseCreateRequest(body)).toMatchObject({
+      request: { downloadURLs: [body.downloadURL] },
+    });
+    expect(
+      parseCreateRequest({
+        ...body,
+        downloadURLs: [body.downloadURL, mirror],
+      }),
+    ).toMatchObject({ request: { downloadURLs: [body.downloadURL, mirror] } });
+    expect(
+      parseCreateRequest({
+        ...body,
+        downloadURL: undefined,
+        downloadURLs: [mirror],
+      }),
+    ).toMatchObject({ request: { downloadURLs: [mirror] } });
+    // Every candidate is validated
+    expect(
+      parseCreateRequest({
+        ...body,
+        downloadURLs: ["https://evil.example.com/app.ipa"],
+      }),
+    ).toMatchObject({ ok: false, error: expect.stringMatching(/Apple/) });
+    expect(parseCreateRequest({ ...body, downloadURLs: [] })).toMatchObject({
+      ok: false,
+      error: expect.stringMatching(/downloadURLs/),
+    });
+  });
+});
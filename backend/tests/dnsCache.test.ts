@@ -0,0 +1,85 @@
+import { describe, it, expect, vi, afterEach } from "vitest";
+import { server as wisp } from "@mercuryworkshop/wisp-js/server";
+import { DnsCache, dohResolver } from "../src/services/dnsCache.js";
+
+const TTL_MS = 60_000;
+
+function countingResolver(address = "17.253.1.1") {
+  return vi.fn(async (_hostname: string) => address);
+}
+
+describe("DNS cache", () => {
+  afterEach(() => {
+    wisp.options.allow_loopback_ips = false;
+    vi.restoreAllMocks();
+  });
+
+  it("should reuse an address until its TTL passes", async () => {
+    const resolve = countingResolver();
+    const cache = new DnsCache(resolve, () => TTL_MS);
+
+    await cache.lookup("buy.itunes.apple.com", 0);
+    await cache.lookup("BUY.itunes.apple.com", TTL_MS - 1);
+    expect(resolve).toHaveBeenCalledTimes(1);
+
+    await cache.lookup("buy.itunes.apple.com", TTL_MS);
+    expect(resolve).toHaveBeenCalledTimes(2);
+  });
+
+  it("should evict only stale entries", async () => {
+    const cache = new DnsCache(countingResolver(), () => TTL_MS);
+    await cache.lookup("auth.itunes.apple.com", 0);
+    await cache.lookup("buy.itunes.apple.com", 30_000);
+
+    expect(cache.evictExpired(TTL_MS - 1)).toBe(0);
+    expect(cache.evictExpired(TTL_MS)).toBe(1);
+    expect(cache.size).toBe(1);
+    expect(cache.evictExpired(30_000 + TTL_MS)).toBe(1);
+    expect(cache.size).toBe(0);
+  });
+
+  it("should not cache with a zero TTL", async () => {
+    const resolve = countingResolver();
+    const cache = new DnsCache(resolve, () => 0);
+    await cache.lookup("buy.itunes.apple.com", 0);
+    await cache.lookup("buy.itunes.apple.com", 0);
+    expect(resolve).toHaveBeenCalledTimes(2);
+    expect(cache.size).toBe(0);
+  });
+
+  it("should share one lookup between concurrent misses", async () => {
+    const resolve = countingResolver();
+    const cache = new DnsCache(resolve, () => TTL_MS);
+    await Promise.all([
+      cache.lookup("init.itunes.apple.com"),
+      cache.lookup("init.itunes.apple.com"),
+    ]);
+    expect(resolve).toHaveBeenCalledTimes(1);
+  });
+
+  it("should re-check cached addresses against the relay options", async () => {
+    const cache = new DnsCache(countingResolver("127.0.0.1"), () => TTL_MS);
+    wisp.options.allow_loopback_ips = true;
+    expect(await cache.lookup("localhost", 0)).toBe("127.0.0.1");
+
+    wisp.options.allow_loopback_ips = false;
+    await expect(cache.lookup("localhost", 1)).rejects.toThrow(/loopback/);
+  });
+
+  it("should resolve A records over DoH, skipping CNAMEs", async () => {
+    const fetchSpy = vi.spyOn(globalThis, "fetch").mockResolvedValue(
+      Response.json({
+        Answer: [
+          { type: 5, data: "buy.itunes-apple.com.akadns.net." },
+          { type: 1, data: "17.253.1.2" },
+        ],
+      }),
+    );
+    const resolve = dohResolver("https://dns.example.com/dns-query");
+
+    expect(await resolve("buy.itunes.apple.com")).toBe("17.253.1.2");
+    const url = new URL(String(fetchSpy.mock.calls[0][0]));
+    expect(url.searchParams.get("name")).toBe("buy.itunes.apple.com");
+    expect(url.searchParams.get("type")).toBe("A");
+  });
+});
@@ -0,0 +1,148 @@
+import { describe, it, expect, vi, beforeEach, afterEach } from "vitest";
+import express from "express";
+import request from "supertest";
+import { config } from "../src/config.js";
+import downloadRoutes from "../src/routes/downloads.js";
+import {
+  createTask,
+  deleteTask,
+  getQueuePosition,
+  getTask,
+  pauseTask,
+  sanitizeTaskForResponse,
+  setTaskPriority,
+  setTaskStore,
+} from "../src/services/downloadManager.js";
+import { MemoryTaskStore } from "../src/services/taskStore.js";
+import type { Software, TaskPriority } from "../src/types/index.js";
+import { downloadsViaFetch } from "./helpers/downloadNetwork.js";
+
+const ACCOUNT_HASH = "abcdef1234567890abcdef1234567890";
+
+// A CDN that accepts the connection and then never sends a byte
+function mockHangingFetch() {
+  return vi
+    .spyOn(globalThis, "fetch")
+    .mockImplementation(
+      (_url, init) =>
+        new Promise((_resolve, reject) => {
+          init?.signal?.addEventListener("abort", () =>
+            reject(new DOMException("aborted", "AbortError")),
+          );
+        }),
+    );
+}
+
+function create(name: string, priority?: TaskPriority) {
+  const software = {
+    id: 1,
+    bundleID: `com.example.${name}`,
+    version: "1.0",
+  } as Software;
+  return createTask({
+    software,
+    accountHash: ACCOUNT_HASH,
+    downloadURL: "https://example.apple.com/app.ipa",
+    sinfs: [],
+    priority,
+  }).id;
+}
+
+describe("download queue", () => {
+  beforeEach(() => {
+    setTaskStore(new MemoryTaskStore());
+    downloadsViaFetch();
+    config.maxConcurrentDownloads = 1;
+    mockHangingFetch();
+  });
+
+  afterEach(() => {
+    config.maxConcurrentDownloads = 0;
+    setTaskStore(new MemoryTaskStore());
+    vi.useRealTimers();
+    vi.restoreAllMocks();
+  });
+
+  it("should start queued tasks by priority, then age", async () => {
+    const running = create("running");
+    const low = create("low", "low");
+    const normal = create("normal");
+    const high = create("high", "high");
+
+    expect(getTask(running)?.status).toBe("downloading");
+    expect(getTask(low)?.status).toBe("pending");
+    expect([high, normal, low].map(getQueuePosition)).toEqual([1, 2, 3]);
+    expect(sanitizeTaskForResponse(getTask(high)!).queuePosition).toBe(1);
+    expect(sanitizeTaskForResponse(getTask(running)!)).not.toHaveProperty(
+      "queuePosition",
+    );
+
+    deleteTask(running);
+    await vi.waitFor(() => expect(getTask(high)?.status).toBe("downloading"));
+    expect(getTask(normal)?.status).toBe("pending");
+    expect(getQueuePosition(normal)).toBe(1);
+  });
+
+  it("should let low-priority tasks catch up after waiting", () => {
+    vi.useFakeTimers({ toFake: ["Date"] });
+    create("running");
+    const low = create("low", "low");
+    vi.advanceTimersByTime(1000);
+    const normal = create("normal");
+    expect(getQueuePosition(normal)).toBe(1);
+
+    // Two aging periods lift both to "high"; the older task goes first
+    vi.advanceTimersByTime(2 * config.priorityAgingSeconds * 1000);
+    expect(getQueuePosition(low)).toBe(1);
+  });
+
+  it("should only reprioritize tasks that are still queued", () => {
+    const running = create("running");
+    const first = create("first");
+    const second = create("second");
+
+    expect(setTaskPriority(second, "high")).toBeNull();
+    expect(getQueuePosition(second)).toBe(1);
+    expect(getQueuePosition(first)).toBe(2);
+    expect(setTaskPriority(running, "high")).toBe("NOT_QUEUED");
+  });
+
+  it("should pause a queued task without it starting later", async () => {
+    const running = create("running");
+    const queued = create("queued");
+
+    expect(pauseTask(queued)).toBeNull();
+    expect(getTask(queued)?.status).toBe("paused");
+    expect(getQueuePosition(queued)).toBeUndefined();
+
+    deleteTask(running);
+    await new Promise((resolve) => setTimeout(resolve, 10));
+    expect(getTask(queued)?.status).toBe("paused");
+  });
+
+  it("should change priority over the API", async () => {
+    const app = express();
+    app.use(express.json());
+    app.use("/api", downloadRoutes);
+    const running = create("running");
+    const queued = create("queued", "low");
+    const url = (id: string) =>
+      `/api/downloads/${id}/priority?accountHash=${ACCOUNT_HASH}`;
+
+    const invalid = await request(app)
+      .post(url(queued))
+      .send({ priority: "urgent" });
+    expect(invalid.status).toBe(400);
+
+    const started = await request(app)
+      .post(url(running))
+      .send({ priority: "high" });
+    expect(started.status).toBe(409);
+    expect(started.body.code).toBe("NOT_QUEUED");
+
+    const res = await request(app).post(url(queued)).send({ priority: "high" });
+    expect(res.status).toBe(200);
+    expect(res.body.priority).toBe("high");
+    expect(res.body.queuePosition).toBe(1);
+  });
+});
@@ -0,0 +1,72 @@
+import { describe, it, expect, vi, beforeEach, afterEach } from "vitest";
+import { config } from "../src/config.js";
+import {
+  createTask,
+  deleteTask,
+  getTask,
+  setTaskStore,
+} from "../src/services/downloadManager.js";
+import { MemoryTaskStore } from "../src/services/taskStore.js";
+import type { Software } from "../src/types/index.js";
+import { downloadsViaFetch } from "./helpers/downloadNetwork.js";
+
+const ACCOUNT = "abcdef1234567890abcdef1234567890";
+const BODY = Buffer.from("package bytes served in ranged chunks".repeat(4));
+const software = {
+  id: 1,
+  bundleID: "com.example.stats",
+  name: "Stats",
+  version: "1.0",
+} as Software;
+
+// A ranged CDN that cuts the first response of the second chunk short
+function mockFlakyRanges() {
+  let cut = false;
+  return vi.spyOn(globalThis, "fetch").mockImplementation(async (_, init) => {
+    const range = new Headers(init?.headers).get("range") ?? "";
+    const [, start, end] = /bytes=(\d+)-(\d+)/.exec(range)!.map(Number);
+    const last = Math.min(end, BODY.length - 1);
+    let slice = BODY.subarray(start, last + 1);
+    if (start > 0 && !cut) {
+      cut = true;
+      slice = slice.subarray(0, 4);
+    }
+    return new Response(slice, {
+      status: 206,
+      headers: { "Content-Range": `bytes ${start}-${last}/${BODY.length}` },
+    });
+  });
+}
+
+describe("download stats", () => {
+  const saved = config.downloadConnections;
+  let id: string;
+
+  beforeEach(() => {
+    setTaskStore(new MemoryTaskStore());
+    downloadsViaFetch();
+    config.downloadConnections = 2;
+  });
+
+  afterEach(() => {
+    deleteTask(id);
+    config.downloadConnections = saved;
+    vi.restoreAllMocks();
+  });
+
+  it("should count a retried chunk", async () => {
+    mockFlakyRanges();
+    id = createTask({
+      software,
+      accountHash: ACCOUNT,
+      downloadURL: "https://a.apple.com/app.ipa",
+      sinfs: [],
+    }).id;
+    await vi.waitFor(() => expect(getTask(id)?.status).toBe("completed"));
+
+    expect(getTask(id)?.stats?.retries).toBe(1);
+    expect(getTask(id)?.events).toContainEqual(
+      expect.objectContaining({ type: "retry", detail: "#1 scheduled" }),
+    );
+  });
+});
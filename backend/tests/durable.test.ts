@@ -0,0 +1,36 @@
+import { describe, it, expect, beforeAll, afterAll } from "vitest";
+import fs from "fs";
+import path from "path";
+import os from "os";
+import {
+  preallocate,
+  syncFileAndParentDir,
+  writeFileAtomicSync,
+} from "../src/utils/durable.js";
+
+const TEMP_DIR = path.join(os.tmpdir(), "durable-test");
+
+beforeAll(() => {
+  fs.mkdirSync(TEMP_DIR, { recursive: true });
+});
+
+afterAll(() => {
+  fs.rmSync(TEMP_DIR, { recursive: true, force: true });
+});
+
+describe("durable writes", () => {
+  it("pre-allocates a file to the requested size", async () => {
+    const filePath = path.join(TEMP_DIR, "prealloc.ipa");
+    await preallocate(filePath, 4096);
+    expect(fs.statSync(filePath).size).toBe(4096);
+    await expect(syncFileAndParentDir(filePath)).resolves.toBeUndefined();
+  });
+
+  it("writes atomically without leaving a temp file behind", () => {
+    const filePath = path.join(TEMP_DIR, "tasks.json");
+    writeFileAtomicSync(filePath, "[]", true);
+    writeFileAtomicSync(filePath, '[{"id":"a"}]', false);
+    expect(fs.readFileSync(filePath, "utf-8")).toBe('[{"id":"a"}]');
+    expect(fs.existsSync(`${filePath}.tmp`)).toBe(false);
+  });
+});
@@ -0,0 +1,41 @@
+import { bench, describe, beforeAll, afterAll } from "vitest";
+import fs from "fs";
+import os from "os";
+import path from "path";
+import { Writable } from "stream";
+import { pipeline } from "stream/promises";
+
+// Streams a 256 MB package into a sink the way sendPackageFile does, at the
+// default read size and at the sizes FILE_READ_BUFFER_KB is likely set to.
+const SIZE = 256 * 1024 * 1024;
+let dir: string;
+let filePath: string;
+
+function sink() {
+  return new Writable({
+    write(_chunk, _encoding, callback) {
+      callback();
+    },
+  });
+}
+
+describe("serving a package file", () => {
+  beforeAll(() => {
+    dir = fs.mkdtempSync(path.join(os.tmpdir(), "file-serve-bench-"));
+    filePath = path.join(dir, "app.ipa");
+    fs.writeFileSync(filePath, Buffer.alloc(SIZE, 1));
+  });
+
+  afterAll(() => {
+    fs.rmSync(dir, { recursive: true, force: true });
+  });
+
+  for (const kb of [64, 256, 1024, 4096]) {
+    bench(`${kb} KB reads`, async () => {
+      await pipeline(
+        fs.createReadStream(filePath, { highWaterMark: kb * 1024 }),
+        sink(),
+      );
+    });
+  }
+});
@@ -0,0 +1,132 @@
+import { describe, it, expect, vi, beforeEach, afterEach } from "vitest";
+import express from "express";
+import request from "supertest";
+import fs from "fs";
+import { config } from "../src/config.js";
+import adminRoutes from "../src/routes/admin.js";
+import {
+  deleteTask,
+  getTask,
+  setTaskStore,
+} from "../src/services/downloadManager.js";
+import { inject } from "../src/services/sinfInjector.js";
+import { MemoryTaskStore } from "../src/services/taskStore.js";
+import { downloadsViaFetch } from "./helpers/downloadNetwork.js";
+import { startDownload } from "./helpers/tasks.js";
+
+const ADMIN_TOKEN = "test-admin-token";
+
+vi.mock("../src/services/sinfInjector.js", async (importOriginal) => ({
+  ...(await importOriginal<typeof import("../src/services/sinfInjector.js")>()),
+  inject: vi.fn(),
+}));
+
+describe("POST /api/admin/tasks/:id/force", () => {
+  const app = express();
+  app.use(express.json());
+  app.use("/api", adminRoutes);
+  const saved = { ...config };
+  let id: string;
+
+  function force(target: unknown) {
+    return request(app)
+      .post(`/api/admin/tasks/${id}/force`)
+      .set("Authorization", `Bearer ${ADMIN_TOKEN}`)
+      .send({ target });
+  }
+
+  beforeEach(async () => {
+    config.adminToken = ADMIN_TOKEN;
+    setTaskStore(new MemoryTaskStore());
+    downloadsViaFetch();
+    // A CDN that never answers, so the task is wedged in "downloading"
+    const fetchSpy = vi
+      .spyOn(globalThis, "fetch")
+      .mockImplementation(() => new Promise(() => {}));
+    id = startDownload("com.example.force");
+    await vi.waitFor(() => expect(fetchSpy).toHaveBeenCalledTimes(1));
+  });
+
+  afterEach(() => {
+    Object.assign(config, saved);
+    deleteTask(id);
+    vi.restoreAllMocks();
+    vi.mocked(inject).mockReset();
+  });
+
+  it("should require the admin token and a known target", async () => {
+    const denied = await request(app)
+      .post(`/api/admin/tasks/${id}/force`)
+      .send({ target: "fail" });
+    expect(denied.status).toBe(401);
+
+    expect((await force("cancel")).status).toBe(400);
+    const missing = await request(app)
+      .post("/api/admin/tasks/nope/force")
+      .set("Authorization", `Bearer ${ADMIN_TOKEN}`)
+      .send({ target: "fail" });
+    expect(missing.status).toBe(404);
+    expect(getTask(id)?.status).toBe("downloading");
+  });
+
+  it("should pause or fail a download stuck in flight", async () => {
+    const paused = await force("pause");
+    expect(paused.body.status).toBe("paused");
+    expect(getTask(id)?.pausedAt).toBeTruthy();
+    // Nothing in flight any more
+    const again = await force("pause");
+    expect(again.status).toBe(409);
+    expect(again.body.code).toBe("NOT_FORCIBLE");
+
+    const failed = await force("fail");
+    expect(failed.status).toBe(200);
+    expect(failed.body).toMatchObject({
+      status: "failed",
+      errorCode: "forced",
+    });
+    expect(failed.body.events.at(-1)).toMatchObject({
+      type: "failed",
+      detail: "forced",
+    });
+    expect((await force("pause")).status).toBe(409);
+  });
+
+  it("should not inject a task forced while it waits for a slot", async () => {
+    config.maxConcurrentInjections = 1;
+    let finishFirst!: () => void;
+    vi.mocked(inject).mockImplementationOnce(
+      () =>
+        new Promise((resolve) => {
+          finishFirst = () => resolve({ kind: "info", executable: "Example" });
+        }),
+    );
+    vi.mocked(globalThis.fetch).mockImplementation(
+      async () => new Response("ipa"),
+    );
+    const sinfs = [{ id: 0, sinf: "AAAA" }];
+    const first = startDownload("com.example.force-first", sinfs);
+    await vi.waitFor(() => expect(inject).toHaveBeenCalledTimes(1));
+    deleteTask(id);
+    id = startDownload("com.example.force-queued", sinfs);
+    await vi.waitFor(() => expect(getTask(id)?.injectionPhase).toBe("queued"));
+
+    expect((await force("fail")).status).toBe(200);
+    finishFirst();
+    await vi.waitFor(() => expect(getTask(first)?.status).toBe("completed"));
+    expect(inject).toHaveBeenCalledTimes(1);
+    expect(getTask(id)?.status).toBe("failed");
+    deleteTask(first);
+  });
+
+  it("should complete only when the package file exists", async () => {
+    const rejected = await force("complete");
+    expect(rejected.status).toBe(409);
+    expect(rejected.body.code).toBe("NO_PACKAGE_FILE");
+
+    fs.writeFileSync(getTask(id)!.filePath!, "ipa-bytes");
+    const res = await force("complete");
+    expect(res.status).toBe(200);
+    expect(res.body).toMatchObject({ status: "completed", fileSize: 9 });
+    expect(getTask(id)?.downloadURL).toBe("");
+  });
+});
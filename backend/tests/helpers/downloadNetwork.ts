@@ -0,0 +1,11 @@
+import { setPinnedNetwork } from "../../src/services/pinnedFetch.js";
+
+// Downloads resolve DNS and connect with https.request, which fetch mocks
+// don't see. This resolves every host to a public address and sends the
+// pinned requests through globalThis.fetch, so tests can keep mocking it.
+export function downloadsViaFetch() {
+  setPinnedNetwork(
+    async () => ["17.253.144.10"],
+    (url, _address, init) => fetch(url.href, { ...init, redirect: "manual" }),
+  );
+}
@@ -0,0 +1,40 @@
+// Minimal Mach-O images for encryption detection tests
+
+/** A thin arm64 image; cryptid null leaves out LC_ENCRYPTION_INFO_64. */
+export function machO64(cryptid: number | null): Buffer {
+  const uuid = Buffer.alloc(24);
+  uuid.writeUInt32LE(0x1b, 0); // LC_UUID
+  uuid.writeUInt32LE(24, 4);
+  const commands = [uuid];
+  if (cryptid !== null) {
+    const encryption = Buffer.alloc(24);
+    encryption.writeUInt32LE(0x2c, 0); // LC_ENCRYPTION_INFO_64
+    encryption.writeUInt32LE(24, 4);
+    encryption.writeUInt32LE(0x4000, 8);
+    encryption.writeUInt32LE(0x8000, 12);
+    encryption.writeUInt32LE(cryptid, 16);
+    commands.push(encryption);
+  }
+  const header = Buffer.alloc(32);
+  header.writeUInt32LE(0xfeedfacf, 0);
+  header.writeUInt32LE(0x0100000c, 4); // CPU_TYPE_ARM64
+  header.writeUInt32LE(commands.length, 16);
+  header.writeUInt32LE(commands.reduce((sum, c) => sum + c.length, 0), 20);
+  return Buffer.concat([header, ...commands, Buffer.alloc(4096)]);
+}
+
+/** A universal binary with each slice on its own 16 KB boundary. */
+export function fatBinary(slices: Buffer[]): Buffer {
+  const align = 16 * 1024;
+  const parts: Buffer[] = [];
+  const header = Buffer.alloc(align);
+  header.writeUInt32BE(0xcafebabe, 0);
+  header.writeUInt32BE(slices.length, 4);
+  slices.forEach((slice, i) => {
+    const offset = align * (i + 1);
+    header.writeUInt32BE(offset, 8 + i * 20 + 8);
+    header.writeUInt32BE(slice.length, 8 + i * 20 + 12);
+    parts.push(Buffer.concat([slice, Buffer.alloc(align - slice.length)]));
+  });
+  return Buffer.concat([header, ...parts]);
+}
@@ -0,0 +1,56 @@
+import fs from "fs";
+import path from "path";
+import { config } from "../../src/config.js";
+import { createTask } from "../../src/services/downloadManager.js";
+import type { DownloadTask, Sinf, Software } from "../../src/types/index.js";
+
+export const TEST_ACCOUNT = "abcdef1234567890abcdef1234567890";
+
+/**
+ * A completed task of `com.example.<id>` 1.0, its package at the usual
+ * place under DATA_DIR/packages. Completed tasks are only restored when
+ * that file exists: pass `contents` to write it.
+ */
+export function completedTask(
+  id: string,
+  overrides: Partial<DownloadTask> = {},
+  contents?: string | Uint8Array,
+): DownloadTask {
+  const accountHash = overrides.accountHash ?? TEST_ACCOUNT;
+  const task = {
+    id,
+    software: { id: 1, bundleID: `com.example.${id}`, version: "1.0" },
+    accountHash,
+    downloadURL: "",
+    sinfs: [],
+    status: "completed",
+    progress: 100,
+    speed: "0 B/s",
+    filePath: path.join(
+      config.dataDir,
+      "packages",
+      accountHash,
+      `com.example.${id}`,
+      "1.0",
+      `${id}.ipa`,
+    ),
+    createdAt: "2026-01-01T00:00:00.000Z",
+    ...overrides,
+  } as DownloadTask;
+  if (contents !== undefined) {
+    fs.mkdirSync(path.dirname(task.filePath!), { recursive: true });
+    fs.writeFileSync(task.filePath!, contents);
+  }
+  return task;
+}
+
+/** Start downloading `bundleID` 1.0 for TEST_ACCOUNT; returns the task id. */
+export function startDownload(bundleID: string, sinfs: Sinf[] = []): string {
+  const software = { id: 1, bundleID, version: "1.0" } as Software;
+  return createTask({
+    software,
+    accountHash: TEST_ACCOUNT,
+    downloadURL: "https://example.apple.com/app.ipa",
+    sinfs,
+  }).id;
+}
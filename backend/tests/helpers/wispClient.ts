@@ -0,0 +1,217 @@
+import { WebSocket } from "ws";
+
+// Minimal Wisp v1 client for exercising the relay from tests and load
+// scripts: TCP streams, CONTINUE flow control and CLOSE reasons. It lives
+// under tests/ so it never ships in the backend build.
+
+export const PacketType = {
+  CONNECT: 0x01,
+  DATA: 0x02,
+  CONTINUE: 0x03,
+  CLOSE: 0x04,
+} as const;
+
+export const CloseReason = {
+  UNKNOWN: 0x01,
+  VOLUNTARY: 0x02,
+  NETWORK_ERROR: 0x03,
+  INVALID_INFO: 0x41,
+  UNREACHABLE: 0x42,
+  TIMEOUT: 0x43,
+  REFUSED: 0x44,
+  TCP_TIMEOUT: 0x47,
+  BLOCKED: 0x48,
+  THROTTLED: 0x49,
+  CLIENT_ERROR: 0x81,
+} as const;
+
+const STREAM_TYPE_TCP = 0x01;
+// Payload size of each DATA packet written by WispStream.write
+const MAX_DATA_PAYLOAD = 16 * 1024;
+
+function encodePacket(type: number, streamId: number, payload: Uint8Array) {
+  const packet = Buffer.alloc(5 + payload.length);
+  packet.writeUInt8(type, 0);
+  packet.writeUInt32LE(streamId, 1);
+  packet.set(payload, 5);
+  return packet;
+}
+
+export class WispStream {
+  /** Resolves with the CLOSE reason, whoever closed the stream. */
+  readonly closed: Promise<number>;
+  private resolveClosed!: (reason: number) => void;
+  private closeReason: number | null = null;
+  private received: Buffer[] = [];
+  private receivedBytes = 0;
+  private reader: {
+    length: number;
+    resolve: (data: Buffer) => void;
+    reject: (err: Error) => void;
+  } | null = null;
+  private creditWaiters: (() => void)[] = [];
+
+  constructor(
+    private readonly client: WispClient,
+    readonly id: number,
+    // DATA packets the server will still buffer before sending CONTINUE
+    private credit: number,
+  ) {
+    this.closed = new Promise((resolve) => {
+      this.resolveClosed = resolve;
+    });
+  }
+
+  /** Send `data`, waiting for CONTINUE whenever the server buffer is full. */
+  async write(data: Uint8Array): Promise<void> {
+    for (let offset = 0; offset < data.length; offset += MAX_DATA_PAYLOAD) {
+      while (this.credit === 0 && this.closeReason === null) {
+        await new Promise<void>((resolve) => this.creditWaiters.push(resolve));
+      }
+      if (this.closeReason !== null) {
+        throw new Error(
+          `Stream ${this.id} closed (reason 0x${this.closeReason.toString(16)})`,
+        );
+      }
+      this.credit--;
+      this.client.send(
+        PacketType.DATA,
+        this.id,
+        data.subarray(offset, offset + MAX_DATA_PAYLOAD),
+      );
+    }
+  }
+
+  /** Resolve with exactly `length` bytes; rejects if the stream closes first. */
+  read(length: number): Promise<Buffer> {
+    if (this.reader) throw new Error("Only one read may be pending");
+    return new Promise((resolve, reject) => {
+      this.reader = { length, resolve, reject };
+      this.flushReader();
+    });
+  }
+
+  /** Close the stream from our side. */
+  close(reason: number = CloseReason.VOLUNTARY) {
+    if (this.closeReason !== null) return;
+    this.client.send(PacketType.CLOSE, this.id, Uint8Array.of(reason));
+    this.handleClose(reason);
+  }
+
+  handleData(data: Buffer) {
+    this.received.push(data);
+    this.receivedBytes += data.length;
+    this.flushReader();
+  }
+
+  handleContinue(bufferRemaining: number) {
+    this.credit = bufferRemaining;
+    for (const wake of this.creditWaiters.splice(0)) wake();
+  }
+
+  handleClose(reason: number) {
+    if (this.closeReason !== null) return;
+    this.closeReason = reason;
+    this.client.forget(this.id);
+    for (const wake of this.creditWaiters.splice(0)) wake();
+    this.flushReader();
+    this.resolveClosed(reason);
+  }
+
+  private flushReader() {
+    const reader = this.reader;
+    if (!reader) return;
+    if (this.receivedBytes >= reader.length) {
+      const all = Buffer.concat(this.received);
+      this.received = [all.subarray(reader.length)];
+      this.receivedBytes = all.length - reader.length;
+      this.reader = null;
+      reader.resolve(all.subarray(0, reader.length));
+    } else if (this.closeReason !== null) {
+      this.reader = null;
+      reader.reject(
+        new Error(
+          `Stream ${this.id} closed after ${this.receivedBytes} of ${reader.length} bytes`,
+        ),
+      );
+    }
+  }
+}
+
+export class WispClient {
+  private readonly streams = new Map<number, WispStream>();
+  private nextStreamId = 1;
+  // Buffer size the server announced on stream 0; each new stream starts here
+  private initialCredit = 0;
+
+  private constructor(private readonly ws: WebSocket) {}
+
+  /** Open the WebSocket and wait for the server's initial CONTINUE. */
+  static connect(url: string): Promise<WispClient> {
+    const ws = new WebSocket(url);
+    const client = new WispClient(ws);
+    return new Promise((resolve, reject) => {
+      ws.on("message", (data: Buffer) => {
+        if (client.handlePacket(data)) resolve(client);
+      });
+      ws.once("error", reject);
+      ws.once("close", () => {
+        reject(new Error("Wisp connection closed before it was ready"));
+        for (const stream of [...client.streams.values()]) {
+          stream.handleClose(CloseReason.NETWORK_ERROR);
+        }
+      });
+    });
+  }
+
+  /** Ask the relay for a TCP connection to `hostname:port`. */
+  openStream(hostname: string, port: number): WispStream {
+    const id = this.nextStreamId++;
+    const host = Buffer.from(hostname, "utf-8");
+    const payload = Buffer.alloc(3 + host.length);
+    payload.writeUInt8(STREAM_TYPE_TCP, 0);
+    payload.writeUInt16LE(port, 1);
+    payload.set(host, 3);
+
+    const stream = new WispStream(this, id, this.initialCredit);
+    this.streams.set(id, stream);
+    this.send(PacketType.CONNECT, id, payload);
+    return stream;
+  }
+
+  close() {
+    this.ws.close();
+  }
+
+  send(type: number, streamId: number, payload: Uint8Array) {
+    this.ws.send(encodePacket(type, streamId, payload));
+  }
+
+  forget(streamId: number) {
+    this.streams.delete(streamId);
+  }
+
+  // Returns true for the stream-0 CONTINUE that makes the connection usable
+  private handlePacket(packet: Buffer): boolean {
+    if (packet.length < 5) return false;
+    const type = packet.readUInt8(0);
+    const streamId = packet.readUInt32LE(1);
+    const payload = packet.subarray(5);
+
+    if (type === PacketType.CONTINUE && streamId === 0) {
+      this.initialCredit = payload.readUInt32LE(0);
+      return true;
+    }
+    const stream = this.streams.get(streamId);
+    if (!stream) return false;
+    if (type === PacketType.DATA) {
+      stream.handleData(Buffer.from(payload));
+    } else if (type === PacketType.CONTINUE) {
+      stream.handleContinue(payload.readUInt32LE(0));
+    } else if (type === PacketType.CLOSE) {
+      stream.handleClose(payload.readUInt8(0));
+    }
+    // Anything else (e.g. Wisp v2 INFO) is ignored, keeping us on v1
+    return false;
+  }
+}
@@ -0,0 +1,75 @@
+import { describe, it, expect, vi, afterEach } from "vitest";
+import fs from "fs";
+import os from "os";
+import path from "path";
+import request from "supertest";
+import { config } from "../src/config.js";
+import {
+  createAppServer,
+  createRedirectServer,
+} from "../src/services/httpServer.js";
+
+const createHttpsServer = vi.hoisted(() => vi.fn());
+vi.mock("https", async (importOriginal) => ({
+  ...(await importOriginal<typeof import("https")>()),
+  createServer: createHttpsServer,
+}));
+
+describe("built-in TLS", () => {
+  const saved = { ...config };
+
+  afterEach(() => {
+    Object.assign(config, saved);
+    createHttpsServer.mockReset();
+  });
+
+  it("should serve HTTPS with the configured certificate and key", () => {
+    const dir = fs.mkdtempSync(path.join(os.tmpdir(), "asspp-tls-"));
+    try {
+      config.tlsCertPath = path.join(dir, "cert.pem");
+      config.tlsKeyPath = path.join(dir, "key.pem");
+      fs.writeFileSync(config.tlsCertPath, "certificate");
+      fs.writeFileSync(config.tlsKeyPath, "private key");
+      const app = () => {};
+
+      createAppServer(app);
+      expect(createHttpsServer).toHaveBeenCalledWith(
+        { cert: Buffer.from("certificate"), key: Buffer.from("private key") },
+        app,
+      );
+    } finally {
+      fs.rmSync(dir, { recursive: true, force: true });
+    }
+  });
+
+  it("should stay on plain HTTP unless both paths are set", () => {
+    config.tlsCertPath = "/etc/asspp/cert.pem";
+    config.tlsKeyPath = "";
+    createAppServer(() => {});
+    expect(createHttpsServer).not.toHaveBeenCalled();
+  });
+
+  it("should redirect to the HTTPS port keeping the path and query", async () => {
+    config.port = 8443;
+    const res = await request(createRedirectServer())
+      .get("/asspp/api/packages?accountHashes=abc")
+      .set("Host", "example.com:8080");
+    expect(res.status).toBe(301);
+    expect(res.headers.location).toBe(
+      "https://example.com:8443/asspp/api/packages?accountHashes=abc",
+    );
+
+    config.port = 443;
+    const standard = await request(createRedirectServer())
+      .get("/asspp/")
+      .set("Host", "example.com");
+    expect(standard.headers.location).toBe("https://example.com/asspp/");
+  });
+
+  it("should refuse a request without a usable Host", async () => {
+    const res = await request(createRedirectServer())
+      .get("/")
+      .set("Host", "<>");
+    expect(res.status).toBe(400);
+  });
+});
@@ -0,0 +1,85 @@
+import { describe, it, expect, vi, beforeEach, afterEach } from "vitest";
+import {
+  addProgressListener,
+  deleteTask,
+  getTask,
+  setTaskStore,
+} from "../src/services/downloadManager.js";
+import { MemoryTaskStore } from "../src/services/taskStore.js";
+import type { DownloadTask } from "../src/types/index.js";
+import { downloadsViaFetch } from "./helpers/downloadNetwork.js";
+import { startDownload } from "./helpers/tasks.js";
+
+type Snapshot = Pick<
+  DownloadTask,
+  "status" | "progress" | "indeterminate" | "downloadedBytes"
+>;
+
+describe("unknown-length download progress", () => {
+  let id: string;
+  let snapshots: Snapshot[];
+
+  function download() {
+    id = startDownload("com.example.indeterminate");
+    addProgressListener(id, (task) => {
+      const { status, progress, indeterminate, downloadedBytes } = task;
+      snapshots.push({ status, progress, indeterminate, downloadedBytes });
+    });
+  }
+
+  beforeEach(() => {
+    snapshots = [];
+    setTaskStore(new MemoryTaskStore());
+    downloadsViaFetch();
+  });
+
+  afterEach(() => {
+    deleteTask(id);
+    vi.restoreAllMocks();
+  });
+
+  it("should report bytes with indeterminate set when there is no length", async () => {
+    // A stream body goes out chunked, without Content-Length
+    vi.spyOn(globalThis, "fetch").mockImplementation(
+      async () =>
+        new Response(
+          new ReadableStream({
+            start(controller) {
+              controller.enqueue(new Uint8Array(300));
+              controller.close();
+            },
+          }),
+        ),
+    );
+    download();
+    await vi.waitFor(() => expect(getTask(id)?.status).toBe("completed"));
+
+    expect(snapshots).toContainEqual({
+      status: "downloading",
+      progress: 0,
+      indeterminate: true,
+      downloadedBytes: 300,
+    });
+    // Only live while downloading
+    expect(getTask(id)?.indeterminate).toBeUndefined();
+    expect(getTask(id)?.downloadedBytes).toBeUndefined();
+  });
+
+  it("should compute percent normally when the length is known", async () => {
+    vi.spyOn(globalThis, "fetch").mockImplementation(
+      async () =>
+        new Response("x".repeat(100), {
+          headers: { "content-length": "100" },
+        }),
+    );
+    download();
+    await vi.waitFor(() => expect(getTask(id)?.status).toBe("completed"));
+
+    expect(snapshots).toContainEqual({
+      status: "downloading",
+      progress: 100,
+      indeterminate: false,
+      downloadedBytes: 100,
+    });
+  });
+});
@@ -0,0 +1,81 @@
+import { describe, it, expect, vi, beforeEach, afterEach } from "vitest";
+import { config } from "../src/config.js";
+import {
+  addProgressListener,
+  deleteTask,
+  getTask,
+  setTaskStore,
+} from "../src/services/downloadManager.js";
+import { inject } from "../src/services/sinfInjector.js";
+import { MemoryTaskStore } from "../src/services/taskStore.js";
+import { downloadsViaFetch } from "./helpers/downloadNetwork.js";
+import { startDownload } from "./helpers/tasks.js";
+
+vi.mock("../src/services/sinfInjector.js", async (importOriginal) => ({
+  ...(await importOriginal<typeof import("../src/services/sinfInjector.js")>()),
+  inject: vi.fn(),
+}));
+
+describe("injection queue", () => {
+  const saved = config.maxConcurrentInjections;
+  const sinfs = [{ id: 0, sinf: "AAAA" }];
+  const ids: string[] = [];
+
+  beforeEach(() => {
+    config.maxConcurrentInjections = 1;
+    setTaskStore(new MemoryTaskStore());
+    downloadsViaFetch();
+    vi.spyOn(globalThis, "fetch").mockImplementation(
+      async () => new Response("ipa"),
+    );
+    vi.mocked(inject).mockResolvedValue({ kind: "info", executable: "App" });
+  });
+
+  afterEach(() => {
+    ids.splice(0).forEach(deleteTask);
+    config.maxConcurrentInjections = saved;
+    vi.restoreAllMocks();
+    vi.mocked(inject).mockReset();
+  });
+
+  it("should queue an injection until a slot frees, then run it", async () => {
+    let finishFirst!: () => void;
+    vi.mocked(inject).mockImplementationOnce(
+      () =>
+        new Promise((resolve) => {
+          finishFirst = () => resolve({ kind: "info", executable: "App" });
+        }),
+    );
+    const first = startDownload("com.example.inject-first", sinfs);
+    ids.push(first);
+    await vi.waitFor(() => expect(inject).toHaveBeenCalledTimes(1));
+    expect(getTask(first)?.injectionPhase).toBe("running");
+
+    const second = startDownload("com.example.inject-queued", sinfs);
+    ids.push(second);
+    const phases: (string | undefined)[] = [];
+    addProgressListener(second, (task) => phases.push(task.injectionPhase));
+    await vi.waitFor(() =>
+      expect(getTask(second)?.injectionPhase).toBe("queued"),
+    );
+    expect(getTask(second)?.status).toBe("injecting");
+    expect(getTask(second)?.events).toContainEqual(
+      expect.objectContaining({
+        type: "injecting",
+        detail: "waiting for injection slot",
+      }),
+    );
+    expect(inject).toHaveBeenCalledTimes(1);
+
+    finishFirst();
+    await vi.waitFor(() => expect(getTask(second)?.status).toBe("completed"));
+    expect(phases).toContain("running");
+    expect(phases.indexOf("running")).toBeGreaterThan(phases.indexOf("queued"));
+    const task = getTask(second)!;
+    expect(task.injectionPhase).toBeUndefined();
+    expect(task.events?.map((e) => e.type)).toEqual(
+      expect.arrayContaining(["injecting", "injected", "completed"]),
+    );
+    expect(inject).toHaveBeenCalledTimes(2);
+  });
+});
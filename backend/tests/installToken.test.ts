@@ -0,0 +1,49 @@
+import { describe, it, expect } from "vitest";
+import { config } from "../src/config.js";
+import {
+  mintInstallToken,
+  verifyInstallToken,
+} from "../src/utils/installToken.js";
+
+describe("installToken", () => {
+  const now = Date.UTC(2024, 0, 1);
+
+  it("should verify a fresh token for the task it was minted for", () => {
+    const token = mintInstallToken("task-1", now);
+    expect(verifyInstallToken("task-1", token, now)).toBe(true);
+    expect(verifyInstallToken("task-2", token, now)).toBe(false);
+  });
+
+  it("should reject expired tokens", () => {
+    const token = mintInstallToken("task-1", now);
+    const ttlMs = config.installTokenTtlSeconds * 1000;
+    expect(verifyInstallToken("task-1", token, now + ttlMs - 1000)).toBe(true);
+    expect(verifyInstallToken("task-1", token, now + ttlMs)).toBe(false);
+  });
+
+  it("should reject tampered or malformed tokens", () => {
+    const token = mintInstallToken("task-1", now);
+    const [expiry, mac] = token.split(".");
+    const extended = `${Number(expiry) + 3600}.${mac}`;
+    expect(verifyInstallToken("task-1", extended, now)).toBe(false);
+    expect(verifyInstallToken("task-1", "", now)).toBe(false);
+    expect(verifyInstallToken("task-1", undefined, now)).toBe(false);
+    expect(verifyInstallToken("task-1", `${expiry}.short`, now)).toBe(false);
+  });
+
+  it("should bind tokens to INSTALL_BASE_URL when it is set", () => {
+    const original = config.installBaseUrl;
+    try {
+      config.installBaseUrl = "https://tunnel-a.example.com";
+      const token = mintInstallToken("task-1", now);
+      expect(verifyInstallToken("task-1", token, now)).toBe(true);
+
+      config.installBaseUrl = "https://tunnel-b.example.com";
+      expect(verifyInstallToken("task-1", token, now)).toBe(false);
+      config.installBaseUrl = "";
+      expect(verifyInstallToken("task-1", token, now)).toBe(false);
+    } finally {
+      config.installBaseUrl = original;
+    }
+  });
+});
@@ -0,0 +1,44 @@
+import { describe, it, expect } from "vitest";
+import { readEncryptionState } from "../src/services/machO.js";
+import { fatBinary, machO64 } from "./helpers/machO.js";
+
+// Feed a buffer in small chunks, like a zip entry stream
+async function* chunked(data: Buffer, size = 1000) {
+  for (let i = 0; i < data.length; i += size) {
+    yield data.subarray(i, i + size);
+  }
+}
+
+describe("readEncryptionState", () => {
+  it("should read cryptid from a thin binary", async () => {
+    expect(await readEncryptionState(chunked(machO64(1)))).toBe("encrypted");
+    expect(await readEncryptionState(chunked(machO64(0)))).toBe(
+      "not_encrypted",
+    );
+  });
+
+  it("should treat a binary without an encryption command as not encrypted", async () => {
+    expect(await readEncryptionState(chunked(machO64(null)))).toBe(
+      "not_encrypted",
+    );
+  });
+
+  it("should count a universal binary as encrypted if any slice is", async () => {
+    const mixed = fatBinary([machO64(0), machO64(1)]);
+    expect(await readEncryptionState(chunked(mixed))).toBe("encrypted");
+    const decrypted = fatBinary([machO64(0), machO64(0)]);
+    expect(await readEncryptionState(chunked(decrypted))).toBe(
+      "not_encrypted",
+    );
+  });
+
+  it("should report anything else as unknown", async () => {
+    expect(
+      await readEncryptionState(chunked(Buffer.from("fake executable"))),
+    ).toBe("unknown");
+    // Load commands cut off by the end of the file
+    expect(
+      await readEncryptionState(chunked(machO64(1).subarray(0, 40))),
+    ).toBe("unknown");
+  });
+});
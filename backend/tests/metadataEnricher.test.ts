@@ -0,0 +1,145 @@
+import {
+  describe,
+  it,
+  expect,
+  vi,
+  beforeAll,
+  afterAll,
+  afterEach,
+} from "vitest";
+import AdmZip from "adm-zip";
+import fs from "fs";
+import path from "path";
+import plist from "plist";
+import { config } from "../src/config.js";
+import { getTask, setTaskStore } from "../src/services/downloadManager.js";
+import { readBundleInfo } from "../src/services/metadataEnricher.js";
+import { MemoryTaskStore } from "../src/services/taskStore.js";
+import type { DownloadTask, Software } from "../src/types/index.js";
+import { completedTask } from "./helpers/tasks.js";
+
+const ACCOUNT = "abcdef1234567890abcdef1234567890";
+const dir = path.resolve(config.dataDir, "packages", ACCOUNT, "enrich");
+const ipaPath = path.join(dir, "imported.ipa");
+
+function importedTask(): DownloadTask {
+  return completedTask("imported-task", {
+    software: {
+      id: 0,
+      bundleID: "unknown",
+      name: "imported",
+      version: "0",
+    } as Software,
+    filePath: ipaPath,
+    origin: "imported",
+  });
+}
+
+describe("metadata enrichment", () => {
+  const saved = config.enrichMetadata;
+
+  beforeAll(() => {
+    const zip = new AdmZip();
+    zip.addFile(
+      "Payload/Notes.app/Info.plist",
+      Buffer.from(
+        plist.build({
+          CFBundleIdentifier: "com.example.notes",
+          CFBundleDisplayName: "Notes",
+          CFBundleShortVersionString: "2.3",
+        }),
+      ),
+    );
+    // Extensions carry their own Info.plist, which must not win
+    zip.addFile(
+      "Payload/Notes.app/PlugIns/Share.appex/Info.plist",
+      Buffer.from(plist.build({ CFBundleIdentifier: "com.example.share" })),
+    );
+    fs.mkdirSync(dir, { recursive: true });
+    zip.writeZip(ipaPath);
+  });
+
+  afterEach(() => {
+    config.enrichMetadata = saved;
+    vi.restoreAllMocks();
+    setTaskStore(new MemoryTaskStore());
+  });
+
+  afterAll(() => {
+    fs.rmSync(dir, { recursive: true, force: true });
+  });
+
+  it("should read identity fields from the app's Info.plist", async () => {
+    expect(await readBundleInfo(ipaPath)).toEqual({
+      bundleID: "com.example.notes",
+      name: "Notes",
+      version: "2.3",
+    });
+  });
+
+  it("should fill store fields from iTunes once", async () => {
+    config.enrichMetadata = "itunes";
+    const fetchSpy = vi.spyOn(globalThis, "fetch").mockImplementation(
+      async () =>
+        Response.json({
+          resultCount: 1,
+          results: [
+            {
+              trackId: 42,
+              bundleId: "com.example.notes",
+              trackName: "Notes: Quick Jotter",
+              version: "9.9",
+              sellerName: "Example Inc.",
+              artworkUrl512: "https://example.com/icon.png",
+            },
+          ],
+        }),
+    );
+    setTaskStore(new MemoryTaskStore([importedTask()]));
+
+    await vi.waitFor(() =>
+      expect(getTask("imported-task")?.metadataEnrichedAt).toBeTruthy(),
+    );
+    const software = getTask("imported-task")!.software as Software;
+    expect(software).toMatchObject({
+      id: 42,
+      bundleID: "com.example.notes",
+      name: "Notes: Quick Jotter",
+      version: "2.3",
+      sellerName: "Example Inc.",
+      artworkUrl: "https://example.com/icon.png",
+    });
+    expect(String(fetchSpy.mock.calls[0][0])).toContain(
+      "bundleId=com.example.notes",
+    );
+    expect(getTask("imported-task")?.events?.at(-1)).toMatchObject({
+      type: "metadata",
+      detail: "from Info.plist and iTunes",
+    });
+
+    // Already enriched: reloading the task does not look it up again
+    setTaskStore(new MemoryTaskStore([getTask("imported-task")!]));
+    await new Promise((resolve) => setTimeout(resolve, 20));
+    expect(fetchSpy).toHaveBeenCalledTimes(1);
+  });
+
+  it("should skip the lookup with ENRICH_METADATA=ipa", async () => {
+    config.enrichMetadata = "ipa";
+    const fetchSpy = vi.spyOn(globalThis, "fetch");
+    setTaskStore(new MemoryTaskStore([importedTask()]));
+
+    await vi.waitFor(() =>
+      expect(getTask("imported-task")?.metadataEnrichedAt).toBeTruthy(),
+    );
+    expect(getTask("imported-task")?.software.name).toBe("Notes");
+    expect(fetchSpy).not.toHaveBeenCalled();
+  });
+
+  it("should leave tasks alone with ENRICH_METADATA=off", async () => {
+    config.enrichMetadata = "off";
+    setTaskStore(new MemoryTaskStore([importedTask()]));
+    await new Promise((resolve) => setTimeout(resolve, 20));
+    expect(getTask("imported-task")?.metadataEnrichedAt).toBeUndefined();
+    expect(getTask("imported-task")?.software.name).toBe("imported");
+  });
+});
@@ -0,0 +1,80 @@
+import { describe, it, expect, vi, beforeEach, afterEach } from "vitest";
+import {
+  createTask,
+  deleteTask,
+  getTask,
+  setTaskStore,
+} from "../src/services/downloadManager.js";
+import { MemoryTaskStore } from "../src/services/taskStore.js";
+import type { Software } from "../src/types/index.js";
+import { downloadsViaFetch } from "./helpers/downloadNetwork.js";
+
+const ACCOUNT = "abcdef1234567890abcdef1234567890";
+const software = {
+  id: 1,
+  bundleID: "com.example.mirror",
+  name: "Mirror",
+  version: "1.0",
+} as Software;
+
+// Edges by hostname: "403" refuses, anything else serves the package
+function mockEdges(edges: Record<string, string>) {
+  return vi
+    .spyOn(globalThis, "fetch")
+    .mockImplementation(async (input) =>
+      edges[new URL(String(input)).hostname] === "403"
+        ? new Response(null, { status: 403 })
+        : new Response("package bytes"),
+    );
+}
+
+function download(urls: string[]) {
+  return createTask({
+    software,
+    accountHash: ACCOUNT,
+    downloadURL: urls[0],
+    sinfs: [],
+    fallbackURLs: urls.slice(1),
+  }).id;
+}
+
+describe("download mirrors", () => {
+  let id: string;
+
+  beforeEach(() => {
+    setTaskStore(new MemoryTaskStore());
+    downloadsViaFetch();
+  });
+
+  afterEach(() => {
+    deleteTask(id);
+    vi.restoreAllMocks();
+  });
+
+  it("should fall back to the next mirror and record which one served", async () => {
+    const fetchSpy = mockEdges({ "a.apple.com": "403", "b.apple.com": "ok" });
+    id = download([
+      "https://a.apple.com/app.ipa",
+      "https://b.apple.com/app.ipa",
+    ]);
+    await vi.waitFor(() => expect(getTask(id)?.status).toBe("completed"));
+    expect(getTask(id)?.mirrorIndex).toBe(1);
+    expect(
+      fetchSpy.mock.calls.map(([url]) => new URL(String(url)).hostname),
+    ).toEqual(["a.apple.com", "b.apple.com"]);
+    expect(getTask(id)?.events).toContainEqual(
+      expect.objectContaining({ type: "retry", detail: "mirror 1" }),
+    );
+  });
+
+  it("should handle the last mirror's failure as before", async () => {
+    mockEdges({ "a.apple.com": "403", "b.apple.com": "403" });
+    id = download([
+      "https://a.apple.com/app.ipa",
+      "https://b.apple.com/app.ipa",
+    ]);
+    await vi.waitFor(() => expect(getTask(id)?.status).toBe("paused"));
+    expect(getTask(id)?.errorCode).toBe("download_url_expired");
+    expect(getTask(id)?.mirrorIndex).toBe(1);
+  });
+});
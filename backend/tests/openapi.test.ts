@@ -0,0 +1,112 @@
+import { describe, it, expect } from "vitest";
+import express, { Router } from "express";
+import request from "supertest";
+import { config } from "../src/config.js";
+import adminRoutes from "../src/routes/admin.js";
+import bagRoutes from "../src/routes/bag.js";
+import devRoutes from "../src/routes/dev.js";
+import downloadRoutes from "../src/routes/downloads.js";
+import installRoutes from "../src/routes/install.js";
+import openapiRoutes, { buildOpenApiDocument } from "../src/routes/openapi.js";
+import packageRoutes from "../src/routes/packages.js";
+import searchRoutes from "../src/routes/search.js";
+import sessionRoutes from "../src/routes/session.js";
+import settingsRoutes from "../src/routes/settings.js";
+import uploadRoutes from "../src/routes/uploads.js";
+
+// Everything index.ts mounts under /api (devRoutes with CHAOS_ENABLED)
+const routers: Router[] = [
+  searchRoutes,
+  downloadRoutes,
+  packageRoutes,
+  uploadRoutes,
+  installRoutes,
+  settingsRoutes,
+  sessionRoutes,
+  bagRoutes,
+  adminRoutes,
+  openapiRoutes,
+  devRoutes,
+];
+
+type RouteLayer = {
+  route?: { path: string; methods: Record<string, boolean> };
+};
+
+// "METHOD /path" for every route, with :params in OpenAPI's {braces}
+function registeredRoutes(): string[] {
+  return routers.flatMap((router) =>
+    (router.stack as RouteLayer[]).flatMap(({ route }) =>
+      route
+        ? Object.keys(route.methods).map(
+            (method) =>
+              `${method} ${route.path.replace(/:(\w+)/g, "{$1}")}`,
+          )
+        : [],
+    ),
+  );
+}
+
+describe("OpenAPI document", () => {
+  const doc = buildOpenApiDocument();
+
+  it("should describe every registered route", () => {
+    const documented = new Set(
+      Object.entries(doc.paths).flatMap(([path, operations]) =>
+        Object.keys(operations).map((method) => `${method} ${path}`),
+      ),
+    );
+    const routes = registeredRoutes();
+    expect(routes.length).toBeGreaterThan(30);
+    expect(routes.filter((route) => !documented.has(route))).toEqual([]);
+    // ...and nothing that no longer exists
+    expect([...documented].filter((op) => !routes.includes(op))).toEqual([]);
+  });
+
+  it("should resolve every schema and response reference", () => {
+    const refs = JSON.stringify(doc).match(/#\/components\/[^"]+/g) ?? [];
+    for (const ref of new Set(refs)) {
+      const [, , section, name] = ref.split("/");
+      const components = doc.components as Record<string, object>;
+      expect(components[section], ref).toHaveProperty(name);
+    }
+  });
+
+  it("should serve the document and the SSE stream type", async () => {
+    const app = express();
+    app.use("/api", openapiRoutes);
+    const res = await request(app).get("/api/openapi.json");
+    expect(res.status).toBe(200);
+    expect(res.body.openapi).toBe("3.1.0");
+    expect(
+      res.body.paths["/downloads/{id}/progress"].get.responses["200"].content,
+    ).toHaveProperty("text/event-stream");
+  });
+
+  it("should let a browser log in to the docs page with the admin token", async () => {
+    const app = express();
+    app.use("/api", adminRoutes);
+    const saved = config.adminToken;
+    config.adminToken = "docs-token";
+    try {
+      const prompt = await request(app).get("/api/admin/docs");
+      expect(prompt.status).toBe(401);
+      expect(prompt.headers["www-authenticate"]).toMatch(/^Basic /);
+
+      const page = await request(app)
+        .get("/api/admin/docs")
+        .auth("admin", "docs-token");
+      expect(page.status).toBe(200);
+      // Pinned with integrity hashes, never a floating CDN version
+      expect(page.text).toContain("swagger-ui-dist@5.17.14/");
+      expect(page.text.match(/integrity="sha384-/g)).toHaveLength(2);
+
+      const wrong = await request(app)
+        .get("/api/admin/docs")
+        .auth("admin", "wrong");
+      expect(wrong.status).toBe(401);
+    } finally {
+      config.adminToken = saved;
+    }
+  });
+});
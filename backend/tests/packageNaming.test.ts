@@ -0,0 +1,91 @@
+import { describe, it, expect, vi, beforeEach, afterEach } from "vitest";
+import path from "path";
+import express from "express";
+import request from "supertest";
+import { config } from "../src/config.js";
+import packageRoutes from "../src/routes/packages.js";
+import {
+  createTask,
+  deleteTask,
+  getTask,
+  setTaskStore,
+} from "../src/services/downloadManager.js";
+import { MemoryTaskStore } from "../src/services/taskStore.js";
+import type { Software } from "../src/types/index.js";
+import { downloadsViaFetch } from "./helpers/downloadNetwork.js";
+
+// Same first 8 characters, so descriptive names collide
+const ACCOUNT_A = "abcdef1234567890abcdef1234567890";
+const ACCOUNT_B = "abcdef12ffffffffffffffffffffffff";
+const software = {
+  id: 1,
+  bundleID: "com.example.naming",
+  name: "Naming",
+  version: "1.0",
+} as Software;
+
+function download(accountHash: string) {
+  return createTask({
+    software,
+    accountHash,
+    downloadURL: "https://example.apple.com/app.ipa",
+    sinfs: [],
+  }).id;
+}
+
+describe("package naming", () => {
+  const ids: string[] = [];
+
+  beforeEach(() => {
+    setTaskStore(new MemoryTaskStore());
+    downloadsViaFetch();
+    vi.spyOn(globalThis, "fetch").mockImplementation(
+      async () => new Response("not really an ipa"),
+    );
+  });
+
+  afterEach(() => {
+    for (const id of ids.splice(0)) deleteTask(id);
+    config.packageNaming = "uuid";
+    vi.restoreAllMocks();
+  });
+
+  it("should nest <id>.ipa by account, bundle and version by default", async () => {
+    const id = download(ACCOUNT_A);
+    ids.push(id);
+    await vi.waitFor(() => expect(getTask(id)?.status).toBe("completed"));
+
+    const packagesDir = path.resolve(config.dataDir, "packages");
+    expect(getTask(id)?.filePath).toBe(
+      path.join(packagesDir, ACCOUNT_A, software.bundleID, "1.0", `${id}.ipa`),
+    );
+  });
+
+  it("should write descriptive names with a counter on collision", async () => {
+    config.packageNaming = "descriptive";
+    const first = download(ACCOUNT_A);
+    const second = download(ACCOUNT_B);
+    ids.push(first, second);
+    await vi.waitFor(() => {
+      expect(getTask(first)?.status).toBe("completed");
+      expect(getTask(second)?.status).toBe("completed");
+    });
+
+    const packagesDir = path.resolve(config.dataDir, "packages");
+    expect(getTask(first)?.filePath).toBe(
+      path.join(packagesDir, "com.example.naming_1.0_abcdef12.ipa"),
+    );
+    expect(getTask(second)?.filePath).toBe(
+      path.join(packagesDir, "com.example.naming_1.0_abcdef12_2.ipa"),
+    );
+
+    const app = express();
+    app.use("/api", packageRoutes);
+    const res = await request(app).get(
+      `/api/packages?accountHashes=${ACCOUNT_B}`,
+    );
+    expect(res.body[0].relativePath).toBe(
+      "com.example.naming_1.0_abcdef12_2.ipa",
+    );
+  });
+});
@@ -0,0 +1,123 @@
+import { describe, it, expect, vi, beforeEach, afterEach } from "vitest";
+import express from "express";
+import request from "supertest";
+import fs from "fs";
+import os from "os";
+import path from "path";
+import { config } from "../src/config.js";
+import packageRoutes from "../src/routes/packages.js";
+import {
+  getTask,
+  setTaskStore,
+  trashTask,
+} from "../src/services/downloadManager.js";
+import {
+  getPackageRoots,
+  getPackageRootUsage,
+  packageRootOf,
+  selectPackageRoot,
+} from "../src/services/packageRoots.js";
+import { MemoryTaskStore } from "../src/services/taskStore.js";
+import { completedTask } from "./helpers/tasks.js";
+
+const ACCOUNT = "abcdef1234567890abcdef1234567890";
+const defaultRoot = path.resolve(config.dataDir, "packages");
+
+describe("package roots", () => {
+  let extraRoot: string;
+
+  beforeEach(() => {
+    extraRoot = fs.mkdtempSync(path.join(os.tmpdir(), "asspp-roots-"));
+    config.packageDirs = [extraRoot];
+  });
+
+  afterEach(() => {
+    config.packageDirs = [];
+    config.packageDirPolicy = "first";
+    setTaskStore(new MemoryTaskStore());
+    fs.rmSync(extraRoot, { recursive: true, force: true });
+  });
+
+  it("should list PACKAGE_DIRS first and always keep DATA_DIR/packages", () => {
+    config.packageDirs = [extraRoot, `${extraRoot}/`, defaultRoot];
+    expect(getPackageRoots()).toEqual([extraRoot, defaultRoot]);
+    expect(selectPackageRoot()).toBe(extraRoot);
+
+    config.packageDirPolicy = "most-free";
+    expect(getPackageRoots()).toContain(selectPackageRoot());
+  });
+
+  it("should leave files it didn't write in a root on startup", async () => {
+    const dataDir = fs.mkdtempSync(path.join(os.tmpdir(), "asspp-data-"));
+    try {
+      const foreign = [
+        path.join(extraRoot, "notes.txt"),
+        path.join(extraRoot, "photos", "holiday.jpg"),
+      ];
+      for (const file of foreign) {
+        fs.mkdirSync(path.dirname(file), { recursive: true });
+        fs.writeFileSync(file, "not a package");
+      }
+      fs.mkdirSync(path.join(extraRoot, "empty"));
+      const orphan = path.join(extraRoot, ACCOUNT, "com.example.o", "1.0");
+      fs.mkdirSync(orphan, { recursive: true });
+      fs.writeFileSync(path.join(orphan, "gone.ipa"), "ipa-bytes");
+
+      vi.stubEnv("DATA_DIR", dataDir);
+      vi.stubEnv("PACKAGE_DIRS", extraRoot);
+      vi.resetModules();
+      await import("../src/services/downloadManager.js");
+
+      for (const file of foreign) expect(fs.existsSync(file)).toBe(true);
+      expect(fs.existsSync(path.join(extraRoot, "empty"))).toBe(true);
+      expect(fs.existsSync(path.join(extraRoot, ACCOUNT))).toBe(false);
+    } finally {
+      vi.unstubAllEnvs();
+      fs.rmSync(dataDir, { recursive: true, force: true });
+    }
+  });
+
+  it("should accept files inside any root and nothing else", () => {
+    expect(packageRootOf(path.join(extraRoot, "a", "b.ipa"))).toBe(extraRoot);
+    expect(packageRootOf(path.join(defaultRoot, "b.ipa"))).toBe(defaultRoot);
+    expect(packageRootOf(`${extraRoot}-evil/b.ipa`)).toBeUndefined();
+    expect(packageRootOf(path.join(extraRoot, "..", "b.ipa"))).toBeUndefined();
+    expect(packageRootOf(extraRoot)).toBeUndefined();
+  });
+
+  it("should serve, count and trash a package on a second root", async () => {
+    const filePath = path.join(extraRoot, ACCOUNT, "roots.ipa");
+    setTaskStore(
+      new MemoryTaskStore([
+        completedTask(
+          "roots",
+          { filePath, fileSize: 9, createdAt: new Date().toISOString() },
+          "ipa-bytes",
+        ),
+      ]),
+    );
+
+    const app = express();
+    app.use("/api", packageRoutes);
+    const res = await request(app)
+      .get("/api/packages/roots/file")
+      .set("X-Account-Hash", ACCOUNT);
+    expect(res.status).toBe(200);
+    expect(res.headers["content-length"]).toBe("9");
+
+    const usage = getPackageRootUsage([{ filePath, fileSize: 9 }]);
+    expect(usage[0]).toMatchObject({
+      path: extraRoot,
+      packages: 1,
+      packageBytes: 9,
+      default: true,
+    });
+    expect(usage[1]).toMatchObject({ path: defaultRoot, default: false });
+
+    expect(trashTask("roots")).toBe(true);
+    expect(getTask("roots")?.trashedFrom).toBe(filePath);
+    // Emptied account directory is removed, the root itself is kept
+    expect(fs.existsSync(path.dirname(filePath))).toBe(false);
+    expect(fs.existsSync(extraRoot)).toBe(true);
+  });
+});
@@ -0,0 +1,143 @@
+import { describe, it, expect, beforeAll, afterAll } from "vitest";
+import fs from "fs";
+import path from "path";
+import express from "express";
+import request from "supertest";
+import { config } from "../src/config.js";
+import packageRoutes from "../src/routes/packages.js";
+import { setTaskStore } from "../src/services/downloadManager.js";
+import { MemoryTaskStore } from "../src/services/taskStore.js";
+import type { DownloadTask, Software } from "../src/types/index.js";
+import { completedTask } from "./helpers/tasks.js";
+
+const ACCOUNT = "abcdef1234567890abcdef1234567890";
+const OTHER = "ffffffffffffffffffffffffffffffff";
+const packagesDir = path.resolve(config.dataDir, "packages", ACCOUNT);
+
+function makeTask(
+  id: string,
+  bundleID: string,
+  name: string,
+  overrides: Partial<DownloadTask> = {},
+): DownloadTask {
+  return completedTask(id, {
+    software: { id: 1, bundleID, name, version: "1.0" } as Software,
+    accountHash: ACCOUNT,
+    filePath: path.join(packagesDir, "search", `${id}.ipa`),
+    fileSize: 3,
+    ...overrides,
+  });
+}
+
+const tasks = [
+  makeTask("substring", "com.example.notes", "My Notes", {
+    createdAt: "2026-01-01T00:00:00.000Z",
+  }),
+  makeTask("prefix", "com.example.writer", "Notes Pro", {
+    createdAt: "2026-01-02T00:00:00.000Z",
+    fileSize: 5000,
+  }),
+  makeTask("exact", "notes", "Jotter", {
+    createdAt: "2026-01-03T00:00:00.000Z",
+    software: { id: 1, bundleID: "notes", name: "Jotter", version: "2.1" },
+  } as Partial<DownloadTask>),
+  makeTask("unrelated", "com.example.maps", "Maps"),
+  makeTask("other-account", "com.example.notes", "Notes", {
+    accountHash: OTHER,
+  }),
+];
+
+function search(query: string) {
+  const app = express();
+  app.use("/api", packageRoutes);
+  return request(app).get(
+    `/api/packages/search?accountHashes=${ACCOUNT}&${query}`,
+  );
+}
+
+describe("package search", () => {
+  beforeAll(() => {
+    for (const task of tasks) {
+      fs.mkdirSync(path.dirname(task.filePath!), { recursive: true });
+      fs.writeFileSync(task.filePath!, "ipa");
+    }
+    setTaskStore(new MemoryTaskStore(tasks));
+  });
+
+  afterAll(() => {
+    fs.rmSync(path.join(packagesDir, "search"), {
+      recursive: true,
+      force: true,
+    });
+    setTaskStore(new MemoryTaskStore());
+  });
+
+  it("should rank exact bundle ID, then name prefix, then substring", async () => {
+    const res = await search("q=NOTES");
+    expect(res.status).toBe(200);
+    expect(res.body.map((p: { id: string }) => p.id)).toEqual([
+      "exact",
+      "prefix",
+      "substring",
+    ]);
+    expect(res.body[0].relativePath).toBe(`${ACCOUNT}/search/exact.ipa`);
+  });
+
+  it("should apply size, date and version filters", async () => {
+    const bySize = await search("q=notes&minSize=1000");
+    expect(bySize.body.map((p: { id: string }) => p.id)).toEqual(["prefix"]);
+
+    const byDate = await search(
+      "q=notes&from=2026-01-02T00:00:00Z&to=2026-01-02T23:59:59Z",
+    );
+    expect(byDate.body.map((p: { id: string }) => p.id)).toEqual(["prefix"]);
+
+    const byVersion = await search("version=2.");
+    expect(byVersion.body.map((p: { id: string }) => p.id)).toEqual([
+      "exact",
+    ]);
+
+    const invalid = await search("minSize=big");
+    expect(invalid.status).toBe(400);
+    expect(invalid.body.code).toBe("INVALID_SEARCH");
+  });
+
+  it("should page through results in relevance order", async () => {
+    const seen: string[] = [];
+    let cursor: string | undefined;
+    do {
+      const res = await search(
+        `q=notes&limit=1${cursor ? `&cursor=${cursor}` : ""}`,
+      );
+      seen.push(...res.body.map((p: { id: string }) => p.id));
+      cursor = res.headers["x-next-cursor"];
+    } while (cursor);
+    expect(seen).toEqual(["exact", "prefix", "substring"]);
+  });
+
+  it("should report packages left out for a missing file on request", async () => {
+    const vanished = makeTask("vanished", "com.example.gone", "Gone");
+    setTaskStore(new MemoryTaskStore([...tasks, vanished]));
+    try {
+      expect((await search("q=gone")).body).toEqual([]);
+
+      const res = await search("q=gone&includeWarnings=true");
+      expect(res.body).toEqual({
+        packages: [],
+        warnings: [{ id: "vanished", reason: "file_missing" }],
+      });
+
+      const app = express();
+      app.use("/api", packageRoutes);
+      const list = await request(app).get(
+        `/api/packages?accountHashes=${ACCOUNT}&includeWarnings=true`,
+      );
+      expect(list.body.packages).toHaveLength(4);
+      expect(list.body.warnings).toEqual([
+        { id: "vanished", reason: "file_missing" },
+      ]);
+    } finally {
+      setTaskStore(new MemoryTaskStore(tasks));
+    }
+  });
+});
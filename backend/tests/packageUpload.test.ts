@@ -0,0 +1,200 @@
+import { describe, it, expect, beforeEach, afterEach } from "vitest";
+import AdmZip from "adm-zip";
+import express from "express";
+import request from "supertest";
+import fs from "fs";
+import plist from "plist";
+import { config, MAX_UPLOADS_PER_ACCOUNT } from "../src/config.js";
+import uploadRoutes from "../src/routes/uploads.js";
+import {
+  deleteTask,
+  getTask,
+  setTaskStore,
+} from "../src/services/downloadManager.js";
+import { MemoryTaskStore } from "../src/services/taskStore.js";
+
+const ACCOUNT_HASH = "abcdef1234567890abcdef1234567890";
+const OTHER_HASH = "0987654321fedcba0987654321fedcba";
+
+function ipa(bundleID = "com.example.uploaded", version = "2.1") {
+  const zip = new AdmZip();
+  zip.addFile(
+    "Payload/Uploaded.app/Info.plist",
+    Buffer.from(
+      plist.build({
+        CFBundleExecutable: "Uploaded",
+        CFBundleIdentifier: bundleID,
+        CFBundleShortVersionString: version,
+        CFBundleDisplayName: "Uploaded",
+      }),
+    ),
+  );
+  zip.addFile("Payload/Uploaded.app/Uploaded", Buffer.from("executable"));
+  return zip.toBuffer();
+}
+
+function metadataHeader(metadata: unknown) {
+  return Buffer.from(JSON.stringify(metadata)).toString("base64");
+}
+
+describe("package uploads", () => {
+  const app = express();
+  app.use(express.json());
+  app.use("/api", uploadRoutes);
+  const ids: string[] = [];
+  const saved = config.enrichMetadata;
+
+  beforeEach(() => {
+    config.enrichMetadata = "off";
+    setTaskStore(new MemoryTaskStore());
+  });
+
+  afterEach(() => {
+    config.enrichMetadata = saved;
+    for (const id of ids.splice(0)) deleteTask(id);
+  });
+
+  function upload(body: Buffer, metadata?: unknown) {
+    const req = request(app)
+      .post("/api/packages/upload")
+      .set("X-Account-Hash", ACCOUNT_HASH)
+      .set("Content-Type", "application/octet-stream");
+    if (metadata) req.set("X-Upload-Metadata", metadataHeader(metadata));
+    return req.send(body);
+  }
+
+  it("should register an uploaded IPA as a completed package", async () => {
+    const res = await upload(ipa(), { software: { id: 42 } });
+
+    expect(res.status).toBe(201);
+    ids.push(res.body.id);
+    expect(res.body.status).toBe("completed");
+    expect(res.body.origin).toBe("uploaded");
+    expect(res.body.software).toMatchObject({
+      id: 42,
+      bundleID: "com.example.uploaded",
+      version: "2.1",
+      name: "Uploaded",
+    });
+    const task = getTask(res.body.id)!;
+    expect(task.accountHash).toBe(ACCOUNT_HASH);
+    expect(fs.existsSync(task.filePath!)).toBe(true);
+    expect(task.fileSize).toBe(fs.statSync(task.filePath!).size);
+  });
+
+  it("should refuse archives that are not an app or do not match", async () => {
+    const notZip = await upload(Buffer.from("not an ipa"));
+    expect(notZip.status).toBe(422);
+    expect(notZip.body.code).toBe("INVALID_IPA");
+
+    const mismatch = await upload(ipa(), {
+      software: { bundleID: "com.example.other" },
+    });
+    expect(mismatch.status).toBe(422);
+    expect(mismatch.body.code).toBe("BUNDLE_MISMATCH");
+
+    const badSinfs = await upload(ipa(), { sinfs: [{ id: "0" }] });
+    expect(badSinfs.status).toBe(400);
+  });
+
+  it("should accept a chunked upload resumed at its offset", async () => {
+    const data = ipa("com.example.chunked", "1.0");
+    const half = Math.floor(data.length / 2);
+    const started = await request(app)
+      .post("/api/packages/uploads")
+      .set("X-Account-Hash", ACCOUNT_HASH)
+      .send({ size: data.length });
+    expect(started.status).toBe(201);
+    const url = `/api/packages/uploads/${started.body.id}`;
+    const put = (offset: number, chunk: Buffer) =>
+      request(app)
+        .put(url)
+        .set("X-Account-Hash", ACCOUNT_HASH)
+        .set("Content-Type", "application/octet-stream")
+        .set("Upload-Offset", String(offset))
+        .send(chunk);
+
+    expect((await put(0, data.subarray(0, half))).body.received).toBe(half);
+    const skipped = await put(0, data.subarray(half));
+    expect(skipped.status).toBe(409);
+    expect(skipped.body.code).toBe("UPLOAD_OFFSET_MISMATCH");
+    const early = await request(app)
+      .post(`${url}/complete`)
+      .set("X-Account-Hash", ACCOUNT_HASH);
+    expect(early.body.code).toBe("UPLOAD_INCOMPLETE");
+    const other = await request(app).get(url).set("X-Account-Hash", OTHER_HASH);
+    expect(other.status).toBe(403);
+
+    const status = await request(app)
+      .get(url)
+      .set("X-Account-Hash", ACCOUNT_HASH);
+    expect(status.body.received).toBe(half);
+    await put(half, data.subarray(half));
+    const done = await request(app)
+      .post(`${url}/complete`)
+      .set("X-Account-Hash", ACCOUNT_HASH);
+
+    expect(done.status).toBe(201);
+    ids.push(done.body.id);
+    expect(done.body.software.bundleID).toBe("com.example.chunked");
+    expect(
+      (await request(app).get(url).set("X-Account-Hash", ACCOUNT_HASH)).status,
+    ).toBe(404);
+  });
+
+  it("should enforce the declared size and the size limit", async () => {
+    const tooLarge = await request(app)
+      .post("/api/packages/uploads")
+      .set("X-Account-Hash", ACCOUNT_HASH)
+      .send({ size: config.maxDownloadSize + 1 });
+    expect(tooLarge.status).toBe(413);
+
+    const started = await request(app)
+      .post("/api/packages/uploads")
+      .set("X-Account-Hash", ACCOUNT_HASH)
+      .send({ size: 4 });
+    const url = `/api/packages/uploads/${started.body.id}`;
+    const overflow = await request(app)
+      .put(url)
+      .set("X-Account-Hash", ACCOUNT_HASH)
+      .set("Content-Type", "application/octet-stream")
+      .set("Upload-Offset", "0")
+      .send(Buffer.from("more than four bytes"));
+    expect(overflow.status).toBe(413);
+    expect(overflow.body.code).toBe("UPLOAD_TOO_LARGE");
+
+    const cancelled = await request(app)
+      .delete(url)
+      .set("X-Account-Hash", ACCOUNT_HASH);
+    expect(cancelled.status).toBe(204);
+  });
+
+  it("should cap the uploads one account has in progress", async () => {
+    const start = (accountHash: string) =>
+      request(app)
+        .post("/api/packages/uploads")
+        .set("X-Account-Hash", accountHash)
+        .send({ size: 4 });
+    const started: [string, string][] = [];
+    try {
+      for (let i = 0; i < MAX_UPLOADS_PER_ACCOUNT; i++) {
+        const res = await start(ACCOUNT_HASH);
+        expect(res.status).toBe(201);
+        started.push([res.body.id, ACCOUNT_HASH]);
+      }
+      const refused = await start(ACCOUNT_HASH);
+      expect(refused.status).toBe(503);
+      expect(refused.body.code).toBe("TOO_MANY_ACCOUNT_UPLOADS");
+
+      const other = await start(OTHER_HASH);
+      expect(other.status).toBe(201);
+      started.push([other.body.id, OTHER_HASH]);
+    } finally {
+      for (const [id, accountHash] of started) {
+        await request(app)
+          .delete(`/api/packages/uploads/${id}`)
+          .set("X-Account-Hash", accountHash);
+      }
+    }
+  });
+});
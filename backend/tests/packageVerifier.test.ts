@@ -0,0 +1,90 @@
+import { describe, it, expect, beforeAll, afterAll } from "vitest";
+import AdmZip from "adm-zip";
+import crypto from "crypto";
+import fs from "fs";
+import os from "os";
+import path from "path";
+import { hashFile, verifyPackage } from "../src/services/packageVerifier.js";
+
+function writeZip(filePath: string, entries: string[]) {
+  const zip = new AdmZip();
+  for (const entry of entries) zip.addFile(entry, Buffer.from(entry));
+  zip.writeZip(filePath);
+}
+
+describe("packageVerifier", () => {
+  let tmpDir: string;
+  let ipaPath: string;
+
+  beforeAll(() => {
+    tmpDir = fs.mkdtempSync(path.join(os.tmpdir(), "verify-test-"));
+    ipaPath = path.join(tmpDir, "app.ipa");
+    writeZip(ipaPath, ["Payload/Example.app/Info.plist"]);
+  });
+
+  afterAll(() => {
+    fs.rmSync(tmpDir, { recursive: true, force: true });
+  });
+
+  it("should hash files with SHA-256", async () => {
+    const expected = crypto
+      .createHash("sha256")
+      .update(fs.readFileSync(ipaPath))
+      .digest("hex");
+    expect(await hashFile(ipaPath)).toBe(expected);
+  });
+
+  it("should pass a package matching its recorded size and hash", async () => {
+    const report = await verifyPackage(ipaPath, {
+      fileSize: fs.statSync(ipaPath).size,
+      sha256: await hashFile(ipaPath),
+    });
+    expect(report.ok).toBe(true);
+    expect(report.validZip).toBe(true);
+    expect(report.problems).toEqual([]);
+  });
+
+  it("should skip expectations that were never recorded", async () => {
+    const report = await verifyPackage(ipaPath, {});
+    expect(report.ok).toBe(true);
+  });
+
+  it("should report truncated files", async () => {
+    const truncated = path.join(tmpDir, "truncated.ipa");
+    const data = fs.readFileSync(ipaPath);
+    fs.writeFileSync(truncated, data.subarray(0, data.length - 10));
+
+    const report = await verifyPackage(truncated, {
+      fileSize: data.length,
+      sha256: await hashFile(ipaPath),
+    });
+    expect(report.ok).toBe(false);
+    expect(report.validZip).toBe(false);
+    expect(report.problems).toHaveLength(3);
+  });
+
+  it("should expect SC_Info sinfs when they were injected", async () => {
+    const missing = await verifyPackage(ipaPath, { sinfs: 1 });
+    expect(missing.ok).toBe(false);
+    expect(missing.sinfEntries).toBe(0);
+
+    const signed = path.join(tmpDir, "signed.ipa");
+    writeZip(signed, [
+      "Payload/Example.app/Info.plist",
+      "Payload/Example.app/SC_Info/Example.sinf",
+    ]);
+    const report = await verifyPackage(signed, { sinfs: 1 });
+    expect(report.ok).toBe(true);
+    expect(report.sinfEntries).toBe(1);
+  });
+
+  it("should reject zips without an app bundle", async () => {
+    const notIpa = path.join(tmpDir, "plain.zip");
+    writeZip(notIpa, ["readme.txt"]);
+
+    const report = await verifyPackage(notIpa, {});
+    expect(report.ok).toBe(false);
+    expect(report.validZip).toBe(false);
+    expect(report.entries).toBe(1);
+  });
+});
@@ -0,0 +1,131 @@
+import { describe, it, expect } from "vitest";
+import express from "express";
+import request from "supertest";
+import { MAX_PAGE_SIZE } from "../src/config.js";
+import {
+  changedSince,
+  paginate,
+  parsePageParams,
+  parseSince,
+  setServerTime,
+} from "../src/utils/pagination.js";
+
+const items = Array.from({ length: 5 }, (_, i) => ({
+  id: `task-${i}`,
+  createdAt: `2026-01-0${5 - i}T00:00:00.000Z`,
+}));
+
+describe("paginate", () => {
+  it("should return everything, oldest first, without a limit", () => {
+    const page = paginate(items, {});
+    expect(page.items.map((t) => t.id)).toEqual([
+      "task-4",
+      "task-3",
+      "task-2",
+      "task-1",
+      "task-0",
+    ]);
+    expect(page.nextCursor).toBeUndefined();
+  });
+
+  it("should walk every item exactly once through the cursors", async () => {
+    const app = express();
+    app.get("/list", (req, res) => {
+      const params = parsePageParams(req, res);
+      if (!params) return;
+      res.json(paginate(items, params));
+    });
+
+    const seen: string[] = [];
+    let cursor: string | undefined;
+    do {
+      const res = await request(app)
+        .get("/list")
+        .query(cursor ? { limit: 2, cursor } : { limit: 2 });
+      expect(res.status).toBe(200);
+      seen.push(...res.body.items.map((t: { id: string }) => t.id));
+      cursor = res.body.nextCursor;
+    } while (cursor);
+
+    expect(seen).toHaveLength(items.length);
+    expect(new Set(seen).size).toBe(items.length);
+  });
+
+  it("should resume after a cursor even if its item was deleted", () => {
+    const first = paginate(items, { limit: 2 });
+    const remaining = items.filter((t) => t.id !== first.items[1].id);
+    const app = express();
+    app.get("/list", (req, res) => {
+      const params = parsePageParams(req, res);
+      if (!params) return;
+      res.json(paginate(remaining, params));
+    });
+
+    return request(app)
+      .get("/list")
+      .query({ limit: 10, cursor: first.nextCursor })
+      .expect(200)
+      .then((res) => {
+        expect(res.body.items.map((t: { id: string }) => t.id)).toEqual([
+          "task-2",
+          "task-1",
+          "task-0",
+        ]);
+      });
+  });
+});
+
+describe("parsePageParams", () => {
+  const app = express();
+  app.get("/list", (req, res) => {
+    const params = parsePageParams(req, res);
+    if (params) res.json(params);
+  });
+
+  it("should reject out-of-range limits and garbage cursors", async () => {
+    for (const limit of ["0", "abc", String(MAX_PAGE_SIZE + 1)]) {
+      const res = await request(app).get(`/list?limit=${limit}`);
+      expect(res.status).toBe(400);
+      expect(res.body.code).toBe("INVALID_LIMIT");
+    }
+    const res = await request(app).get("/list?cursor=not-a-cursor");
+    expect(res.status).toBe(400);
+    expect(res.body.code).toBe("INVALID_CURSOR");
+  });
+});
+
+describe("since", () => {
+  const tasks = [
+    { id: "old", createdAt: "2026-01-01T00:00:00.000Z" },
+    {
+      id: "updated",
+      createdAt: "2026-01-01T00:00:00.000Z",
+      updatedAt: "2026-01-03T00:00:00.000Z",
+    },
+    { id: "new", createdAt: "2026-01-02T00:00:00.000Z" },
+  ];
+  const app = express();
+  app.get("/list", (req, res) => {
+    const since = parseSince(req, res);
+    if (since === null) return;
+    setServerTime(res);
+    res.json(changedSince(tasks, since).map((t) => t.id));
+  });
+
+  it("should list tasks created or updated since the given time", async () => {
+    const all = await request(app).get("/list");
+    expect(all.body).toEqual(["old", "updated", "new"]);
+    expect(Date.parse(all.headers["x-server-time"])).not.toBeNaN();
+
+    const iso = await request(app).get("/list?since=2026-01-02T00:00:00.000Z");
+    expect(iso.body).toEqual(["updated", "new"]);
+    const ms = Date.parse("2026-01-02T12:00:00.000Z");
+    expect((await request(app).get(`/list?since=${ms}`)).body).toEqual([
+      "updated",
+    ]);
+
+    const bad = await request(app).get("/list?since=yesterday");
+    expect(bad.status).toBe(400);
+    expect(bad.body.code).toBe("INVALID_SINCE");
+  });
+});
@@ -0,0 +1,67 @@
+import { describe, it, expect, vi, beforeEach, afterEach } from "vitest";
+import { config } from "../src/config.js";
+import {
+  deleteTask,
+  expirePausedTasks,
+  getTask,
+  pauseTask,
+  sanitizeTaskForResponse,
+  setTaskStore,
+} from "../src/services/downloadManager.js";
+import { MemoryTaskStore } from "../src/services/taskStore.js";
+import { downloadsViaFetch } from "./helpers/downloadNetwork.js";
+import { startDownload } from "./helpers/tasks.js";
+
+const HOUR = 60 * 60 * 1000;
+describe("paused task expiry", () => {
+  const saved = config.pauseMaxAgeHours;
+  let id: string;
+
+  beforeEach(() => {
+    config.pauseMaxAgeHours = 2;
+    setTaskStore(new MemoryTaskStore());
+    downloadsViaFetch();
+  });
+
+  afterEach(() => {
+    config.pauseMaxAgeHours = saved;
+    deleteTask(id);
+    vi.restoreAllMocks();
+  });
+
+  it("should resume a pause older than the limit and show owners the deadline", async () => {
+    // A CDN that never answers, so the task stays downloading
+    const fetchSpy = vi
+      .spyOn(globalThis, "fetch")
+      .mockImplementation(() => new Promise(() => {}));
+    id = startDownload("com.example.pause");
+    await vi.waitFor(() => expect(fetchSpy).toHaveBeenCalledTimes(1));
+    pauseTask(id);
+
+    const task = getTask(id)!;
+    const deadline = new Date(task.pausedAt!).getTime() + 2 * HOUR;
+    expect(
+      sanitizeTaskForResponse(task, { owner: true }).pauseExpiresAt,
+    ).toBe(new Date(deadline).toISOString());
+    expect(sanitizeTaskForResponse(task).pauseExpiresAt).toBeUndefined();
+
+    expirePausedTasks(deadline - 1000);
+    expect(getTask(id)?.status).toBe("paused");
+    expirePausedTasks(deadline + 1000);
+    expect(getTask(id)?.status).not.toBe("paused");
+    expect(getTask(id)?.pausedAt).toBeUndefined();
+    await vi.waitFor(() => expect(fetchSpy).toHaveBeenCalledTimes(2));
+  });
+
+  it("should fail a pause whose URL already expired", async () => {
+    vi.spyOn(globalThis, "fetch").mockImplementation(
+      async () => new Response(null, { status: 403 }),
+    );
+    id = startDownload("com.example.pause");
+    await vi.waitFor(() => expect(getTask(id)?.status).toBe("paused"));
+
+    expirePausedTasks(Date.now() + 3 * HOUR);
+    expect(getTask(id)?.status).toBe("failed");
+    expect(getTask(id)?.errorCode).toBe("download_url_expired");
+  });
+});
@@ -0,0 +1,66 @@
+import { describe, it, expect, afterEach } from "vitest";
+import crypto from "crypto";
+import { config } from "../src/config.js";
+import {
+  getEncryptionKeys,
+  openSecrets,
+  sealSecrets,
+} from "../src/utils/persistEncryption.js";
+
+const secrets = {
+  downloadURL: "https://iosapps.itunes.apple.com/app.ipa?token=secret",
+  sinfs: [{ id: 0, sinf: "c2luZg==" }],
+};
+
+describe("persistEncryption", () => {
+  const originalKeys = config.persistEncryptionKeys;
+
+  afterEach(() => {
+    config.persistEncryptionKeys = originalKeys;
+  });
+
+  it("should round-trip secrets and never store them in plaintext", () => {
+    const key = crypto.randomBytes(32);
+    const sealed = sealSecrets("task-1", secrets, key);
+    expect(JSON.stringify(sealed)).not.toContain("token=secret");
+    expect(openSecrets("task-1", sealed, [key])).toEqual(secrets);
+  });
+
+  it("should use a fresh nonce for every record", () => {
+    const key = crypto.randomBytes(32);
+    const a = sealSecrets("task-1", secrets, key);
+    const b = sealSecrets("task-1", secrets, key);
+    expect(a.nonce).not.toBe(b.nonce);
+    expect(a.data).not.toBe(b.data);
+  });
+
+  it("should reject the wrong key, a swapped task id and tampering", () => {
+    const key = crypto.randomBytes(32);
+    const sealed = sealSecrets("task-1", secrets, key);
+    expect(openSecrets("task-1", sealed, [crypto.randomBytes(32)])).toBeNull();
+    expect(openSecrets("task-2", sealed, [key])).toBeNull();
+    const data = Buffer.from(sealed.data, "base64");
+    data[0] ^= 1;
+    expect(
+      openSecrets("task-1", { ...sealed, data: data.toString("base64") }, [
+        key,
+      ]),
+    ).toBeNull();
+    expect(openSecrets("task-1", "garbage", [key])).toBeNull();
+  });
+
+  it("should decrypt with any configured key to allow rotation", () => {
+    const oldKey = crypto.randomBytes(32);
+    const newKey = crypto.randomBytes(32);
+    const sealed = sealSecrets("task-1", secrets, oldKey);
+    expect(openSecrets("task-1", sealed, [newKey, oldKey])).toEqual(secrets);
+  });
+
+  it("should ignore keys that are not 32 bytes", () => {
+    const key = crypto.randomBytes(32).toString("base64");
+    config.persistEncryptionKeys = ["c2hvcnQ=", key];
+    const keys = getEncryptionKeys();
+    expect(keys).toHaveLength(1);
+    expect(keys[0].toString("base64")).toBe(key);
+  });
+});
@@ -0,0 +1,254 @@
+import { describe, it, expect, vi, afterEach } from "vitest";
+import {
+  createTask,
+  deleteTask,
+  getTask,
+  setTaskStore,
+  validateDownloadURL,
+} from "../src/services/downloadManager.js";
+import { isNonPublicAddress } from "../src/services/dnsCache.js";
+import {
+  createPinnedFetch,
+  RedirectRefusedError,
+  setPinnedNetwork,
+  UnsafeAddressError,
+  type PinnedTransport,
+} from "../src/services/pinnedFetch.js";
+import { MemoryTaskStore } from "../src/services/taskStore.js";
+import type { Software } from "../src/types/index.js";
+
+const PUBLIC = "17.253.144.10";
+const pinnedFetch = createPinnedFetch(validateDownloadURL);
+
+function resolver(addresses: Record<string, string[]>) {
+  return async (hostname: string) => addresses[hostname] ?? [PUBLIC];
+}
+
+describe("pinned download fetch", () => {
+  afterEach(() => {
+    setPinnedNetwork();
+  });
+
+  it("should classify private, loopback and mapped addresses", () => {
+    for (const address of [
+      "10.1.2.3",
+      "127.0.0.1",
+      "169.254.169.254",
+      "::1",
+      "::",
+      "fd00::1",
+      "::ffff:192.168.1.1",
+    ]) {
+      expect(isNonPublicAddress(address), address).toBe(true);
+    }
+    expect(isNonPublicAddress(PUBLIC)).toBe(false);
+    expect(isNonPublicAddress("2620:149:a44::1")).toBe(false);
+  });
+
+  it("should refuse a host if any resolved address is not public", async () => {
+    const transport = vi.fn<PinnedTransport>();
+    setPinnedNetwork(
+      resolver({ "rebind.apple.com": [PUBLIC, "10.0.0.5"] }),
+      transport,
+    );
+    await expect(
+      pinnedFetch("https://rebind.apple.com/app.ipa"),
+    ).rejects.toBeInstanceOf(UnsafeAddressError);
+    expect(transport).not.toHaveBeenCalled();
+  });
+
+  it("should connect to the validated address and re-check redirects", async () => {
+    const transport = vi.fn<PinnedTransport>(async (url) =>
+      url.hostname === "a.apple.com"
+        ? new Response(null, {
+            status: 302,
+            headers: { location: "https://b.apple.com/app.ipa" },
+          })
+        : new Response("ipa"),
+    );
+    setPinnedNetwork(resolver({ "b.apple.com": ["17.0.0.2"] }), transport);
+
+    const res = await pinnedFetch("https://a.apple.com/app.ipa", {
+      headers: { "X-Test": "1" },
+    });
+    expect(await res.text()).toBe("ipa");
+    expect(res.redirected).toBe(true);
+    expect(res.url).toBe("https://b.apple.com/app.ipa");
+    expect(
+      transport.mock.calls.map(([url, address]) => [url.href, address]),
+    ).toEqual([
+      ["https://a.apple.com/app.ipa", PUBLIC],
+      ["https://b.apple.com/app.ipa", "17.0.0.2"],
+    ]);
+    expect(transport.mock.calls[1][2].headers["x-test"]).toBe("1");
+
+    // Redirects to internal hosts or off Apple's domains are refused
+    setPinnedNetwork(resolver({ "b.apple.com": ["127.0.0.1"] }), transport);
+    await expect(
+      pinnedFetch("https://a.apple.com/app.ipa"),
+    ).rejects.toBeInstanceOf(UnsafeAddressError);
+    transport.mockImplementationOnce(
+      async () =>
+        new Response(null, {
+          status: 301,
+          headers: { location: "https://metadata.internal/" },
+        }),
+    );
+    await expect(pinnedFetch("https://a.apple.com/app.ipa")).rejects.toThrow(
+      /Apple domain/,
+    );
+    // ... as are downgrades to plain http
+    transport.mockImplementationOnce(
+      async () =>
+        new Response(null, {
+          status: 302,
+          headers: { location: "http://b.apple.com/app.ipa" },
+        }),
+    );
+    await expect(
+      pinnedFetch("https://a.apple.com/app.ipa"),
+    ).rejects.toBeInstanceOf(RedirectRefusedError);
+  });
+
+  it("should drop credentials on a cross-origin redirect", async () => {
+    const moves: Record<string, string> = {
+      "https://a.apple.com/app.ipa": "/moved/app.ipa",
+      "https://c.apple.com/app.ipa": "https://b.apple.com/app.ipa",
+    };
+    const transport = vi.fn<PinnedTransport>(async (url) =>
+      moves[url.href]
+        ? new Response(null, {
+            status: 302,
+            headers: { location: moves[url.href] },
+          })
+        : new Response("ipa"),
+    );
+    setPinnedNetwork(resolver({}), transport);
+
+    await pinnedFetch("https://a.apple.com/app.ipa", {
+      headers: {
+        Authorization: "Bearer secret",
+        Cookie: "session=1",
+        "Proxy-Authorization": "Basic abc",
+        "X-Test": "1",
+      },
+    });
+    // Same origin: kept
+    expect(transport.mock.calls[1][0].href).toBe(
+      "https://a.apple.com/moved/app.ipa",
+    );
+    expect(transport.mock.calls[1][2].headers.authorization).toBe(
+      "Bearer secret",
+    );
+
+    transport.mockClear();
+    await pinnedFetch("https://c.apple.com/app.ipa", {
+      headers: {
+        Authorization: "Bearer secret",
+        Cookie: "session=1",
+        "Proxy-Authorization": "Basic abc",
+        "X-Test": "1",
+      },
+    });
+    expect(transport.mock.calls[1][0].hostname).toBe("b.apple.com");
+    expect(transport.mock.calls[1][2].headers).toEqual({ "x-test": "1" });
+  });
+
+  it("should give up on a redirect loop", async () => {
+    const transport = vi.fn<PinnedTransport>(
+      async () =>
+        new Response(null, {
+          status: 302,
+          headers: { location: "https://a.apple.com/app.ipa" },
+        }),
+    );
+    setPinnedNetwork(resolver({}), transport);
+    await expect(
+      pinnedFetch("https://a.apple.com/app.ipa"),
+    ).rejects.toBeInstanceOf(RedirectRefusedError);
+    // The first request plus MAX_DOWNLOAD_REDIRECTS hops
+    expect(transport).toHaveBeenCalledTimes(6);
+  });
+
+  it("should fail a download whose host resolves privately", async () => {
+    setTaskStore(new MemoryTaskStore());
+    setPinnedNetwork(async () => ["192.168.1.10"], vi.fn<PinnedTransport>());
+    const id = createTask({
+      software: {
+        id: 1,
+        bundleID: "com.example.ssrf",
+        version: "1.0",
+      } as Software,
+      accountHash: "abcdef1234567890abcdef1234567890",
+      downloadURL: "https://example.apple.com/app.ipa",
+      sinfs: [],
+    }).id;
+    await vi.waitFor(() => expect(getTask(id)?.status).toBe("failed"));
+    expect(getTask(id)?.errorCode).toBe("blocked_address");
+    deleteTask(id);
+  });
+
+  it("should fail a download redirected to a blocked host", async () => {
+    setTaskStore(new MemoryTaskStore());
+    setPinnedNetwork(
+      resolver({}),
+      vi.fn<PinnedTransport>(
+        async () =>
+          new Response(null, {
+            status: 302,
+            headers: { location: "https://169.254.169.254/latest/" },
+          }),
+      ),
+    );
+    const id = createTask({
+      software: {
+        id: 1,
+        bundleID: "com.example.redirect",
+        version: "1.0",
+      } as Software,
+      accountHash: "abcdef1234567890abcdef1234567890",
+      downloadURL: "https://example.apple.com/app.ipa",
+      sinfs: [],
+    }).id;
+    await vi.waitFor(() => expect(getTask(id)?.status).toBe("failed"));
+    expect(getTask(id)?.errorCode).toBe("redirect_rejected");
+    expect(getTask(id)?.errorDetail).toMatch(
+      /Redirect to 169\.254\.169\.254 refused/,
+    );
+    deleteTask(id);
+  });
+
+  it("should record where a download was redirected", async () => {
+    setTaskStore(new MemoryTaskStore());
+    setPinnedNetwork(
+      resolver({}),
+      vi.fn<PinnedTransport>(async (url) =>
+        url.hostname === "example.apple.com"
+          ? new Response(null, {
+              status: 302,
+              headers: { location: "https://edge2.apple.com/app.ipa?sig=s" },
+            })
+          : new Response("ipa"),
+      ),
+    );
+    const id = createTask({
+      software: {
+        id: 1,
+        bundleID: "com.example.edge",
+        version: "1.0",
+      } as Software,
+      accountHash: "abcdef1234567890abcdef1234567890",
+      downloadURL: "https://example.apple.com/app.ipa",
+      sinfs: [],
+    }).id;
+    await vi.waitFor(() => expect(getTask(id)?.status).toBe("failed"));
+    const redirects = getTask(id)!.events!.filter(
+      (e) => e.type === "redirected",
+    );
+    // Once, however many requests the attempt made, and without the query
+    expect(redirects.map((e) => e.detail)).toEqual([
+      "https://edge2.apple.com/app.ipa",
+    ]);
+    deleteTask(id);
+  });
+});
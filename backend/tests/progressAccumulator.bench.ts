@@ -0,0 +1,40 @@
+import { bench, describe } from "vitest";
+import { ProgressAccumulator } from "../src/services/progressAccumulator.js";
+
+// Simulates 30 concurrent downloads of 64 KB chunks, each with an SSE
+// listener that serializes the task on every notification.
+const DOWNLOADS = 30;
+const CHUNKS = 2_000;
+const CHUNK = 64 * 1024;
+const TOTAL = CHUNKS * CHUNK;
+
+function makeTask() {
+  return { id: "bench", progress: 0, speed: "0 B/s", status: "downloading" };
+}
+
+describe("progress notifications", () => {
+  bench("notify on every chunk (before)", () => {
+    for (let d = 0; d < DOWNLOADS; d++) {
+      const task = makeTask();
+      let downloaded = 0;
+      for (let i = 0; i < CHUNKS; i++) {
+        downloaded += CHUNK;
+        task.progress = Math.round((downloaded / TOTAL) * 100);
+        JSON.stringify(task);
+      }
+    }
+  });
+
+  bench("coalesced via ProgressAccumulator (after)", () => {
+    for (let d = 0; d < DOWNLOADS; d++) {
+      const task = makeTask();
+      const acc = new ProgressAccumulator();
+      for (let i = 0; i < CHUNKS; i++) {
+        const update = acc.add(CHUNK, TOTAL);
+        if (!update) continue;
+        task.progress = update.progress;
+        JSON.stringify(task);
+      }
+    }
+  });
+});
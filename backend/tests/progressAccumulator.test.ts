@@ -0,0 +1,26 @@
+import { describe, it, expect } from "vitest";
+import { ProgressAccumulator } from "../src/services/progressAccumulator.js";
+
+describe("ProgressAccumulator", () => {
+  it("only publishes when the whole percent changes", () => {
+    const clock = 0;
+    const acc = new ProgressAccumulator(() => clock);
+
+    expect(acc.add(1, 1000)).toEqual({ progress: 0, bytesPerSec: null });
+    // Still 0% and no new speed window: nothing to publish
+    expect(acc.add(1, 1000)).toBeNull();
+    expect(acc.add(8, 1000)).toEqual({ progress: 1, bytesPerSec: null });
+    expect(acc.downloaded).toBe(10);
+  });
+
+  it("publishes a speed sample every 500ms", () => {
+    let clock = 0;
+    const acc = new ProgressAccumulator(() => clock);
+
+    acc.add(100, 0);
+    clock = 250;
+    expect(acc.add(100, 0)).toBeNull();
+    clock = 500;
+    expect(acc.add(300, 0)).toEqual({ progress: 0, bytesPerSec: 1000 });
+  });
+});
@@ -0,0 +1,120 @@
+import { describe, it, expect, vi, beforeEach, afterEach } from "vitest";
+import express from "express";
+import request from "supertest";
+import fs from "fs";
+import os from "os";
+import path from "path";
+import { config } from "../src/config.js";
+import adminRoutes from "../src/routes/admin.js";
+import downloadRoutes from "../src/routes/downloads.js";
+import packageRoutes from "../src/routes/packages.js";
+import settingsRoutes from "../src/routes/settings.js";
+import {
+  deleteTask,
+  getTask,
+  setReadOnly,
+  setTaskStore,
+} from "../src/services/downloadManager.js";
+import { MemoryTaskStore } from "../src/services/taskStore.js";
+import { downloadsViaFetch } from "./helpers/downloadNetwork.js";
+import { startDownload } from "./helpers/tasks.js";
+
+const ADMIN_TOKEN = "test-admin-token";
+const ACCOUNT_HASH = "abcdef1234567890abcdef1234567890";
+
+describe("read-only maintenance mode", () => {
+  const app = express();
+  app.use(express.json());
+  app.use("/api", downloadRoutes);
+  app.use("/api", packageRoutes);
+  app.use("/api", settingsRoutes);
+  app.use("/api", adminRoutes);
+  const saved = config.adminToken;
+  let id: string | undefined;
+
+  function toggle(enabled: unknown) {
+    return request(app)
+      .put("/api/admin/read-only")
+      .set("Authorization", `Bearer ${ADMIN_TOKEN}`)
+      .send({ enabled });
+  }
+
+  beforeEach(() => {
+    config.adminToken = ADMIN_TOKEN;
+    setTaskStore(new MemoryTaskStore());
+    downloadsViaFetch();
+  });
+
+  afterEach(() => {
+    setReadOnly(false);
+    config.adminToken = saved;
+    if (id) deleteTask(id);
+    vi.restoreAllMocks();
+  });
+
+  it("should toggle through the admin API and show in settings", async () => {
+    expect((await toggle("yes")).status).toBe(400);
+
+    const on = await toggle(true);
+    expect(on.body).toEqual({ readOnly: true });
+    expect((await request(app).get("/api/settings")).body.readOnly).toBe(true);
+
+    await toggle(false);
+    expect((await request(app).get("/api/settings")).body.readOnly).toBe(false);
+  });
+
+  it("should refuse writes and keep serving reads", async () => {
+    await toggle(true);
+
+    const created = await request(app).post("/api/downloads").send({});
+    expect(created.status).toBe(503);
+    expect(created.body.code).toBe("READ_ONLY");
+    const deleted = await request(app)
+      .delete("/api/packages/some-id")
+      .set("X-Account-Hash", ACCOUNT_HASH);
+    expect(deleted.status).toBe(503);
+
+    const listed = await request(app).get(
+      `/api/packages?accountHashes=${ACCOUNT_HASH}`,
+    );
+    expect(listed.status).toBe(200);
+  });
+
+  it("should hold queued downloads until the mode is lifted", async () => {
+    const fetchSpy = vi
+      .spyOn(globalThis, "fetch")
+      .mockImplementation(async () => new Response("ipa"));
+    setReadOnly(true);
+    id = startDownload("com.example.readonly");
+    expect(getTask(id)?.status).toBe("pending");
+    expect(fetchSpy).not.toHaveBeenCalled();
+
+    setReadOnly(false);
+    await vi.waitFor(() => expect(getTask(id!)?.status).toBe("completed"));
+  });
+
+  it("should skip startup cleanup when started read-only", async () => {
+    const dataDir = fs.mkdtempSync(path.join(os.tmpdir(), "asspp-readonly-"));
+    try {
+      const orphan = path.join(
+        dataDir,
+        "packages",
+        ACCOUNT_HASH,
+        "com.example.app",
+        "1.0",
+        "orphan.ipa",
+      );
+      fs.mkdirSync(path.dirname(orphan), { recursive: true });
+      fs.writeFileSync(orphan, "ipa-bytes");
+
+      vi.stubEnv("DATA_DIR", dataDir);
+      vi.stubEnv("READ_ONLY", "true");
+      vi.resetModules();
+      await import("../src/services/downloadManager.js");
+      expect(fs.existsSync(orphan)).toBe(true);
+    } finally {
+      vi.unstubAllEnvs();
+      fs.rmSync(dataDir, { recursive: true, force: true });
+    }
+  });
+});
@@ -0,0 +1,103 @@
+import { describe, it, expect, vi, beforeEach, afterEach } from "vitest";
+import fs from "fs";
+import path from "path";
+import AdmZip from "adm-zip";
+import { config } from "../src/config.js";
+import {
+  createTask,
+  deleteTask,
+  findDuplicateTask,
+  getTask,
+  setTaskStore,
+} from "../src/services/downloadManager.js";
+import { MemoryTaskStore } from "../src/services/taskStore.js";
+import type { Software } from "../src/types/index.js";
+import { downloadsViaFetch } from "./helpers/downloadNetwork.js";
+import { completedTask } from "./helpers/tasks.js";
+
+const ACCOUNT = "abcdef1234567890abcdef1234567890";
+const software = {
+  id: 1,
+  bundleID: "com.example.replace",
+  version: "1.0",
+} as Software;
+const oldPath = path.resolve(
+  config.dataDir,
+  "packages",
+  ACCOUNT,
+  software.bundleID,
+  software.version,
+  "old.ipa",
+);
+
+function ipaBytes(): Uint8Array {
+  const zip = new AdmZip();
+  zip.addFile("Payload/Example.app/Info.plist", Buffer.from("plist"));
+  return new Uint8Array(zip.toBuffer());
+}
+
+function replace() {
+  return createTask({
+    software,
+    accountHash: ACCOUNT,
+    downloadURL: "https://example.apple.com/app.ipa",
+    sinfs: [],
+    replace: true,
+  }).id;
+}
+
+describe("replace=true downloads", () => {
+  let id: string;
+
+  beforeEach(() => {
+    setTaskStore(
+      new MemoryTaskStore([
+        completedTask("old", { software, filePath: oldPath }, ipaBytes()),
+      ]),
+    );
+    downloadsViaFetch();
+  });
+
+  afterEach(() => {
+    deleteTask(id);
+    deleteTask("old");
+    vi.restoreAllMocks();
+  });
+
+  it("should only skip the completed duplicate when replacing", () => {
+    const { bundleID, version } = software;
+    expect(findDuplicateTask(ACCOUNT, bundleID, version)?.id).toBe("old");
+    expect(findDuplicateTask(ACCOUNT, bundleID, version, true)).toBeUndefined();
+  });
+
+  it("should swap in the new package and drop the old task", async () => {
+    vi.spyOn(globalThis, "fetch").mockImplementation(
+      async () => new Response(ipaBytes()),
+    );
+    id = replace();
+    expect(getTask(id)?.replaces).toEqual(["old"]);
+    // The old package stays until the new one is complete
+    expect(getTask("old")?.status).toBe("completed");
+
+    await vi.waitFor(() => expect(getTask(id)?.status).toBe("completed"));
+    expect(getTask("old")).toBeUndefined();
+    expect(fs.existsSync(oldPath)).toBe(false);
+    expect(getTask(id)?.filePath).not.toBe(oldPath);
+    expect(fs.existsSync(getTask(id)!.filePath!)).toBe(true);
+    expect(getTask(id)?.events?.at(-1)).toMatchObject({
+      type: "completed",
+      detail: "replaced old",
+    });
+  });
+
+  it("should keep the old package when the new one does not verify", async () => {
+    vi.spyOn(globalThis, "fetch").mockImplementation(
+      async () => new Response("not a zip"),
+    );
+    id = replace();
+    await vi.waitFor(() => expect(getTask(id)?.status).toBe("failed"));
+    expect(getTask(id)?.errorCode).toBe("zip_invalid");
+    expect(getTask("old")?.status).toBe("completed");
+    expect(fs.existsSync(oldPath)).toBe(true);
+  });
+});
@@ -0,0 +1,147 @@
+import { describe, it, expect, afterEach } from "vitest";
+import fs from "fs";
+import { config } from "../src/config.js";
+import {
+  addProgressListener,
+  deleteTask,
+  getTask,
+  runCountCleanup,
+  setTaskStore,
+} from "../src/services/downloadManager.js";
+import { MemoryTaskStore } from "../src/services/taskStore.js";
+import { completedTask } from "./helpers/tasks.js";
+
+const ACCOUNT_HASH = "abcdef1234567890abcdef1234567890";
+const OTHER_HASH = "0987654321fedcba0987654321fedcba";
+
+function completed(id: string, accountHash: string, day: number) {
+  const createdAt = new Date(Date.UTC(2024, 0, day)).toISOString();
+  return completedTask(id, { accountHash, createdAt }, "ipa");
+}
+
+describe("per-account retention", () => {
+  afterEach(() => {
+    config.autoCleanupMaxPerAccount = 0;
+    config.autoCleanupAccountLimits = new Map();
+    for (const id of ["a1", "a2", "a3", "b1", "b2"]) deleteTask(id);
+  });
+
+  it("should delete the oldest packages beyond the limit", () => {
+    setTaskStore(
+      new MemoryTaskStore([
+        completed("a1", ACCOUNT_HASH, 1),
+        completed("a3", ACCOUNT_HASH, 3),
+        completed("a2", ACCOUNT_HASH, 2),
+        completed("b1", OTHER_HASH, 1),
+      ]),
+    );
+    const oldest = getTask("a1")!.filePath!;
+    config.autoCleanupMaxPerAccount = 2;
+    runCountCleanup();
+
+    expect(getTask("a1")?.status).toBe("removed");
+    expect(fs.existsSync(oldest)).toBe(false);
+    expect(getTask("a2")).toBeDefined();
+    expect(getTask("a3")).toBeDefined();
+    expect(getTask("b1")).toBeDefined();
+  });
+
+  it("should keep the packages that finished last", () => {
+    // a1 was started first but took the longest to download
+    const slow = completed("a1", ACCOUNT_HASH, 1);
+    slow.events = [
+      { type: "created", at: slow.createdAt },
+      { type: "completed", at: new Date(Date.UTC(2024, 0, 9)).toISOString() },
+    ];
+    setTaskStore(
+      new MemoryTaskStore([
+        slow,
+        completed("a2", ACCOUNT_HASH, 2),
+        completed("a3", ACCOUNT_HASH, 3),
+      ]),
+    );
+    config.autoCleanupMaxPerAccount = 2;
+    runCountCleanup();
+
+    expect(getTask("a1")?.status).toBe("completed");
+    expect(getTask("a2")?.status).toBe("removed");
+    expect(getTask("a3")?.status).toBe("completed");
+  });
+
+  it("should leave everything alone when disabled", () => {
+    setTaskStore(
+      new MemoryTaskStore([
+        completed("a1", ACCOUNT_HASH, 1),
+        completed("a2", ACCOUNT_HASH, 2),
+      ]),
+    );
+    runCountCleanup();
+    expect(getTask("a1")).toBeDefined();
+  });
+
+  it("should apply per-account overrides", () => {
+    setTaskStore(
+      new MemoryTaskStore([
+        completed("a1", ACCOUNT_HASH, 1),
+        completed("a2", ACCOUNT_HASH, 2),
+        completed("b1", OTHER_HASH, 1),
+        completed("b2", OTHER_HASH, 2),
+      ]),
+    );
+    config.autoCleanupMaxPerAccount = 1;
+    config.autoCleanupAccountLimits = new Map([[OTHER_HASH, 0]]);
+    runCountCleanup();
+
+    expect(getTask("a1")?.status).toBe("removed");
+    expect(getTask("a2")?.status).toBe("completed");
+    expect(getTask("b1")?.status).toBe("completed");
+  });
+
+  it("should tell progress listeners the package was removed", () => {
+    setTaskStore(
+      new MemoryTaskStore([
+        completed("a1", ACCOUNT_HASH, 1),
+        completed("a2", ACCOUNT_HASH, 2),
+      ]),
+    );
+    const seen: string[] = [];
+    addProgressListener("a1", (task) => seen.push(task.status));
+    config.autoCleanupMaxPerAccount = 1;
+    runCountCleanup();
+
+    expect(seen).toEqual(["removed"]);
+    const removed = getTask("a1")!;
+    expect(removed.filePath).toBeUndefined();
+    expect(removed.removedReason).toBe("account_limit");
+    expect(removed.events?.at(-1)).toMatchObject({
+      type: "removed",
+      detail: "account_limit",
+    });
+  });
+
+  it("should persist when a removal happened so it survives a restart", () => {
+    const store = new MemoryTaskStore([
+      completed("a1", ACCOUNT_HASH, 1),
+      completed("a2", ACCOUNT_HASH, 2),
+    ]);
+    setTaskStore(store);
+    config.autoCleanupMaxPerAccount = 1;
+    runCountCleanup();
+
+    const updatedAt = getTask("a1")!.updatedAt;
+    expect(updatedAt).toBeDefined();
+    expect(store.records.get("a1")?.updatedAt).toBe(updatedAt);
+    setTaskStore(new MemoryTaskStore([...store.records.values()]));
+    expect(getTask("a1")?.updatedAt).toBe(updatedAt);
+  });
+
+  it("should not report explicit deletions as removals", () => {
+    setTaskStore(new MemoryTaskStore([completed("a1", ACCOUNT_HASH, 1)]));
+    const seen: string[] = [];
+    addProgressListener("a1", (task) => seen.push(task.status));
+    deleteTask("a1");
+
+    expect(getTask("a1")).toBeUndefined();
+    expect(seen).toEqual([]);
+  });
+});
@@ -0,0 +1,210 @@
+import { describe, it, expect, vi, afterEach } from "vitest";
+import express from "express";
+import request from "supertest";
+import { config, UPSTREAM_FAILURE_THRESHOLD } from "../src/config.js";
+import { itunesBreaker } from "../src/services/circuitBreaker.js";
+import searchRoutes from "../src/routes/search.js";
+
+function createApp() {
+  const app = express();
+  app.use("/api", searchRoutes);
+  return app;
+}
+
+function mockItunes(results: object[]) {
+  const body = JSON.stringify({ resultCount: results.length, results });
+  return vi
+    .spyOn(globalThis, "fetch")
+    .mockImplementation(async () => new Response(body));
+}
+
+describe("Search Route", () => {
+  const originalTtl = config.searchCacheTtlSeconds;
+  const originalStorefront = config.defaultStorefront;
+
+  afterEach(() => {
+    vi.restoreAllMocks();
+    config.searchCacheTtlSeconds = originalTtl;
+    config.defaultStorefront = originalStorefront;
+    itunesBreaker.recordSuccess();
+  });
+
+  it("sets Cache-Control and ETag on search results", async () => {
+    mockItunes([{ trackId: 1, bundleId: "com.example.app" }]);
+    config.searchCacheTtlSeconds = 120;
+
+    const res = await request(createApp()).get("/api/search?term=example");
+
+    expect(res.status).toBe(200);
+    expect(res.headers["cache-control"]).toBe("public, max-age=120");
+    expect(res.headers.etag).toBeDefined();
+  });
+
+  it("returns 304 for a repeated lookup with If-None-Match", async () => {
+    mockItunes([{ trackId: 1, bundleId: "com.example.app" }]);
+    const app = createApp();
+
+    const url = "/api/lookup?bundleId=com.example.app";
+    const first = await request(app).get(url);
+    const second = await request(app)
+      .get(url)
+      .set("If-None-Match", first.headers.etag);
+
+    expect(first.status).toBe(200);
+    expect(second.status).toBe(304);
+  });
+
+  it("disables caching when the TTL is 0", async () => {
+    mockItunes([]);
+    config.searchCacheTtlSeconds = 0;
+
+    const res = await request(createApp()).get("/api/search?term=none");
+
+    expect(res.headers["cache-control"]).toBe("no-cache");
+  });
+
+  it("does not mark upstream failures as cacheable", async () => {
+    vi.spyOn(globalThis, "fetch").mockRejectedValue(new Error("down"));
+
+    const res = await request(createApp()).get("/api/search?term=example");
+
+    expect(res.status).toBe(500);
+    expect(res.headers["cache-control"]).toBeUndefined();
+  });
+
+  it("injects DEFAULT_STOREFRONT when country is omitted", async () => {
+    const fetchSpy = mockItunes([]);
+    config.defaultStorefront = "jp";
+
+    const res = await request(createApp()).get("/api/search?term=example");
+
+    expect(res.status).toBe(200);
+    expect(res.headers["x-storefront"]).toBe("jp");
+    expect(String(fetchSpy.mock.calls[0][0])).toContain("country=jp");
+  });
+
+  it("defaults searches to software unless overridden", async () => {
+    const fetchSpy = mockItunes([]);
+    const app = createApp();
+
+    await request(app).get("/api/search?term=example");
+    const defaulted = new URL(String(fetchSpy.mock.calls[0][0]));
+    expect(defaulted.searchParams.get("media")).toBe("software");
+    expect(defaulted.searchParams.get("entity")).toBe("software,iPadSoftware");
+
+    await request(app).get("/api/search?term=example&entity=macSoftware");
+    const overridden = new URL(String(fetchSpy.mock.calls[1][0]));
+    expect(overridden.searchParams.get("entity")).toBe("macSoftware");
+
+    await request(app).get("/api/lookup?id=1");
+    const lookup = new URL(String(fetchSpy.mock.calls[2][0]));
+    expect(lookup.searchParams.has("entity")).toBe(false);
+  });
+
+  it("normalizes and echoes a supplied country code", async () => {
+    const fetchSpy = mockItunes([]);
+
+    const res = await request(createApp()).get(
+      "/api/lookup?bundleId=com.example.app&country=GB",
+    );
+
+    expect(res.status).toBe(200);
+    expect(res.headers["x-storefront"]).toBe("gb");
+    expect(String(fetchSpy.mock.calls[0][0])).toContain("country=gb");
+  });
+
+  it("rejects country codes outside ISO 3166-1 alpha-2", async () => {
+    const fetchSpy = mockItunes([]);
+    const app = createApp();
+
+    for (const country of ["xx", "usa", "u$"]) {
+      const res = await request(app).get(
+        `/api/search?term=example&country=${encodeURIComponent(country)}`,
+      );
+      expect(res.status).toBe(400);
+      expect(res.body.code).toBe("INVALID_COUNTRY");
+    }
+    expect(fetchSpy).not.toHaveBeenCalled();
+  });
+
+  it("fast-fails with 503 once the iTunes circuit is open", async () => {
+    const fetchSpy = vi
+      .spyOn(globalThis, "fetch")
+      .mockResolvedValue(new Response("unavailable", { status: 503 }));
+    const app = createApp();
+
+    for (let i = 0; i < UPSTREAM_FAILURE_THRESHOLD; i++) {
+      const res = await request(app).get("/api/search?term=example");
+      expect(res.status).toBe(500);
+    }
+    const rejected = await request(app).get("/api/lookup?id=1");
+
+    expect(rejected.status).toBe(503);
+    expect(rejected.body.code).toBe("UPSTREAM_UNAVAILABLE");
+    expect(rejected.headers["retry-after"]).toBeDefined();
+    expect(fetchSpy).toHaveBeenCalledTimes(UPSTREAM_FAILURE_THRESHOLD);
+  });
+
+  it("coalesces 50 concurrent identical queries into one upstream hit", async () => {
+    let release!: () => void;
+    const gate = new Promise<void>((resolve) => {
+      release = resolve;
+    });
+    const body = JSON.stringify({
+      resultCount: 1,
+      results: [{ trackId: 1, bundleId: "com.example.app" }],
+    });
+    const fetchSpy = vi
+      .spyOn(globalThis, "fetch")
+      .mockImplementation(async () => {
+        await gate;
+        return new Response(body);
+      });
+    const app = createApp();
+
+    // Same query modulo case, whitespace and parameter order
+    const pending = Array.from({ length: 50 }, (_, i) =>
+      request(app).get(
+        i % 2
+          ? "/api/search?term=Example%20%20App&country=us"
+          : "/api/search?country=us&term=example%20app",
+      ),
+    );
+    await new Promise((resolve) => setTimeout(resolve, 100));
+    release();
+    const responses = await Promise.all(pending);
+
+    expect(fetchSpy).toHaveBeenCalledTimes(1);
+    for (const res of responses) {
+      expect(res.status).toBe(200);
+      expect(res.body[0].bundleID).toBe("com.example.app");
+    }
+  });
+
+  it("propagates a shared failure to all waiters without poisoning later queries", async () => {
+    let release!: () => void;
+    const gate = new Promise<void>((resolve) => {
+      release = resolve;
+    });
+    const fetchSpy = vi
+      .spyOn(globalThis, "fetch")
+      .mockImplementationOnce(async () => {
+        await gate;
+        throw new Error("down");
+      })
+      .mockImplementation(async () => new Response('{"results":[]}'));
+    const app = createApp();
+
+    const pending = Array.from({ length: 5 }, () =>
+      request(app).get("/api/search?term=flaky"),
+    );
+    await new Promise((resolve) => setTimeout(resolve, 100));
+    release();
+    const failed = await Promise.all(pending);
+    const retried = await request(app).get("/api/search?term=flaky");
+
+    for (const res of failed) expect(res.status).toBe(500);
+    expect(retried.status).toBe(200);
+    expect(fetchSpy).toHaveBeenCalledTimes(2);
+  });
+});
@@ -0,0 +1,72 @@
+import { describe, it, expect } from "vitest";
+import { Semaphore } from "../src/utils/semaphore.js";
+
+// A task that runs until its release() is called
+function deferred() {
+  let release!: () => void;
+  const done = new Promise<void>((resolve) => (release = resolve));
+  return { done, release };
+}
+
+describe("Semaphore", () => {
+  it("should run at most `limit` callers at once, in FIFO order", async () => {
+    const semaphore = new Semaphore(() => 1);
+    const order: string[] = [];
+    const first = deferred();
+    const second = deferred();
+
+    const a = semaphore.run(async () => {
+      order.push("a");
+      await first.done;
+    });
+    expect(semaphore.full).toBe(true);
+    const b = semaphore.run(async () => {
+      order.push("b");
+      await second.done;
+    });
+    const c = semaphore.run(async () => order.push("c"));
+    await Promise.resolve();
+    expect(order).toEqual(["a"]);
+    expect(semaphore.waiting).toBe(2);
+
+    first.release();
+    await a;
+    await new Promise((resolve) => setImmediate(resolve));
+    expect(order).toEqual(["a", "b"]);
+
+    second.release();
+    await Promise.all([b, c]);
+    expect(order).toEqual(["a", "b", "c"]);
+    expect(semaphore.full).toBe(false);
+  });
+
+  it("should release the slot when the caller throws", async () => {
+    const semaphore = new Semaphore(() => 1);
+    await expect(
+      semaphore.run(async () => {
+        throw new Error("boom");
+      }),
+    ).rejects.toThrow("boom");
+    await expect(semaphore.run(async () => "ok")).resolves.toBe("ok");
+  });
+
+  it("should follow a changed limit (0 = unlimited)", async () => {
+    let limit = 1;
+    const semaphore = new Semaphore(() => limit);
+    const held = deferred();
+    const running = semaphore.run(() => held.done);
+    let started = false;
+    const waiting = semaphore.run(async () => {
+      started = true;
+    });
+    await Promise.resolve();
+    expect(started).toBe(false);
+
+    limit = 0;
+    await semaphore.run(async () => {});
+    await waiting;
+    expect(started).toBe(true);
+    held.release();
+    await running;
+  });
+});
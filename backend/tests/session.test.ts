@@ -0,0 +1,120 @@
+import { describe, it, expect, beforeEach, afterEach } from "vitest";
+import express from "express";
+import request from "supertest";
+import { config, MAX_SESSIONS } from "../src/config.js";
+import packageRoutes from "../src/routes/packages.js";
+import sessionRoutes from "../src/routes/session.js";
+import { deleteTask, setTaskStore } from "../src/services/downloadManager.js";
+import {
+  createSession,
+  resolveSession,
+  sweepSessions,
+} from "../src/services/sessions.js";
+import { MemoryTaskStore } from "../src/services/taskStore.js";
+import { completedTask } from "./helpers/tasks.js";
+
+const ACCOUNT_HASH = "abcdef1234567890abcdef1234567890";
+const OTHER_HASH = "0987654321fedcba0987654321fedcba";
+
+describe("account sessions", () => {
+  const app = express();
+  app.use(express.json());
+  app.use("/api", sessionRoutes);
+  app.use("/api", packageRoutes);
+
+  beforeEach(() => {
+    setTaskStore(
+      new MemoryTaskStore([completedTask("session-pkg", {}, "ipa")]),
+    );
+  });
+
+  afterEach(() => {
+    deleteTask("session-pkg");
+  });
+
+  async function signIn(accountHash: string) {
+    const res = await request(app).post("/api/session").send({ accountHash });
+    expect(res.status).toBe(201);
+    return res.body.token as string;
+  }
+
+  it("should stand in for the account hash until revoked", async () => {
+    expect(
+      (await request(app).post("/api/session").send({ accountHash: "x" }))
+        .status,
+    ).toBe(400);
+    const token = await signIn(ACCOUNT_HASH);
+    const auth = `Session ${token}`;
+
+    const listed = await request(app)
+      .get("/api/packages")
+      .set("Authorization", auth);
+    expect(listed.body.map((p: { id: string }) => p.id)).toEqual([
+      "session-pkg",
+    ]);
+    const file = await request(app)
+      .get("/api/packages/session-pkg/file")
+      .set("Authorization", auth);
+    expect(file.status).toBe(200);
+
+    const revoked = await request(app)
+      .delete("/api/session")
+      .set("Authorization", auth);
+    expect(revoked.status).toBe(204);
+    const after = await request(app)
+      .get("/api/packages/session-pkg/file")
+      .set("Authorization", auth);
+    expect(after.status).toBe(401);
+    expect(after.body.code).toBe("INVALID_SESSION");
+    expect(
+      (await request(app).delete("/api/session").set("Authorization", auth))
+        .status,
+    ).toBe(404);
+  });
+
+  it("should still run ownership checks on the session's account", async () => {
+    const token = await signIn(OTHER_HASH);
+    const res = await request(app)
+      .get("/api/packages/session-pkg/file")
+      .set("Authorization", `Session ${token}`);
+    expect(res.status).toBe(403);
+  });
+
+  it("should keep accepting account hashes without a session", async () => {
+    const res = await request(app)
+      .get("/api/packages/session-pkg/file")
+      .set("X-Account-Hash", ACCOUNT_HASH);
+    expect(res.status).toBe(200);
+    const listed = await request(app).get(
+      `/api/packages?accountHashes=${ACCOUNT_HASH}`,
+    );
+    expect(listed.body).toHaveLength(1);
+  });
+
+  it("should expire sessions and sweep them", () => {
+    const now = Date.now();
+    const { token } = createSession(ACCOUNT_HASH, now)!;
+    const ttl = config.sessionTtlSeconds * 1000;
+    expect(resolveSession(token, now + ttl - 1)).toBe(ACCOUNT_HASH);
+    expect(sweepSessions(now + ttl)).toBeGreaterThanOrEqual(1);
+    expect(resolveSession(token, now)).toBeNull();
+  });
+
+  it("should refuse new sessions at the cap instead of evicting", async () => {
+    const now = Date.now();
+    const ttl = config.sessionTtlSeconds * 1000;
+    const first = createSession(ACCOUNT_HASH, now)!;
+    for (let i = 1; i < MAX_SESSIONS; i++) createSession(OTHER_HASH, now);
+
+    const refused = await request(app)
+      .post("/api/session")
+      .send({ accountHash: ACCOUNT_HASH });
+    expect(refused.status).toBe(503);
+    expect(refused.body.code).toBe("TOO_MANY_SESSIONS");
+    expect(resolveSession(first.token, now)).toBe(ACCOUNT_HASH);
+
+    // Expired sessions make room again
+    expect(createSession(ACCOUNT_HASH, now + ttl)).not.toBeNull();
+    sweepSessions(Number.MAX_SAFE_INTEGER);
+  });
+});
@@ -1,10 +1,68 @@
-import { describe, it, expect, beforeAll, afterAll } from "vitest";
-import { inject } from "../src/services/sinfInjector.js";
+import {
+  describe,
+  it,
+  expect,
+  vi,
+  beforeAll,
+  afterAll,
+  afterEach,
+} from "vitest";
+import {
+  inject,
+  InjectionVerificationError,
+  streamToBuffer,
+} from "../src/services/sinfInjector.js";
+import {
+  rawEntryName,
+  UTF8_NAME_FLAG,
+} from "../src/services/zipEntryNames.js";
 import AdmZip from "adm-zip";
+import { open as openZip } from "yauzl-promise";
 import fs from "fs";
 import path from "path";
 import os from "os";
 import plist from "plist";
+import {
+  deleteTask,
+  getTask,
+  sanitizeTaskForResponse,
+  setTaskStore,
+} from "../src/services/downloadManager.js";
+import { MemoryTaskStore } from "../src/services/taskStore.js";
+import { downloadsViaFetch } from "./helpers/downloadNetwork.js";
+import { machO64 } from "./helpers/machO.js";
+import { startDownload } from "./helpers/tasks.js";
+
+// How the next `zip` run that rewrites an IPA goes wrong, if at all: not
+// at all ("skip"), or with every sinf cut to one byte ("truncate")
+const zipFault = vi.hoisted(() => ({
+  mode: undefined as "skip" | "truncate" | undefined,
+}));
+vi.mock("child_process", async (importOriginal) => {
+  const original = await importOriginal<typeof import("child_process")>();
+  return {
+    ...original,
+    execFile: (
+      file: string,
+      args: string[],
+      options: { cwd: string },
+      callback: (err: Error | null) => void,
+    ) => {
+      const mode = zipFault.mode;
+      zipFault.mode = undefined;
+      if (mode === "skip") return callback(null);
+      if (mode === "truncate") {
+        const staged = fs.readdirSync(options.cwd, { recursive: true });
+        for (const name of staged.map(String)) {
+          if (name.endsWith(".sinf")) {
+            fs.writeFileSync(path.join(options.cwd, name), "x");
+          }
+        }
+      }
+      return original.execFile(file, args, options, (err) => callback(err));
+    },
+  };
+});
 
 const TEMP_DIR = path.join(os.tmpdir(), "sinf-injector-test");
 
@@ -22,6 +80,7 @@ function createMockIPA(
     addManifest?: boolean;
     sinfPaths?: string[];
     executableName?: string;
+    executable?: Buffer;
   },
 ): string {
   const zip = new AdmZip();
@@ -37,7 +96,7 @@ function createMockIPA(
   );
   zip.addFile(
     `Payload/${bundleName}.app/${execName}`,
-    Buffer.from("fake executable"),
+    opts?.executable ?? Buffer.from("fake executable"),
   );
 
   if (opts?.addManifest && opts?.sinfPaths) {
@@ -55,6 +114,23 @@ function createMockIPA(
   return ipaPath;
 }
 
+// Every entry by its stored name (latin1), with flags and contents
+async function rawEntries(ipaPath: string) {
+  const entries = new Map<string, { flags: number; data: Buffer }>();
+  const zip = await openZip(ipaPath, { decodeStrings: false });
+  try {
+    for await (const entry of zip) {
+      entries.set(rawEntryName(entry), {
+        flags: entry.generalPurposeBitFlag,
+        data: await streamToBuffer(await entry.openReadStream()),
+      });
+    }
+  } finally {
+    await zip.close();
+  }
+  return entries;
+}
+
 describe("sinfInjector", () => {
   it("should inject sinf via Info.plist fallback (no manifest)", async () => {
     const ipaPath = createMockIPA("TestApp", { executableName: "TestApp" });
@@ -62,7 +138,8 @@ describe("sinfInjector", () => {
       "base64",
     );
 
-    await inject([{ id: 1, sinf: sinfData }], ipaPath);
+    const source = await inject([{ id: 1, sinf: sinfData }], ipaPath);
+    expect(source).toEqual({ kind: "info", executable: "TestApp" });
 
     const resultZip = new AdmZip(ipaPath);
     const sinfEntry = resultZip.getEntry(
@@ -97,13 +174,14 @@ describe("sinfInjector", () => {
     const sinf1 = Buffer.from("sinf data 1").toString("base64");
     const sinf2 = Buffer.from("sinf data 2").toString("base64");
 
-    await inject(
+    const source = await inject(
       [
         { id: 1, sinf: sinf1 },
         { id: 2, sinf: sinf2 },
       ],
       ipaPath,
     );
+    expect(source).toEqual({ kind: "manifest", sinfPaths: 2 });
 
     const resultZip = new AdmZip(ipaPath);
 
@@ -120,6 +198,36 @@ describe("sinfInjector", () => {
     expect(resultZip.readFile(entry2!)!.toString()).toBe("sinf data 2");
   });
 
+  it("should skip sinfs when the executable is not encrypted", async () => {
+    const ipaPath = createMockIPA("Decrypted", { executable: machO64(0) });
+    const sinfData = Buffer.from("sinf").toString("base64");
+    const metadata = Buffer.from(plist.build({ itemId: 1 })).toString(
+      "base64",
+    );
+
+    const source = await inject([{ id: 1, sinf: sinfData }], ipaPath, metadata);
+    expect(source).toEqual({ kind: "not_encrypted", executable: "Decrypted" });
+
+    const resultZip = new AdmZip(ipaPath);
+    expect(
+      resultZip.getEntries().filter((e) => e.entryName.endsWith(".sinf")),
+    ).toHaveLength(0);
+    // Metadata is still written
+    expect(resultZip.getEntry("iTunesMetadata.plist")).not.toBeNull();
+  });
+
+  it("should inject sinfs when the executable is encrypted", async () => {
+    const ipaPath = createMockIPA("Encrypted", { executable: machO64(1) });
+    const sinfData = Buffer.from("sinf").toString("base64");
+
+    const source = await inject([{ id: 1, sinf: sinfData }], ipaPath);
+    expect(source).toEqual({ kind: "info", executable: "Encrypted" });
+    const resultZip = new AdmZip(ipaPath);
+    expect(
+      resultZip.getEntry("Payload/Encrypted.app/SC_Info/Encrypted.sinf"),
+    ).not.toBeNull();
+  });
+
   it("should handle empty sinfs array with no-manifest fallback", async () => {
     const ipaPath = createMockIPA("EmptyTest");
     await inject([], ipaPath);
@@ -159,6 +267,65 @@ describe("sinfInjector", () => {
     expect(sinfEntry).not.toBeNull();
   });
 
+  it("should replace a stale sinf and pass post-injection verification", async () => {
+    const ipaPath = createMockIPA("StaleSinf");
+    const stale = new AdmZip(ipaPath);
+    stale.addFile(
+      "Payload/StaleSinf.app/SC_Info/StaleSinf.sinf",
+      Buffer.from("old"),
+    );
+    stale.writeZip(ipaPath);
+
+    const sinfData = Buffer.from("fresh sinf data").toString("base64");
+    await expect(
+      inject([{ id: 1, sinf: sinfData }], ipaPath),
+    ).resolves.toEqual({ kind: "info", executable: "StaleSinf" });
+
+    const resultZip = new AdmZip(ipaPath);
+    const entry = resultZip.getEntry(
+      "Payload/StaleSinf.app/SC_Info/StaleSinf.sinf",
+    );
+    expect(resultZip.readFile(entry!)!.toString()).toBe("fresh sinf data");
+  });
+
+  it("should keep sinfs stored while deflating metadata on request", async () => {
+    const ipaPath = createMockIPA("Compressed", {
+      executableName: "Compressed",
+    });
+    // Both compress well, so a stored entry was written stored on purpose
+    const sinfData = Buffer.alloc(4096, "s").toString("base64");
+    const metadata = Buffer.from(
+      plist.build({ itemName: "x".repeat(4096) }),
+    ).toString("base64");
+
+    await inject([{ id: 1, sinf: sinfData }], ipaPath, metadata, "deflated");
+
+    const resultZip = new AdmZip(ipaPath);
+    const sinfEntry = resultZip.getEntry(
+      "Payload/Compressed.app/SC_Info/Compressed.sinf",
+    );
+    const metadataEntry = resultZip.getEntry("iTunesMetadata.plist");
+    // 0 = stored, 8 = deflated
+    expect(sinfEntry!.header.method).toBe(0);
+    expect(metadataEntry!.header.method).toBe(8);
+    expect(metadataEntry!.header.compressedSize).toBeLessThan(
+      metadataEntry!.header.size,
+    );
+    expect(resultZip.readFile(sinfEntry!)!.length).toBe(4096);
+  });
+
+  it("should store metadata by default", async () => {
+    const ipaPath = createMockIPA("Stored", { executableName: "Stored" });
+    const metadata = Buffer.from(
+      plist.build({ itemName: "x".repeat(4096) }),
+    ).toString("base64");
+
+    await inject([], ipaPath, metadata);
+
+    const metadataEntry = new AdmZip(ipaPath).getEntry("iTunesMetadata.plist");
+    expect(metadataEntry!.header.method).toBe(0);
+  });
+
   it("should prefer manifest over Info.plist when both exist", async () => {
     const ipaPath = createMockIPA("WithManifest", {
       addManifest: true,
@@ -179,4 +346,99 @@ describe("sinfInjector", () => {
       "manifest sinf",
     );
   });
+
+  it("should inject next to CP437 names without touching other entries", async () => {
+    // "Café" in CP437 (0x82), unflagged, as older tools wrote it
+    const ipaPath = createMockIPA("CafX", { executableName: "Cafe" });
+    const bytes = fs.readFileSync(ipaPath);
+    const from = Buffer.from("CafX.app");
+    const to = Buffer.from("Caf\x82.app", "latin1");
+    for (let at = bytes.indexOf(from); at >= 0; at = bytes.indexOf(from)) {
+      to.copy(bytes, at);
+    }
+    fs.writeFileSync(ipaPath, bytes);
+    const before = await rawEntries(ipaPath);
+
+    const sinfData = Buffer.from("cp437 sinf").toString("base64");
+    await inject([{ id: 1, sinf: sinfData }], ipaPath);
+
+    const after = await rawEntries(ipaPath);
+    for (const [name, entry] of before) {
+      expect(after.get(name), name).toEqual(entry);
+    }
+    expect(after.size).toBe(before.size + 1);
+    const sinf = after.get("Payload/Caf\x82.app/SC_Info/Cafe.sinf");
+    expect(sinf?.data.toString()).toBe("cp437 sinf");
+    expect(sinf!.flags & UTF8_NAME_FLAG).toBe(0);
+  });
+
+  it("should flag injected names like a UTF-8 bundle", async () => {
+    const ipaPath = createMockIPA("Café", { executableName: "Café" });
+    const before = await rawEntries(ipaPath);
+    const infoName = Buffer.from("Payload/Café.app/Info.plist").toString(
+      "latin1",
+    );
+
+    const sinfData = Buffer.from("utf-8 sinf").toString("base64");
+    await inject([{ id: 1, sinf: sinfData }], ipaPath);
+
+    const after = await rawEntries(ipaPath);
+    expect(after.size).toBe(before.size + 1);
+    const sinf = after.get(
+      Buffer.from("Payload/Café.app/SC_Info/Café.sinf").toString("latin1"),
+    );
+    expect(sinf?.data.toString()).toBe("utf-8 sinf");
+    expect(sinf!.flags & UTF8_NAME_FLAG).toBe(
+      before.get(infoName)!.flags & UTF8_NAME_FLAG,
+    );
+  });
+
+  it("should fail verification when the rewritten IPA lacks the sinf", async () => {
+    const ipaPath = createMockIPA("MissingSinf");
+    const sinfData = Buffer.from("never written").toString("base64");
+
+    zipFault.mode = "skip";
+    await expect(
+      inject([{ id: 1, sinf: sinfData }], ipaPath),
+    ).rejects.toThrow(InjectionVerificationError);
+  });
+
+  it("should fail verification when a sinf was written wrong", async () => {
+    const ipaPath = createMockIPA("CorruptSinf");
+    const sinfData = Buffer.from("sinf cut short").toString("base64");
+
+    zipFault.mode = "truncate";
+    await expect(
+      inject([{ id: 1, sinf: sinfData }], ipaPath),
+    ).rejects.toThrow(/has size 1, expected 14/);
+  });
+});
+
+describe("failed injection verification", () => {
+  let id: string | undefined;
+
+  afterEach(() => {
+    if (id) deleteTask(id);
+    zipFault.mode = undefined;
+    vi.restoreAllMocks();
+  });
+
+  it("should fail the download with the INJECTION category", async () => {
+    setTaskStore(new MemoryTaskStore());
+    downloadsViaFetch();
+    const ipa = fs.readFileSync(createMockIPA("FailedTask"));
+    vi.spyOn(globalThis, "fetch").mockImplementation(
+      async () => new Response(ipa),
+    );
+
+    zipFault.mode = "skip";
+    const sinf = Buffer.from("task sinf").toString("base64");
+    id = startDownload("com.example.failedtask", [{ id: 0, sinf }]);
+    await vi.waitFor(() => expect(getTask(id!)?.status).toBe("failed"));
+
+    expect(getTask(id)?.errorCode).toBe("injection_failed");
+    expect(sanitizeTaskForResponse(getTask(id)!).errorCategory).toBe(
+      "INJECTION",
+    );
+  });
 });
@@ -0,0 +1,109 @@
+import { describe, it, expect } from "vitest";
+import { EventEmitter } from "events";
+import type { Response } from "express";
+import { createCoalescingSseWriter } from "../src/utils/sse.js";
+
+function createSlowRes() {
+  const emitter = new EventEmitter() as EventEmitter & {
+    writableNeedDrain: boolean;
+    written: string[];
+    write: (chunk: string) => boolean;
+  };
+  emitter.writableNeedDrain = false;
+  emitter.written = [];
+  emitter.write = (chunk: string) => {
+    emitter.written.push(chunk);
+    return true;
+  };
+  return emitter;
+}
+
+describe("createCoalescingSseWriter", () => {
+  it("writes updates directly when the client keeps up", () => {
+    const res = createSlowRes();
+    const send = createCoalescingSseWriter<number>(
+      res as unknown as Response,
+      (n) => ({ progress: n }),
+    );
+
+    send(1);
+    send(2);
+
+    expect(res.written).toEqual([
+      'data: {"progress":1}\n\n',
+      'data: {"progress":2}\n\n',
+    ]);
+  });
+
+  it("survives a slow consumer and ends with the latest state", () => {
+    const res = createSlowRes();
+    type Snapshot = { status: string; progress: number };
+    const send = createCoalescingSseWriter<Snapshot>(
+      res as unknown as Response,
+      (t) => t,
+    );
+
+    send({ status: "downloading", progress: 0 });
+    res.writableNeedDrain = true;
+
+    // Fast producer: many updates while the client is backed up
+    for (let i = 1; i < 500; i++) {
+      send({ status: "downloading", progress: i % 100 });
+    }
+    send({ status: "completed", progress: 100 });
+
+    expect(res.written.length).toBe(1);
+
+    res.writableNeedDrain = false;
+    res.emit("drain");
+
+    expect(res.written.length).toBe(2);
+    expect(JSON.parse(res.written[1].slice(6))).toEqual({
+      status: "completed",
+      progress: 100,
+    });
+
+    // Stream keeps working after catching up
+    send({ status: "completed", progress: 100 });
+    expect(res.written.length).toBe(3);
+  });
+
+  it("replays a short backlog in order and resyncs past its capacity", () => {
+    const res = createSlowRes();
+    type Snapshot = { status: string; progress: number };
+    const task: Snapshot = { status: "downloading", progress: 0 };
+    const send = createCoalescingSseWriter<Snapshot>(
+      res as unknown as Response,
+      (t) => ({ ...t }),
+      3,
+    );
+    const frames = () => res.written.map((chunk) => JSON.parse(chunk.slice(6)));
+
+    // Brief hiccup: the live object keeps changing, each update is kept
+    res.writableNeedDrain = true;
+    for (const progress of [10, 20, 30]) {
+      task.progress = progress;
+      send(task);
+    }
+    res.writableNeedDrain = false;
+    res.emit("drain");
+    expect(frames().map((t) => t.progress)).toEqual([10, 20, 30]);
+
+    // Lagging past the capacity: no disconnect, just the newest snapshot
+    res.writableNeedDrain = true;
+    for (let i = 31; i < 300; i++) {
+      task.progress = i % 100;
+      send(task);
+    }
+    task.status = "completed";
+    task.progress = 100;
+    send(task);
+    res.writableNeedDrain = false;
+    res.emit("drain");
+    expect(frames().slice(3)).toEqual([{ status: "completed", progress: 100 }]);
+
+    // Back to direct writes
+    send(task);
+    expect(res.written.length).toBe(5);
+  });
+});
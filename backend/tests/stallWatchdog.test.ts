@@ -0,0 +1,98 @@
+import { describe, it, expect, vi, beforeEach, afterEach } from "vitest";
+import { config } from "../src/config.js";
+import {
+  checkStalledDownloads,
+  createTask,
+  deleteTask,
+  getStallCounters,
+  getTask,
+  pauseTask,
+  setTaskStore,
+} from "../src/services/downloadManager.js";
+import { MemoryTaskStore } from "../src/services/taskStore.js";
+import type { Software } from "../src/types/index.js";
+import { downloadsViaFetch } from "./helpers/downloadNetwork.js";
+
+const ACCOUNT_HASH = "abcdef1234567890abcdef1234567890";
+const software = {
+  id: 1,
+  bundleID: "com.example.stall",
+  version: "1.0",
+} as Software;
+
+// A CDN that accepts the connection and then never sends a byte
+function mockHangingFetch() {
+  return vi
+    .spyOn(globalThis, "fetch")
+    .mockImplementation(
+      (_url, init) =>
+        new Promise((_resolve, reject) => {
+          init?.signal?.addEventListener("abort", () =>
+            reject(new DOMException("aborted", "AbortError")),
+          );
+        }),
+    );
+}
+
+describe("stall watchdog", () => {
+  const windowMs = config.stallTimeoutSeconds * 1000;
+  let id: string;
+
+  beforeEach(() => {
+    setTaskStore(new MemoryTaskStore());
+    downloadsViaFetch();
+  });
+
+  afterEach(() => {
+    deleteTask(id);
+    vi.restoreAllMocks();
+  });
+
+  it("should restart a stalled download once, then fail it", async () => {
+    const fetchSpy = mockHangingFetch();
+    const before = getStallCounters();
+    id = createTask({
+      software,
+      accountHash: ACCOUNT_HASH,
+      downloadURL: "https://example.apple.com/app.ipa",
+      sinfs: [],
+    }).id;
+    expect(getTask(id)?.status).toBe("downloading");
+    // The request goes out once the host has been resolved
+    await vi.waitFor(() => expect(fetchSpy).toHaveBeenCalledTimes(1));
+
+    checkStalledDownloads(Date.now() + windowMs - 1000);
+    expect(fetchSpy).toHaveBeenCalledTimes(1);
+
+    checkStalledDownloads(Date.now() + windowMs + 1000);
+    await vi.waitFor(() => expect(fetchSpy).toHaveBeenCalledTimes(2));
+    expect(getTask(id)?.status).toBe("downloading");
+    expect(getTask(id)?.events?.some((e) => e.detail === "stalled")).toBe(
+      true,
+    );
+
+    checkStalledDownloads(Date.now() + windowMs + 1000);
+    // The abandoned attempts settle without overriding the outcome
+    await new Promise((resolve) => setTimeout(resolve, 10));
+    expect(getTask(id)?.status).toBe("failed");
+    expect(getTask(id)?.errorCode).toBe("stalled");
+    expect(getStallCounters()).toEqual({
+      recovered: before.recovered + 1,
+      failed: before.failed + 1,
+    });
+  });
+
+  it("should leave paused downloads alone", async () => {
+    mockHangingFetch();
+    id = createTask({
+      software,
+      accountHash: ACCOUNT_HASH,
+      downloadURL: "https://example.apple.com/app.ipa",
+      sinfs: [],
+    }).id;
+    pauseTask(id);
+
+    checkStalledDownloads(Date.now() + windowMs * 10);
+    expect(getTask(id)?.status).toBe("paused");
+  });
+});
@@ -0,0 +1,247 @@
+import { describe, it, expect, vi, beforeEach, afterEach } from "vitest";
+import crypto from "crypto";
+import fs from "fs";
+import path from "path";
+import express from "express";
+import request from "supertest";
+import { config } from "../src/config.js";
+import adminRoutes from "../src/routes/admin.js";
+import {
+  deleteTask,
+  getTask,
+  setTaskStore,
+} from "../src/services/downloadManager.js";
+import {
+  getMigrationState,
+  resumeStorageMigration,
+} from "../src/services/storageMigration.js";
+import { MemoryTaskStore } from "../src/services/taskStore.js";
+import { completedTask } from "./helpers/tasks.js";
+
+const ADMIN_TOKEN = "test-admin-token";
+const ACCOUNT = "abcdef1234567890abcdef1234567890";
+const BYTES = Buffer.from("0123456789");
+const dir = path.resolve(config.dataDir, "packages", ACCOUNT, "migrate");
+const ipaPath = path.join(dir, "app.ipa");
+const stateFile = path.join(config.dataDir, "storage-migration.json");
+const KEY = `library/${ACCOUNT}/migrate/app.ipa`;
+const saved = { ...config };
+
+const md5 = (data: Buffer) => crypto.createHash("md5").update(data);
+
+// In-memory S3: multipart uploads and the objects they complete into
+const uploads = new Map<string, Map<number, Buffer>>();
+const objects = new Map<string, { body: Buffer; etag: string }>();
+const uploadedParts: number[] = [];
+
+async function fakeS3(input: string | URL | Request, init?: RequestInit) {
+  const url = new URL(String(input));
+  const key = decodeURIComponent(url.pathname.replace(/^\/bucket\//, ""));
+  const method = init?.method ?? "GET";
+  const uploadId = url.searchParams.get("uploadId") ?? "";
+  const parts = uploads.get(uploadId);
+
+  if (method === "POST" && url.searchParams.has("uploads")) {
+    const id = `upload-${uploads.size + 1}`;
+    uploads.set(id, new Map());
+    return new Response(`<Result><UploadId>${id}</UploadId></Result>`);
+  }
+  if (method === "HEAD") {
+    const object = objects.get(key);
+    if (!object) return new Response(null, { status: 404 });
+    return new Response(null, {
+      headers: {
+        "content-length": String(object.body.length),
+        etag: object.etag,
+      },
+    });
+  }
+  if (!parts) {
+    return new Response("<Error><Code>NoSuchUpload</Code></Error>", {
+      status: 404,
+    });
+  }
+  if (method === "DELETE") {
+    uploads.delete(uploadId);
+    return new Response(null, { status: 204 });
+  }
+  if (method === "PUT") {
+    const partNumber = Number(url.searchParams.get("partNumber"));
+    const body = Buffer.from(init!.body as Uint8Array);
+    parts.set(partNumber, body);
+    uploadedParts.push(partNumber);
+    return new Response(null, {
+      headers: { etag: `"${md5(body).digest("hex")}"` },
+    });
+  }
+  if (method === "GET") {
+    const xml = [...parts]
+      .map(
+        ([n, body]) =>
+          `<Part><PartNumber>${n}</PartNumber><ETag>&quot;${md5(body).digest("hex")}&quot;</ETag><Size>${body.length}</Size></Part>`,
+      )
+      .join("");
+    return new Response(
+      `<Result><IsTruncated>false</IsTruncated>${xml}</Result>`,
+    );
+  }
+  // Complete: concatenate parts, S3-style multipart ETag
+  const ordered = [...parts].sort(([a], [b]) => a - b).map(([, b]) => b);
+  const digests = Buffer.concat(ordered.map((b) => md5(b).digest()));
+  objects.set(key, {
+    body: Buffer.concat(ordered),
+    etag: `"${md5(digests).digest("hex")}-${ordered.length}"`,
+  });
+  uploads.delete(uploadId);
+  return new Response("<CompleteMultipartUploadResult/>");
+}
+
+describe("storage migration", () => {
+  beforeEach(() => {
+    Object.assign(config, {
+      adminToken: ADMIN_TOKEN,
+      migrateS3Endpoint: "https://s3.example.com",
+      migrateS3Bucket: "bucket",
+      migrateS3AccessKeyId: "key-id",
+      migrateS3SecretAccessKey: "secret",
+      migrateS3Prefix: "library/",
+    });
+    setTaskStore(
+      new MemoryTaskStore([
+        completedTask(
+          "migrate-task",
+          { filePath: ipaPath, fileSize: BYTES.length },
+          BYTES,
+        ),
+      ]),
+    );
+    vi.spyOn(globalThis, "fetch").mockImplementation(fakeS3 as typeof fetch);
+  });
+
+  afterEach(() => {
+    Object.assign(config, saved);
+    vi.restoreAllMocks();
+    uploads.clear();
+    objects.clear();
+    uploadedParts.length = 0;
+    fs.rmSync(dir, { recursive: true, force: true });
+    fs.rmSync(stateFile, { force: true });
+    setTaskStore(new MemoryTaskStore());
+  });
+
+  it("should copy completed packages and report per-task status", async () => {
+    const app = express();
+    app.use(express.json());
+    app.use("/api", adminRoutes);
+    const auth = `Bearer ${ADMIN_TOKEN}`;
+
+    config.migrateS3Bucket = "";
+    const unconfigured = await request(app)
+      .post("/api/admin/migrate-storage")
+      .set("Authorization", auth);
+    expect(unconfigured.status).toBe(400);
+    expect(unconfigured.body.code).toBe("MIGRATION_NOT_CONFIGURED");
+    config.migrateS3Bucket = "bucket";
+
+    const started = await request(app)
+      .post("/api/admin/migrate-storage")
+      .set("Authorization", auth)
+      .send({});
+    expect(started.status).toBe(202);
+    expect(started.body.totals.packages).toBe(1);
+
+    await vi.waitFor(() =>
+      expect(getMigrationState()?.status).toBe("completed"),
+    );
+    expect(objects.get(KEY)?.body).toEqual(BYTES);
+    // Without deleteSource the package stays where it was
+    expect(fs.existsSync(ipaPath)).toBe(true);
+
+    const status = await request(app)
+      .get("/api/admin/migrate-storage/status")
+      .set("Authorization", auth);
+    expect(status.body.totals).toMatchObject({ migrated: 1, failed: 0 });
+    expect(status.body.tasks[0]).toMatchObject({
+      taskId: "migrate-task",
+      key: KEY,
+      status: "migrated",
+      uploadedBytes: BYTES.length,
+    });
+  });
+
+  it("should abort the upload of a package deleted mid-copy", async () => {
+    fs.writeFileSync(
+      stateFile,
+      JSON.stringify({
+        status: "running",
+        startedAt: "2026-01-01T00:00:00.000Z",
+        deleteSource: false,
+        partSize: 4,
+        cursor: 0,
+        entries: [
+          {
+            taskId: "migrate-task",
+            key: KEY,
+            size: BYTES.length,
+            status: "pending",
+            uploadedBytes: 0,
+          },
+        ],
+      }),
+    );
+    // The owner deletes the package while its first part is in flight
+    vi.spyOn(globalThis, "fetch").mockImplementation(async (input, init) => {
+      if (init?.method === "PUT") deleteTask("migrate-task");
+      return fakeS3(input, init);
+    });
+
+    resumeStorageMigration();
+    await vi.waitFor(() =>
+      expect(getMigrationState()?.status).toBe("completed"),
+    );
+    expect(uploadedParts).toEqual([1]);
+    expect(getMigrationState()?.entries[0]).toMatchObject({
+      status: "skipped",
+      error: "package deleted during migration",
+    });
+    expect(getMigrationState()?.entries[0].uploadId).toBeUndefined();
+    // Nothing left under the package's key: no object, no open upload
+    expect(uploads.size).toBe(0);
+    expect(objects.has(KEY)).toBe(false);
+  });
+
+  it("should resume an interrupted upload after a restart", async () => {
+    // A previous run stored the first 4-byte part before stopping
+    uploads.set("upload-prev", new Map([[1, BYTES.subarray(0, 4)]]));
+    fs.writeFileSync(
+      stateFile,
+      JSON.stringify({
+        status: "running",
+        startedAt: "2026-01-01T00:00:00.000Z",
+        deleteSource: true,
+        partSize: 4,
+        cursor: 0,
+        entries: [
+          {
+            taskId: "migrate-task",
+            key: KEY,
+            size: BYTES.length,
+            status: "uploading",
+            uploadedBytes: 4,
+            uploadId: "upload-prev",
+          },
+        ],
+      }),
+    );
+
+    resumeStorageMigration();
+    await vi.waitFor(() =>
+      expect(getMigrationState()?.status).toBe("completed"),
+    );
+    expect(uploadedParts).toEqual([2, 3]);
+    expect(objects.get(KEY)?.body).toEqual(BYTES);
+    // deleteSource removes the package only after the copy checked out
+    expect(getTask("migrate-task")).toBeUndefined();
+    expect(fs.existsSync(ipaPath)).toBe(false);
+  });
+});
@@ -0,0 +1,88 @@
+import { describe, it, expect, vi, afterEach } from "vitest";
+import fs from "fs";
+import express from "express";
+import request from "supertest";
+import { config } from "../src/config.js";
+import downloadRoutes from "../src/routes/downloads.js";
+import {
+  checkStorage,
+  isUnderStoragePressure,
+  onStoragePressureChange,
+} from "../src/services/storageMonitor.js";
+
+const MB = 1024 * 1024;
+
+// Report `freeMB` of free space on every volume
+function mockFreeSpace(freeMB: number) {
+  return vi.spyOn(fs.promises, "statfs").mockResolvedValue({
+    bavail: freeMB * 256,
+    bsize: 4096,
+  } as fs.StatsFs);
+}
+
+describe("storage monitor", () => {
+  afterEach(async () => {
+    config.minFreeSpaceMB = 0;
+    mockFreeSpace(1024);
+    await checkStorage();
+    vi.restoreAllMocks();
+  });
+
+  it("should stay clear while the floor is disabled", async () => {
+    mockFreeSpace(1);
+    const status = await checkStorage();
+    expect(status.freeBytes).toBe(MB);
+    expect(status.pressure).toBe(false);
+  });
+
+  it("should flag pressure below the floor and clear it on recovery", async () => {
+    const changes: boolean[] = [];
+    const unsubscribe = onStoragePressureChange((p) => changes.push(p));
+    config.minFreeSpaceMB = 100;
+
+    mockFreeSpace(50);
+    await checkStorage();
+    expect(isUnderStoragePressure()).toBe(true);
+
+    // Still low: no repeated notification
+    await checkStorage();
+
+    mockFreeSpace(500);
+    await checkStorage();
+    expect(isUnderStoragePressure()).toBe(false);
+    expect(changes).toEqual([true, false]);
+    unsubscribe();
+  });
+
+  it("should keep the previous state when statfs fails", async () => {
+    config.minFreeSpaceMB = 100;
+    mockFreeSpace(50);
+    await checkStorage();
+
+    vi.spyOn(fs.promises, "statfs").mockRejectedValue(new Error("EIO"));
+    vi.spyOn(console, "warn").mockImplementation(() => {});
+    await checkStorage();
+    expect(isUnderStoragePressure()).toBe(true);
+  });
+
+  it("should answer new downloads with 507 under pressure", async () => {
+    config.minFreeSpaceMB = 100;
+    mockFreeSpace(50);
+    vi.spyOn(console, "warn").mockImplementation(() => {});
+    await checkStorage();
+
+    const app = express();
+    app.use(express.json());
+    app.use("/api", downloadRoutes);
+    const res = await request(app)
+      .post("/api/downloads")
+      .send({
+        software: { id: 1, bundleID: "com.example.space", version: "1.0" },
+        accountHash: "abcdef1234567890abcdef1234567890",
+        downloadURL: "https://example.apple.com/app.ipa",
+        sinfs: [],
+      });
+    expect(res.status).toBe(507);
+    expect(res.body.code).toBe("INSUFFICIENT_STORAGE");
+  });
+});
@@ -0,0 +1,56 @@
+import { describe, it, expect } from "vitest";
+import {
+  MAX_TASK_EVENTS,
+  recordTaskEvent,
+  restoreTaskEvents,
+} from "../src/services/taskEvents.js";
+import type { TaskEvent } from "../src/types/index.js";
+
+describe("taskEvents", () => {
+  it("should append events with timestamps and optional detail", () => {
+    const task: { events?: TaskEvent[] } = {};
+    const now = new Date("2026-01-01T00:00:00.000Z");
+    recordTaskEvent(task, "created", undefined, now);
+    recordTaskEvent(task, "failed", "network", now);
+
+    expect(task.events).toEqual([
+      { type: "created", at: "2026-01-01T00:00:00.000Z" },
+      { type: "failed", at: "2026-01-01T00:00:00.000Z", detail: "network" },
+    ]);
+  });
+
+  it("should drop the oldest events beyond the cap", () => {
+    const task: { events?: TaskEvent[] } = {};
+    for (let i = 0; i < MAX_TASK_EVENTS + 10; i++) {
+      recordTaskEvent(task, "retry", `#${i}`);
+    }
+
+    expect(task.events).toHaveLength(MAX_TASK_EVENTS);
+    expect(task.events![0].detail).toBe("#10");
+    expect(task.events![MAX_TASK_EVENTS - 1].detail).toBe(
+      `#${MAX_TASK_EVENTS + 9}`,
+    );
+  });
+
+  it("should round-trip through JSON persistence", () => {
+    const task: { events?: TaskEvent[] } = {};
+    recordTaskEvent(task, "started");
+    recordTaskEvent(task, "progress", "50%");
+    recordTaskEvent(task, "completed");
+
+    const restored = restoreTaskEvents(JSON.parse(JSON.stringify(task.events)));
+    expect(restored).toEqual(task.events);
+  });
+
+  it("should ignore malformed or oversized persisted histories", () => {
+    expect(restoreTaskEvents(undefined)).toBeUndefined();
+    expect(restoreTaskEvents("nope")).toBeUndefined();
+    expect(restoreTaskEvents([null, { type: 1 }])).toBeUndefined();
+
+    const tooMany = Array.from({ length: MAX_TASK_EVENTS + 5 }, (_, i) => ({
+      type: "retry",
+      at: new Date(i).toISOString(),
+    }));
+    expect(restoreTaskEvents(tooMany)).toHaveLength(MAX_TASK_EVENTS);
+  });
+});
@@ -0,0 +1,121 @@
+import { describe, it, expect, beforeEach, afterEach } from "vitest";
+import express from "express";
+import request from "supertest";
+import fs from "fs";
+import path from "path";
+import { config } from "../src/config.js";
+import adminRoutes from "../src/routes/admin.js";
+import {
+  deleteTask,
+  getTask,
+  setTaskStore,
+} from "../src/services/downloadManager.js";
+import { MemoryTaskStore } from "../src/services/taskStore.js";
+import type { DownloadTask } from "../src/types/index.js";
+import { completedTask } from "./helpers/tasks.js";
+
+const ADMIN_TOKEN = "test-admin-token";
+const ACCOUNT_HASH = "abcdef1234567890abcdef1234567890";
+const IDS = ["export-a", "export-b", "import-c", "import-d"];
+
+function jsonl(records: unknown[]) {
+  return records.map((r) => JSON.stringify(r)).join("\n") + "\n";
+}
+
+describe("task database export and import", () => {
+  const app = express();
+  app.use("/api", adminRoutes);
+  const saved = config.adminToken;
+
+  beforeEach(() => {
+    config.adminToken = ADMIN_TOKEN;
+  });
+
+  afterEach(() => {
+    config.adminToken = saved;
+    for (const id of IDS) {
+      deleteTask(id);
+      const bundleDir = `packages/${ACCOUNT_HASH}/com.example.${id}`;
+      fs.rmSync(path.join(config.dataDir, bundleDir), {
+        recursive: true,
+        force: true,
+      });
+    }
+  });
+
+  function importBody(text: string) {
+    return request(app)
+      .post("/api/admin/import")
+      .set("Authorization", `Bearer ${ADMIN_TOKEN}`)
+      .set("Content-Type", "application/x-ndjson")
+      .send(text);
+  }
+
+  it("should export one record per line without secrets", async () => {
+    const failed = {
+      ...completedTask("export-b"),
+      status: "failed",
+      downloadURL: "https://example.apple.com/app.ipa?token=secret",
+      sinfs: [{ id: 0, sinf: "c2luZg==" }],
+    } as DownloadTask;
+    setTaskStore(
+      new MemoryTaskStore([completedTask("export-a", {}, "ipa"), failed]),
+    );
+
+    expect((await request(app).get("/api/admin/export")).status).toBe(401);
+    const res = await request(app)
+      .get("/api/admin/export")
+      .set("Authorization", `Bearer ${ADMIN_TOKEN}`)
+      .buffer(true)
+      .parse((response, callback) => {
+        let text = "";
+        response.on("data", (chunk) => (text += chunk));
+        response.on("end", () => callback(null, text));
+      });
+
+    expect(res.status).toBe(200);
+    expect(res.headers["content-type"]).toContain("application/x-ndjson");
+    expect(res.headers["content-disposition"]).toContain(".jsonl");
+    const lines = (res.body as string).trim().split("\n");
+    const records = lines.map((line) => JSON.parse(line));
+    expect(records.map((r) => r.id).sort()).toEqual(["export-a", "export-b"]);
+    expect(res.body).not.toContain("token=secret");
+    expect(res.body).not.toContain("c2luZg==");
+    for (const record of records) expect(record.secrets).toBeUndefined();
+  });
+
+  it("should merge new tasks and report the ones it skipped", async () => {
+    setTaskStore(new MemoryTaskStore([completedTask("export-a", {}, "ipa")]));
+    const exported = JSON.parse(
+      JSON.stringify(completedTask("import-c", {}, "ipa")),
+    );
+    const missing = JSON.parse(JSON.stringify(completedTask("import-d")));
+    const existing = JSON.parse(JSON.stringify(getTask("export-a")));
+
+    const res = await importBody(
+      jsonl([exported, missing, existing, { id: "x", status: "completed" }]),
+    );
+
+    expect(res.status).toBe(200);
+    expect(res.body.imported).toBe(1);
+    expect(res.body.skipped).toEqual([
+      { index: 1, id: "import-d", reason: "package file not found" },
+      { index: 2, id: "export-a", reason: "already exists" },
+      { index: 3, id: "x", reason: "invalid accountHash" },
+    ]);
+    expect(getTask("import-c")?.status).toBe("completed");
+    expect(getTask("import-d")).toBeUndefined();
+  });
+
+  it("should reject a line that is not JSON", async () => {
+    setTaskStore(new MemoryTaskStore());
+    const res = await importBody(
+      `${JSON.stringify(completedTask("import-c", {}, "ipa"))}\n{oops\n`,
+    );
+
+    expect(res.status).toBe(400);
+    expect(res.body.code).toBe("INVALID_IMPORT");
+    expect(res.body.error).toContain("Line 2");
+    expect(getTask("import-c")).toBeUndefined();
+  });
+});
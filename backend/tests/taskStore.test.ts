@@ -0,0 +1,418 @@
+import {
+  describe,
+  it,
+  expect,
+  vi,
+  beforeAll,
+  afterAll,
+  beforeEach,
+  afterEach,
+} from "vitest";
+import crypto from "crypto";
+import fs from "fs";
+import os from "os";
+import path from "path";
+import { config } from "../src/config.js";
+import {
+  createTask,
+  deleteTask,
+  getTask,
+  pauseTask,
+  sanitizeTaskForResponse,
+  setTaskStore,
+  updateTaskSharing,
+} from "../src/services/downloadManager.js";
+import {
+  createFileTaskStore,
+  MemoryTaskStore,
+  TASKS_SCHEMA_VERSION,
+} from "../src/services/taskStore.js";
+import { setPinnedNetwork } from "../src/services/pinnedFetch.js";
+import type { DownloadTask, Software } from "../src/types/index.js";
+import { downloadsViaFetch } from "./helpers/downloadNetwork.js";
+
+function makeTask(overrides: Partial<DownloadTask> = {}): DownloadTask {
+  return {
+    id: "task-1",
+    software: {
+      id: 1,
+      bundleID: "com.example.app",
+      name: "Example",
+      version: "1.0",
+      artistName: "Example",
+      sellerName: "Example",
+      description: "",
+      averageUserRating: 0,
+      userRatingCount: 0,
+      artworkUrl: "",
+      screenshotUrls: [],
+      minimumOsVersion: "15.0",
+      releaseDate: "2026-01-01",
+      primaryGenreName: "Utilities",
+    },
+    accountHash: "abcdef1234567890abcdef1234567890",
+    downloadURL: "",
+    sinfs: [],
+    status: "failed",
+    progress: 40,
+    speed: "0 B/s",
+    error: "Download failed",
+    errorCode: "network",
+    events: [{ type: "failed", at: "2026-01-01T00:00:00.000Z" }],
+    createdAt: "2026-01-01T00:00:00.000Z",
+    ...overrides,
+  };
+}
+
+describe("MemoryTaskStore", () => {
+  it("should return copies of what was saved", () => {
+    const store = new MemoryTaskStore([makeTask()]);
+    const [loaded] = store.load() as DownloadTask[];
+    loaded.progress = 99;
+    expect((store.load() as DownloadTask[])[0].progress).toBe(40);
+  });
+});
+
+// Package files must live under DATA_DIR/packages to be restored
+const bundleDir = path.resolve(
+  config.dataDir,
+  "packages",
+  makeTask().accountHash,
+  "com.example.taskstore",
+);
+
+describe("download manager with a MemoryTaskStore", () => {
+  const ipaPath = path.join(bundleDir, "1.0", "kept.ipa");
+
+  beforeAll(() => {
+    fs.mkdirSync(path.dirname(ipaPath), { recursive: true });
+    fs.writeFileSync(ipaPath, "ipa");
+  });
+
+  afterAll(() => {
+    fs.rmSync(bundleDir, { recursive: true, force: true });
+  });
+
+  it("should restore failed tasks with their error and history", () => {
+    setTaskStore(new MemoryTaskStore([makeTask()]));
+    const task = getTask("task-1");
+    expect(task?.status).toBe("failed");
+    expect(task?.errorCode).toBe("network");
+    expect(task?.events).toHaveLength(1);
+  });
+
+  it("should expose the error category without the detail", () => {
+    setTaskStore(
+      new MemoryTaskStore([
+        makeTask(),
+        makeTask({ id: "expired", errorCode: "download_url_expired" }),
+        makeTask({ id: "odd", errorCode: "unknown" }),
+      ]),
+    );
+    const shared = sanitizeTaskForResponse(getTask("task-1")!);
+    expect(shared.errorCategory).toBe("NETWORK");
+    expect(shared).not.toHaveProperty("errorCode");
+    expect(sanitizeTaskForResponse(getTask("expired")!).errorCategory).toBe(
+      "URL_EXPIRED",
+    );
+    expect(sanitizeTaskForResponse(getTask("odd")!).errorCategory).toBe(
+      undefined,
+    );
+  });
+
+  it("should drop completed tasks whose file no longer exists", () => {
+    setTaskStore(
+      new MemoryTaskStore([
+        makeTask({ id: "gone", status: "completed", filePath: "/nope.ipa" }),
+        makeTask({ id: "kept", status: "completed", filePath: ipaPath }),
+      ]),
+    );
+    expect(getTask("gone")).toBeUndefined();
+    expect(getTask("kept")?.filePath).toBe(ipaPath);
+  });
+
+  it("should persist paths relative to the data directory", () => {
+    const store = new MemoryTaskStore([
+      makeTask({ id: "kept", status: "completed", filePath: ipaPath }),
+    ]);
+    setTaskStore(store);
+    updateTaskSharing("kept", ["0987654321fedcba0987654321fedcba"], []);
+    expect(store.records.get("kept")?.filePath).toBe(
+      `packages/${makeTask().accountHash}/com.example.taskstore/1.0/kept.ipa`,
+    );
+
+    // Reloading resolves the relative path against DATA_DIR again
+    setTaskStore(new MemoryTaskStore(store.load() as DownloadTask[]));
+    expect(getTask("kept")?.filePath).toBe(ipaPath);
+  });
+
+  it("should re-root absolute paths from a moved data directory", () => {
+    const oldPath = path.join(
+      "/mnt/old-volume/asspp",
+      path.relative(path.resolve(config.dataDir), ipaPath),
+    );
+    setTaskStore(
+      new MemoryTaskStore([
+        makeTask({ id: "kept", status: "completed", filePath: oldPath }),
+        makeTask({
+          id: "escape",
+          status: "completed",
+          filePath: "packages/../../outside.ipa",
+        }),
+      ]),
+    );
+    expect(getTask("kept")?.filePath).toBe(ipaPath);
+    expect(getTask("escape")).toBeUndefined();
+  });
+
+  it("should persist sharing changes and deletions to the store", () => {
+    const store = new MemoryTaskStore([makeTask()]);
+    setTaskStore(store);
+
+    updateTaskSharing("task-1", ["0987654321fedcba0987654321fedcba"], []);
+    expect(store.records.get("task-1")?.sharedWith).toEqual([
+      "0987654321fedcba0987654321fedcba",
+    ]);
+
+    deleteTask("task-1");
+    expect(store.records.has("task-1")).toBe(false);
+  });
+});
+
+describe("encrypted download tickets", () => {
+  const originalKeys = config.persistEncryptionKeys;
+  const key = crypto.randomBytes(32);
+  const ticket = {
+    downloadURL: "https://iosapps.itunes.apple.com/app.ipa?token=secret",
+    sinfs: [{ id: 0, sinf: "c2luZg==" }],
+  };
+  const { software, accountHash } = makeTask();
+  let id: string;
+
+  const doneDir = path.join(bundleDir, "2.0");
+
+  // Start a download against a CDN that never answers, then pause it
+  async function startPaused(store: MemoryTaskStore): Promise<string> {
+    setTaskStore(store);
+    const task = createTask({
+      software,
+      accountHash,
+      downloadURL: ticket.downloadURL,
+      sinfs: ticket.sinfs,
+    });
+    await vi.waitFor(() => expect(task.status).toBe("downloading"));
+    expect(pauseTask(task.id)).toBeNull();
+    return task.id;
+  }
+
+  beforeAll(() => {
+    fs.mkdirSync(doneDir, { recursive: true });
+    fs.writeFileSync(path.join(doneDir, "done.ipa"), "ipa");
+  });
+
+  afterAll(() => {
+    fs.rmSync(bundleDir, { recursive: true, force: true });
+  });
+
+  beforeEach(() => {
+    downloadsViaFetch();
+    vi.spyOn(globalThis, "fetch").mockImplementation(
+      (_url, init) =>
+        new Promise((_resolve, reject) => {
+          init?.signal?.addEventListener("abort", () =>
+            reject(new DOMException("aborted", "AbortError")),
+          );
+        }),
+    );
+  });
+
+  afterEach(() => {
+    deleteTask(id);
+    config.persistEncryptionKeys = originalKeys;
+    vi.restoreAllMocks();
+    setPinnedNetwork();
+  });
+
+  it("should seal paused tickets and restore them after a restart", async () => {
+    config.persistEncryptionKeys = [key.toString("base64")];
+    const store = new MemoryTaskStore([
+      makeTask({
+        id: "done",
+        status: "completed",
+        filePath: path.join(doneDir, "done.ipa"),
+        downloadURL: ticket.downloadURL,
+      }),
+    ]);
+    id = await startPaused(store);
+
+    const records = store.records as Map<string, any>;
+    expect(JSON.stringify([...records.values()])).not.toContain(
+      "token=secret",
+    );
+    const sealed = records.get(id).secrets.data;
+    expect(records.get(id).status).toBe("paused");
+    updateTaskSharing(id, ["0987654321fedcba0987654321fedcba"], []);
+    expect(records.get(id).secrets.data).not.toBe(sealed);
+    // Completed tasks are stripped before anything is encrypted
+    expect(records.get("done").downloadURL).toBe("");
+    expect(records.get("done").secrets).toBeUndefined();
+
+    setTaskStore(new MemoryTaskStore([...records.values()]));
+    const restored = getTask(id);
+    expect(restored?.status).toBe("paused");
+    expect(restored?.downloadURL).toBe(ticket.downloadURL);
+    expect(restored?.sinfs).toEqual(ticket.sinfs);
+  });
+
+  it("should drop only the ticket when no key decrypts it", async () => {
+    config.persistEncryptionKeys = [key.toString("base64")];
+    const store = new MemoryTaskStore();
+    id = await startPaused(store);
+
+    config.persistEncryptionKeys = [crypto.randomBytes(32).toString("base64")];
+    setTaskStore(new MemoryTaskStore([...store.records.values()]));
+    const task = getTask(id);
+    expect(task?.status).toBe("paused");
+    expect(task?.downloadURL).toBe("");
+    expect(task?.sinfs).toEqual([]);
+  });
+
+  it("should not persist unfinished tasks without a key", async () => {
+    config.persistEncryptionKeys = [];
+    const store = new MemoryTaskStore();
+    id = await startPaused(store);
+    expect(store.records.has(id)).toBe(false);
+    expect(JSON.stringify(store.load())).not.toContain("token=secret");
+  });
+});
+
+describe("createFileTaskStore", () => {
+  it("should round-trip tasks through the JSON file", () => {
+    const dir = fs.mkdtempSync(path.join(os.tmpdir(), "taskstore-file-"));
+    try {
+      const store = createFileTaskStore(path.join(dir, "tasks.json"), false);
+      expect(store.load()).toEqual([]);
+
+      store.save([makeTask()]);
+      expect(store.load()).toEqual([makeTask()]);
+    } finally {
+      fs.rmSync(dir, { recursive: true, force: true });
+    }
+  });
+
+  it("should write gzip when compressed and read either format", () => {
+    const dir = fs.mkdtempSync(path.join(os.tmpdir(), "taskstore-gz-"));
+    const filePath = path.join(dir, "tasks.json");
+    try {
+      createFileTaskStore(filePath, false).save([makeTask()]);
+
+      // Upgrading: the compressed store reads the existing plain file
+      const compressed = createFileTaskStore(filePath, false, true);
+      expect(compressed.load()).toEqual([makeTask()]);
+
+      compressed.save([makeTask({ id: "task-2" })]);
+      expect(fs.existsSync(filePath)).toBe(false);
+      const raw = fs.readFileSync(`${filePath}.gz`);
+      expect(raw.subarray(0, 2)).toEqual(Buffer.from([0x1f, 0x8b]));
+      expect(compressed.load()).toEqual([makeTask({ id: "task-2" })]);
+
+      // And back: the plain store reads the gzip file, then replaces it
+      const plain = createFileTaskStore(filePath, false);
+      expect(plain.load()).toEqual([makeTask({ id: "task-2" })]);
+      plain.save([makeTask()]);
+      expect(fs.existsSync(`${filePath}.gz`)).toBe(false);
+      expect(plain.load()).toEqual([makeTask()]);
+    } finally {
+      fs.rmSync(dir, { recursive: true, force: true });
+    }
+  });
+
+  it("should write the schema version and migrate bare arrays", () => {
+    const dir = fs.mkdtempSync(path.join(os.tmpdir(), "taskstore-schema-"));
+    const filePath = path.join(dir, "tasks.json");
+    try {
+      // Files from before versioning are a bare array of tasks
+      fs.writeFileSync(filePath, JSON.stringify([makeTask()]));
+      const store = createFileTaskStore(filePath, false);
+      expect(store.load()).toEqual([makeTask()]);
+
+      store.save([makeTask()]);
+      const written = JSON.parse(fs.readFileSync(filePath, "utf-8"));
+      expect(written.schemaVersion).toBe(TASKS_SCHEMA_VERSION);
+      expect(written.tasks).toEqual([makeTask()]);
+    } finally {
+      fs.rmSync(dir, { recursive: true, force: true });
+    }
+  });
+
+  it("should refuse a newer schema and keep a copy of the file", () => {
+    const dir = fs.mkdtempSync(path.join(os.tmpdir(), "taskstore-newer-"));
+    const filePath = path.join(dir, "tasks.json");
+    try {
+      const newer = JSON.stringify({
+        schemaVersion: TASKS_SCHEMA_VERSION + 1,
+        tasks: [makeTask()],
+      });
+      fs.writeFileSync(filePath, newer);
+      expect(() => createFileTaskStore(filePath, false).load()).toThrow(
+        /newer than supported/,
+      );
+      expect(fs.readFileSync(`${filePath}.unreadable`, "utf-8")).toBe(newer);
+    } finally {
+      fs.rmSync(dir, { recursive: true, force: true });
+    }
+  });
+});
+
+describe("startup with an unreadable task file", () => {
+  let dir: string;
+
+  beforeEach(() => {
+    dir = fs.mkdtempSync(path.join(os.tmpdir(), "taskstore-startup-"));
+  });
+
+  afterEach(() => {
+    vi.unstubAllEnvs();
+    fs.rmSync(dir, { recursive: true, force: true });
+  });
+
+  it("should keep the file and every package, and stay read-only", async () => {
+    const tasksFile = path.join(dir, "tasks.json");
+    const newer = JSON.stringify({
+      schemaVersion: TASKS_SCHEMA_VERSION + 1,
+      tasks: [makeTask()],
+    });
+    fs.writeFileSync(tasksFile, newer);
+    const packageDir = path.join(dir, "packages", "acct", "com.example.app");
+    const ipa = path.join(packageDir, "1.0", "task-1.ipa");
+    fs.mkdirSync(path.dirname(ipa), { recursive: true });
+    fs.writeFileSync(ipa, "ipa-bytes");
+    fs.writeFileSync(`${ipa}.tmp`, "partial");
+
+    vi.stubEnv("DATA_DIR", dir);
+    vi.resetModules();
+    const fresh = await import("../src/services/downloadManager.js");
+    const freshConfig = (await import("../src/config.js")).config;
+
+    expect(fresh.isTaskStoreUnreadable()).toBe(true);
+    expect(freshConfig.readOnly).toBe(true);
+    fresh.setReadOnly(false);
+    expect(freshConfig.readOnly).toBe(true);
+    expect(fs.readFileSync(ipa, "utf-8")).toBe("ipa-bytes");
+    expect(fs.existsSync(`${ipa}.tmp`)).toBe(true);
+
+    // Changes stay in memory rather than replacing the file
+    const task = fresh.createTask({
+      software: {
+        id: 1,
+        bundleID: "com.example.new",
+        version: "1.0",
+      } as Software,
+      accountHash: "abcdef1234567890abcdef1234567890",
+      downloadURL: "https://example.apple.com/app.ipa",
+      sinfs: [],
+    });
+    fresh.deleteTask(task.id);
+    expect(fs.readFileSync(tasksFile, "utf-8")).toBe(newer);
+  });
+});
@@ -0,0 +1,70 @@
+import { describe, it, expect, vi, afterEach } from "vitest";
+import fs from "fs";
+import path from "path";
+import { config } from "../src/config.js";
+import {
+  setTaskStore,
+  sweepTempFiles,
+} from "../src/services/downloadManager.js";
+import { MemoryTaskStore } from "../src/services/taskStore.js";
+import { completedTask } from "./helpers/tasks.js";
+
+const ACCOUNT = "abcdef1234567890abcdef1234567890";
+const dir = path.resolve(config.dataDir, "packages", ACCOUNT, "tmp-sweep");
+
+function sized(id: string, fileSize: number) {
+  return completedTask(id, { filePath: path.join(dir, `${id}.ipa`), fileSize });
+}
+
+describe("startup temp file sweep", () => {
+  afterEach(() => {
+    vi.restoreAllMocks();
+    fs.rmSync(dir, { recursive: true, force: true });
+    setTaskStore(new MemoryTaskStore());
+  });
+
+  it("should remove temp files next to intact packages only", () => {
+    fs.mkdirSync(dir, { recursive: true });
+    fs.writeFileSync(path.join(dir, "intact.ipa"), "ipa");
+    fs.writeFileSync(path.join(dir, "intact.ipa.reinject"), "half-written");
+    fs.writeFileSync(path.join(dir, "intact.ipa.tmp"), "half-written");
+    // Recorded size disagrees: the package itself may be the damaged one
+    fs.writeFileSync(path.join(dir, "damaged.ipa"), "ip");
+    fs.writeFileSync(path.join(dir, "damaged.ipa.reinject"), "copy");
+    setTaskStore(
+      new MemoryTaskStore([sized("intact", 3), sized("damaged", 3)]),
+    );
+
+    expect(sweepTempFiles()).toBe(2);
+    expect(fs.readdirSync(dir).sort()).toEqual([
+      "damaged.ipa",
+      "damaged.ipa.reinject",
+      "intact.ipa",
+    ]);
+  });
+
+  it("should keep sweeping past a file it cannot remove", () => {
+    fs.mkdirSync(dir, { recursive: true });
+    for (const id of ["locked", "free"]) {
+      fs.writeFileSync(path.join(dir, `${id}.ipa`), "ipa");
+      fs.writeFileSync(path.join(dir, `${id}.ipa.tmp`), "half-written");
+    }
+    setTaskStore(new MemoryTaskStore([sized("locked", 3), sized("free", 3)]));
+    const unlink = fs.unlinkSync;
+    vi.spyOn(fs, "unlinkSync").mockImplementation((target) => {
+      if (String(target).endsWith("locked.ipa.tmp")) {
+        throw Object.assign(new Error("EBUSY: resource busy"), {
+          code: "EBUSY",
+        });
+      }
+      unlink(target);
+    });
+    const warn = vi.spyOn(console, "warn").mockImplementation(() => {});
+
+    expect(sweepTempFiles()).toBe(1);
+    expect(fs.existsSync(path.join(dir, "free.ipa.tmp"))).toBe(false);
+    expect(warn).toHaveBeenCalledWith(
+      expect.stringContaining("locked.ipa.tmp"),
+    );
+  });
+});
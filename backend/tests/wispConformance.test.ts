@@ -0,0 +1,205 @@
+import { describe, it, expect, beforeAll, afterAll, vi } from "vitest";
+import { createServer, Server } from "http";
+import net from "net";
+import crypto from "crypto";
+import express from "express";
+import { server as wisp } from "@mercuryworkshop/wisp-js/server";
+import { config } from "../src/config.js";
+import {
+  closeWispConnection,
+  listWispConnections,
+} from "../src/services/wispConnections.js";
+import { setupWsProxy } from "../src/services/wsProxy.js";
+import {
+  CloseReason,
+  PacketType,
+  WispClient,
+} from "./helpers/wispClient.js";
+
+// Drives the real relay end to end with a Wisp client, tunnelling to local
+// TCP servers. The Apple-only allowlist is widened to localhost for the
+// duration of the suite.
+
+const STREAMS = 4;
+const BYTES_PER_STREAM = 2 * 1024 * 1024;
+
+let httpServer: Server;
+let wispUrl: string;
+// Echoes everything back
+let echoServer: net.Server;
+let echoPort: number;
+// Sends a greeting and hangs up
+let byeServer: net.Server;
+let byePort: number;
+let savedOptions: Record<string, unknown>;
+
+async function listen(server: net.Server | Server): Promise<number> {
+  await new Promise<void>((resolve) => server.listen(0, resolve));
+  return (server.address() as net.AddressInfo).port;
+}
+
+async function close(server: net.Server | Server) {
+  await new Promise<void>((resolve) => server.close(() => resolve()));
+}
+
+beforeAll(async () => {
+  echoServer = net.createServer((socket) => socket.pipe(socket));
+  echoPort = await listen(echoServer);
+  byeServer = net.createServer((socket) => socket.end("bye"));
+  byePort = await listen(byeServer);
+
+  savedOptions = { ...wisp.options };
+  wisp.options.hostname_whitelist = [/^localhost$/];
+  wisp.options.port_whitelist = [echoPort, byePort];
+  wisp.options.allow_loopback_ips = true;
+
+  httpServer = createServer(express());
+  setupWsProxy(httpServer);
+  wispUrl = `ws://127.0.0.1:${await listen(httpServer)}/wisp/`;
+});
+
+afterAll(async () => {
+  Object.assign(wisp.options, savedOptions);
+  httpServer.closeAllConnections();
+  await Promise.all([close(httpServer), close(echoServer), close(byeServer)]);
+});
+
+describe("Wisp relay conformance", () => {
+  it("round-trips megabytes over concurrent streams byte for byte", async () => {
+    const client = await WispClient.connect(wispUrl);
+    try {
+      await Promise.all(
+        Array.from({ length: STREAMS }, async () => {
+          const stream = client.openStream("localhost", echoPort);
+          const payload = crypto.randomBytes(BYTES_PER_STREAM);
+          const [, echoed] = await Promise.all([
+            stream.write(payload),
+            stream.read(payload.length),
+          ]);
+          expect(echoed.equals(payload)).toBe(true);
+          stream.close();
+        }),
+      );
+    } finally {
+      client.close();
+    }
+  });
+
+  it("reports a voluntary close when the remote end hangs up", async () => {
+    const client = await WispClient.connect(wispUrl);
+    try {
+      const stream = client.openStream("localhost", byePort);
+      expect((await stream.read(3)).toString()).toBe("bye");
+      expect(await stream.closed).toBe(CloseReason.VOLUNTARY);
+    } finally {
+      client.close();
+    }
+  });
+
+  it("closes streams to hosts outside the allowlist as blocked", async () => {
+    const client = await WispClient.connect(wispUrl);
+    try {
+      const blocked = client.openStream("example.com", echoPort);
+      expect(await blocked.closed).toBe(CloseReason.BLOCKED);
+
+      const wrongPort = client.openStream("localhost", 22);
+      expect(await wrongPort.closed).toBe(CloseReason.BLOCKED);
+    } finally {
+      client.close();
+    }
+  });
+
+  it("drops the connection when a DATA packet exceeds the limit", async () => {
+    const saved = config.wispMaxDataBytes;
+    config.wispMaxDataBytes = 64 * 1024;
+    const client = await WispClient.connect(wispUrl);
+    try {
+      const stream = client.openStream("localhost", echoPort);
+      client.send(PacketType.DATA, stream.id, Buffer.alloc(128 * 1024));
+      expect(await stream.closed).toBe(CloseReason.NETWORK_ERROR);
+    } finally {
+      config.wispMaxDataBytes = saved;
+      client.close();
+    }
+  });
+
+  it("lists connections with their streams and closes one", async () => {
+    const before = new Set(listWispConnections().map((c) => c.id));
+    const client = await WispClient.connect(wispUrl);
+    try {
+      const stream = client.openStream("localhost", echoPort);
+      await stream.write(Buffer.from("hello"));
+      expect((await stream.read(5)).toString()).toBe("hello");
+
+      const connection = listWispConnections().find((c) => !before.has(c.id));
+      expect(connection?.remoteAddress).toContain("127.0.0.1");
+      expect(connection?.streams).toEqual([
+        expect.objectContaining({
+          streamId: stream.id,
+          hostname: "localhost",
+          port: echoPort,
+          bytesSent: 5,
+          bytesReceived: 5,
+        }),
+      ]);
+
+      expect(closeWispConnection(connection!.id)).toBe(true);
+      expect(await stream.closed).toBe(CloseReason.VOLUNTARY);
+      expect(listWispConnections().map((c) => c.id)).not.toContain(
+        connection!.id,
+      );
+      expect(closeWispConnection(connection!.id)).toBe(false);
+    } finally {
+      client.close();
+    }
+  });
+
+  it("logs stream lifecycle events with hosts shortened in privacy mode", async () => {
+    const saved = { ...config };
+    config.wispLogLevel = "streams";
+    config.wispLogPrivacy = true;
+    const log = vi.spyOn(console, "log").mockImplementation(() => {});
+    const client = await WispClient.connect(wispUrl);
+    try {
+      const blocked = client.openStream("tracker.example.com", echoPort);
+      expect(await blocked.closed).toBe(CloseReason.BLOCKED);
+      const lines = log.mock.calls.map(([line]) => String(line));
+      expect(lines).toContainEqual(
+        expect.stringMatching(/^\[Wisp\] connection\.open conn=/),
+      );
+      expect(lines).toContainEqual(
+        expect.stringMatching(
+          `stream.connect .* target=\\*\\.example\\.com:${echoPort}$`,
+        ),
+      );
+      const close = lines.find((line) => line.includes("stream.close"));
+      expect(close).toContain("reason=blocked by=server sent=0 received=0");
+      expect(lines.join("\n")).not.toContain("tracker.example.com");
+      expect(lines).toContainEqual(expect.stringContaining("remote=*"));
+      expect(lines.join("\n")).not.toContain("127.0.0.1");
+    } finally {
+      client.close();
+      Object.assign(config, saved);
+      log.mockRestore();
+    }
+  });
+
+  it("quotes hostnames that would break the log line", async () => {
+    const saved = { ...config };
+    config.wispLogLevel = "streams";
+    const log = vi.spyOn(console, "log").mockImplementation(() => {});
+    const client = await WispClient.connect(wispUrl);
+    try {
+      const stream = client.openStream("a.com\n[Wisp] fake x=1", echoPort);
+      await stream.closed;
+      const lines = log.mock.calls.map(([line]) => String(line));
+      const target = `target="a.com\\n[Wisp] fake x=1:${echoPort}"`;
+      expect(lines).toContainEqual(expect.stringContaining(target));
+      for (const line of lines) expect(line).not.toContain("\n");
+    } finally {
+      client.close();
+      Object.assign(config, saved);
+      log.mockRestore();
+    }
+  });
+});
@@ -0,0 +1,64 @@
+import { describe, it, expect } from "vitest";
+import { WispFrameGuard } from "../src/services/wispFrameGuard.js";
+
+// A masked client frame header for a payload of `length` bytes
+function frameHeader(length: number, opcode = 0x2, fin = true): Buffer {
+  const first = (fin ? 0x80 : 0) | opcode;
+  let header: Buffer;
+  if (length < 126) {
+    header = Buffer.from([first, 0x80 | length]);
+  } else if (length < 0x10000) {
+    header = Buffer.alloc(4);
+    header.writeUInt8(first, 0);
+    header.writeUInt8(0x80 | 126, 1);
+    header.writeUInt16BE(length, 2);
+  } else {
+    header = Buffer.alloc(10);
+    header.writeUInt8(first, 0);
+    header.writeUInt8(0x80 | 127, 1);
+    header.writeBigUInt64BE(BigInt(length), 2);
+  }
+  return Buffer.concat([header, Buffer.from([1, 2, 3, 4])]);
+}
+
+function frame(length: number, opcode = 0x2, fin = true): Buffer {
+  return Buffer.concat([
+    frameHeader(length, opcode, fin),
+    Buffer.alloc(length),
+  ]);
+}
+
+describe("Wisp frame guard", () => {
+  it("should accept frames up to the limit across arbitrary chunking", () => {
+    const guard = new WispFrameGuard(1000);
+    const stream = Buffer.concat([frame(10), frame(1000), frame(300)]);
+    // Byte-at-a-time splits headers and payloads everywhere
+    for (let i = 0; i < stream.length; i++) {
+      expect(guard.feed(stream.subarray(i, i + 1))).toBe(true);
+    }
+  });
+
+  it("should refuse an oversized frame from its header alone", () => {
+    const guard = new WispFrameGuard(64 * 1024);
+    expect(guard.feed(frame(100))).toBe(true);
+    expect(guard.feed(frameHeader(128 * 1024))).toBe(false);
+  });
+
+  it("should count continuation frames toward one message", () => {
+    const guard = new WispFrameGuard(1000);
+    expect(guard.feed(frame(600, 0x2, false))).toBe(true);
+    expect(guard.feed(frame(600, 0x0, true))).toBe(false);
+
+    const fresh = new WispFrameGuard(1000);
+    expect(fresh.feed(frame(600, 0x2, false))).toBe(true);
+    // Pings may interleave with fragments
+    expect(fresh.feed(frame(4, 0x9))).toBe(true);
+    expect(fresh.feed(frame(400, 0x0, true))).toBe(true);
+    expect(fresh.feed(frame(1000))).toBe(true);
+  });
+
+  it("should refuse oversized control frames", () => {
+    const guard = new WispFrameGuard(1000);
+    expect(guard.feed(frameHeader(200, 0x9))).toBe(false);
+  });
+});
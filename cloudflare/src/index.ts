@@ -11,11 +11,50 @@ export class AssppContainer extends Container {
     NODE_ENV: "production",
     PORT: "8080",
   };
+
+  constructor(ctx: ConstructorParameters<typeof Container>[0], env: Env) {
+    super(ctx, env);
+    // Serve the app under the same prefix the worker is routed on
+    const basePath = normalizeBasePath(env.BASE_PATH);
+    if (basePath) {
+      this.envVars = { ...this.envVars, BASE_PATH: basePath };
+      this.pingEndpoint = `${basePath}/api/settings`;
+    }
+    for (const name of PASSTHROUGH_VARS) {
+      const value = env[name]?.trim();
+      if (value) this.envVars = { ...this.envVars, [name]: value };
+    }
+    // Secrets are opaque: whitespace may be part of the value
+    for (const name of PASSTHROUGH_SECRETS) {
+      const value = env[name];
+      if (value) this.envVars = { ...this.envVars, [name]: value };
+    }
+  }
 }
 
+// Worker vars handed to the container (trimmed), so a Workers deployment
+// is configured (and reports its limits in /api/settings) the same way as
+// a standalone one
+const PASSTHROUGH_VARS = [
+  "DEFAULT_STOREFRONT",
+  "MAX_DOWNLOAD_SIZE_MB",
+  "STRICT_ACCOUNT_HEADER",
+] as const;
+
+// Worker secrets handed to the container byte for byte
+const PASSTHROUGH_SECRETS = [
+  // Stable install-link signing key, so links survive container restarts
+  "INSTALL_TOKEN_SECRET",
+] as const;
+
 interface Env {
   ASPP_CONTAINER: ContainerNamespace;
   CONTAINER_INSTANCE_NAME?: string;
+  BASE_PATH?: string;
+  INSTALL_TOKEN_SECRET?: string;
+  DEFAULT_STOREFRONT?: string;
+  MAX_DOWNLOAD_SIZE_MB?: string;
+  STRICT_ACCOUNT_HEADER?: string;
 }
 
 interface ContainerInstance {
@@ -27,6 +66,11 @@ interface ContainerNamespace {
   getByName(name: string): ContainerInstance;
 }
 
+function normalizeBasePath(value: string | undefined): string {
+  const trimmed = (value ?? "").trim().replace(/^\/+|\/+$/g, "");
+  return trimmed ? `/${trimmed}` : "";
+}
+
 function withForwardHeaders(request: Request): Request {
   const headers = new Headers(request.headers);
   if (!headers.has("x-forwarded-proto")) {
@@ -1,22 +1,49 @@
-const BASE_URL = "";
+// Sub-path prefix (BASE_PATH) announced by the server via <base href>
+const baseElement = document.querySelector("base");
+export const BASE_PATH = baseElement
+  ? new URL(baseElement.href).pathname.replace(/\/+$/, "")
+  : "";
 
-export async function apiGet<T>(path: string): Promise<T> {
-  const res = await fetch(`${BASE_URL}${path}`);
+const BASE_URL = BASE_PATH;
+
+type RequestHeaders = Record<string, string>;
+
+// Account hashes travel in headers, keeping them out of proxy access logs
+export function accountHeader(accountHash: string): RequestHeaders {
+  return { "X-Account-Hash": accountHash };
+}
+
+export function accountHashesHeader(accountHashes: string[]): RequestHeaders {
+  return { "X-Account-Hashes": accountHashes.join(",") };
+}
+
+export async function apiGet<T>(
+  path: string,
+  headers?: RequestHeaders,
+): Promise<T> {
+  const res = await fetch(`${BASE_URL}${path}`, { headers });
   if (!res.ok) throw new Error(await res.text());
   return res.json();
 }
 
-export async function apiPost<T>(path: string, body?: any): Promise<T> {
+export async function apiPost<T>(
+  path: string,
+  body?: any,
+  headers?: RequestHeaders,
+): Promise<T> {
   const res = await fetch(`${BASE_URL}${path}`, {
     method: "POST",
-    headers: { "Content-Type": "application/json" },
+    headers: { "Content-Type": "application/json", ...headers },
     body: body ? JSON.stringify(body) : undefined,
   });
   if (!res.ok) throw new Error(await res.text());
   return res.json();
 }
 
-export async function apiDelete(path: string): Promise<void> {
-  const res = await fetch(`${BASE_URL}${path}`, { method: "DELETE" });
+export async function apiDelete(
+  path: string,
+  headers?: RequestHeaders,
+): Promise<void> {
+  const res = await fetch(`${BASE_URL}${path}`, { method: "DELETE", headers });
   if (!res.ok) throw new Error(await res.text());
 }
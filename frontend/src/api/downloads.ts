@@ -1,14 +1,30 @@
-import { apiGet, apiPost, apiDelete } from "./client";
+import {
+  accountHashesHeader,
+  accountHeader,
+  apiGet,
+  apiPost,
+  apiDelete,
+} from "./client";
 import type { DownloadTask, Software, Sinf } from "../types";
 
 export async function fetchDownloads(
   accountHashes: string[],
 ): Promise<DownloadTask[]> {
   if (accountHashes.length === 0) return [];
-  const params = new URLSearchParams({
-    accountHashes: accountHashes.join(","),
-  });
-  return apiGet<DownloadTask[]>(`/api/downloads?${params}`);
+  return apiGet<DownloadTask[]>(
+    "/api/downloads",
+    accountHashesHeader(accountHashes),
+  );
+}
+
+export async function getDownload(
+  id: string,
+  accountHash: string,
+): Promise<DownloadTask> {
+  return apiGet<DownloadTask>(
+    `/api/downloads/${id}`,
+    accountHeader(accountHash),
+  );
 }
 
 export async function startDownload(data: {
@@ -16,6 +32,9 @@ export async function startDownload(data: {
   accountHash: string;
   downloadURL: string;
   sinfs: Sinf[];
+  priority?: DownloadTask["priority"];
+  expectedMd5?: string;
+  expectedSha1?: string;
 }): Promise<DownloadTask> {
   return apiPost<DownloadTask>("/api/downloads", data);
 }
@@ -24,22 +43,39 @@ export async function pauseDownload(
   id: string,
   accountHash: string,
 ): Promise<void> {
-  const params = new URLSearchParams({ accountHash });
-  await apiPost(`/api/downloads/${id}/pause?${params}`);
+  await apiPost(
+    `/api/downloads/${id}/pause`,
+    undefined,
+    accountHeader(accountHash),
+  );
 }
 
 export async function resumeDownload(
   id: string,
   accountHash: string,
 ): Promise<void> {
-  const params = new URLSearchParams({ accountHash });
-  await apiPost(`/api/downloads/${id}/resume?${params}`);
+  await apiPost(
+    `/api/downloads/${id}/resume`,
+    undefined,
+    accountHeader(accountHash),
+  );
+}
+
+export async function setDownloadPriority(
+  id: string,
+  accountHash: string,
+  priority: NonNullable<DownloadTask["priority"]>,
+): Promise<void> {
+  await apiPost(
+    `/api/downloads/${id}/priority`,
+    { priority },
+    accountHeader(accountHash),
+  );
 }
 
 export async function deleteDownload(
   id: string,
   accountHash: string,
 ): Promise<void> {
-  const params = new URLSearchParams({ accountHash });
-  await apiDelete(`/api/downloads/${id}?${params}`);
+  await apiDelete(`/api/downloads/${id}`, accountHeader(accountHash));
 }
@@ -1,11 +1,17 @@
+import { accountHeader, apiGet } from "./client";
+
 export interface InstallInfo {
   installUrl: string;
   manifestUrl: string;
 }
 
-export function getInstallInfo(id: string): InstallInfo {
-  const baseUrl = window.location.origin;
-  const manifestUrl = `${baseUrl}/api/install/${id}/manifest.plist`;
-  const installUrl = `itms-services://?action=download-manifest&url=${encodeURIComponent(manifestUrl)}`;
-  return { installUrl, manifestUrl };
+// Install links carry a short-lived token minted by the server
+export async function getInstallInfo(
+  id: string,
+  accountHash: string,
+): Promise<InstallInfo> {
+  return apiGet<InstallInfo>(
+    `/api/install/${id}/url`,
+    accountHeader(accountHash),
+  );
 }
@@ -0,0 +1,35 @@
+import { accountHashesHeader, apiGet } from "./client";
+import type { PackageInfo } from "../types";
+
+export interface PackageSearchFilters {
+  minSize?: number;
+  from?: string;
+  to?: string;
+  version?: string;
+}
+
+export async function fetchPackages(
+  accountHashes: string[],
+): Promise<PackageInfo[]> {
+  if (accountHashes.length === 0) return [];
+  return apiGet<PackageInfo[]>(
+    "/api/packages",
+    accountHashesHeader(accountHashes),
+  );
+}
+
+export async function searchPackages(
+  accountHashes: string[],
+  q: string,
+  filters: PackageSearchFilters = {},
+): Promise<PackageInfo[]> {
+  if (accountHashes.length === 0) return [];
+  const params = new URLSearchParams({ q });
+  for (const [key, value] of Object.entries(filters)) {
+    if (value !== undefined) params.set(key, String(value));
+  }
+  return apiGet<PackageInfo[]>(
+    `/api/packages/search?${params}`,
+    accountHashesHeader(accountHashes),
+  );
+}
@@ -1,4 +1,5 @@
 import { parsePlist } from "./plist";
+import { BASE_PATH } from "../api/client";
 
 export interface BagOutput {
   authURL: string;
@@ -12,7 +13,9 @@ export const defaultAuthURL =
 // The bag response is public data (Apple service URLs, no credentials).
 export async function fetchBag(deviceId: string): Promise<BagOutput> {
   try {
-    const resp = await fetch(`/api/bag?guid=${encodeURIComponent(deviceId)}`);
+    const resp = await fetch(
+      `${BASE_PATH}/api/bag?guid=${encodeURIComponent(deviceId)}`,
+    );
     if (!resp.ok) {
       const err = await resp.json().catch(() => ({ error: resp.statusText }));
       console.warn(
@@ -1,4 +1,5 @@
 import { libcurl } from "libcurl.js/bundled";
+import { BASE_PATH } from "../api/client";
 
 let initialized = false;
 let initPromise: Promise<void> | null = null;
@@ -9,7 +10,7 @@ export async function initLibcurl(): Promise<void> {
 
   initPromise = (async () => {
     const wsProto = location.protocol === "https:" ? "wss:" : "ws:";
-    libcurl.set_websocket(`${wsProto}//${location.host}/wisp/`);
+    libcurl.set_websocket(`${wsProto}//${location.host}${BASE_PATH}/wisp/`);
     await libcurl.load_wasm();
     initialized = true;
   })();
@@ -54,14 +54,49 @@ export default function DownloadItem({
 
           {(isActive || isPaused) && (
             <div className="mt-2.5">
-              <ProgressBar progress={task.progress} />
+              <ProgressBar
+                progress={task.progress}
+                indeterminate={task.indeterminate}
+              />
               <div className="flex justify-between mt-1.5 text-xs text-gray-500 dark:text-gray-400 font-medium">
-                <span>{Math.round(task.progress)}%</span>
-                {task.speed && isActive && <span>{task.speed}</span>}
+                {task.indeterminate ? (
+                  <span>
+                    {t("downloads.item.downloadedSoFar", {
+                      size: `${((task.downloadedBytes ?? 0) / 1024 / 1024).toFixed(1)} MB`,
+                    })}
+                  </span>
+                ) : (
+                  <span>{Math.round(task.progress)}%</span>
+                )}
+                {task.injectionPhase === "queued" ? (
+                  <span>{t("downloads.item.injectionQueued")}</span>
+                ) : (
+                  task.speed && isActive && <span>{task.speed}</span>
+                )}
               </div>
             </div>
           )}
 
+          {isPaused && task.pauseExpiresAt && (
+            <p className="mt-2 text-xs text-gray-500 dark:text-gray-400">
+              {t("downloads.item.pauseExpires", {
+                count: Math.max(
+                  0,
+                  Math.ceil(
+                    (new Date(task.pauseExpiresAt).getTime() - Date.now()) /
+                      3_600_000,
+                  ),
+                ),
+              })}
+            </p>
+          )}
+
+          {task.status === "removed" && (
+            <p className="mt-2 text-xs text-gray-500 dark:text-gray-400">
+              {t("downloads.item.removedByCleanup")}
+            </p>
+          )}
+
           {task.error && (
             <p className="mt-2 text-xs text-red-600 dark:text-red-400 font-medium bg-red-50 dark:bg-red-900/20 p-2 rounded-md border border-red-100 dark:border-red-900/30">
               {task.error}
@@ -1,3 +1,4 @@
+import { useEffect, useState } from "react";
 import { useParams, useNavigate } from "react-router-dom";
 import { useTranslation } from "react-i18next";
 import { QRCodeSVG } from "qrcode.react";
@@ -8,7 +9,8 @@ import ProgressBar from "../common/ProgressBar";
 import { useDownloads } from "../../hooks/useDownloads";
 import { useAccounts } from "../../hooks/useAccounts";
 import { useToastStore } from "../../store/toast";
-import { getInstallInfo } from "../../api/install";
+import { getInstallInfo, type InstallInfo } from "../../api/install";
+import { BASE_PATH } from "../../api/client";
 import { getAccountContext } from "../../utils/toast";
 
 export default function PackageDetail() {
@@ -21,6 +23,24 @@ export default function PackageDetail() {
   const { accounts } = useAccounts();
 
   const task = tasks.find((t) => t.id === id);
+  const [installInfo, setInstallInfo] = useState<InstallInfo | null>(null);
+
+  // Install links are minted per visit and expire, so fetch a fresh one
+  const completedId = task?.status === "completed" ? task.id : undefined;
+  const ownerHash = task?.accountHash;
+  useEffect(() => {
+    setInstallInfo(null);
+    if (!completedId || !ownerHash) return;
+    let cancelled = false;
+    getInstallInfo(completedId, ownerHash)
+      .then((info) => {
+        if (!cancelled) setInstallInfo(info);
+      })
+      .catch((err) => console.warn("Failed to get install link:", err));
+    return () => {
+      cancelled = true;
+    };
+  }, [completedId, ownerHash]);
 
   if (!task) {
     return (
@@ -35,7 +55,6 @@ export default function PackageDetail() {
   const isActive = task.status === "downloading" || task.status === "injecting";
   const isPaused = task.status === "paused";
   const isCompleted = task.status === "completed";
-  const installInfo = isCompleted ? getInstallInfo(task.id) : null;
 
   const accountEmail = hashToEmail[task.accountHash];
   const account = accounts.find((a) => a.email === accountEmail);
@@ -122,9 +141,20 @@ export default function PackageDetail() {
 
         {(isActive || isPaused) && (
           <div>
-            <ProgressBar progress={task.progress} />
+            <ProgressBar
+              progress={task.progress}
+              indeterminate={task.indeterminate}
+            />
             <div className="flex justify-between mt-1 text-sm text-gray-500 dark:text-gray-400">
-              <span>{Math.round(task.progress)}%</span>
+              {task.indeterminate ? (
+                <span>
+                  {t("downloads.item.downloadedSoFar", {
+                    size: `${((task.downloadedBytes ?? 0) / 1024 / 1024).toFixed(1)} MB`,
+                  })}
+                </span>
+              ) : (
+                <span>{Math.round(task.progress)}%</span>
+              )}
               {task.speed && isActive && <span>{task.speed}</span>}
             </div>
           </div>
@@ -209,7 +239,7 @@ export default function PackageDetail() {
                   </>
                 )}
                 <a
-                  href={`/api/packages/${task.id}/file?accountHash=${encodeURIComponent(task.accountHash)}`}
+                  href={`${BASE_PATH}/api/packages/${task.id}/file?accountHash=${encodeURIComponent(task.accountHash)}`}
                   download
                   onClick={() => toastAction("toast.title.downloadIpaStarted")}
                   className="px-4 py-2 bg-blue-600 text-white text-sm font-medium rounded-lg hover:bg-blue-700 transition-colors"
@@ -6,6 +6,7 @@ import { useAccountsStore } from "../../store/accounts";
 import { useToastStore } from "../../store/toast";
 import { encryptData, decryptData } from "../../utils/crypto";
 import { countryCodeMap } from "../../apple/config";
+import { BASE_PATH } from "../../api/client";
 import type { Account } from "../../types";
 
 interface ServerInfo {
@@ -18,6 +19,7 @@ interface ServerInfo {
   disableHttpsRedirect?: boolean;
   autoCleanupDays?: number;
   autoCleanupMaxMB?: number;
+  autoCleanupMaxPerAccount?: number;
 }
 
 const entityTypes = [
@@ -60,7 +62,7 @@ export default function SettingsPage() {
   }, [entity]);
 
   useEffect(() => {
-    fetch("/api/settings")
+    fetch(`${BASE_PATH}/api/settings`)
       .then((r) => (r.ok ? r.json() : null))
       .then(setServerInfo)
       .catch(() => setServerInfo(null));
@@ -340,6 +342,15 @@ export default function SettingsPage() {
                         t("settings.server.disabled")}
                     </dd>
                   </div>
+                  <div>
+                    <dt className="text-sm font-medium text-gray-500 dark:text-gray-400">
+                      AUTO_CLEANUP_MAX_PER_ACCOUNT
+                    </dt>
+                    <dd className="text-sm text-gray-900 dark:text-gray-200 font-mono">
+                      {serverInfo.autoCleanupMaxPerAccount ||
+                        t("settings.server.disabled")}
+                    </dd>
+                  </div>
                 </dl>
               </div>
             </div>
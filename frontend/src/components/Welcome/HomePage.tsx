@@ -3,7 +3,9 @@ import { Link } from "react-router-dom";
 import { useTranslation } from "react-i18next";
 import PageContainer from "../Layout/PageContainer";
 import { useAccounts } from "../../hooks/useAccounts";
-import { accountHash } from "../../utils/account";
+import { accountLookupHashes } from "../../utils/account";
+import { fetchDownloads } from "../../api/downloads";
+import { fetchPackages } from "../../api/packages";
 
 interface Stats {
   accounts: number;
@@ -31,20 +33,15 @@ export default function HomePage() {
     let cancelled = false;
 
     (async () => {
-      const hashes = await Promise.all(accounts.map((a) => accountHash(a)));
+      const lookups = await Promise.all(
+        accounts.map((a) => accountLookupHashes(a)),
+      );
+      const hashes = lookups.flat();
       if (cancelled) return;
 
-      const params = new URLSearchParams({
-        accountHashes: hashes.join(","),
-      });
-
       const [downloads, packages] = await Promise.all([
-        fetch(`/api/downloads?${params}`)
-          .then((r) => (r.ok ? r.json() : []))
-          .catch(() => []),
-        fetch(`/api/packages?${params}`)
-          .then((r) => (r.ok ? r.json() : []))
-          .catch(() => []),
+        fetchDownloads(hashes).catch(() => []),
+        fetchPackages(hashes).catch(() => []),
       ]);
 
       if (cancelled) return;
@@ -7,7 +7,8 @@ interface BadgeProps {
     | "paused"
     | "injecting"
     | "completed"
-    | "failed";
+    | "failed"
+    | "removed";
 }
 
 const styles: Record<BadgeProps["status"], string> = {
@@ -21,6 +22,7 @@ const styles: Record<BadgeProps["status"], string> = {
   completed:
     "bg-green-100 dark:bg-green-900/30 text-green-700 dark:text-green-400",
   failed: "bg-red-100 dark:bg-red-900/30 text-red-700 dark:text-red-400",
+  removed: "bg-gray-100 dark:bg-gray-800 text-gray-500 dark:text-gray-400",
 };
 
 export default function Badge({ status }: BadgeProps) {
@@ -1,10 +1,13 @@
 interface ProgressBarProps {
   progress: number;
+  // Unknown total: animate instead of showing a fill level
+  indeterminate?: boolean;
   className?: string;
 }
 
 export default function ProgressBar({
   progress,
+  indeterminate = false,
   className = "",
 }: ProgressBarProps) {
   const clamped = Math.min(100, Math.max(0, progress));
@@ -13,10 +16,14 @@ export default function ProgressBar({
     <div
       className={`w-full bg-gray-200 dark:bg-gray-800 rounded-full h-2 ${className}`}
     >
-      <div
-        className="bg-blue-600 dark:bg-blue-500 h-2 rounded-full transition-all duration-300"
-        style={{ width: `${clamped}%` }}
-      />
+      {indeterminate ? (
+        <div className="bg-blue-600 dark:bg-blue-500 h-2 rounded-full w-full animate-pulse" />
+      ) : (
+        <div
+          className="bg-blue-600 dark:bg-blue-500 h-2 rounded-full transition-all duration-300"
+          style={{ width: `${clamped}%` }}
+        />
+      )}
     </div>
   );
 }
@@ -43,6 +43,7 @@ export function useDownloadAction() {
       downloadURL: output.downloadURL,
       sinfs: output.sinfs,
       iTunesMetadata: output.iTunesMetadata,
+      externalVersionId: versionId,
     });
 
     fetchTasks();
@@ -1,7 +1,7 @@
 import { useEffect, useRef, useState } from "react";
 import { useDownloadsStore } from "../store/downloads";
 import { useAccounts } from "./useAccounts";
-import { accountHash } from "../utils/account";
+import { accountLookupHashes } from "../utils/account";
 
 export function useDownloads() {
   const {
@@ -21,8 +21,11 @@ export function useDownloads() {
   useEffect(() => {
     let cancelled = false;
     (async () => {
-      // Calculate original hashes preserving the order corresponding to 'accounts'
-      const hashes = await Promise.all(accounts.map((a) => accountHash(a)));
+      // Calculate hashes preserving the order corresponding to 'accounts'
+      const lookups = await Promise.all(
+        accounts.map((a) => accountLookupHashes(a)),
+      );
+      const hashes = lookups.flat();
       // Use slice() before sort() so we don't mutate the original 'hashes' array
       const key = hashes.slice().sort().join(",");
       if (cancelled || key === hashesRef.current) return;
@@ -30,8 +33,8 @@ export function useDownloads() {
 
       const map: Record<string, string> = {};
       for (let i = 0; i < accounts.length; i++) {
-        // Now hashes[i] correctly maps to accounts[i]
-        map[hashes[i]] = accounts[i].email;
+        // Every hash in lookups[i] belongs to accounts[i]
+        for (const hash of lookups[i]) map[hash] = accounts[i].email;
       }
       setHashToEmail(map);
 
@@ -4,6 +4,7 @@ import { BrowserRouter } from "react-router-dom";
 import App from "./App";
 import "./index.css";
 import { initLibcurl } from "./apple/libcurl-init";
+import { BASE_PATH } from "./api/client";
 
 import "./i18n";
 
@@ -12,7 +13,7 @@ initLibcurl().catch((e) => console.warn("[libcurl] Init failed:", e));
 
 createRoot(document.getElementById("root")!).render(
   <StrictMode>
-    <BrowserRouter>
+    <BrowserRouter basename={BASE_PATH || undefined}>
       <App />
     </BrowserRouter>
   </StrictMode>,
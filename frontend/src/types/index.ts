@@ -17,6 +17,8 @@ export interface Software {
   releaseNotes?: string;
   formattedPrice?: string;
   primaryGenreName: string;
+  externalVersionId?: string;
+  purchaseDate?: string;
 }
 
 export interface Cookie {
@@ -71,10 +73,31 @@ export interface DownloadTask {
     | "paused"
     | "injecting"
     | "completed"
-    | "failed";
+    | "failed"
+    | "removed";
+  priority?: "high" | "normal" | "low";
+  // Set while the task waits for a download slot (1 = next to start)
+  queuePosition?: number;
+  // While injecting: waiting for an injection slot, or rewriting the IPA
+  injectionPhase?: "queued" | "running";
   progress: number;
   speed: string;
+  // Size unknown: progress stays 0, so show downloadedBytes instead
+  indeterminate?: boolean;
+  downloadedBytes?: number;
+  // Probed from the download URL when the task was created
+  totalBytes?: number;
+  resumable?: boolean;
+  // Paused tasks, when the server expires pauses: resumed or failed then
+  pauseExpiresAt?: string;
   error?: string;
+  errorCategory?:
+    | "NETWORK"
+    | "URL_EXPIRED"
+    | "DISK_FULL"
+    | "TOO_LARGE"
+    | "INJECTION"
+    | "CHECKSUM";
   hasFile?: boolean;
   createdAt: string;
 }
@@ -83,6 +106,8 @@ export interface PackageInfo {
   id: string;
   software: Software;
   accountHash: string;
+  relativePath?: string;
   fileSize: number;
+  corrupt?: boolean;
   createdAt: string;
 }
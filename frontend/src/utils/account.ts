@@ -23,22 +23,113 @@ export function firstAccountCountry(accounts: Account[]): string | undefined {
   return undefined;
 }
 
+function hashSource(account: Account): string {
+  return (
+    account.directoryServicesIdentifier || account.appleId || account.email
+  );
+}
+
 export async function accountHash(account: Account): Promise<string> {
-  const source =
-    account.directoryServicesIdentifier || account.appleId || account.email;
-  return sha256Hex(source);
+  return sha256Hex(hashSource(account));
+}
+
+/**
+ * Hashes to list an account's downloads and packages under. Without
+ * crypto.subtle, clients before the SHA-256 fallback sent a 16-hex FNV-1a
+ * hash instead, so that one is listed too; the server only accepts it with
+ * ALLOW_LEGACY_ACCOUNT_HASHES. Drop this with that setting.
+ */
+export async function accountLookupHashes(
+  account: Account,
+): Promise<string[]> {
+  const hash = await accountHash(account);
+  if (globalThis.crypto?.subtle) return [hash];
+  return [hash, fnv1a64Hex(hashSource(account))];
 }
 
 async function sha256Hex(value: string): Promise<string> {
+  const data = new TextEncoder().encode(value);
   if (globalThis.crypto?.subtle) {
-    const data = new TextEncoder().encode(value);
     const digest = await globalThis.crypto.subtle.digest("SHA-256", data);
     return toHex(new Uint8Array(digest));
   }
 
-  return fnv1a64Hex(value);
+  // crypto.subtle only exists in secure contexts (plain-http LAN hosts);
+  // the backend expects the same SHA-256 hash either way
+  return toHex(sha256(data));
+}
+
+const K = new Uint32Array([
+  0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1,
+  0x923f82a4, 0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3,
+  0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786,
+  0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+  0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147,
+  0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13,
+  0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+  0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+  0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a,
+  0x5b9cca4f, 0x682e6ff3, 0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208,
+  0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+]);
+
+function rotr(x: number, n: number): number {
+  return (x >>> n) | (x << (32 - n));
+}
+
+// Plain SHA-256 (FIPS 180-4) over the given bytes
+function sha256(data: Uint8Array): Uint8Array {
+  const padded = new Uint8Array((((data.length + 8) >> 6) + 1) * 64);
+  padded.set(data);
+  padded[data.length] = 0x80;
+  const view = new DataView(padded.buffer);
+  view.setUint32(padded.length - 8, Math.floor(data.length / 0x20000000));
+  view.setUint32(padded.length - 4, data.length << 3);
+
+  const h = new Uint32Array([
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c,
+    0x1f83d9ab, 0x5be0cd19,
+  ]);
+  const w = new Uint32Array(64);
+  for (let offset = 0; offset < padded.length; offset += 64) {
+    for (let i = 0; i < 16; i += 1) w[i] = view.getUint32(offset + i * 4);
+    for (let i = 16; i < 64; i += 1) {
+      const s0 = rotr(w[i - 15], 7) ^ rotr(w[i - 15], 18) ^ (w[i - 15] >>> 3);
+      const s1 = rotr(w[i - 2], 17) ^ rotr(w[i - 2], 19) ^ (w[i - 2] >>> 10);
+      w[i] = w[i - 16] + s0 + w[i - 7] + s1;
+    }
+    let [a, b, c, d, e, f, g, hh] = h;
+    for (let i = 0; i < 64; i += 1) {
+      const s1 = rotr(e, 6) ^ rotr(e, 11) ^ rotr(e, 25);
+      const t1 = (hh + s1 + ((e & f) ^ (~e & g)) + K[i] + w[i]) >>> 0;
+      const s0 = rotr(a, 2) ^ rotr(a, 13) ^ rotr(a, 22);
+      const t2 = (s0 + ((a & b) ^ (a & c) ^ (b & c))) >>> 0;
+      hh = g;
+      g = f;
+      f = e;
+      e = (d + t1) >>> 0;
+      d = c;
+      c = b;
+      b = a;
+      a = (t1 + t2) >>> 0;
+    }
+    h[0] += a;
+    h[1] += b;
+    h[2] += c;
+    h[3] += d;
+    h[4] += e;
+    h[5] += f;
+    h[6] += g;
+    h[7] += hh;
+  }
+
+  const out = new Uint8Array(32);
+  const outView = new DataView(out.buffer);
+  h.forEach((word, i) => outView.setUint32(i * 4, word));
+  return out;
 }
 
+// The fallback hash of older clients, kept for accountLookupHashes()
 function fnv1a64Hex(value: string): string {
   let hash = 0xcbf29ce484222325n;
   const prime = 0x100000001b3n;
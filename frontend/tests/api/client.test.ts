@@ -1,5 +1,10 @@
 import { describe, it, expect, vi, beforeEach } from "vitest";
-import { apiGet, apiPost, apiDelete } from "../../src/api/client";
+import {
+  accountHeader,
+  apiGet,
+  apiPost,
+  apiDelete,
+} from "../../src/api/client";
 
 describe("api/client", () => {
   beforeEach(() => {
@@ -16,7 +21,19 @@ describe("api/client", () => {
 
       const result = await apiGet("/api/test");
       expect(result).toEqual(mockData);
-      expect(fetch).toHaveBeenCalledWith("/api/test");
+      expect(fetch).toHaveBeenCalledWith("/api/test", { headers: undefined });
+    });
+
+    it("should send the given headers", async () => {
+      vi.spyOn(globalThis, "fetch").mockResolvedValueOnce({
+        ok: true,
+        json: () => Promise.resolve({}),
+      } as Response);
+
+      await apiGet("/api/test", accountHeader("aaa"));
+      expect(fetch).toHaveBeenCalledWith("/api/test", {
+        headers: { "X-Account-Hash": "aaa" },
+      });
     });
 
     it("should throw on non-ok response", async () => {
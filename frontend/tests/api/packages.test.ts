@@ -0,0 +1,52 @@
+import { describe, it, expect, vi, beforeEach } from "vitest";
+import { fetchPackages, searchPackages } from "../../src/api/packages";
+import { getDownload } from "../../src/api/downloads";
+
+function mockJson(data: unknown) {
+  vi.spyOn(globalThis, "fetch").mockResolvedValueOnce({
+    ok: true,
+    json: () => Promise.resolve(data),
+  } as Response);
+}
+
+describe("api/packages", () => {
+  beforeEach(() => {
+    vi.restoreAllMocks();
+  });
+
+  it("should not call the server without account hashes", async () => {
+    const spy = vi.spyOn(globalThis, "fetch");
+    expect(await fetchPackages([])).toEqual([]);
+    expect(await searchPackages([], "notes")).toEqual([]);
+    expect(spy).not.toHaveBeenCalled();
+  });
+
+  it("should list packages for the given accounts", async () => {
+    mockJson([{ id: "1" }]);
+    const result = await fetchPackages(["aaa", "bbb"]);
+    expect(result).toEqual([{ id: "1" }]);
+    expect(fetch).toHaveBeenCalledWith("/api/packages", {
+      headers: { "X-Account-Hashes": "aaa,bbb" },
+    });
+  });
+
+  it("should pass only the filters that are set to search", async () => {
+    mockJson([]);
+    await searchPackages(["aaa"], "my app", { minSize: 1024, version: "2." });
+    expect(fetch).toHaveBeenCalledWith(
+      "/api/packages/search?q=my+app&minSize=1024&version=2.",
+      { headers: { "X-Account-Hashes": "aaa" } },
+    );
+  });
+});
+
+describe("api/downloads getDownload", () => {
+  it("should fetch one task with the account hash", async () => {
+    mockJson({ id: "task-1" });
+    const task = await getDownload("task-1", "aaa");
+    expect(task.id).toBe("task-1");
+    expect(fetch).toHaveBeenCalledWith("/api/downloads/task-1", {
+      headers: { "X-Account-Hash": "aaa" },
+    });
+  });
+});
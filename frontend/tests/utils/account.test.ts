@@ -0,0 +1,47 @@
+import { describe, it, expect, vi, afterEach } from "vitest";
+import { accountHash, accountLookupHashes } from "../../src/utils/account";
+import type { Account } from "../../src/types";
+
+const account: Account = {
+  email: "tëst@example.com",
+  password: "secret",
+  appleId: "tëst@example.com",
+  store: "143441",
+  firstName: "Test",
+  lastName: "User",
+  passwordToken: "token123",
+  directoryServicesIdentifier: "",
+  cookies: [],
+};
+
+// sha256("tëst@example.com"), UTF-8 encoded
+const EXPECTED =
+  "53c726841ce68f224bf077731224c5ece92829cfaa6171beacf340ac25a3f46d";
+
+describe("utils/account accountHash", () => {
+  afterEach(() => {
+    vi.unstubAllGlobals();
+  });
+
+  it("should hash with Web Crypto when available", async () => {
+    expect(await accountHash(account)).toBe(EXPECTED);
+  });
+
+  it("should produce the same SHA-256 hash without crypto.subtle", async () => {
+    vi.stubGlobal("crypto", {});
+    expect(await accountHash(account)).toBe(EXPECTED);
+    expect(
+      await accountHash({ ...account, directoryServicesIdentifier: "12345" }),
+    ).toBe("5994471abb01112afcc18159f6cc74b4f511b99806da59b3caf5a9c173cacfc5");
+  });
+
+  it("should also list the legacy hash without crypto.subtle", async () => {
+    expect(await accountLookupHashes(account)).toEqual([EXPECTED]);
+    vi.stubGlobal("crypto", {});
+    // fnv1a64 of the UTF-16 code units, as older clients computed it
+    expect(await accountLookupHashes(account)).toEqual([
+      EXPECTED,
+      "dc9aec3746fcbade",
+    ]);
+  });
+});
@@ -3,6 +3,8 @@ import react from "@vitejs/plugin-react";
 import tailwindcss from "@tailwindcss/vite";
 
 export default defineConfig({
+  // Relative asset URLs so the build works under any BASE_PATH prefix
+  base: "./",
   plugins: [react(), tailwindcss()],
   server: {
     proxy: {